@@ -14,6 +14,10 @@ pub struct BaseModule {
     u32le: FormatRef,
     u64be: FormatRef,
     u64le: FormatRef,
+    s16be: FormatRef,
+    s16le: FormatRef,
+    s32be: FormatRef,
+    s32le: FormatRef,
     ascii_char: FormatRef,
     ascii_char_strict: FormatRef,
     asciiz_string: FormatRef,
@@ -38,6 +42,10 @@ impl BaseModule {
     pub fn u32le(&self) -> Format { self.u32le.call() }
     pub fn u64be(&self) -> Format { self.u64be.call() }
     pub fn u64le(&self) -> Format { self.u64le.call() }
+    pub fn s16be(&self) -> Format { self.s16be.call() }
+    pub fn s16le(&self) -> Format { self.s16le.call() }
+    pub fn s32be(&self) -> Format { self.s32be.call() }
+    pub fn s32le(&self) -> Format { self.s32le.call() }
     pub fn ascii_char(&self) -> Format { self.ascii_char.call() }
     pub fn ascii_char_strict(&self) -> Format { self.ascii_char_strict.call() }
     pub fn asciiz_string(&self) -> Format { self.asciiz_string.call() }
@@ -149,6 +157,38 @@ pub fn main(module: &mut FormatModule) -> BaseModule {
         ),
     );
 
+    let s16be = module.define_format(
+        "base.s16be",
+        map(
+            tuple([u8.call(), u8.call()]),
+            lambda("x", Expr::I16Be(Box::new(var("x")))),
+        ),
+    );
+
+    let s16le = module.define_format(
+        "base.s16le",
+        map(
+            tuple([u8.call(), u8.call()]),
+            lambda("x", Expr::I16Le(Box::new(var("x")))),
+        ),
+    );
+
+    let s32be = module.define_format(
+        "base.s32be",
+        map(
+            tuple([u8.call(), u8.call(), u8.call(), u8.call()]),
+            lambda("x", Expr::I32Be(Box::new(var("x")))),
+        ),
+    );
+
+    let s32le = module.define_format(
+        "base.s32le",
+        map(
+            tuple([u8.call(), u8.call(), u8.call(), u8.call()]),
+            lambda("x", Expr::I32Le(Box::new(var("x")))),
+        ),
+    );
+
     let ascii_char = module.define_format("base.ascii-char", Format::Byte(ByteSet::full()));
 
     let mut bs = ByteSet::from(32..=127);
@@ -194,6 +234,10 @@ pub fn main(module: &mut FormatModule) -> BaseModule {
         u32le,
         u64be,
         u64le,
+        s16be,
+        s16le,
+        s32be,
+        s32le,
         ascii_char,
         ascii_char_strict,
         asciiz_string,
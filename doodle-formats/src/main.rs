@@ -6,10 +6,11 @@ use std::fs;
 use std::path::PathBuf;
 
 use clap::{Parser, ValueEnum};
+use doodle::codegen::GenOptions;
 use doodle::decoder::Compiler;
 use doodle::read::ReadCtxt;
 use doodle::typecheck;
-use doodle::FormatModule;
+use doodle::{FormatModule, Label};
 
 mod format;
 
@@ -44,6 +45,18 @@ enum Command {
         output: FormatOutput,
         #[arg(long, default_value = None)]
         dest: Option<PathBuf>,
+        /// Emit a builder-type alongside each generated record-struct (Rust output only)
+        #[arg(long)]
+        builders: bool,
+        /// Emit decoders that can never fail without a `PResult` return type (Rust output only)
+        #[arg(long)]
+        elide_infallible: bool,
+        /// Check newtype invariants with `debug_assert!` instead of returning an error (Rust output only)
+        #[arg(long)]
+        debug_invariants: bool,
+        /// Emit a public parse function of the given name for the top-level format (Rust output only)
+        #[arg(long)]
+        entry_point: Option<String>,
     },
     /// Decode a binary file
     File {
@@ -61,7 +74,14 @@ enum Command {
 
 fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
     match Command::parse() {
-        Command::Format { output, dest } => {
+        Command::Format {
+            output,
+            dest,
+            builders,
+            elide_infallible,
+            debug_invariants,
+            entry_point,
+        } => {
             let mut module = FormatModule::new();
             let format = format::main(&mut module).call();
 
@@ -69,7 +89,14 @@ fn main() -> Result<(), Box<dyn std::error::Error + 'static>> {
                 FormatOutput::Debug => println!("{module:?}"),
                 FormatOutput::Json => serde_json::to_writer(std::io::stdout(), &module).unwrap(),
                 FormatOutput::Rust => {
-                    doodle::codegen::print_generated_code(&module, &format, dest);
+                    let options = GenOptions {
+                        generate_builders: builders,
+                        elide_infallible,
+                        debug_invariants,
+                        entry_point: entry_point.map(Label::from),
+                        ..GenOptions::default()
+                    };
+                    doodle::codegen::print_generated_code_with(&module, &format, dest, options);
 
                     // let program = Compiler::compile_program(&module, &format)?;
                     // doodle::codegen::print_program(&program);
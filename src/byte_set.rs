@@ -65,6 +65,26 @@ impl ByteSet {
         (0..=255).filter(|b| self.contains(*b))
     }
 
+    /// Iterates over the maximal runs of consecutive bytes in the set, in ascending order.
+    pub fn ranges(&self) -> impl '_ + Iterator<Item = RangeInclusive<u8>> {
+        let mut bytes = self.iter().peekable();
+        std::iter::from_fn(move || {
+            let start = bytes.next()?;
+            let mut end = start;
+            while let Some(b) = bytes.next_if(|b| end.checked_add(1) == Some(*b)) {
+                end = b;
+            }
+            Some(start..=end)
+        })
+    }
+
+    /// Constructs the ByteSet consisting of every byte within any of the given `ranges`.
+    pub fn from_ranges(ranges: impl IntoIterator<Item = RangeInclusive<u8>>) -> ByteSet {
+        ranges.into_iter().fold(ByteSet::empty(), |bs, range| {
+            bs.union(&ByteSet::from(range))
+        })
+    }
+
     pub const fn min_elem(&self) -> Option<u8> {
         if let Some(i) = min_set_bit(self.bits[0]) {
             return Some(i);
@@ -369,6 +389,11 @@ mod tests {
                 assert!(ByteSet::is_disjoint(&ByteSet::difference(&bs0, &bs1), &bs1));
             }
 
+            #[test]
+            fn test_ranges_roundtrip(bs in any_byte_set()) {
+                prop_assert_eq!(ByteSet::from_ranges(bs.ranges()), bs);
+            }
+
             #[test]
             fn test_difference_right(bs0 in any_byte_set(), bs1 in any_byte_set()) {
                 assert!(ByteSet::is_disjoint(&bs0, &ByteSet::difference(&bs1, &bs0)));
@@ -376,6 +401,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_ranges() {
+        let bs = ByteSet::from([0, 1, 2, 5, 6]);
+        assert_eq!(bs.ranges().collect::<Vec<_>>(), vec![0..=2, 5..=6]);
+        assert_eq!(ByteSet::full().ranges().collect::<Vec<_>>(), vec![0..=255]);
+        assert_eq!(ByteSet::empty().ranges().count(), 0);
+        assert_eq!(ByteSet::from_ranges([0..=2, 5..=6]), bs);
+    }
+
     #[test]
     fn test_debug_below_128() {
         assert_eq!(format!("{:?}", ByteSet::from([32, 1])), "{1, 32}");
@@ -12,7 +12,7 @@ use crate::{
 
 use std::{
     borrow::Cow,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     hash::{Hash, Hasher},
     rc::Rc,
 };
@@ -23,7 +23,7 @@ use rust_ast::*;
 use typed_format::{GenType, TypedExpr, TypedFormat, TypedPattern};
 
 use self::{
-    typed_decoder::{GTCompiler, GTDecoder, TypedDecoder},
+    typed_decoder::{GTCompiler, GTDecoder, GTDecoderExt, TypedDecoder},
     typed_format::TypedDynFormat,
 };
 
@@ -129,6 +129,8 @@ use path_names::NameGen;
 pub struct CodeGen {
     name_gen: NameGen,
     defined_types: Vec<RustTypeDef>,
+    /// Newtypes whose predicate only rejects zero, which are lifted over `NonZeroU*` rather than the plain integer type
+    nonzero_newtypes: HashSet<Label>,
 }
 
 impl CodeGen {
@@ -138,6 +140,7 @@ impl CodeGen {
         CodeGen {
             name_gen,
             defined_types,
+            nonzero_newtypes: HashSet::new(),
         }
     }
 
@@ -152,6 +155,10 @@ impl CodeGen {
             ValueType::Base(BaseType::U16) => PrimType::U16.into(),
             ValueType::Base(BaseType::U32) => PrimType::U32.into(),
             ValueType::Base(BaseType::U64) => PrimType::U64.into(),
+            ValueType::Base(BaseType::I8) => PrimType::I8.into(),
+            ValueType::Base(BaseType::I16) => PrimType::I16.into(),
+            ValueType::Base(BaseType::I32) => PrimType::I32.into(),
+            ValueType::Base(BaseType::I64) => PrimType::I64.into(),
             ValueType::Base(BaseType::Char) => PrimType::Char.into(),
             ValueType::Tuple(vs) => {
                 match &vs[..] {
@@ -250,7 +257,59 @@ impl CodeGen {
                 }
                 GenType::Def((ix, tname), rtdef)
             }
+            ValueType::Newtype(name, inner) => {
+                // FIXME - hardcoded path_names version
+                self.name_gen
+                    .ctxt
+                    .push_atom(NameAtom::Explicit(name.clone()));
+                let rt_inner = match self.lift_type(inner).to_rust_type() {
+                    RustType::Atom(AtomType::Prim(pt)) if self.nonzero_newtypes.contains(name) => {
+                        RustType::from(pt.to_nonzero().unwrap_or(pt))
+                    }
+                    rt => rt,
+                };
+                let rtdef =
+                    RustTypeDef::Struct(RustStruct::Newtype(name.clone(), Box::new(rt_inner)));
+                let (tname, (ix, is_new)) = self.name_gen.get_name(&rtdef);
+                if is_new {
+                    self.defined_types.push(rtdef.clone());
+                }
+                // FIXME - hardcoded path_names version
+                self.name_gen.ctxt.escape();
+                GenType::Def((ix, tname), rtdef)
+            }
+        }
+    }
+
+    // NOTE - split out of `translate` to keep its (recursive) stack frame from growing
+    #[inline(never)]
+    fn translate_newtype(
+        &self,
+        gt: &GenType,
+        inner: &GTDecoderExt,
+        validate: Option<&GTExpr>,
+    ) -> CaseLogic<GTExpr> {
+        let Some((_ix, type_name)) = gt.try_as_adhoc() else {
+            panic!("unexpected type_hint for Decoder::Newtype: {:?}", gt)
+        };
+        if let GenType::Def(_, RustTypeDef::Struct(RustStruct::Newtype(_, inner_t))) = gt {
+            match inner_t.as_ref() {
+                RustType::Atom(AtomType::Prim(pt)) if pt.is_nonzero() => {
+                    // the non-zero check subsumes the predicate
+                    return CaseLogic::Derived(DerivedLogic::NonZeroOf(
+                        Constructor::Simple(type_name.clone()),
+                        *pt,
+                        Box::new(self.translate(inner.get_dec())),
+                    ));
+                }
+                _ => {}
+            }
         }
+        CaseLogic::Derived(DerivedLogic::NewtypeOf(
+            Constructor::Simple(type_name.clone()),
+            validate.map(|f| embed_lambda_dft(f, ClosureKind::Transform, true)),
+            Box::new(self.translate(inner.get_dec())),
+        ))
     }
 
     fn translate(&self, decoder: &GTDecoder) -> CaseLogic<GTExpr> {
@@ -260,6 +319,8 @@ impl CodeGen {
             TypedDecoder::Fail => CaseLogic::Simple(SimpleLogic::Fail),
             TypedDecoder::EndOfInput => CaseLogic::Simple(SimpleLogic::ExpectEnd),
             TypedDecoder::Align(n) => CaseLogic::Simple(SimpleLogic::SkipToNextMultiple(*n)),
+            TypedDecoder::AbsolutePos => CaseLogic::Simple(SimpleLogic::AbsolutePos),
+            TypedDecoder::SlicePos => CaseLogic::Simple(SimpleLogic::SlicePos),
             TypedDecoder::Byte(bs) => CaseLogic::Simple(SimpleLogic::ByteIn(*bs)),
             TypedDecoder::Variant(gt, name, inner) => {
                 let (type_name, def) = {
@@ -354,6 +415,8 @@ impl CodeGen {
                     }
                 }
             }
+            TypedDecoder::Newtype(gt, _name, inner, validate) =>
+                self.translate_newtype(gt, inner, validate.as_ref()),
             TypedDecoder::Parallel(_, alts) =>
                 CaseLogic::Parallel(
                     ParallelLogic::Alts(
@@ -423,11 +486,12 @@ impl CodeGen {
                     )
                 ),
 
-            TypedDecoder::RepeatCount(_gt, expr_count, single) =>
+            TypedDecoder::RepeatCount(_gt, expr_count, single, single_last) =>
                 CaseLogic::Repeat(
                     RepeatLogic::ExactCount(
                         embed_expr_dft(expr_count),
-                        Box::new(self.translate(single.get_dec()))
+                        Box::new(self.translate(single.get_dec())),
+                        single_last.as_ref().map(|last| Box::new(self.translate(last.get_dec())))
                     )
                 ),
             TypedDecoder::RepeatBetween(_gt, tree, expr_min, expr_max, single) => {
@@ -455,11 +519,26 @@ impl CodeGen {
                     )
                 )
             }
+            TypedDecoder::RepeatWhileInput(_gt, single) => {
+                CaseLogic::Repeat(
+                    RepeatLogic::WhileInput(Box::new(self.translate(single.get_dec())))
+                )
+            }
+            TypedDecoder::ForEach(_gt, seq, name, single) => {
+                CaseLogic::Repeat(
+                    RepeatLogic::ForEach(
+                        embed_expr(seq, ExprInfo::EmbedCloned),
+                        name.clone(),
+                        Box::new(self.translate(single.get_dec()))
+                    )
+                )
+            }
             TypedDecoder::Map(_gt, inner, f) => {
                 let cl_inner = self.translate(inner.get_dec());
                 CaseLogic::Derived(
                     DerivedLogic::MapOf(
                         embed_lambda_dft(f, ClosureKind::Transform, true),
+                        embed_lambda_dft(f, ClosureKind::Transform, false),
                         Box::new(cl_inner)
                     )
                 )
@@ -535,6 +614,11 @@ impl CodeGen {
                 let cl_inner = self.translate(inner.get_dec());
                 CaseLogic::Engine(EngineLogic::OffsetPeek(re_offset, Box::new(cl_inner)))
             }
+            TypedDecoder::FollowOffset(_t, offset, inner) => {
+                let re_offset = embed_expr(offset, ExprInfo::Natural);
+                let cl_inner = self.translate(inner.get_dec());
+                CaseLogic::Engine(EngineLogic::OffsetFollow(re_offset, Box::new(cl_inner)))
+            }
         }
     }
 }
@@ -561,6 +645,7 @@ fn embed_pattern_t(pat: &GTPattern) -> RustPattern {
         TypedPattern::Seq(_t, elts) => {
             RustPattern::ArrayLiteral(elts.iter().map(embed_pattern_t).collect())
         }
+        TypedPattern::Or(_t, alts) => RustPattern::Or(alts.iter().map(embed_pattern_t).collect()),
         TypedPattern::Wildcard(_) => RustPattern::CatchAll(None),
         TypedPattern::Binding(_, name) => RustPattern::CatchAll(Some(name.clone())),
         TypedPattern::Bool(b) => RustPattern::PrimLiteral(RustPrimLit::Boolean(*b)),
@@ -576,10 +661,69 @@ fn embed_pattern_t(pat: &GTPattern) -> RustPattern {
         TypedPattern::U64(n) => {
             RustPattern::PrimLiteral(RustPrimLit::Numeric(RustNumLit::Usize(*n as usize)))
         }
+        TypedPattern::U8Range(lo, hi) => RustPattern::PrimRange(
+            RustPrimLit::Numeric(RustNumLit::Usize(*lo as usize)),
+            RustPrimLit::Numeric(RustNumLit::Usize(*hi as usize)),
+        ),
+        TypedPattern::U16Range(lo, hi) => RustPattern::PrimRange(
+            RustPrimLit::Numeric(RustNumLit::Usize(*lo as usize)),
+            RustPrimLit::Numeric(RustNumLit::Usize(*hi as usize)),
+        ),
+        TypedPattern::U32Range(lo, hi) => RustPattern::PrimRange(
+            RustPrimLit::Numeric(RustNumLit::Usize(*lo as usize)),
+            RustPrimLit::Numeric(RustNumLit::Usize(*hi as usize)),
+        ),
+        TypedPattern::I8(n) => RustPattern::PrimLiteral(RustPrimLit::Numeric(RustNumLit::I8(*n))),
+        TypedPattern::I16(n) => RustPattern::PrimLiteral(RustPrimLit::Numeric(RustNumLit::I16(*n))),
+        TypedPattern::I32(n) => RustPattern::PrimLiteral(RustPrimLit::Numeric(RustNumLit::I32(*n))),
+        TypedPattern::I64(n) => RustPattern::PrimLiteral(RustPrimLit::Numeric(RustNumLit::I64(*n))),
         TypedPattern::Char(c) => RustPattern::PrimLiteral(RustPrimLit::Char(*c)),
     }
 }
 
+/// Embeds a boolean-valued match over `head` as a `matches!` test, provided that its final case is a
+/// catch-all yielding `false`, and that it is preceded either by any number of cases yielding `true`,
+/// or by a single case whose right-hand side can be used as a guard.
+///
+/// Returns `None` if `cases` are not of this shape.
+fn embed_matches(head: &RustExpr, cases: &[(GTPattern, GTExpr)]) -> Option<RustExpr> {
+    let [init @ .., (TypedPattern::Wildcard(_), TypedExpr::Bool(false))] = cases else {
+        return None;
+    };
+    match init {
+        [] => None,
+        // NOTE - guards can only observe pattern-bound variables by reference, so these must be `Copy` to be used freely
+        [(pat, rhs)] if !matches!(rhs, TypedExpr::Bool(_)) && binds_copy_only(pat) => {
+            let guard = embed_expr_dft(rhs);
+            if guard.may_short_circuit() {
+                return None;
+            }
+            Some(head.clone().matches([embed_pattern_t(pat)], Some(guard)))
+        }
+        _ => {
+            let pats = init
+                .iter()
+                .map(|(pat, rhs)| {
+                    matches!(rhs, TypedExpr::Bool(true)).then(|| embed_pattern_t(pat))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            Some(head.clone().matches(pats, None))
+        }
+    }
+}
+
+/// Returns `true` if every variable bound by `pat` is of a `Copy` type.
+fn binds_copy_only(pat: &GTPattern) -> bool {
+    match pat {
+        TypedPattern::Binding(gt, _) => gt.to_rust_type().is_copy(),
+        TypedPattern::Tuple(_, pats) | TypedPattern::Seq(_, pats) | TypedPattern::Or(_, pats) => {
+            pats.iter().all(binds_copy_only)
+        }
+        TypedPattern::Variant(_, _, inner) => binds_copy_only(inner),
+        _ => true,
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 enum ExprInfo {
     #[default]
@@ -609,7 +753,7 @@ fn embed_expr(expr: &GTExpr, info: ExprInfo) -> RustExpr {
                         Some(Box::new(embed_expr(val, ExprInfo::Natural))),
                     ))
                     .collect()
-            )
+            ).sequence_short_circuits()
         }
         TypedExpr::Variant(gt, vname, inner) => {
             match gt {
@@ -660,6 +804,9 @@ fn embed_expr(expr: &GTExpr, info: ExprInfo) -> RustExpr {
                 ) => scrutinized.call_method("as_ref"),
                 _ => scrutinized,
             };
+            if let Some(test) = embed_matches(&head, cases) {
+                return test;
+            }
             let ck = refutability_check(
                 &*scrutinee.get_type().expect("unexpected lambda in match-scrutinee position"),
                 cases
@@ -689,7 +836,7 @@ fn embed_expr(expr: &GTExpr, info: ExprInfo) -> RustExpr {
                     .iter()
                     .map(|x| embed_expr(x, info))
                     .collect()
-            ),
+            ).sequence_short_circuits(),
         TypedExpr::TupleProj(_, expr_tup, ix) => {
             // FIXME - field and index projections should be optimized around whole-object clone avoidance, when possible
             embed_expr(expr_tup, ExprInfo::EmbedCloned).nth(*ix)
@@ -713,6 +860,7 @@ fn embed_expr(expr: &GTExpr, info: ExprInfo) -> RustExpr {
             let op = match arith {
                 Arith::BitAnd => Operator::BitAnd,
                 Arith::BitOr => Operator::BitOr,
+                Arith::BitXor => Operator::BitXor,
                 Arith::Add => Operator::Add,
                 Arith::Sub => Operator::Sub,
                 Arith::Mul => Operator::Mul,
@@ -746,6 +894,13 @@ fn embed_expr(expr: &GTExpr, info: ExprInfo) -> RustExpr {
             RustExpr::Operation(RustOp::AsCast(Box::new(embed_expr_dft(x)), PrimType::U32.into())),
         TypedExpr::AsU64(x) =>
             RustExpr::Operation(RustOp::AsCast(Box::new(embed_expr_dft(x)), PrimType::U64.into())),
+        // NOTE - `as`-casts between integers of the same width reinterpret the two's-complement bit-pattern
+        TypedExpr::AsSigned8(x) =>
+            RustExpr::Operation(RustOp::AsCast(Box::new(embed_expr_dft(x)), PrimType::I8.into())),
+        TypedExpr::AsSigned16(x) =>
+            RustExpr::Operation(RustOp::AsCast(Box::new(embed_expr_dft(x)), PrimType::I16.into())),
+        TypedExpr::AsSigned32(x) =>
+            RustExpr::Operation(RustOp::AsCast(Box::new(embed_expr_dft(x)), PrimType::I32.into())),
         TypedExpr::U16Be(be_bytes) =>
             RustExpr::local("u16be").call_with([embed_expr_dft(be_bytes)]),
         TypedExpr::U16Le(le_bytes) =>
@@ -758,6 +913,14 @@ fn embed_expr(expr: &GTExpr, info: ExprInfo) -> RustExpr {
             RustExpr::local("u64be").call_with([embed_expr_dft(be_bytes)]),
         TypedExpr::U64Le(le_bytes) =>
             RustExpr::local("u64le").call_with([embed_expr_dft(le_bytes)]),
+        TypedExpr::I16Be(be_bytes) =>
+            RustExpr::local("i16be").call_with([embed_expr_dft(be_bytes)]),
+        TypedExpr::I16Le(le_bytes) =>
+            RustExpr::local("i16le").call_with([embed_expr_dft(le_bytes)]),
+        TypedExpr::I32Be(be_bytes) =>
+            RustExpr::local("i32be").call_with([embed_expr_dft(be_bytes)]),
+        TypedExpr::I32Le(le_bytes) =>
+            RustExpr::local("i32le").call_with([embed_expr_dft(le_bytes)]),
         TypedExpr::AsChar(codepoint) =>
             RustExpr::scoped(["char"], "from_u32")
                 .call_with([embed_expr_dft(codepoint)])
@@ -771,6 +934,20 @@ fn embed_expr(expr: &GTExpr, info: ExprInfo) -> RustExpr {
                 )
             )
         }
+        TypedExpr::SeqIx(gt, seq, ix) => {
+            let elem = RustExpr::Index(
+                Box::new(embed_expr(seq, ExprInfo::Natural)),
+                Box::new(
+                    RustExpr::Operation(RustOp::AsCast(Box::new(embed_expr_dft(ix)), PrimType::Usize.into()))
+                )
+            );
+            // NOTE - elements of `Copy` type are moved out of the sequence as-is, without an explicit `.clone()`
+            if gt.to_rust_type().is_copy() {
+                elem
+            } else {
+                elem.call_method("clone")
+            }
+        }
         TypedExpr::SubSeq(_, seq, ix, len) => {
             let start_expr = embed_expr_dft(ix);
             let bind_ix = RustStmt::assign(
@@ -821,14 +998,14 @@ fn embed_expr(expr: &GTExpr, info: ExprInfo) -> RustExpr {
         TypedExpr::FlatMap(_, f, seq) =>
             RustExpr::local("try_flat_map_vec")
                 .call_with([
-                    embed_expr(seq, ExprInfo::Natural).call_method("iter").call_method("cloned"),
+                    embed_seq_iter(seq),
                     embed_lambda(f, ClosureKind::Transform, true, ExprInfo::EmbedCloned),
                 ])
                 .wrap_try(),
         TypedExpr::FlatMapAccum(_, f, acc_init, _acc_type, seq) =>
             RustExpr::local("try_fold_map_curried")
                 .call_with([
-                    embed_expr(seq, ExprInfo::Natural).call_method("iter").call_method("cloned"),
+                    embed_seq_iter(seq),
                     embed_expr(acc_init, ExprInfo::EmbedCloned),
                     embed_lambda(f, ClosureKind::Transform, true, ExprInfo::EmbedCloned),
                 ])
@@ -836,7 +1013,7 @@ fn embed_expr(expr: &GTExpr, info: ExprInfo) -> RustExpr {
         TypedExpr::FlatMapList(_, f, _ret_type, seq) =>
             RustExpr::local("try_flat_map_append_vec")
                 .call_with([
-                    embed_expr(seq, ExprInfo::Natural).call_method("iter").call_method("cloned"),
+                    embed_seq_iter(seq),
                     embed_lambda_dft(f, ClosureKind::PairBorrowOwned, true),
                 ])
                 .wrap_try(),
@@ -860,6 +1037,10 @@ fn embed_expr(expr: &GTExpr, info: ExprInfo) -> RustExpr {
         TypedExpr::U16(n) => RustExpr::u16lit(*n),
         TypedExpr::U32(n) => RustExpr::u32lit(*n),
         TypedExpr::U64(n) => RustExpr::u64lit(*n),
+        TypedExpr::I8(n) => RustExpr::PrimitiveLit(RustPrimLit::Numeric(RustNumLit::I8(*n))),
+        TypedExpr::I16(n) => RustExpr::PrimitiveLit(RustPrimLit::Numeric(RustNumLit::I16(*n))),
+        TypedExpr::I32(n) => RustExpr::PrimitiveLit(RustPrimLit::Numeric(RustNumLit::I32(*n))),
+        TypedExpr::I64(n) => RustExpr::PrimitiveLit(RustPrimLit::Numeric(RustNumLit::I64(*n))),
         TypedExpr::Lambda(_, _, _) =>
             unreachable!(
                 "TypedExpr::Lambda unsupported as first-class embed (requires embed_lambda with proper ClosureKind argument)"
@@ -871,6 +1052,36 @@ fn embed_expr_dft(expr: &TypedExpr<GenType>) -> RustExpr {
     embed_expr(expr, ExprInfo::default())
 }
 
+/// Embeds an iterator over the elements of `seq` by value, copying rather than cloning them when they are
+/// of a `Copy` type.
+fn embed_seq_iter(seq: &GTExpr) -> RustExpr {
+    let elem_type = seq
+        .get_type()
+        .and_then(|gt| Lens::ElemOf(Box::new(Lens::Ground(gt.to_rust_type()))).resolve());
+    match elem_type {
+        Some(elem_type) => embed_expr(seq, ExprInfo::Natural).iter_owned(elem_type),
+        None => embed_expr(seq, ExprInfo::Natural)
+            .call_method("iter")
+            .call_method("cloned"),
+    }
+}
+
+/// Converts the `Vec` decoded by a `RepeatCount` into a fixed-size array of `elem_t`, if
+/// its count-expression is a numeric constant.
+///
+/// Returns `None` if the count is not known until runtime.
+#[cfg_attr(not(test), allow(dead_code))]
+fn embed_fixed_array(expr_count: &GTExpr, elem_t: RustType, seq: RustExpr) -> Option<RustExpr> {
+    let len = match expr_count {
+        TypedExpr::U8(n) => usize::from(*n),
+        TypedExpr::U16(n) => usize::from(*n),
+        TypedExpr::U32(n) => usize::try_from(*n).ok()?,
+        TypedExpr::U64(n) => usize::try_from(*n).ok()?,
+        _ => return None,
+    };
+    Some(seq.vec_into_array(elem_t, len))
+}
+
 #[derive(Clone, Copy, Debug, PartialOrd, PartialEq, Ord, Eq, Default)]
 enum Refutability {
     Refutable,
@@ -896,6 +1107,17 @@ fn refutability_check<A: std::fmt::Debug>(
     if contains_irrefutable_pattern(cases) {
         return Refutability::Irrefutable;
     }
+    if cases
+        .iter()
+        .any(|(pat, _)| matches!(pat, TypedPattern::Or(..)))
+    {
+        // each alternative of a top-level or-pattern covers values just as a standalone case would
+        let mut flat_cases = Vec::with_capacity(cases.len());
+        for (pat, _) in cases {
+            flatten_or_pattern(pat, &mut flat_cases);
+        }
+        return refutability_check(head_type, &flat_cases);
+    }
     match head_type {
         GenType::Inline(rt) =>
             match rt {
@@ -921,12 +1143,28 @@ fn refutability_check<A: std::fmt::Debug>(
                                         Refutability::Irrefutable
                                     }
                                 }
+                                // byte-sized domains are small enough to check for full literal coverage
+                                PrimType::U8 => literal_coverage(cases, |pat| match pat {
+                                    TypedPattern::U8(n) => Some(*n..=*n),
+                                    TypedPattern::U8Range(lo, hi) => Some(*lo..=*hi),
+                                    _ => None,
+                                }),
+                                PrimType::I8 => literal_coverage(cases, |pat| match pat {
+                                    TypedPattern::I8(n) => Some(*n as u8..=*n as u8),
+                                    _ => None,
+                                }),
                                 // these cases have too many values to practically cover...
-                                | PrimType::U8
                                 | PrimType::U16
                                 | PrimType::U32
                                 | PrimType::U64
-                                | PrimType::Char => Refutability::Indeterminate,
+                                | PrimType::I16
+                                | PrimType::I32
+                                | PrimType::I64
+                                | PrimType::Char
+                                | PrimType::NonZeroU8
+                                | PrimType::NonZeroU16
+                                | PrimType::NonZeroU32
+                                | PrimType::NonZeroU64 => Refutability::Indeterminate,
                                 //
                                 PrimType::Bool => {
                                     // mask for inclusion with indices 0: false, 1: true
@@ -953,10 +1191,11 @@ fn refutability_check<A: std::fmt::Debug>(
                             }
                         AtomType::Comp(ct) =>
                             match ct {
-                                CompType::Vec(_) => Refutability::Refutable, // Vec can have any length, so no match can be exhaustive without catchalls
+                                CompType::Vec(_) | CompType::Slice(_) => Refutability::Refutable, // Vec can have any length, so no match can be exhaustive without catchalls
+                                CompType::Array(..) => Refutability::Refutable,
                                 CompType::Result(_, _) =>
                                     unreachable!("unexpected result in pattern head-type"),
-                                CompType::Borrow(_, _, t) => {
+                                CompType::Borrow(_, _, t) | CompType::Boxed(t) | CompType::Cow(_, t) => {
                                     refutability_check(&GenType::Inline((&**t).clone()), cases)
                                 }
                             }
@@ -971,6 +1210,8 @@ fn refutability_check<A: std::fmt::Debug>(
                 }
                 RustType::Verbatim(_, _) =>
                     unreachable!("verbatim types not expected in generated match-expressions"),
+                RustType::DynTrait(..) =>
+                    unreachable!("trait objects not expected in generated match-expressions"),
             }
         GenType::Def(_, def) => {
             match def {
@@ -1011,9 +1252,117 @@ fn refutability_check<A: std::fmt::Debug>(
     }
 }
 
+/// Determines whether the literal patterns among `cases` jointly cover every value of a byte-sized
+/// type, with `as_bytes` mapping each literal or range pattern onto the bit-patterns it matches.
+fn literal_coverage<A>(
+    cases: &[(TypedPattern<GenType>, A)],
+    as_bytes: impl Fn(&TypedPattern<GenType>) -> Option<std::ops::RangeInclusive<u8>>,
+) -> Refutability {
+    let mut cover_mask = [false; 256];
+    for (pat, _) in cases {
+        if let Some(bytes) = as_bytes(pat) {
+            for byte in bytes {
+                cover_mask[byte as usize] = true;
+            }
+        }
+    }
+    if cover_mask.iter().all(|covered| *covered) {
+        Refutability::Irrefutable
+    } else {
+        Refutability::Refutable
+    }
+}
+
+/// Recognizes predicates of the form `|x| x != 0` (or `|x| 0 != x`).
+fn is_nonzero_predicate(expr: &Expr) -> bool {
+    let is_zero = |e: &Expr| matches!(e, Expr::U8(0) | Expr::U16(0) | Expr::U32(0) | Expr::U64(0));
+    match expr {
+        Expr::Lambda(head, body) => match body.as_ref() {
+            Expr::IntRel(IntRel::Ne, lhs, rhs) => match (lhs.as_ref(), rhs.as_ref()) {
+                (Expr::Var(x), zero) | (zero, Expr::Var(x)) => x == head && is_zero(zero),
+                _ => false,
+            },
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Collects the names of all newtypes reachable from `format` whose predicate satisfies [`is_nonzero_predicate`].
+fn collect_nonzero_newtypes(
+    module: &FormatModule,
+    format: &Format,
+    visited: &mut HashSet<usize>,
+    acc: &mut HashSet<Label>,
+) {
+    let children: Vec<&Format> = match format {
+        Format::ItemVar(level, _args) => {
+            if visited.insert(*level) {
+                vec![module.get_format(*level)]
+            } else {
+                Vec::new()
+            }
+        }
+        Format::Fail
+        | Format::EndOfInput
+        | Format::Align(_)
+        | Format::AbsolutePos
+        | Format::SlicePos
+        | Format::Byte(_)
+        | Format::Compute(_)
+        | Format::Apply(_) => Vec::new(),
+        Format::Newtype(name, inner, validate) => {
+            if validate.as_ref().is_some_and(is_nonzero_predicate) {
+                acc.insert(name.clone());
+            }
+            vec![inner]
+        }
+        Format::Union(branches) | Format::UnionNondet(branches) | Format::Tuple(branches) => {
+            branches.iter().collect()
+        }
+        Format::Record(fields) => fields.iter().map(|(_, f)| f).collect(),
+        Format::Match(_, branches) => branches.iter().map(|(_, f)| f).collect(),
+        Format::RepeatSep(f, sep) | Format::Signature(_, f, sep) => vec![f, sep],
+        Format::Variant(_, f)
+        | Format::Repeat(f)
+        | Format::Repeat1(f)
+        | Format::RepeatCount(_, f)
+        | Format::RepeatBetween(_, _, f)
+        | Format::RepeatUntilLast(_, f)
+        | Format::RepeatUntilSeq(_, f)
+        | Format::UntilSeq(_, f)
+        | Format::ForEach(_, _, f)
+        | Format::RepeatWhileInput(f)
+        | Format::Peek(f)
+        | Format::PeekNot(f)
+        | Format::Slice(_, f)
+        | Format::Bits(f)
+        | Format::WithRelativeOffset(_, f)
+        | Format::FollowOffset(_, f)
+        | Format::Map(f, _)
+        | Format::Let(_, _, f)
+        | Format::Dynamic(_, _, f) => vec![f],
+    };
+    for f in children {
+        collect_nonzero_newtypes(module, f, visited, acc);
+    }
+}
+
+fn flatten_or_pattern(pat: &TypedPattern<GenType>, acc: &mut Vec<(TypedPattern<GenType>, ())>) {
+    match pat {
+        TypedPattern::Or(_, alts) => {
+            for alt in alts {
+                flatten_or_pattern(alt, acc);
+            }
+        }
+        _ => acc.push((pat.clone(), ())),
+    }
+}
+
 fn is_pattern_irrefutable(pat: &TypedPattern<GenType>) -> bool {
     match pat {
         TypedPattern::Binding(..) | TypedPattern::Wildcard(..) => true,
+        TypedPattern::Or(_, alts) => alts.iter().any(is_pattern_irrefutable),
         TypedPattern::Tuple(_, elts) => elts.iter().all(is_pattern_irrefutable),
         TypedPattern::Seq(..) => false, // there is no exhaustive pattern-set for sequences as they can have any length...
         TypedPattern::Variant(gt, lab, inner) => {
@@ -1113,12 +1462,21 @@ type RustBlock = (Vec<RustStmt>, Option<RustExpr>);
 #[derive(Clone, Copy)]
 pub(crate) struct ProdCtxt<'a> {
     input_varname: &'a Label,
+    /// Indexed by decoder number, `true` for decoders whose functions cannot fail and so do not return a `PResult`
+    infallible: &'a [bool],
+    /// If `true`, validation predicates are checked with `debug_assert!` instead of by an early return
+    debug_invariants: bool,
+    /// If `true`, the logic being produced belongs to an infallible decoder, and so cannot use `?`
+    elide_result: bool,
 }
 
 impl<'a> Default for ProdCtxt<'a> {
     fn default() -> Self {
         Self {
             input_varname: &Cow::Borrowed(""),
+            infallible: &[],
+            debug_invariants: false,
+            elide_result: false,
         }
     }
 }
@@ -1191,7 +1549,11 @@ impl SimpleLogic<GTExpr> {
                     }
                 };
                 let call = RustExpr::local(fname).call_with(call_args);
-                (Vec::new(), Some(call.wrap_try()))
+                if ctxt.infallible.get(*ix_dec) == Some(&true) {
+                    (Vec::new(), Some(call))
+                } else {
+                    (Vec::new(), Some(call.wrap_try()))
+                }
             }
             SimpleLogic::CallDynamic(dynf_name) => {
                 let call = RustExpr::local(dynf_name.clone())
@@ -1206,6 +1568,22 @@ impl SimpleLogic<GTExpr> {
                         .wrap_try(),
                 ),
             ),
+            SimpleLogic::AbsolutePos => (
+                Vec::new(),
+                Some(
+                    RustExpr::local(ctxt.input_varname.clone())
+                        .call_method("get_absolute_pos")
+                        .wrap_try(),
+                ),
+            ),
+            SimpleLogic::SlicePos => (
+                Vec::new(),
+                Some(
+                    RustExpr::local(ctxt.input_varname.clone())
+                        .call_method("get_slice_pos")
+                        .wrap_try(),
+                ),
+            ),
             SimpleLogic::ByteIn(bs) => {
                 let call = RustExpr::local(ctxt.input_varname.clone())
                     .call_method("read_byte")
@@ -1213,20 +1591,15 @@ impl SimpleLogic<GTExpr> {
                 let b_let = RustStmt::assign("b", call);
                 let (cond, always_true) =
                     ByteCriterion::from(bs).as_predicate(RustExpr::local("b"));
-                let logic = if always_true {
-                    RustExpr::local("b")
-                } else {
-                    let b_true = vec![RustStmt::Return(ReturnKind::Implicit, RustExpr::local("b"))];
-                    let b_false = vec![RustStmt::Return(
-                        ReturnKind::Keyword,
-                        RustExpr::err(
-                            RustExpr::scoped(["ParseError"], "ExcludedBranch")
-                                .call_with([RustExpr::u64lit(get_trace(bs))]),
-                        ),
-                    )];
-                    RustExpr::Control(Box::new(RustControl::If(cond, b_true, Some(b_false))))
-                };
-                ([b_let].to_vec(), Some(logic))
+                let mut stmts = vec![b_let];
+                if !always_true {
+                    stmts.push(RustStmt::guard(
+                        cond,
+                        RustExpr::scoped(["ParseError"], "ExcludedBranch")
+                            .call_with([RustExpr::u64lit(get_trace(bs))]),
+                    ));
+                }
+                (stmts, Some(RustExpr::local("b")))
             }
             SimpleLogic::Eval(expr) => (vec![], Some(expr.clone())),
         }
@@ -1507,6 +1880,7 @@ enum EngineLogic<ExprT> {
     Bits(Box<CaseLogic<ExprT>>),
     PeekNot(Box<CaseLogic<ExprT>>),
     OffsetPeek(RustExpr, Box<CaseLogic<ExprT>>),
+    OffsetFollow(RustExpr, Box<CaseLogic<ExprT>>),
 }
 
 impl<ExprT> ToAst for EngineLogic<ExprT>
@@ -1609,6 +1983,22 @@ where
                 ],
                 Some(RustExpr::local("ret")),
             ),
+            EngineLogic::OffsetFollow(offs, cl_inner) => (
+                vec![
+                    RustStmt::Expr(
+                        RustExpr::local(ctxt.input_varname.clone())
+                            .call_method_with("advance_by", [offs.clone()])
+                            .wrap_try(),
+                    ),
+                    RustStmt::assign(
+                        "ret",
+                        abstracted_try_block(cl_inner.to_ast(ctxt))
+                            .call()
+                            .wrap_try(),
+                    ),
+                ],
+                Some(RustExpr::local("ret")),
+            ),
 
             EngineLogic::PeekNot(cl_inner) => (
                 vec![
@@ -1667,13 +2057,83 @@ enum RepeatLogic<ExprT> {
     /// evaluates a matchtree and breaks if it is matched
     Repeat1BreakOnMatch(MatchTree, Box<CaseLogic<ExprT>>),
     /// repeats a specific number of times
-    ExactCount(RustExpr, Box<CaseLogic<ExprT>>),
+    /// Element logic, along with distinct logic for the final element if its lookahead differs
+    ExactCount(
+        RustExpr,
+        Box<CaseLogic<ExprT>>,
+        Option<Box<CaseLogic<ExprT>>>,
+    ),
     /// Repeats between N and M times
     BetweenCounts(MatchTree, RustExpr, RustExpr, Box<CaseLogic<ExprT>>),
     /// Repetition stops after a predicate for 'terminal element' is satisfied
     ConditionTerminal(RustExpr, Box<CaseLogic<ExprT>>),
     /// Repetition stops after a predicate for 'complete sequence' is satisfied (post-append)
     ConditionComplete(RustExpr, Box<CaseLogic<ExprT>>),
+    /// Repeats once for each element of a sequence, which is bound to the given name in each iteration
+    ForEach(RustExpr, Label, Box<CaseLogic<ExprT>>),
+    /// Repeats for as long as input remains in the current slice (or buffer)
+    WhileInput(Box<CaseLogic<ExprT>>),
+}
+
+fn for_each_to_ast<ExprT>(
+    seq: &RustExpr,
+    name: &Label,
+    elt: &CaseLogic<ExprT>,
+    ctxt: ProdCtxt<'_>,
+) -> RustBlock
+where
+    CaseLogic<ExprT>: ToAst<AstElem = RustBlock>,
+{
+    let mut stmts = Vec::new();
+
+    let elt_expr = elt.to_ast(ctxt).into();
+
+    stmts.push(RustStmt::Let(
+        Mut::Mutable,
+        Label::from("accum"),
+        None,
+        RustExpr::scoped(["Vec"], "new").call(),
+    ));
+    let body = vec![RustStmt::Expr(
+        RustExpr::local("accum").call_method_with("push", [elt_expr]),
+    )];
+    stmts.push(RustStmt::Control(RustControl::ForIter(
+        name.clone(),
+        seq.clone(),
+        body,
+    )));
+
+    (stmts, Some(RustExpr::local("accum")))
+}
+
+fn while_input_to_ast<ExprT>(elt: &CaseLogic<ExprT>, ctxt: ProdCtxt<'_>) -> RustBlock
+where
+    CaseLogic<ExprT>: ToAst<AstElem = RustBlock>,
+{
+    let mut stmts = Vec::new();
+
+    let elt_expr = elt.to_ast(ctxt).into();
+
+    stmts.push(RustStmt::Let(
+        Mut::Mutable,
+        Label::from("accum"),
+        None,
+        RustExpr::scoped(["Vec"], "new").call(),
+    ));
+    let cond = RustExpr::infix(
+        RustExpr::local(ctxt.input_varname.clone()).call_method("remaining"),
+        Operator::Gt,
+        RustExpr::num_lit(0usize),
+    );
+    let body = vec![
+        RustStmt::assign("elem", elt_expr),
+        RustStmt::Expr(
+            RustExpr::local("accum").call_method_with("push", [RustExpr::local("elem")]),
+        ),
+    ];
+    stmts.push(RustStmt::Control(RustControl::While(cond, body)));
+
+    (stmts, Some(RustExpr::local("accum")))
 }
 
 pub(crate) trait ToAst {
@@ -1837,10 +2297,15 @@ where
                 stmts.push(ctrl);
                 (stmts, Some(RustExpr::local("accum")))
             }
-            RepeatLogic::ExactCount(expr_n, elt) => {
+            RepeatLogic::ExactCount(expr_n, elt, elt_last) => {
                 let mut stmts = Vec::new();
 
-                let elt_expr = elt.to_ast(ctxt).into();
+                let push = |elt: &CaseLogic<ExprT>| {
+                    vec![RustStmt::Expr(
+                        RustExpr::local("accum")
+                            .call_method_with("push", [elt.to_ast(ctxt).into()]),
+                    )]
+                };
 
                 stmts.push(RustStmt::Let(
                     Mut::Mutable,
@@ -1848,18 +2313,40 @@ where
                     None,
                     RustExpr::scoped(["Vec"], "new").call(),
                 ));
-                // N non-loop blocks rather than 1 block representing an N-iteration loop
-                let body = vec![RustStmt::Expr(
-                    RustExpr::local("accum").call_method_with("push", [elt_expr]),
-                )];
-                stmts.push(RustStmt::Control(RustControl::ForRange0(
-                    Label::from("_"),
-                    expr_n.clone(),
-                    body,
-                )));
+                match elt_last {
+                    // N non-loop blocks rather than 1 block representing an N-iteration loop
+                    None => stmts.push(RustStmt::Control(RustControl::ForRange0(
+                        Label::from("_"),
+                        expr_n.clone(),
+                        push(elt),
+                    ))),
+                    Some(elt_last) => {
+                        stmts.push(RustStmt::assign("repeat_count", expr_n.clone()));
+                        let is_last = RustExpr::Operation(RustOp::op_eq(
+                            RustExpr::infix(
+                                RustExpr::local("ix"),
+                                Operator::Add,
+                                RustExpr::num_lit(1usize),
+                            ),
+                            RustExpr::local("repeat_count"),
+                        ));
+                        let body = vec![RustStmt::Control(RustControl::If(
+                            is_last,
+                            push(elt_last),
+                            Some(push(elt)),
+                        ))];
+                        stmts.push(RustStmt::Control(RustControl::ForRange0(
+                            Label::from("ix"),
+                            RustExpr::local("repeat_count"),
+                            body,
+                        )));
+                    }
+                }
 
                 (stmts, Some(RustExpr::local("accum")))
             }
+            RepeatLogic::ForEach(seq, name, elt) => for_each_to_ast(seq, name, elt, ctxt),
+            RepeatLogic::WhileInput(elt) => while_input_to_ast(elt, ctxt),
             RepeatLogic::ConditionTerminal(tpred, elt) => {
                 let mut stmts = Vec::new();
                 let elt_expr = elt.to_ast(ctxt).into();
@@ -1884,13 +2371,35 @@ where
                         RustStmt::Control(RustControl::Break),
                     ]
                     .to_vec();
-                    let b_else = [RustStmt::Expr(
-                        RustExpr::local("accum")
-                            .call_method_with("push", [RustExpr::local("elem")]),
-                    )]
+                    // an empty non-terminal iteration would be repeated identically forever
+                    let no_progress = RustExpr::infix(
+                        RustExpr::local(ctxt.input_varname.clone())
+                            .call_method("get_current_offset"),
+                        Operator::Eq,
+                        RustExpr::local("iter_start"),
+                    );
+                    let b_else = [
+                        RustStmt::Control(RustControl::If(
+                            no_progress,
+                            vec![RustStmt::Return(
+                                ReturnKind::Keyword,
+                                RustExpr::err(RustExpr::scoped(["ParseError"], "FailToken")),
+                            )],
+                            None,
+                        )),
+                        RustStmt::Expr(
+                            RustExpr::local("accum")
+                                .call_method_with("push", [RustExpr::local("elem")]),
+                        ),
+                    ]
                     .to_vec();
                     let escape_clause = RustControl::If(cond, b_terminal, Some(b_else));
                     RustStmt::Control(RustControl::Loop(vec![
+                        RustStmt::assign(
+                            "iter_start",
+                            RustExpr::local(ctxt.input_varname.clone())
+                                .call_method("get_current_offset"),
+                        ),
                         elt_bind,
                         RustStmt::Control(escape_clause),
                     ]))
@@ -2188,6 +2697,8 @@ enum SimpleLogic<ExprT> {
     ExpectEnd,
     Invoke(usize, Vec<(Label, ExprT)>),
     SkipToNextMultiple(usize),
+    AbsolutePos,
+    SlicePos,
     ByteIn(ByteSet),
     Eval(RustExpr),
     CallDynamic(Label),
@@ -2198,7 +2709,10 @@ enum SimpleLogic<ExprT> {
 enum DerivedLogic<ExprT> {
     VariantOf(Constructor, Box<CaseLogic<ExprT>>),
     UnitVariantOf(Constructor, Box<CaseLogic<ExprT>>),
-    MapOf(RustExpr, Box<CaseLogic<ExprT>>),
+    NewtypeOf(Constructor, Option<RustExpr>, Box<CaseLogic<ExprT>>),
+    NonZeroOf(Constructor, PrimType, Box<CaseLogic<ExprT>>),
+    /// Fallible and infallible forms of the mapped closure, followed by the inner logic
+    MapOf(RustExpr, RustExpr, Box<CaseLogic<ExprT>>),
     Let(Label, RustExpr, Box<CaseLogic<ExprT>>),
     Dynamic(DynamicLogic<ExprT>, Box<CaseLogic<TypedExpr<GenType>>>),
 }
@@ -2260,13 +2774,53 @@ impl ToAst for DerivedLogic<GTExpr> {
                     None => (vec![], Some(RustExpr::local(Label::from(constr.clone())))),
                 }
             }
-            DerivedLogic::MapOf(f, inner) => {
+            DerivedLogic::NewtypeOf(constr, validate, inner) => {
+                let mut stmts = vec![RustStmt::assign(
+                    "inner",
+                    RustExpr::from(inner.to_ast(ctxt)),
+                )];
+                if let Some(pred) = validate {
+                    let cond = pred
+                        .clone()
+                        .call_with([RustExpr::local("inner").call_method("clone")])
+                        .wrap_try();
+                    if ctxt.debug_invariants {
+                        stmts.push(RustStmt::Expr(cond.assert(true)));
+                    } else {
+                        stmts.push(RustStmt::guard(
+                            cond,
+                            RustExpr::scoped(["ParseError"], "FailToken"),
+                        ));
+                    }
+                }
+                (
+                    stmts,
+                    Some(
+                        RustExpr::local(Label::from(constr.clone()))
+                            .call_with([RustExpr::local("inner")]),
+                    ),
+                )
+            }
+            DerivedLogic::NonZeroOf(constr, pt, inner) => {
                 let assign_inner = RustStmt::assign("inner", RustExpr::from(inner.to_ast(ctxt)));
+                let nonzero = RustExpr::scoped([pt.to_fragment().to_string()], "new")
+                    .call_with([RustExpr::local("inner")])
+                    .call_method_with("ok_or", [RustExpr::scoped(["ParseError"], "FailToken")])
+                    .wrap_try();
                 (
                     vec![assign_inner],
-                    Some(f.clone().call_with([RustExpr::local("inner")]).wrap_try()),
+                    Some(RustExpr::local(Label::from(constr.clone())).call_with([nonzero])),
                 )
             }
+            DerivedLogic::MapOf(f, f_infallible, inner) => {
+                let assign_inner = RustStmt::assign("inner", RustExpr::from(inner.to_ast(ctxt)));
+                let ret = if ctxt.elide_result {
+                    f_infallible.clone().call_with([RustExpr::local("inner")])
+                } else {
+                    f.clone().call_with([RustExpr::local("inner")]).wrap_try()
+                };
+                (vec![assign_inner], Some(ret))
+            }
             DerivedLogic::Let(name, expr, inner) => {
                 let mut stmts = Vec::new();
                 stmts.push(RustStmt::assign(name.clone(), expr.clone()));
@@ -2278,11 +2832,75 @@ impl ToAst for DerivedLogic<GTExpr> {
     }
 }
 
+/// Options controlling the form of the code emitted by [`print_generated_code_with`].
+#[derive(Clone, Debug, Default)]
+pub struct GenOptions {
+    /// Emit `unsafe { x.get_unchecked(ix) }` in place of `x[ix]` for indexing operations (see [`RustExpr::index`]).
+    ///
+    /// Off by default. Enabling it is only sound if every index is in-bounds for every input the generated code is
+    /// run on; an out-of-bounds index is undefined behavior rather than a panic.
+    pub use_unchecked_indexing: bool,
+    /// Emit a builder-type alongside each record-struct definition.
+    pub generate_builders: bool,
+    /// Emit decoder functions for formats that can never fail (see [`Format::is_infallible`])
+    /// as returning their value directly, rather than wrapped in a `PResult`.
+    pub elide_infallible: bool,
+    /// Check the validation predicates of newtypes with `debug_assert!` rather than rejecting invalid values
+    /// with a `ParseError`, so that the check is skipped entirely in release builds.
+    pub debug_invariants: bool,
+    /// Emit a public function of the given name that parses a byte-slice according to the top-level format,
+    /// so that callers need not know which numbered decoder function corresponds to it.
+    pub entry_point: Option<Label>,
+}
+
 pub fn print_generated_code(
     module: &FormatModule,
     top_format: &Format,
     dest: Option<std::path::PathBuf>,
 ) {
+    print_generated_code_with(module, top_format, dest, GenOptions::default())
+}
+
+pub fn print_generated_code_with(
+    module: &FormatModule,
+    top_format: &Format,
+    dest: Option<std::path::PathBuf>,
+    options: GenOptions,
+) {
+    let content = generate_program(module, top_format, options);
+
+    fn write_to(mut f: impl std::io::Write, content: RustProgram) -> std::io::Result<()> {
+        write!(f, "{}", content.render())
+    }
+
+    match dest {
+        None => write_to(std::io::stdout().lock(), content).expect("failed to write"),
+        Some(path) => {
+            if !path.exists()
+                || (path.is_file()
+                    && path
+                        .file_name()
+                        .is_some_and(|s| s.to_string_lossy().contains("codegen.rs")))
+            {
+                let f = std::fs::File::create(path).unwrap_or_else(|err| panic!("error: {err}"));
+                write_to(f, content).expect("failed to write");
+            } else {
+                panic!(
+                    "will not overwrite directory or protected file: {}",
+                    path.to_string_lossy()
+                );
+            }
+        }
+    }
+}
+
+/// Assembles the complete Rust program (type definitions, decoder functions, imports and module attributes)
+/// generated for `top_format`.
+fn generate_program(
+    module: &FormatModule,
+    top_format: &Format,
+    options: GenOptions,
+) -> RustProgram {
     let mut items = Vec::new();
 
     let Generator {
@@ -2306,14 +2924,32 @@ pub fn print_generated_code(
             .ctxt
             .find_name_for(&path)
             .expect("no name found");
-        let it = RustItem::pub_decl(RustDecl::type_def(name, tdef.clone()));
+        let it = RustItem::pub_decl(RustDecl::type_def(name.clone(), tdef.clone()));
         items.push(it);
+        if options.generate_builders {
+            if let RustTypeDef::Struct(RustStruct::Record(fields)) = tdef {
+                items.extend(RustItem::struct_builder(&name, fields));
+            }
+        }
     }
 
+    let ctxt = ProdCtxt {
+        infallible: if options.elide_infallible {
+            &sourcemap.infallible
+        } else {
+            &[]
+        },
+        debug_invariants: options.debug_invariants,
+        ..ProdCtxt::default()
+    };
     for decfn in sourcemap.decoder_skels.iter() {
-        items.push(RustItem::from_decl(RustDecl::Function(
-            decfn.to_ast(ProdCtxt::default()),
-        )));
+        items.push(RustItem::from_decl(RustDecl::Function(decfn.to_ast(ctxt))));
+    }
+
+    if let Some(name) = options.entry_point {
+        let infallible = options.elide_infallible && sourcemap.infallible.first() == Some(&true);
+        let entry = sourcemap.decoder_skels[0].entry_fn(name, infallible);
+        items.push(RustItem::pub_decl(RustDecl::Function(entry)));
     }
 
     let mut content = RustProgram::from_iter(items);
@@ -2326,30 +2962,7 @@ pub fn print_generated_code(
     }
     content.add_submodule(RustSubmodule::new("codegen_tests"));
     content.add_submodule(RustSubmodule::new_pub("api_helper"));
-
-    fn write_to(mut f: impl std::io::Write, content: impl ToFragment) -> std::io::Result<()> {
-        write!(f, "{}", content.to_fragment())
-    }
-
-    match dest {
-        None => write_to(std::io::stdout().lock(), content).expect("failed to write"),
-        Some(path) => {
-            if !path.exists()
-                || (path.is_file()
-                    && path
-                        .file_name()
-                        .is_some_and(|s| s.to_string_lossy().contains("codegen.rs")))
-            {
-                let f = std::fs::File::create(path).unwrap_or_else(|err| panic!("error: {err}"));
-                write_to(f, content).expect("failed to write");
-            } else {
-                panic!(
-                    "will not overwrite directory or protected file: {}",
-                    path.to_string_lossy()
-                );
-            }
-        }
-    }
+    content
 }
 
 #[derive(Clone, Debug)]
@@ -2367,8 +2980,10 @@ where
 {
     type AstElem = RustFn;
 
-    fn to_ast(&self, _ctxt: ProdCtxt<'_>) -> RustFn {
-        let name = Label::from(format!("Decoder{}", self.ixlabel.to_usize()));
+    fn to_ast(&self, ctxt: ProdCtxt<'_>) -> RustFn {
+        let ix = self.ixlabel.to_usize();
+        let name = Label::from(format!("Decoder{ix}"));
+        let infallible = ctxt.infallible.get(ix) == Some(&true);
         let params = {
             let mut tmp = DefParams::new();
             tmp.push_lifetime("'input");
@@ -2400,25 +3015,28 @@ where
                     [arg0].to_vec()
                 }
             };
-            FnSig::new(
-                args,
-                Some(RustType::result_of(
-                    self.ret_type.clone(),
-                    RustType::imported("ParseError"),
-                )),
-            )
+            let ret_type = if infallible {
+                self.ret_type.clone()
+            } else {
+                RustType::result_of(self.ret_type.clone(), RustType::imported("ParseError"))
+            };
+            FnSig::new(args, Some(ret_type))
         };
         let ctxt = ProdCtxt {
             input_varname: &Label::from("_input"),
+            elide_result: infallible,
+            ..ctxt
         };
         let (stmts, ret) = self.logic.to_ast(ctxt);
         let body = if let Some(ret) = ret {
+            let ret = if infallible {
+                ret
+            } else {
+                RustExpr::scoped(["PResult"], "Ok").call_with([ret])
+            };
             Iterator::chain(
                 stmts.into_iter(),
-                std::iter::once(RustStmt::Return(
-                    ReturnKind::Implicit,
-                    RustExpr::scoped(["PResult"], "Ok").call_with([ret]),
-                )),
+                std::iter::once(RustStmt::Return(ReturnKind::Implicit, ret)),
             )
             .collect()
         } else {
@@ -2429,15 +3047,57 @@ where
     }
 }
 
+impl<ExprT> DecoderFn<ExprT> {
+    /// Constructs a public entry-point `fn <name>(input: &[u8]) -> Result<T, ParseError>` that runs
+    /// this decoder over a fresh `Parser` for `input`.
+    ///
+    /// If `infallible` is set, the decoder is assumed to have been emitted as returning its value
+    /// directly, and the result is wrapped in `Ok` accordingly.
+    fn entry_fn(&self, name: Label, infallible: bool) -> RustFn {
+        let sig = FnSig::new(
+            vec![(
+                Label::from("input"),
+                RustType::borrow_of(
+                    None,
+                    Mut::Immutable,
+                    RustType::slice_of(RustType::from(PrimType::U8)),
+                ),
+            )],
+            Some(RustType::result_of(
+                self.ret_type.clone(),
+                RustType::imported("ParseError"),
+            )),
+        );
+        let call = RustExpr::local(format!("Decoder{}", self.ixlabel.to_usize()))
+            .call_with([RustExpr::BorrowMut(Box::new(RustExpr::local("parser")))]);
+        let ret = if infallible {
+            RustExpr::scoped(["PResult"], "Ok").call_with([call])
+        } else {
+            call
+        };
+        let body = vec![
+            RustStmt::assign_mut(
+                "parser",
+                RustExpr::scoped(["Parser"], "new").call_with([RustExpr::local("input")]),
+            ),
+            RustStmt::Return(ReturnKind::Implicit, ret),
+        ];
+        RustFn::new(name, None, sig, body)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SourceMap<ExprT> {
     pub(crate) decoder_skels: Vec<DecoderFn<ExprT>>,
+    /// Indexed by decoder number, `true` for decoders compiled from formats that can never fail
+    pub(crate) infallible: Vec<bool>,
 }
 
 impl<TypeRep> SourceMap<TypeRep> {
     pub const fn new() -> SourceMap<TypeRep> {
         SourceMap {
             decoder_skels: Vec::new(),
+            infallible: Vec::new(),
         }
     }
 }
@@ -2454,8 +3114,16 @@ impl<'a> Generator<'a> {
         let _ = tc
             .infer_utype_format(top_format, ctxt)
             .unwrap_or_else(|err| panic!("Failed to infer top-level format type: {err}"));
+        let mut codegen = CodeGen::new();
+        // newtypes may be lifted before they are elaborated, so they must be known up front
+        collect_nonzero_newtypes(
+            module,
+            top_format,
+            &mut HashSet::new(),
+            &mut codegen.nonzero_newtypes,
+        );
         let mut gen = Self {
-            elaborator: Elaborator::new(module, tc, CodeGen::new()),
+            elaborator: Elaborator::new(module, tc, codegen),
             sourcemap: SourceMap::new(),
         };
         let elab = &mut gen.elaborator;
@@ -2477,6 +3145,7 @@ impl<'a> Generator<'a> {
             };
             gen.sourcemap.decoder_skels.push(dec_fn);
         }
+        gen.sourcemap.infallible = prog.infallible;
         gen
     }
 }
@@ -2541,6 +3210,13 @@ impl<'a> Elaborator<'a> {
             Pattern::U16(n) => GTPattern::U16(*n),
             Pattern::U32(n) => GTPattern::U32(*n),
             Pattern::U64(n) => GTPattern::U64(*n),
+            Pattern::U8Range(lo, hi) => GTPattern::U8Range(*lo, *hi),
+            Pattern::U16Range(lo, hi) => GTPattern::U16Range(*lo, *hi),
+            Pattern::U32Range(lo, hi) => GTPattern::U32Range(*lo, *hi),
+            Pattern::I8(n) => GTPattern::I8(*n),
+            Pattern::I16(n) => GTPattern::I16(*n),
+            Pattern::I32(n) => GTPattern::I32(*n),
+            Pattern::I64(n) => GTPattern::I64(*n),
             Pattern::Char(c) => GTPattern::Char(*c),
             Pattern::Tuple(elts) => {
                 let mut t_elts = Vec::with_capacity(elts.len());
@@ -2567,6 +3243,15 @@ impl<'a> Elaborator<'a> {
                 let gt = self.get_gt_from_index(index);
                 GTPattern::Seq(gt, t_elts)
             }
+            Pattern::Or(alts) => {
+                let mut t_alts = Vec::with_capacity(alts.len());
+                for alt in alts {
+                    let t_alt = self.elaborate_pattern(alt);
+                    t_alts.push(t_alt);
+                }
+                let gt = self.get_gt_from_index(index);
+                GTPattern::Or(gt, t_alts)
+            }
         }
     }
 
@@ -2608,6 +3293,38 @@ impl<'a> Elaborator<'a> {
         }
     }
 
+    fn elaborate_for_each(
+        &mut self,
+        expr: &Expr,
+        lbl: &Label,
+        inner: &Format,
+        dyns: &TypedDynScope<'_>,
+    ) -> GTFormat {
+        let index = self.get_and_increment_index();
+        let t_expr = self.elaborate_expr(expr);
+        // skip the index of the element-type variable
+        self.increment_index();
+        let t_inner = self.elaborate_format(inner, dyns);
+        let gt = self.get_gt_from_index(index);
+        GTFormat::ForEach(gt, t_expr, lbl.clone(), Box::new(t_inner))
+    }
+
+    // NOTE - split out of `elaborate_format` to keep its (recursive) stack frame from growing
+    #[inline(never)]
+    fn elaborate_newtype(
+        &mut self,
+        name: &Label,
+        inner: &Format,
+        validate: Option<&Expr>,
+        dyns: &TypedDynScope<'_>,
+    ) -> GTFormat {
+        let index = self.get_and_increment_index();
+        let t_inner = self.elaborate_format(inner, dyns);
+        let t_validate = validate.map(|f| self.elaborate_expr_lambda(f));
+        let gt = self.get_gt_from_index(index);
+        GTFormat::Newtype(gt, name.clone(), Box::new(t_inner), t_validate)
+    }
+
     fn elaborate_format(&mut self, format: &Format, dyns: &TypedDynScope<'_>) -> GTFormat {
         match format {
             Format::ItemVar(level, args) => {
@@ -2649,6 +3366,14 @@ impl<'a> Elaborator<'a> {
                 self.increment_index();
                 GTFormat::Align(*n)
             }
+            Format::AbsolutePos => {
+                self.increment_index();
+                GTFormat::AbsolutePos
+            }
+            Format::SlicePos => {
+                self.increment_index();
+                GTFormat::SlicePos
+            }
             Format::Byte(bs) => {
                 self.increment_index();
                 GTFormat::Byte(*bs)
@@ -2671,6 +3396,9 @@ impl<'a> Elaborator<'a> {
                 }
                 GTFormat::Variant(gt, label.clone(), Box::new(t_inner))
             }
+            Format::Newtype(name, inner, validate) => {
+                self.elaborate_newtype(name, inner, validate.as_ref(), dyns)
+            }
             Format::Union(branches) => self.elaborate_format_union(branches, dyns, true),
             Format::UnionNondet(branches) => self.elaborate_format_union(branches, dyns, false),
             Format::Tuple(elts) => {
@@ -2716,6 +3444,12 @@ impl<'a> Elaborator<'a> {
                 let gt = self.get_gt_from_index(index);
                 GTFormat::Repeat(gt, Box::new(t_inner))
             }
+            Format::RepeatWhileInput(inner) => {
+                let index = self.get_and_increment_index();
+                let t_inner = self.elaborate_format(inner, dyns);
+                let gt = self.get_gt_from_index(index);
+                GTFormat::RepeatWhileInput(gt, Box::new(t_inner))
+            }
             Format::Repeat1(inner) => {
                 let index = self.get_and_increment_index();
                 let t_inner = self.elaborate_format(inner, dyns);
@@ -2763,6 +3497,16 @@ impl<'a> Elaborator<'a> {
                 let gt = self.get_gt_from_index(index);
                 GTFormat::PeekNot(gt, Box::new(t_inner))
             }
+            Format::RepeatSep(elem, sep) => {
+                self.elaborate_format(&Format::repeat_sep_equivalent(elem, sep), dyns)
+            }
+            Format::UntilSeq(terminator, body) => {
+                self.elaborate_format(&Format::until_seq_equivalent(terminator, body), dyns)
+            }
+            Format::Signature(_name, magic, body) => {
+                // generated parsers report a mismatched signature like any other mismatch
+                self.elaborate_format(&Format::signature_equivalent(magic, body), dyns)
+            }
             Format::Slice(expr, inner) => {
                 let index = self.get_and_increment_index();
                 let t_expr = self.elaborate_expr(expr);
@@ -2783,6 +3527,13 @@ impl<'a> Elaborator<'a> {
                 let gt = self.get_gt_from_index(index);
                 GTFormat::WithRelativeOffset(gt, t_expr, Box::new(t_inner))
             }
+            Format::FollowOffset(expr, inner) => {
+                let index = self.get_and_increment_index();
+                let t_expr = self.elaborate_expr(expr);
+                let t_inner = self.elaborate_format(inner, dyns);
+                let gt = self.get_gt_from_index(index);
+                GTFormat::FollowOffset(gt, t_expr, Box::new(t_inner))
+            }
             Format::Map(inner, lambda) => {
                 let index = self.get_and_increment_index();
                 let t_inner = self.elaborate_format(inner, dyns);
@@ -2796,6 +3547,7 @@ impl<'a> Elaborator<'a> {
                 let gt = self.get_gt_from_index(index);
                 GTFormat::Compute(gt, t_expr)
             }
+            Format::ForEach(expr, lbl, inner) => self.elaborate_for_each(expr, lbl, inner, dyns),
             Format::Let(lbl, expr, inner) => {
                 let index = self.get_and_increment_index();
                 let t_expr = self.elaborate_expr(expr);
@@ -2858,6 +3610,10 @@ impl<'a> Elaborator<'a> {
             Expr::U16(n) => GTExpr::U16(*n),
             Expr::U32(n) => GTExpr::U32(*n),
             Expr::U64(n) => GTExpr::U64(*n),
+            Expr::I8(n) => GTExpr::I8(*n),
+            Expr::I16(n) => GTExpr::I16(*n),
+            Expr::I32(n) => GTExpr::I32(*n),
+            Expr::I64(n) => GTExpr::I64(*n),
             Expr::Tuple(elts) => {
                 let mut t_elts = Vec::with_capacity(elts.len());
                 for elt in elts {
@@ -2956,6 +3712,18 @@ impl<'a> Elaborator<'a> {
                 let t_inner = self.elaborate_expr(inner);
                 GTExpr::AsU8(Box::new(t_inner))
             }
+            Expr::AsSigned8(inner) => {
+                let t_inner = self.elaborate_expr(inner);
+                GTExpr::AsSigned8(Box::new(t_inner))
+            }
+            Expr::AsSigned16(inner) => {
+                let t_inner = self.elaborate_expr(inner);
+                GTExpr::AsSigned16(Box::new(t_inner))
+            }
+            Expr::AsSigned32(inner) => {
+                let t_inner = self.elaborate_expr(inner);
+                GTExpr::AsSigned32(Box::new(t_inner))
+            }
             Expr::AsU16(inner) => {
                 let t_inner = self.elaborate_expr(inner);
                 GTExpr::AsU16(Box::new(t_inner))
@@ -2996,12 +3764,34 @@ impl<'a> Elaborator<'a> {
                 let t_bytes = self.elaborate_expr(bytes);
                 GTExpr::U64Le(Box::new(t_bytes))
             }
+            Expr::I16Be(bytes) => {
+                let t_bytes = self.elaborate_expr(bytes);
+                GTExpr::I16Be(Box::new(t_bytes))
+            }
+            Expr::I16Le(bytes) => {
+                let t_bytes = self.elaborate_expr(bytes);
+                GTExpr::I16Le(Box::new(t_bytes))
+            }
+            Expr::I32Be(bytes) => {
+                let t_bytes = self.elaborate_expr(bytes);
+                GTExpr::I32Be(Box::new(t_bytes))
+            }
+            Expr::I32Le(bytes) => {
+                let t_bytes = self.elaborate_expr(bytes);
+                GTExpr::I32Le(Box::new(t_bytes))
+            }
             Expr::SeqLength(seq) => {
                 let t_seq = self.elaborate_expr(seq);
                 // NOTE - for element type of sequence
                 self.increment_index();
                 GTExpr::SeqLength(Box::new(t_seq))
             }
+            Expr::SeqIx(seq, ix) => {
+                let t_seq = self.elaborate_expr(seq);
+                let t_ix = self.elaborate_expr(ix);
+                let gt = self.get_gt_from_index(index);
+                GTExpr::SeqIx(gt, Box::new(t_seq), Box::new(t_ix))
+            }
             Expr::SubSeq(seq, start, length) => {
                 let t_seq = self.elaborate_expr(seq);
                 let t_start = self.elaborate_expr(start);
@@ -3195,6 +3985,54 @@ mod tests {
         run_popcheck(&formats);
     }
 
+    #[test]
+    fn test_popcheck_newtype() {
+        let f = Format::Newtype(
+            "Tag".into(),
+            Box::new(Format::Byte(ByteSet::full())),
+            Some(Expr::Lambda(
+                "t".into(),
+                Box::new(Expr::IntRel(
+                    IntRel::Ne,
+                    Box::new(Expr::Var("t".into())),
+                    Box::new(Expr::U8(0)),
+                )),
+            )),
+        );
+        run_popcheck(&[("test.newtype", f)]);
+    }
+
+    #[test]
+    fn test_popcheck_repeat_while_input() {
+        let f = Format::Slice(
+            Expr::U8(4),
+            Format::RepeatWhileInput(Box::new(Format::Byte(ByteSet::full()))).into(),
+        );
+        run_popcheck(&[("test.repeat_while_input", f)]);
+    }
+
+    #[test]
+    fn test_popcheck_for_each() {
+        let f = Format::ForEach(
+            Expr::Seq(vec![Expr::U8(1), Expr::U8(2)]),
+            "n".into(),
+            Format::RepeatCount(
+                Expr::Var("n".into()),
+                Box::new(Format::Byte(ByteSet::full())),
+            )
+            .into(),
+        );
+        run_popcheck(&[("test.for_each", f)]);
+    }
+
+    #[test]
+    fn test_popcheck_positions() {
+        run_popcheck(&[
+            ("test.abs_pos", Format::AbsolutePos),
+            ("test.slice_pos", Format::SlicePos),
+        ]);
+    }
+
     #[test]
     fn test_popcheck_record_simple() {
         let f = Format::Record(vec![
@@ -3276,4 +4114,624 @@ mod tests {
         let f = Format::Record(vec![("xs".into(), xs), ("fxs".into(), fxs)]);
         run_popcheck(&[("test.compute_complex", f)]);
     }
+
+    #[test]
+    fn test_fixed_array_conversion() {
+        let conv = embed_fixed_array(
+            &TypedExpr::U8(4),
+            PrimType::U8.into(),
+            RustExpr::local("seq"),
+        )
+        .unwrap();
+        assert_eq!(
+            conv.to_fragment().to_string(),
+            "(<[u8; 4]>::try_from(seq)).map_err(|_| ParseError::WrongLength)?"
+        );
+        let dynamic = TypedExpr::Var(GenType::from(RustType::from(PrimType::U8)), "n".into());
+        assert!(embed_fixed_array(&dynamic, PrimType::U8.into(), RustExpr::local("seq")).is_none());
+    }
+
+    #[test]
+    fn test_infallible_decoder_fn() {
+        let mut module = FormatModule::new();
+        let succ = module.define_format_args(
+            "test.succ",
+            vec![(Label::Borrowed("x"), ValueType::Base(BaseType::U8))],
+            Format::Compute(Expr::Arith(
+                Arith::Add,
+                Box::new(Expr::Var("x".into())),
+                Box::new(Expr::U8(1)),
+            )),
+        );
+        let f = Format::Record(vec![
+            ("x".into(), Format::Byte(ByteSet::full())),
+            ("y".into(), succ.call_args(vec![Expr::Var("x".into())])),
+        ]);
+        assert!(module.get_format(succ.get_level()).is_infallible());
+        assert!(!f.is_infallible());
+
+        let Generator { sourcemap, .. } = Generator::compile(&module, &f);
+        assert_eq!(sourcemap.infallible, vec![false, true]);
+        let render = |infallible: &[bool]| {
+            let ctxt = ProdCtxt {
+                infallible,
+                ..ProdCtxt::default()
+            };
+            sourcemap
+                .decoder_skels
+                .iter()
+                .map(|decfn| decfn.to_ast(ctxt).to_fragment().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let fallible = render(&[]);
+        assert!(fallible[1].contains("-> Result<u8, ParseError>"));
+        assert!(fallible[0].contains("(Decoder1(_input, x.clone()))?"));
+
+        let elided = render(&sourcemap.infallible);
+        assert!(elided[1].contains("-> u8 {"));
+        assert!(!elided[1].contains("PResult"));
+        assert!(!elided[0].contains("(Decoder1(_input, x.clone()))?"));
+        assert!(elided[0].contains("Decoder1(_input, x.clone())"));
+    }
+
+    #[test]
+    fn test_infallible_map_decoder_fn() {
+        let mut module = FormatModule::new();
+        let double = module.define_format_args(
+            "test.double",
+            vec![(Label::Borrowed("x"), ValueType::Base(BaseType::U8))],
+            Format::Map(
+                Box::new(Format::Compute(Expr::Var("x".into()))),
+                Expr::Lambda(
+                    "y".into(),
+                    Box::new(Expr::Arith(
+                        Arith::BitOr,
+                        Box::new(Expr::Var("y".into())),
+                        Box::new(Expr::U8(1)),
+                    )),
+                ),
+            ),
+        );
+        let f = Format::Record(vec![
+            ("x".into(), Format::Byte(ByteSet::full())),
+            ("y".into(), double.call_args(vec![Expr::Var("x".into())])),
+        ]);
+        assert!(module.get_format(double.get_level()).is_infallible());
+
+        let Generator { sourcemap, .. } = Generator::compile(&module, &f);
+        assert_eq!(sourcemap.infallible, vec![false, true]);
+        let ctxt = ProdCtxt {
+            infallible: &sourcemap.infallible,
+            ..ProdCtxt::default()
+        };
+        let elided = sourcemap.decoder_skels[1]
+            .to_ast(ctxt)
+            .to_fragment()
+            .to_string();
+        assert!(elided.contains("-> u8 {"), "{elided}");
+        assert!(elided.contains("(|y: u8| y | 1u8)(inner)"), "{elided}");
+        assert!(!elided.contains('?'), "{elided}");
+        assert!(!elided.contains("PResult"), "{elided}");
+
+        let fallible = sourcemap.decoder_skels[1]
+            .to_ast(ProdCtxt::default())
+            .to_fragment()
+            .to_string();
+        assert!(fallible.contains("PResult::Ok(y | 1u8)"), "{fallible}");
+    }
+
+    #[test]
+    fn test_newtype_decoder() {
+        let module = FormatModule::new();
+        let u32be = Format::Map(
+            Box::new(Format::Tuple(vec![Format::Byte(ByteSet::full()); 4])),
+            Expr::Lambda(
+                "x".into(),
+                Box::new(Expr::U32Be(Box::new(Expr::Var("x".into())))),
+            ),
+        );
+        let is_small = Expr::Lambda(
+            "t".into(),
+            Box::new(Expr::IntRel(
+                IntRel::Lt,
+                Box::new(Expr::Var("t".into())),
+                Box::new(Expr::U32(0x10000)),
+            )),
+        );
+        let f = Format::Record(vec![
+            (
+                "tag".into(),
+                Format::Newtype("Tag".into(), Box::new(u32be), Some(is_small)),
+            ),
+            ("len".into(), Format::Byte(ByteSet::full())),
+        ]);
+
+        let Generator {
+            sourcemap,
+            elaborator,
+        } = Generator::compile(&module, &f);
+        let tdefs = &elaborator.codegen.defined_types;
+        let (ix, tag_def) = tdefs
+            .iter()
+            .enumerate()
+            .find(|(_, def)| matches!(def, RustTypeDef::Struct(RustStruct::Newtype(..))))
+            .expect("missing newtype definition");
+        let decl = RustDecl::type_def(Label::from("Tag"), tag_def.clone());
+        assert_eq!(format!("{}", decl.to_fragment()), "struct Tag(u32);");
+        let Some(RustTypeDef::Struct(RustStruct::Record(fields))) = tdefs.get(1 - ix) else {
+            panic!("missing record definition: {tdefs:?}")
+        };
+        assert_eq!(fields[0].1, RustType::defined(ix, "Tag"));
+
+        let body = sourcemap.decoder_skels[0]
+            .to_ast(ProdCtxt::default())
+            .to_fragment()
+            .to_string();
+        assert!(body.contains(
+            "if !((|t: u32| PResult::Ok(t < 65536u32))(inner.clone()))? {\nreturn Err(ParseError::FailToken);\n}"
+        ), "{body}");
+        assert!(body.contains("Tag(inner)"), "{body}");
+
+        let debug_ctxt = ProdCtxt {
+            debug_invariants: true,
+            ..ProdCtxt::default()
+        };
+        let body = sourcemap.decoder_skels[0]
+            .to_ast(debug_ctxt)
+            .to_fragment()
+            .to_string();
+        assert!(
+            body.contains("debug_assert!(((|t: u32| PResult::Ok(t < 65536u32))(inner.clone()))?);"),
+            "{body}"
+        );
+        assert!(!body.contains("ParseError::FailToken"), "{body}");
+    }
+
+    #[test]
+    fn test_nonzero_newtype_decoder() {
+        use crate::helper::optional;
+        let module = FormatModule::new();
+        let u16be = Format::Map(
+            Box::new(Format::Tuple(vec![Format::Byte(ByteSet::full()); 2])),
+            Expr::Lambda(
+                "x".into(),
+                Box::new(Expr::U16Be(Box::new(Expr::Var("x".into())))),
+            ),
+        );
+        let is_nonzero = Expr::Lambda(
+            "x".into(),
+            Box::new(Expr::IntRel(
+                IntRel::Ne,
+                Box::new(Expr::Var("x".into())),
+                Box::new(Expr::U16(0)),
+            )),
+        );
+        let f = Format::Record(vec![(
+            "offset".into(),
+            optional(Format::Newtype(
+                "Offset".into(),
+                Box::new(u16be),
+                Some(is_nonzero),
+            )),
+        )]);
+
+        let Generator {
+            sourcemap,
+            elaborator,
+        } = Generator::compile(&module, &f);
+        let tdefs = &elaborator.codegen.defined_types;
+        let (ix, offset_def) = tdefs
+            .iter()
+            .enumerate()
+            .find(|(_, def)| matches!(def, RustTypeDef::Struct(RustStruct::Newtype(..))))
+            .expect("missing newtype definition");
+        let decl = RustDecl::type_def(Label::from("Offset"), offset_def.clone());
+        assert_eq!(
+            format!("{}", decl.to_fragment()),
+            "struct Offset(NonZeroU16);"
+        );
+        // the `some` payload is the only one, so the optional enum can use the `NonZeroU16` niche
+        let some_payload = tdefs.iter().find_map(|def| match def {
+            RustTypeDef::Enum(vars) => vars.iter().find_map(|var| match var {
+                RustVariant::Tuple(name, payload) if name == "some" => Some(payload.clone()),
+                _ => None,
+            }),
+            _ => None,
+        });
+        assert_eq!(
+            some_payload,
+            Some(vec![RustType::defined(ix, "Offset")]),
+            "{tdefs:?}"
+        );
+
+        let body = sourcemap.decoder_skels[0]
+            .to_ast(ProdCtxt::default())
+            .to_fragment()
+            .to_string();
+        assert!(
+            body.contains("Offset((NonZeroU16::new(inner)).ok_or(ParseError::FailToken)?)"),
+            "{body}"
+        );
+    }
+
+    #[test]
+    fn test_signed_decoder() {
+        let module = FormatModule::new();
+        let s16be = Format::Map(
+            Box::new(Format::Tuple(vec![Format::Byte(ByteSet::full()); 2])),
+            Expr::Lambda(
+                "x".into(),
+                Box::new(Expr::I16Be(Box::new(Expr::Var("x".into())))),
+            ),
+        );
+        let f = Format::Record(vec![
+            ("delta".into(), s16be),
+            (
+                "is_minus_one".into(),
+                Format::Compute(Expr::Match(
+                    Box::new(Expr::Var("delta".into())),
+                    vec![
+                        (Pattern::I16(-1), Expr::Bool(true)),
+                        (Pattern::Wildcard, Expr::Bool(false)),
+                    ],
+                )),
+            ),
+        ]);
+
+        let Generator {
+            sourcemap,
+            elaborator,
+        } = Generator::compile(&module, &f);
+        let Some(RustTypeDef::Struct(RustStruct::Record(fields))) =
+            elaborator.codegen.defined_types.first()
+        else {
+            panic!("missing record definition");
+        };
+        assert_eq!(fields[0].1, RustType::from(PrimType::I16));
+
+        let body = sourcemap.decoder_skels[0]
+            .to_ast(ProdCtxt::default())
+            .to_fragment()
+            .to_string();
+        assert!(body.contains("i16be(x)"), "{body}");
+        assert!(body.contains("matches!(delta, -1i16)"), "{body}");
+    }
+
+    #[test]
+    fn test_signature_decoder() {
+        let module = FormatModule::new();
+        let f = Format::Signature(
+            "TEST".into(),
+            Box::new(Format::Tuple(vec![
+                Format::Byte(ByteSet::from([0xCA])),
+                Format::Byte(ByteSet::from([0xFE])),
+            ])),
+            Box::new(Format::Byte(ByteSet::full())),
+        );
+        let Generator { sourcemap, .. } = Generator::compile(&module, &f);
+        let body = sourcemap.decoder_skels[0]
+            .to_ast(ProdCtxt::default())
+            .to_fragment()
+            .to_string();
+        // the signature bytes are checked in order, before the payload is decoded
+        let sig_hi = body.find("if b != 202 {\nreturn Err(ParseError::ExcludedBranch(");
+        let sig_lo = body.find("if b != 254 {\nreturn Err(ParseError::ExcludedBranch(");
+        assert!(
+            matches!((sig_hi, sig_lo), (Some(hi), Some(lo)) if hi < lo),
+            "{body}"
+        );
+        // only the payload is retained in the decoded value
+        assert!(body.contains("-> Result<u8, ParseError>"), "{body}");
+        assert!(
+            body.contains("(|x: ((u8, u8), u8)| PResult::Ok(x.clone().1))(inner)"),
+            "{body}"
+        );
+    }
+
+    #[test]
+    fn test_entry_point() {
+        let mut module = FormatModule::new();
+        let f = module
+            .define_format(
+                "main",
+                Format::Record(vec![
+                    ("x".into(), Format::Byte(ByteSet::full())),
+                    ("y".into(), Format::Byte(ByteSet::full())),
+                ]),
+            )
+            .call();
+        let options = GenOptions {
+            entry_point: Some(Label::from("parse")),
+            ..GenOptions::default()
+        };
+        let content = generate_program(&module, &f, options).render().to_string();
+        assert!(
+            content.contains("pub fn parse(input: &[u8]) -> Result<main, ParseError>"),
+            "{content}"
+        );
+        assert!(content.contains("Decoder0(&mut parser)"), "{content}");
+
+        let content = generate_program(&module, &f, GenOptions::default())
+            .render()
+            .to_string();
+        assert!(!content.contains("pub fn parse"), "{content}");
+    }
+
+    fn render_decoder(f: &Format) -> String {
+        let module = FormatModule::new();
+        let Generator { sourcemap, .. } = Generator::compile(&module, f);
+        sourcemap.decoder_skels[0]
+            .to_ast(ProdCtxt::default())
+            .to_fragment()
+            .to_string()
+    }
+
+    #[test]
+    fn test_seq_ix_decoder() {
+        use crate::helper::{record, repeat_count, seq_ix, var};
+        let f = record([
+            (
+                "xs",
+                repeat_count(Expr::U8(3), Format::Byte(ByteSet::full())),
+            ),
+            ("last", Format::Compute(seq_ix(var("xs"), Expr::U32(2)))),
+        ]);
+        let body = render_decoder(&f);
+        assert!(body.contains("xs[2u32 as usize]"), "{body}");
+        assert!(!body.contains(".clone()"), "{body}");
+
+        let f_nested = record([
+            (
+                "xss",
+                repeat_count(
+                    Expr::U8(2),
+                    repeat_count(Expr::U8(2), Format::Byte(ByteSet::full())),
+                ),
+            ),
+            ("first", Format::Compute(seq_ix(var("xss"), Expr::U32(0)))),
+        ]);
+        let body = render_decoder(&f_nested);
+        assert!(body.contains("xss[0u32 as usize].clone()"), "{body}");
+    }
+
+    #[test]
+    fn test_repeat_until_last_progress_guard() {
+        use crate::helper::{expr_eq, is_byte, lambda, optional_or, repeat_until_last, var};
+        let f = repeat_until_last(
+            lambda("x", expr_eq(var("x"), Expr::U8(0))),
+            optional_or(is_byte(0x01), Expr::U8(0)),
+        );
+        let body = render_decoder(&f);
+        assert!(body.contains("let iter_start = "), "{body}");
+        assert!(
+            body.contains("get_current_offset() == iter_start"),
+            "{body}"
+        );
+    }
+
+    #[test]
+    fn test_flat_map_iter_decoder() {
+        use crate::helper::{dup, flat_map, lambda, record, repeat_count, var};
+        let f = record([
+            (
+                "xs",
+                repeat_count(Expr::U8(3), Format::Byte(ByteSet::full())),
+            ),
+            (
+                "ys",
+                Format::Compute(flat_map(
+                    lambda("x", dup(Expr::U32(2), var("x"))),
+                    var("xs"),
+                )),
+            ),
+        ]);
+        let body = render_decoder(&f);
+        assert!(body.contains("xs.iter().copied()"), "{body}");
+
+        let f_nested = record([
+            (
+                "xss",
+                repeat_count(
+                    Expr::U8(2),
+                    repeat_count(Expr::U8(2), Format::Byte(ByteSet::full())),
+                ),
+            ),
+            (
+                "ys",
+                Format::Compute(flat_map(lambda("xs", var("xs")), var("xss"))),
+            ),
+        ]);
+        let body = render_decoder(&f_nested);
+        assert!(body.contains("xss.iter().cloned()"), "{body}");
+    }
+
+    #[test]
+    fn test_or_pattern_decoder() {
+        use crate::helper::{record, var};
+        let f = record([
+            ("b", Format::Byte(ByteSet::full())),
+            (
+                "is_newline",
+                Format::Match(
+                    var("b"),
+                    vec![
+                        (
+                            Pattern::or([Pattern::U8(0x0A), Pattern::U8(0x0D)]),
+                            Format::Compute(Expr::Bool(true)),
+                        ),
+                        (Pattern::Wildcard, Format::Compute(Expr::Bool(false))),
+                    ],
+                ),
+            ),
+        ]);
+        let body = render_decoder(&f);
+        assert!(body.contains("10 | 13 =>"), "{body}");
+    }
+
+    #[test]
+    fn test_matches_expr_decoder() {
+        use crate::helper::{bind, expr_match, record, var};
+        let f = record([
+            ("b", Format::Byte(ByteSet::full())),
+            (
+                "is_newline",
+                Format::Compute(expr_match(
+                    var("b"),
+                    [
+                        (Pattern::U8(0x0A), Expr::Bool(true)),
+                        (Pattern::U8(0x0D), Expr::Bool(true)),
+                        (Pattern::Wildcard, Expr::Bool(false)),
+                    ],
+                )),
+            ),
+            (
+                "is_large",
+                Format::Compute(expr_match(
+                    var("b"),
+                    [
+                        (
+                            bind("x"),
+                            Expr::IntRel(IntRel::Gt, Box::new(var("x")), Box::new(Expr::U8(0x7F))),
+                        ),
+                        (Pattern::Wildcard, Expr::Bool(false)),
+                    ],
+                )),
+            ),
+        ]);
+        let body = render_decoder(&f);
+        assert!(body.contains("matches!(b, 10 | 13)"), "{body}");
+        assert!(body.contains("matches!(b, x if x > 127u8)"), "{body}");
+    }
+
+    #[test]
+    fn test_u8_range_pattern_decoder() {
+        use crate::helper::{record, var};
+        let f = record([
+            ("b", Format::Byte(ByteSet::full())),
+            (
+                "is_digit",
+                Format::Match(
+                    var("b"),
+                    vec![
+                        (
+                            Pattern::U8Range(0x30, 0x39),
+                            Format::Compute(Expr::Bool(true)),
+                        ),
+                        (Pattern::Wildcard, Format::Compute(Expr::Bool(false))),
+                    ],
+                ),
+            ),
+        ]);
+        let body = render_decoder(&f);
+        assert!(body.contains("48..=57 =>"), "{body}");
+    }
+
+    #[test]
+    fn test_until_seq_decoder() {
+        let f = crate::helper::until_seq(b"\r\n", Format::Byte(ByteSet::full()));
+        let body = render_decoder(&f);
+        // each element is preceded by a two-byte lookahead that only stops the loop on a full terminator
+        assert!(body.contains("while _input.remaining() > 0 {"), "{body}");
+        assert!(
+            body.contains("13u8 => {\nlet b = _input.read_byte()?;\nmatch b {\ntmp if (tmp != 10) => {\n0\n},"),
+            "{body}"
+        );
+        assert!(body.contains("if matching_ix == 0 {"), "{body}");
+        assert!(body.contains("} else {\nbreak\n}"), "{body}");
+        assert!(body.contains("_input.open_peek_not_context();"), "{body}");
+        // the terminator itself is consumed after the loop, but not retained
+        assert!(
+            body.contains("accum\n}))())?;\nlet field1 = ((|| PResult::Ok({\nlet field0 = ((|| PResult::Ok({\nlet b = _input.read_byte()?;\nif b != 13 {"),
+            "{body}"
+        );
+        assert!(
+            body.contains("(|x: (Vec<u8>, (u8, u8))| PResult::Ok(x.clone().0))(inner)"),
+            "{body}"
+        );
+    }
+
+    #[test]
+    fn test_repeat_count_last_element_decoder() {
+        use crate::helper::{alts, is_byte, repeat_count, tuple};
+        let elem = alts([
+            ("a", is_byte(0x00)),
+            ("ab", tuple([is_byte(0x00), is_byte(0x01)])),
+        ]);
+        let f = tuple([repeat_count(Expr::U8(2), elem), is_byte(0x01)]);
+        let body = render_decoder(&f);
+        assert!(body.contains("let repeat_count = 2u8;"), "{body}");
+        assert!(
+            body.contains("for ix in 0..repeat_count {\nif ix + 1 == repeat_count {"),
+            "{body}"
+        );
+
+        // elements whose lookahead is unaffected by what follows share a single decoder
+        let f = tuple([repeat_count(Expr::U8(2), is_byte(0x00)), is_byte(0x01)]);
+        let body = render_decoder(&f);
+        assert!(!body.contains("repeat_count"), "{body}");
+    }
+
+    #[test]
+    fn test_repeat_sep_decoder() {
+        let f = Format::RepeatSep(
+            Box::new(Format::Byte(ByteSet::from(b'0'..=b'9'))),
+            Box::new(Format::Byte(ByteSet::from([b',']))),
+        );
+        let body = render_decoder(&f);
+        // the first element is decoded unconditionally, and every later one only after a separator
+        assert!(
+            body.starts_with("fn Decoder0<'input>(_input: &mut Parser<'input>) -> Result<Vec<u8>, ParseError> {\nlet inner = {\nlet field0 = ((|| PResult::Ok({\nlet b = _input.read_byte()?;\nif !(ByteSet::from_bits("),
+            "{body}"
+        );
+        assert!(
+            body.contains("let ret = if b == 44 {\n0\n} else {\n1\n};"),
+            "{body}"
+        );
+        assert!(body.contains("if matching_ix == 0 {"), "{body}");
+        assert!(body.contains("if b != 44 {"), "{body}");
+        assert!(body.contains("accum.push(next_elem);"), "{body}");
+        assert!(body.contains("} else {\nbreak\n}"), "{body}");
+        assert!(body.contains("try_flat_map_vec("), "{body}");
+    }
+
+    #[test]
+    fn test_exhaustive_match_omits_catchall() {
+        use crate::helper::{alts, bind, record, var};
+
+        let tagged = |cases: Vec<(Pattern, Format)>| {
+            record([
+                (
+                    "x",
+                    alts([
+                        ("A", Format::Byte(ByteSet::from([0]))),
+                        ("B", Format::Byte(ByteSet::from([1]))),
+                    ]),
+                ),
+                ("y", Format::Match(var("x"), cases)),
+            ])
+        };
+        let all_variants = tagged(vec![
+            (Pattern::variant("A", bind("a")), Format::EMPTY),
+            (Pattern::variant("B", Pattern::Wildcard), Format::EMPTY),
+        ]);
+        let body = render_decoder(&all_variants);
+        assert!(!body.contains("unreachable!"), "{body}");
+        let some_variants = tagged(vec![(Pattern::variant("A", bind("a")), Format::EMPTY)]);
+        let body = render_decoder(&some_variants);
+        assert!(body.contains("unreachable!"), "{body}");
+
+        let bytes = |cases: Vec<(Pattern, Format)>| {
+            record([
+                ("x", Format::Byte(ByteSet::full())),
+                ("y", Format::Match(var("x"), cases)),
+            ])
+        };
+        let all_bytes = bytes((0..=255).map(|b| (Pattern::U8(b), Format::EMPTY)).collect());
+        let body = render_decoder(&all_bytes);
+        assert!(!body.contains("unreachable!"), "{body}");
+        let some_bytes = bytes((0..255).map(|b| (Pattern::U8(b), Format::EMPTY)).collect());
+        let body = render_decoder(&some_bytes);
+        assert!(body.contains("unreachable!"), "{body}");
+    }
 }
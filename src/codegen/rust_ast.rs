@@ -1,4 +1,6 @@
 use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::path::Path;
 use std::rc::Rc;
 
 use crate::output::{Fragment, FragmentBuilder};
@@ -6,6 +8,8 @@ use crate::output::{Fragment, FragmentBuilder};
 use crate::precedence::{cond_paren, Precedence};
 use crate::{BaseType, IntoLabel, Label, ValueType};
 
+use super::GenOptions;
+
 /// Enum-type (currently degenerate) for specifying the visibility of a top-level item
 #[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
 pub(crate) enum Visibility {
@@ -58,15 +62,23 @@ impl ToFragment for ModuleAttr {
     }
 }
 
-pub(crate) struct RustSubmodule(Visibility, Label);
+/// Declaration of a submodule, along with its contents if they are generated alongside the parent
+/// module (rather than being maintained separately).
+pub(crate) struct RustSubmodule(Visibility, Label, Option<RustProgram>);
 
 impl RustSubmodule {
     pub fn new(label: impl IntoLabel) -> Self {
-        RustSubmodule(Visibility::default(), label.into())
+        RustSubmodule(Visibility::default(), label.into(), None)
     }
 
     pub fn new_pub(label: impl IntoLabel) -> Self {
-        RustSubmodule(Visibility::Public, label.into())
+        RustSubmodule(Visibility::Public, label.into(), None)
+    }
+
+    /// Attaches generated contents to this submodule, to be emitted by [`RustProgram::emit_multi`].
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn with_content(self, content: RustProgram) -> Self {
+        RustSubmodule(self.0, self.1, Some(content))
     }
 }
 
@@ -120,6 +132,49 @@ impl RustProgram {
     pub fn add_import(&mut self, import: RustImport) {
         self.imports.push(import)
     }
+
+    /// Renders the program to a string, collapsing any runs of more than one blank line into a single blank line.
+    pub fn render(&self) -> String {
+        collapse_blank_lines(&format!("{}", self.to_fragment()))
+    }
+
+    /// Writes the program to `out_dir/mod.rs`, and the contents of each of its submodules to a separate
+    /// file in `out_dir` named after the submodule, creating `out_dir` if necessary.
+    ///
+    /// Submodules that themselves have generated submodules are emitted recursively, into a subdirectory
+    /// of the same name. Submodules without any generated contents are only declared.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn emit_multi(&self, out_dir: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(out_dir)?;
+        std::fs::write(out_dir.join("mod.rs"), self.render())?;
+        for RustSubmodule(_, label, content) in self.submodules.iter() {
+            let Some(content) = content else { continue };
+            if content.submodules.iter().any(|sub| sub.2.is_some()) {
+                content.emit_multi(&out_dir.join(label.as_ref()))?;
+            } else {
+                std::fs::write(out_dir.join(format!("{label}.rs")), content.render())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replaces every run of three or more consecutive newlines in `text` with exactly two, matching `rustfmt`.
+fn collapse_blank_lines(text: &str) -> String {
+    let mut ret = String::with_capacity(text.len());
+    let mut newlines = 0;
+    for c in text.chars() {
+        if c == '\n' {
+            newlines += 1;
+            if newlines > 2 {
+                continue;
+            }
+        } else {
+            newlines = 0;
+        }
+        ret.push(c);
+    }
+    ret
 }
 
 impl ToFragment for RustProgram {
@@ -190,6 +245,7 @@ impl ToFragment for RustImportItems {
 }
 
 /// Top-level declared item (e.g. struct definitions and functions)
+#[derive(Clone, Debug)]
 pub(crate) struct RustItem {
     vis: Visibility,
     attrs: Vec<RustAttr>,
@@ -205,7 +261,7 @@ impl RustItem {
                 Label::from("Debug"),
                 Label::from("Clone"),
             ]))],
-            RustDecl::Function(_) => Vec::new(),
+            RustDecl::Function(_) | RustDecl::Impl(..) => Vec::new(),
         };
         Self {
             attrs,
@@ -221,7 +277,7 @@ impl RustItem {
                 Label::from("Debug"),
                 Label::from("Clone"),
             ]))],
-            RustDecl::Function(_) => Vec::new(),
+            RustDecl::Function(_) | RustDecl::Impl(..) => Vec::new(),
         };
         Self {
             attrs,
@@ -232,6 +288,78 @@ impl RustItem {
 }
 
 impl RustItem {
+    /// Appends `trait_name` to the list of traits derived for this item, if it has one.
+    pub fn add_derive(&mut self, trait_name: impl Into<Label>) {
+        if let Some(RustAttr::DeriveTraits(DeclDerives(traits))) = self.attrs.first_mut() {
+            traits.push(trait_name.into());
+        }
+    }
+
+    /// Returns the items for a builder-type `<name>Builder` of the record-struct `name` with fields `fields`.
+    ///
+    /// The builder-type holds an `Option` for each field, and is accompanied by an `impl` block containing
+    /// one setter-method per field, as well as a method `build` that returns `None` unless every field has been set.
+    pub fn struct_builder(name: &Label, fields: &[(Label, RustType)]) -> [RustItem; 2] {
+        let builder_name = Label::from(format!("{name}Builder"));
+        let builder_type = RustType::imported(builder_name.clone());
+        let opt_fields = fields
+            .iter()
+            .map(|(lab, ty)| (lab.clone(), RustType::option_of(ty.clone())))
+            .collect();
+        let mut builder_def = RustItem::pub_decl(RustDecl::type_def(
+            builder_name.clone(),
+            RustTypeDef::Struct(RustStruct::Record(opt_fields)),
+        ));
+        builder_def.add_derive("Default");
+
+        let mut methods = Vec::with_capacity(fields.len() + 1);
+        for (field, ty) in fields.iter() {
+            let assigns = fields
+                .iter()
+                .map(|(other, _)| {
+                    let val = if other == field {
+                        RustExpr::some(RustExpr::local("value"))
+                    } else {
+                        RustExpr::SELF.field(other.clone())
+                    };
+                    (other.clone(), Some(Box::new(val)))
+                })
+                .collect();
+            let body = RustExpr::Struct(RustEntity::Local(builder_name.clone()), assigns);
+            methods.push(RustItem::pub_decl(RustDecl::Function(RustFn::new(
+                sanitize_label(field),
+                None,
+                FnSig::method(
+                    vec![(Label::from("value"), ty.clone())],
+                    Some(builder_type.clone()),
+                ),
+                vec![RustStmt::Return(ReturnKind::Implicit, body)],
+            ))));
+        }
+        let assigns = fields
+            .iter()
+            .map(|(field, _)| {
+                let val = RustExpr::SELF.field(field.clone()).wrap_try();
+                (field.clone(), Some(Box::new(val)))
+            })
+            .collect();
+        let built = RustExpr::some(RustExpr::Struct(RustEntity::Local(name.clone()), assigns));
+        methods.push(RustItem::pub_decl(RustDecl::Function(RustFn::new(
+            Label::from("build"),
+            None,
+            FnSig::method(
+                Vec::new(),
+                Some(RustType::option_of(RustType::imported(name.clone()))),
+            ),
+            vec![RustStmt::Return(ReturnKind::Implicit, built)],
+        ))));
+
+        [
+            builder_def,
+            RustItem::from_decl(RustDecl::Impl(builder_name, methods)),
+        ]
+    }
+
     pub fn to_fragment(&self) -> Fragment {
         let mut builder = FragmentBuilder::new();
         for attr in self.attrs.iter() {
@@ -277,6 +405,8 @@ impl ToFragment for DeclDerives {
 pub(crate) enum RustDecl {
     TypeDef(Label, RustTypeDef),
     Function(RustFn),
+    /// Inherent `impl` block for the named type, containing associated functions (typically methods)
+    Impl(Label, Vec<RustItem>),
 }
 
 impl RustDecl {
@@ -291,10 +421,26 @@ impl ToFragment for RustDecl {
         match self {
             RustDecl::TypeDef(name, tdef) => {
                 let frag_key = Fragment::string(tdef.keyword_for());
-                Fragment::intervene(frag_key, Fragment::Char(' '), name.to_fragment())
-                    .intervene(Fragment::Char(' '), tdef.to_fragment())
+                let frag_head =
+                    Fragment::intervene(frag_key, Fragment::Char(' '), name.to_fragment());
+                match tdef {
+                    RustTypeDef::Struct(RustStruct::Newtype(..)) => {
+                        frag_head.cat(tdef.to_fragment())
+                    }
+                    _ => frag_head.intervene(Fragment::Char(' '), tdef.to_fragment()),
+                }
             }
             RustDecl::Function(fn_def) => fn_def.to_fragment(),
+            RustDecl::Impl(name, items) => Fragment::string("impl ")
+                .cat(name.to_fragment())
+                .cat(Fragment::Char(' '))
+                .cat(
+                    Fragment::seq(
+                        items.iter().map(RustItem::to_fragment),
+                        Some(Fragment::string("\n\n")),
+                    )
+                    .delimit(Fragment::string("{\n"), Fragment::string("\n}")),
+                ),
         }
     }
 }
@@ -334,6 +480,10 @@ impl<Lt, Ty> RustParams<Lt, Ty> {
     pub fn push_lifetime(&mut self, lt: impl Into<Lt>) {
         self.lt_params.push(lt.into())
     }
+
+    pub fn push_type(&mut self, ty: impl Into<Ty>) {
+        self.ty_params.push(ty.into())
+    }
 }
 
 impl ToFragment for RustParams<Label, Label> {
@@ -359,6 +509,8 @@ impl ToFragment for RustParams<RustLt, RustType> {
 /// Representation for the signature, both arguments and return type, for a non-closure function
 #[derive(Clone, Debug)]
 pub(crate) struct FnSig {
+    /// Whether the function is a method taking `self` by value, preceding all other arguments
+    receiver: bool,
     /// List of arguments with accompanying type annotations
     args: Vec<(Label, RustType)>,
     /// Return type (assumed to be unit if omitted)
@@ -367,7 +519,20 @@ pub(crate) struct FnSig {
 
 impl FnSig {
     pub fn new(args: Vec<(Label, RustType)>, ret: Option<RustType>) -> Self {
-        Self { args, ret }
+        Self {
+            receiver: false,
+            args,
+            ret,
+        }
+    }
+
+    /// Like [`FnSig::new`], but for a method taking `self` by value.
+    pub fn method(args: Vec<(Label, RustType)>, ret: Option<RustType>) -> Self {
+        Self {
+            receiver: true,
+            args,
+            ret,
+        }
     }
 }
 
@@ -381,10 +546,16 @@ impl ToFragment for (Label, RustType) {
 
 impl ToFragment for FnSig {
     fn to_fragment(&self) -> Fragment {
-        ToFragment::paren_list(self.args.iter()).intervene(
-            Fragment::string(" -> "),
-            Fragment::opt(self.ret.as_ref(), RustType::to_fragment),
-        )
+        let receiver = self.receiver.then(|| Fragment::string("self"));
+        let args = receiver
+            .into_iter()
+            .chain(self.args.iter().map(<(Label, RustType)>::to_fragment));
+        Fragment::seq(args, Some(Fragment::string(", ")))
+            .delimit(Fragment::Char('('), Fragment::Char(')'))
+            .intervene(
+                Fragment::string(" -> "),
+                Fragment::opt(self.ret.as_ref(), RustType::to_fragment),
+            )
     }
 }
 
@@ -462,6 +633,9 @@ pub(crate) enum RustType {
     AnonTuple(Vec<RustType>),
     /// Catch-all for generics that we may not be able or willing to hardcode
     Verbatim(Label, UseParams),
+    /// Owned trait object `Box<dyn Trait + 'a>`, for payloads decoded by pluggable sub-decoders
+    #[cfg_attr(not(test), allow(dead_code))]
+    DynTrait(Label, Option<RustLt>),
 }
 
 impl RustType {
@@ -495,12 +669,48 @@ impl RustType {
         Self::Verbatim(con.into(), params.unwrap_or_default())
     }
 
+    /// Maps the provided RustType according to the transformation `T -> Option<T>`
+    pub fn option_of(inner: Self) -> Self {
+        let mut params = UseParams::new();
+        params.push_type(inner);
+        Self::verbatim("Option", Some(params))
+    }
+
+    /// Maps the provided RustType according to the transformation `T -> Box<T>`
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn box_of(inner: Self) -> Self {
+        Self::Atom(AtomType::Comp(CompType::Boxed(Box::new(inner))))
+    }
+
     /// Constructs a `RustType` representing `&'a (mut|) T` from parameters representing `'a` (optional),
     /// the mutability of the reference, and `T`, respectively.
     pub fn borrow_of(lt: Option<RustLt>, m: Mut, ty: RustType) -> Self {
         Self::Atom(AtomType::Comp(CompType::Borrow(lt, m, Box::new(ty))))
     }
 
+    /// Maps the provided RustType according to the transformation `T -> [T; N]`
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn array_of(inner: Self, len: usize) -> Self {
+        Self::Atom(AtomType::Comp(CompType::Array(Box::new(inner), len)))
+    }
+
+    /// Maps the provided RustType according to the transformation `T -> [T]`
+    pub fn slice_of(inner: Self) -> Self {
+        Self::Atom(AtomType::Comp(CompType::Slice(Box::new(inner))))
+    }
+
+    /// Constructs a `RustType` representing `Cow<'a, T>` from parameters representing `'a` (optional) and `T`, respectively.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn cow_of(lt: Option<RustLt>, ty: RustType) -> Self {
+        Self::Atom(AtomType::Comp(CompType::Cow(lt, Box::new(ty))))
+    }
+
+    /// Constructs a `RustType` representing `Box<dyn Trait + 'a>` from parameters representing `Trait` and `'a` (optional), respectively.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn dyn_trait_of(trait_name: impl Into<Label>, lt: Option<RustLt>) -> Self {
+        Self::DynTrait(trait_name.into(), lt)
+    }
+
     /// Constructs a `RustType` representing `Result<T, E>` from parameters representing `T` and `E`, respectively.
     pub fn result_of(ok_type: RustType, err_type: RustType) -> RustType {
         Self::Atom(AtomType::Comp(CompType::Result(
@@ -509,6 +719,71 @@ impl RustType {
         )))
     }
 
+    /// Conservatively determines whether values of this type are `Copy`, in which case they can be
+    /// used by-value without an explicit `.clone()`.
+    ///
+    /// Locally-defined and imported types are assumed not to be `Copy`, as the code-generation
+    /// phase only derives `Debug` and `Clone` for the former and has no information about the latter.
+    pub fn is_copy(&self) -> bool {
+        match self {
+            RustType::Atom(at) => match at {
+                AtomType::Prim(..) => true,
+                AtomType::TypeRef(..) => false,
+                AtomType::Comp(ct) => match ct {
+                    CompType::Vec(..)
+                    | CompType::Boxed(..)
+                    | CompType::Result(..)
+                    | CompType::Slice(..)
+                    | CompType::Cow(..) => false,
+                    CompType::Array(elem, _) => elem.is_copy(),
+                    CompType::Borrow(_, m, _) => *m == Mut::Immutable,
+                },
+            },
+            RustType::AnonTuple(elts) => elts.iter().all(Self::is_copy),
+            RustType::Verbatim(..) | RustType::DynTrait(..) => false,
+        }
+    }
+
+    /// Collects every lifetime mentioned within this type, which any type definition or function
+    /// signature that uses it must declare as a parameter.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn lifetimes(&self) -> BTreeSet<RustLt> {
+        let mut acc = BTreeSet::new();
+        self.collect_lifetimes(&mut acc);
+        acc
+    }
+
+    fn collect_lifetimes(&self, acc: &mut BTreeSet<RustLt>) {
+        match self {
+            RustType::Atom(at) => match at {
+                AtomType::Prim(..) | AtomType::TypeRef(..) => {}
+                AtomType::Comp(ct) => match ct {
+                    CompType::Vec(t)
+                    | CompType::Array(t, _)
+                    | CompType::Boxed(t)
+                    | CompType::Slice(t) => t.collect_lifetimes(acc),
+                    CompType::Result(ok, err) => {
+                        ok.collect_lifetimes(acc);
+                        err.collect_lifetimes(acc);
+                    }
+                    CompType::Borrow(lt, _, t) | CompType::Cow(lt, t) => {
+                        acc.extend(lt.iter().cloned());
+                        t.collect_lifetimes(acc);
+                    }
+                },
+            },
+            RustType::AnonTuple(elts) => elts.iter().for_each(|t| t.collect_lifetimes(acc)),
+            RustType::Verbatim(_, params) => {
+                acc.extend(params.lt_params.iter().cloned());
+                params
+                    .ty_params
+                    .iter()
+                    .for_each(|t| t.collect_lifetimes(acc));
+            }
+            RustType::DynTrait(_, lt) => acc.extend(lt.iter().cloned()),
+        }
+    }
+
     fn try_as_primtype(&self) -> Option<PrimType> {
         match self {
             RustType::Atom(at) => match at {
@@ -534,6 +809,12 @@ impl ToFragment for RustType {
                 elems.delimit(Fragment::Char('('), Fragment::Char(')'))
             }
             RustType::Verbatim(con, params) => con.to_fragment().cat(params.to_fragment()),
+            RustType::DynTrait(trait_name, lt) => {
+                let f_lt = Fragment::opt(lt.as_ref(), <RustLt as ToFragment>::to_fragment);
+                let tmp =
+                    Fragment::intervene(trait_name.to_fragment(), Fragment::string(" + "), f_lt);
+                tmp.delimit(Fragment::string("Box<dyn "), Fragment::Char('>'))
+            }
         }
     }
 }
@@ -545,9 +826,93 @@ impl ToFragmentExt for RustType {
     }
 }
 
+/// Type-level path from a `Ground` value to some component nested within it, which can be
+/// used to compute the type of the value obtained by a chain of element-, parameter-, and field-accesses.
+#[derive(Clone, Debug)]
+pub(crate) enum Lens<T> {
+    Ground(T),
+    /// Element-type of a sequence (e.g. `Vec<T>`)
+    ElemOf(Box<Lens<T>>),
+    /// N-th type parameter of a parametric type (e.g. `Option<T>` or `Result<T, E>`)
+    #[cfg_attr(not(test), allow(dead_code))]
+    ParamOf(Box<Lens<T>>, usize),
+    /// Type of a positional field of a tuple, or a named field of a record
+    #[cfg_attr(not(test), allow(dead_code))]
+    FieldAccess(Box<Lens<T>>, SubIdent),
+}
+
+impl Lens<RustType> {
+    /// Computes the concrete type this lens points at, without any information about locally-defined types.
+    ///
+    /// Returns `None` if any step of the lens is applied to an incompatible type.
+    pub fn resolve(&self) -> Option<RustType> {
+        self.resolve_with(&|_| None)
+    }
+
+    /// Computes the concrete type this lens points at, using `lookup` to obtain the definitions of
+    /// locally-defined types (by index) when accessing named record fields.
+    ///
+    /// Returns `None` if any step of the lens is applied to an incompatible type.
+    pub fn resolve_with(&self, lookup: &dyn Fn(usize) -> Option<RustTypeDef>) -> Option<RustType> {
+        match self {
+            Lens::Ground(t) => Some(t.clone()),
+            Lens::ElemOf(inner) => match inner.resolve_with(lookup)?.auto_deref() {
+                RustType::Atom(AtomType::Comp(
+                    CompType::Vec(elem) | CompType::Slice(elem) | CompType::Array(elem, _),
+                )) => Some(elem.as_ref().clone()),
+                _ => None,
+            },
+            Lens::ParamOf(inner, ix) => match inner.resolve_with(lookup)? {
+                RustType::Atom(AtomType::Comp(CompType::Result(ok, err))) => match ix {
+                    0 => Some(ok.as_ref().clone()),
+                    1 => Some(err.as_ref().clone()),
+                    _ => None,
+                },
+                RustType::Atom(AtomType::Comp(CompType::Vec(elem))) if *ix == 0 => {
+                    Some(elem.as_ref().clone())
+                }
+                RustType::Verbatim(_, params) => params.ty_params.get(*ix).cloned(),
+                _ => None,
+            },
+            Lens::FieldAccess(inner, field) => {
+                match (inner.resolve_with(lookup)?.auto_deref(), field) {
+                    (RustType::AnonTuple(elts), SubIdent::ByIndex(ix)) => elts.get(*ix).cloned(),
+                    (
+                        RustType::Atom(AtomType::TypeRef(LocalType::LocalDef(ix, _))),
+                        SubIdent::ByName(name),
+                    ) => match lookup(ix)? {
+                        RustTypeDef::Struct(RustStruct::Record(fields)) => fields
+                            .into_iter()
+                            .find_map(|(lab, t)| (&lab == name).then_some(t)),
+                        RustTypeDef::Struct(RustStruct::Newtype(..)) | RustTypeDef::Enum(..) => {
+                            None
+                        }
+                    },
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+impl RustType {
+    /// Strips any outer layers of borrowing, as with auto-dereferencing of method receivers and field accesses.
+    fn auto_deref(self) -> RustType {
+        match self {
+            RustType::Atom(AtomType::Comp(
+                CompType::Borrow(_, _, inner) | CompType::Boxed(inner) | CompType::Cow(_, inner),
+            )) => inner.auto_deref(),
+            other => other,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(crate) enum RustStruct {
     Record(Vec<(Label, RustType)>),
+    /// Single-field tuple-struct; the label is the nominal identity of the newtype, so that two newtypes
+    /// over the same inner type are never conflated with one another.
+    Newtype(Label, Box<RustType>),
 }
 
 impl ToFragment for RustStruct {
@@ -556,6 +921,9 @@ impl ToFragment for RustStruct {
             RustStruct::Record(flds) => {
                 <(Label, RustType)>::block_sep(flds.iter(), Fragment::Char(','))
             }
+            RustStruct::Newtype(_name, inner) => inner
+                .to_fragment()
+                .delimit(Fragment::Char('('), Fragment::string(");")),
         }
     }
 }
@@ -704,24 +1072,82 @@ pub(crate) enum PrimType {
     U16,
     U32,
     U64,
+    I8,
+    I16,
+    I32,
+    I64,
     Bool,
     Char,
     Usize,
+    NonZeroU8,
+    NonZeroU16,
+    NonZeroU32,
+    NonZeroU64,
 }
 
 impl PrimType {
+    /// Returns the `NonZero` counterpart of an unsigned integer type, if it has one.
+    pub(crate) fn to_nonzero(self) -> Option<PrimType> {
+        match self {
+            PrimType::U8 => Some(PrimType::NonZeroU8),
+            PrimType::U16 => Some(PrimType::NonZeroU16),
+            PrimType::U32 => Some(PrimType::NonZeroU32),
+            PrimType::U64 => Some(PrimType::NonZeroU64),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn is_nonzero(&self) -> bool {
+        matches!(
+            self,
+            PrimType::NonZeroU8
+                | PrimType::NonZeroU16
+                | PrimType::NonZeroU32
+                | PrimType::NonZeroU64
+        )
+    }
+
     fn is_numeric(&self) -> bool {
         matches!(
             self,
-            PrimType::U8 | PrimType::U16 | PrimType::U32 | PrimType::U64 | PrimType::Usize
+            PrimType::U8
+                | PrimType::U16
+                | PrimType::U32
+                | PrimType::U64
+                | PrimType::I8
+                | PrimType::I16
+                | PrimType::I32
+                | PrimType::I64
+                | PrimType::Usize
         )
     }
 
     fn compare_width(pt0: PrimType, pt1: PrimType) -> Option<Ordering> {
         match (pt0, pt1) {
             (PrimType::Unit, _) | (_, PrimType::Unit) => None,
+            (
+                PrimType::NonZeroU8
+                | PrimType::NonZeroU16
+                | PrimType::NonZeroU32
+                | PrimType::NonZeroU64,
+                _,
+            )
+            | (
+                _,
+                PrimType::NonZeroU8
+                | PrimType::NonZeroU16
+                | PrimType::NonZeroU32
+                | PrimType::NonZeroU64,
+            ) => None,
             (PrimType::Char, _) | (_, PrimType::Char) => None,
             (PrimType::Bool, _) | (_, PrimType::Bool) => None,
+            // signed types are declared in order of increasing width
+            (
+                PrimType::I8 | PrimType::I16 | PrimType::I32 | PrimType::I64,
+                PrimType::I8 | PrimType::I16 | PrimType::I32 | PrimType::I64,
+            ) => Some(pt0.cmp(&pt1)),
+            (PrimType::I8 | PrimType::I16 | PrimType::I32 | PrimType::I64, _)
+            | (_, PrimType::I8 | PrimType::I16 | PrimType::I32 | PrimType::I64) => None,
             (PrimType::U8, PrimType::U8) => Some(Ordering::Equal),
             (PrimType::U8, _) => Some(Ordering::Less),
             (_, PrimType::U8) => Some(Ordering::Greater),
@@ -746,6 +1172,10 @@ impl From<BaseType> for PrimType {
             BaseType::U16 => PrimType::U16,
             BaseType::U32 => PrimType::U32,
             BaseType::U64 => PrimType::U64,
+            BaseType::I8 => PrimType::I8,
+            BaseType::I16 => PrimType::I16,
+            BaseType::I32 => PrimType::I32,
+            BaseType::I64 => PrimType::I64,
             BaseType::Char => PrimType::Char,
         }
     }
@@ -759,9 +1189,17 @@ impl ToFragment for PrimType {
             PrimType::U16 => "u16",
             PrimType::U32 => "u32",
             PrimType::U64 => "u64",
+            PrimType::I8 => "i8",
+            PrimType::I16 => "i16",
+            PrimType::I32 => "i32",
+            PrimType::I64 => "i64",
             PrimType::Bool => "bool",
             PrimType::Char => "char",
             PrimType::Usize => "usize",
+            PrimType::NonZeroU8 => "NonZeroU8",
+            PrimType::NonZeroU16 => "NonZeroU16",
+            PrimType::NonZeroU32 => "NonZeroU32",
+            PrimType::NonZeroU64 => "NonZeroU64",
         })
     }
 }
@@ -787,8 +1225,19 @@ impl ToFragment for RustLt {
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub(crate) enum CompType<T = Box<RustType>, U = T> {
     Vec(T),
+    /// Fixed-size array-type `[T; N]`
+    #[cfg_attr(not(test), allow(dead_code))]
+    Array(T, usize),
+    /// Owned heap-allocation, as required for recursive types
+    #[cfg_attr(not(test), allow(dead_code))]
+    Boxed(T),
     Result(T, U),
     Borrow(Option<RustLt>, Mut, T),
+    /// Unsized slice-type `[T]`, only meaningful behind a borrow or `Cow`
+    Slice(T),
+    /// Clone-on-write `Cow<'a, T>`, for values that are either borrowed from the input or owned after transformation
+    #[cfg_attr(not(test), allow(dead_code))]
+    Cow(Option<RustLt>, T),
 }
 
 impl<T, U> ToFragment for CompType<T, U>
@@ -802,6 +1251,16 @@ where
                 let tmp = inner.to_fragment();
                 tmp.delimit(Fragment::string("Vec<"), Fragment::Char('>'))
             }
+            CompType::Array(inner, len) => {
+                let tmp = inner
+                    .to_fragment()
+                    .intervene(Fragment::string("; "), Fragment::DisplayAtom(Rc::new(*len)));
+                tmp.delimit(Fragment::Char('['), Fragment::Char(']'))
+            }
+            CompType::Boxed(inner) => {
+                let tmp = inner.to_fragment();
+                tmp.delimit(Fragment::string("Box<"), Fragment::Char('>'))
+            }
             CompType::Result(ok, err) => {
                 let tmp = ok
                     .to_fragment()
@@ -815,6 +1274,15 @@ where
                 let f_body = Fragment::intervene(f_aux, Fragment::Char(' '), ty.to_fragment());
                 Fragment::cat(Fragment::Char('&'), f_body)
             }
+            CompType::Slice(inner) => {
+                let tmp = inner.to_fragment();
+                tmp.delimit(Fragment::Char('['), Fragment::Char(']'))
+            }
+            CompType::Cow(lt, ty) => {
+                let f_lt = Fragment::opt(lt.as_ref(), <RustLt as ToFragment>::to_fragment);
+                let tmp = Fragment::intervene(f_lt, Fragment::string(", "), ty.to_fragment());
+                tmp.delimit(Fragment::string("Cow<"), Fragment::Char('>'))
+            }
         }
     }
 }
@@ -860,6 +1328,10 @@ impl TryFrom<ValueType> for RustType {
             ValueType::Base(BaseType::U16) => Ok(PrimType::U16.into()),
             ValueType::Base(BaseType::U32) => Ok(PrimType::U32.into()),
             ValueType::Base(BaseType::U64) => Ok(PrimType::U64.into()),
+            ValueType::Base(BaseType::I8) => Ok(PrimType::I8.into()),
+            ValueType::Base(BaseType::I16) => Ok(PrimType::I16.into()),
+            ValueType::Base(BaseType::I32) => Ok(PrimType::I32.into()),
+            ValueType::Base(BaseType::I64) => Ok(PrimType::I64.into()),
             ValueType::Base(BaseType::Char) => Ok(PrimType::Char.into()),
             ValueType::Tuple(mut vs) => {
                 let mut buf = Vec::with_capacity(vs.len());
@@ -872,7 +1344,10 @@ impl TryFrom<ValueType> for RustType {
                 let inner = Self::try_from(t.as_ref().clone())?;
                 Ok(CompType::<Box<RustType>>::Vec(Box::new(inner)).into())
             }
-            ValueType::Any | ValueType::Record(..) | ValueType::Union(..) => Err(value),
+            ValueType::Any
+            | ValueType::Record(..)
+            | ValueType::Union(..)
+            | ValueType::Newtype(..) => Err(value),
         }
     }
 }
@@ -897,6 +1372,11 @@ impl ToFragment for Mut {
 pub(crate) enum RustEntity {
     Local(Label),
     Scoped(Vec<Label>, Label),
+    /// An associated item of a type, in the qualified form `<T>::name`
+    #[cfg_attr(not(test), allow(dead_code))]
+    Qualified(Box<RustType>, Label),
+    /// The `self` receiver of a method
+    SelfValue,
 }
 
 impl RustEntity {
@@ -909,6 +1389,11 @@ impl RustEntity {
                     .map(|scope| scope.to_fragment()),
                 Some(Fragment::string("::")),
             ),
+            RustEntity::Qualified(ty, v) => ty
+                .to_fragment()
+                .delimit(Fragment::Char('<'), Fragment::string(">::"))
+                .cat(v.to_fragment()),
+            RustEntity::SelfValue => Fragment::string("self"),
         }
     }
 }
@@ -942,6 +1427,10 @@ pub(crate) enum RustNumLit {
     U16(u16),
     U32(u32),
     U64(u64),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
     Usize(usize),
 }
 
@@ -952,6 +1441,10 @@ impl ToFragment for RustNumLit {
             RustNumLit::U16(n) => Fragment::string(format!("{n}u16")),
             RustNumLit::U32(n) => Fragment::string(format!("{n}u32")),
             RustNumLit::U64(n) => Fragment::string(format!("{n}u64")),
+            RustNumLit::I8(n) => Fragment::string(format!("{n}i8")),
+            RustNumLit::I16(n) => Fragment::string(format!("{n}i16")),
+            RustNumLit::I32(n) => Fragment::string(format!("{n}i32")),
+            RustNumLit::I64(n) => Fragment::string(format!("{n}i64")),
             RustNumLit::Usize(n) => Fragment::string(format!("{n}")),
         }
     }
@@ -990,6 +1483,82 @@ pub(crate) enum RustExpr {
     Closure(RustClosure),                     // only simple lambdas for now
     Slice(Box<RustExpr>, Box<RustExpr>, Box<RustExpr>), // object, start ix, end ix (exclusive)
     RangeExclusive(Box<RustExpr>, Box<RustExpr>),
+    Index(Box<RustExpr>, Box<RustExpr>), // object, index
+    #[cfg_attr(not(test), allow(dead_code))]
+    Unsafe(Box<RustExpr>), // `unsafe { .. }` block around a single expression
+    Macro(RustMacro),
+    /// Owned copy of the value of a place-expression, obtained as specified by the `OwnedKind`
+    #[cfg_attr(not(test), allow(dead_code))]
+    Owned(OwnedKind, Box<RustExpr>),
+}
+
+/// Strategy for obtaining an owned value from a place-expression, e.g. an element of a sequence or a field of a record
+#[derive(Clone, Debug)]
+pub(crate) enum OwnedKind {
+    /// `x.clone()`, for values whose type is not `Copy`
+    Cloned,
+    /// `x` as-is, for values whose type is `Copy`
+    Copied,
+    /// `*x`, for borrows of values whose type is `Copy`
+    Deref,
+    /// To be determined from the type at the end of the lens, by [`RustExpr::resolve_ownership`]
+    Unresolved(Box<Lens<RustType>>),
+}
+
+impl OwnedKind {
+    /// Determines how to obtain an owned value of the type `t` from a place of that type.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn for_type(t: &RustType) -> OwnedKind {
+        match t {
+            RustType::Atom(AtomType::Comp(CompType::Borrow(_, _, inner))) if inner.is_copy() => {
+                OwnedKind::Deref
+            }
+            RustType::Atom(AtomType::Comp(CompType::Borrow(..))) => OwnedKind::Cloned,
+            _ if t.is_copy() => OwnedKind::Copied,
+            _ => OwnedKind::Cloned,
+        }
+    }
+}
+
+/// Representation for invocations of standard-library macros in expression position
+#[derive(Clone, Debug)]
+pub(crate) enum RustMacro {
+    /// `matches!(<expr>, <pat> | <pat> ... if <guard>)`, where the guard is optional and may refer to
+    /// variables bound by the patterns.
+    ///
+    /// As with `match`-arms, there can only be a single guard, shared by all the alternatives.
+    Matches(Box<RustExpr>, Vec<RustPattern>, Option<Box<RustExpr>>),
+    /// `assert!(<cond>)`, or `debug_assert!(<cond>)` if the flag is set, in which case the condition
+    /// is only checked in builds with debug-assertions enabled.
+    Assert(bool, Box<RustExpr>),
+}
+
+impl ToFragment for RustMacro {
+    fn to_fragment(&self) -> Fragment {
+        match self {
+            RustMacro::Matches(expr, pats, guard) => {
+                let f_pats = Fragment::seq(
+                    pats.iter().map(RustPattern::to_fragment),
+                    Some(Fragment::string(" | ")),
+                );
+                let f_guard = Fragment::opt(guard.as_deref(), |guard| {
+                    Fragment::string(" if ").cat(guard.to_fragment_precedence(Precedence::Top))
+                });
+                expr.to_fragment_precedence(Precedence::Top)
+                    .intervene(Fragment::string(", "), f_pats.cat(f_guard))
+                    .delimit(Fragment::string("matches!("), Fragment::Char(')'))
+            }
+            RustMacro::Assert(is_debug, cond) => {
+                let name = if *is_debug {
+                    "debug_assert!("
+                } else {
+                    "assert!("
+                };
+                cond.to_fragment_precedence(Precedence::Top)
+                    .delimit(Fragment::string(name), Fragment::Char(')'))
+            }
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -1120,6 +1689,7 @@ pub(crate) enum Operator {
     Shl,
     Shr,
     BitOr,
+    BitXor,
     BitAnd,
 }
 
@@ -1133,6 +1703,7 @@ impl Operator {
             Operator::Mul => Precedence::MUL,
             Operator::Shl | Operator::Shr => Precedence::BITSHIFT,
             Operator::BitOr => Precedence::BITOR,
+            Operator::BitXor => Precedence::BITXOR,
             Operator::BitAnd => Precedence::BITAND,
         }
     }
@@ -1154,6 +1725,7 @@ impl Operator {
                 }
             }
             Operator::BitOr
+            | Operator::BitXor
             | Operator::BitAnd
             | Operator::Div
             | Operator::Rem
@@ -1194,6 +1766,7 @@ impl Operator {
             Operator::Shl => " << ",
             Operator::Shr => " >> ",
             Operator::BitOr => " | ",
+            Operator::BitXor => " ^ ",
             Operator::BitAnd => " & ",
         }
     }
@@ -1204,6 +1777,8 @@ pub(crate) enum RustOp {
     // scaffolding to allow for flexible infix operations from operator tokens; should contain spaces already
     InfixOp(Operator, Box<RustExpr>, Box<RustExpr>),
     AsCast(Box<RustExpr>, RustType),
+    /// Logical negation of a boolean expression, `!x`
+    Not(Box<RustExpr>),
 }
 
 impl RustOp {
@@ -1211,6 +1786,7 @@ impl RustOp {
         match self {
             Self::InfixOp(op, _, _) => op.precedence(),
             Self::AsCast(_, _) => Precedence::CAST_INFIX,
+            Self::Not(_) => Precedence::Prefix,
         }
     }
 
@@ -1235,6 +1811,7 @@ impl RustOp {
                 },
                 _ => false,
             },
+            RustOp::Not(expr) => expr.try_get_primtype() == Some(PrimType::Bool),
         }
     }
 }
@@ -1266,6 +1843,11 @@ impl ToFragmentExt for RustOp {
                 prec,
                 inherent,
             ),
+            RustOp::Not(expr) => cond_paren(
+                Fragment::Char('!').cat(expr.to_fragment_precedence(inherent)),
+                prec,
+                inherent,
+            ),
         }
     }
 }
@@ -1285,6 +1867,8 @@ impl RustExpr {
 
     pub const FALSE: Self = Self::PrimitiveLit(RustPrimLit::Boolean(false));
 
+    pub const SELF: Self = Self::Entity(RustEntity::SelfValue);
+
     pub fn some(inner: Self) -> Self {
         Self::local("Some").call_with([inner])
     }
@@ -1329,6 +1913,68 @@ impl RustExpr {
         Self::FieldAccess(Box::new(self), SubIdent::ByIndex(ix))
     }
 
+    /// Constructs a `matches!` expression testing `self` against the alternatives `pats`, with an optional guard.
+    pub fn matches(self, pats: impl IntoIterator<Item = RustPattern>, guard: Option<Self>) -> Self {
+        Self::Macro(RustMacro::Matches(
+            Box::new(self),
+            pats.into_iter().collect(),
+            guard.map(Box::new),
+        ))
+    }
+
+    /// Constructs an `assert!(self)` invocation, or `debug_assert!(self)` if `is_debug` is set.
+    pub fn assert(self, is_debug: bool) -> Self {
+        Self::Macro(RustMacro::Assert(is_debug, Box::new(self)))
+    }
+
+    /// Indexes into `self` at `ix` without panicking, as `self.get(ix).copied()` if `elem_type` is
+    /// [`Copy`](RustType::is_copy) and `self.get(ix).cloned()` otherwise.
+    ///
+    /// The resulting expression is `Option`-typed, being `None` whenever `ix` is out-of-bounds.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn safe_index(self, ix: Self, elem_type: RustType) -> Self {
+        let method = if elem_type.is_copy() {
+            "copied"
+        } else {
+            "cloned"
+        };
+        self.call_method_with("get", [ix]).call_method(method)
+    }
+
+    /// Indexes into `self` at `ix`, as `self[ix]`, unless `options.use_unchecked_indexing` is set,
+    /// in which case the unchecked form produced by [`RustExpr::get_unchecked`] is emitted instead.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn index(self, ix: Self, options: &GenOptions) -> Self {
+        if options.use_unchecked_indexing {
+            self.get_unchecked(ix)
+        } else {
+            Self::Index(Box::new(self), Box::new(ix))
+        }
+    }
+
+    /// Indexes into `self` at `ix` without bounds-checking, as `unsafe { self.get_unchecked(ix) }`.
+    ///
+    /// # Safety
+    ///
+    /// The generated code has undefined behavior unless `ix` is in-bounds for `self` whenever it
+    /// is evaluated, so this should only be used where the bound is already established by
+    /// the surrounding generated code (e.g. a prior length-check on the same slice).
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn get_unchecked(self, ix: Self) -> Self {
+        Self::Unsafe(Box::new(self.call_method_with("get_unchecked", [ix])))
+    }
+
+    /// Iterates over the elements of `self` by value, as `self.iter().copied()` if `elem_type` is
+    /// [`Copy`](RustType::is_copy) and `self.iter().cloned()` otherwise.
+    pub fn iter_owned(self, elem_type: RustType) -> Self {
+        let method = if elem_type.is_copy() {
+            "copied"
+        } else {
+            "cloned"
+        };
+        self.call_method("iter").call_method(method)
+    }
+
     pub fn call_with(self, args: impl IntoIterator<Item = Self>) -> Self {
         Self::FunctionCall(Box::new(self), args.into_iter().collect())
     }
@@ -1369,6 +2015,121 @@ impl RustExpr {
         Self::local("Err").call_with([err_val])
     }
 
+    /// Converts `self`, a `Vec<T>` of (presumed) length `len`, into a fixed-size array `[T; len]`,
+    /// as `<[T; len]>::try_from(self).map_err(|_| ParseError::WrongLength)?`.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn vec_into_array(self, elem_t: RustType, len: usize) -> RustExpr {
+        let on_err =
+            RustClosure::new_transform("_", None, RustExpr::scoped(["ParseError"], "WrongLength"));
+        Self::Entity(RustEntity::Qualified(
+            Box::new(RustType::array_of(elem_t, len)),
+            Label::from("try_from"),
+        ))
+        .call_with([self])
+        .call_method_with("map_err", [RustExpr::Closure(on_err)])
+        .wrap_try()
+    }
+
+    /// Marks `self` as a place-expression to be converted into an owned value, according to the type
+    /// at the end of `lens`.
+    ///
+    /// The result must be passed through [`RustExpr::resolve_ownership`] before it can be rendered.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn owned(self, lens: Lens<RustType>) -> RustExpr {
+        RustExpr::Owned(OwnedKind::Unresolved(Box::new(lens)), Box::new(self))
+    }
+
+    /// Replaces every `OwnedKind::Unresolved` within `self` with `Cloned`, `Copied`, or `Deref`, according to the
+    /// type its lens resolves to, using `type_ctx` to look up the definitions of locally-defined types.
+    ///
+    /// Lenses that cannot be resolved fall back to `Cloned`, which is valid for any `Clone` type.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn resolve_ownership(
+        mut self,
+        type_ctx: &dyn Fn(usize) -> Option<RustTypeDef>,
+    ) -> RustExpr {
+        self.resolve_ownership_mut(type_ctx);
+        self
+    }
+
+    fn resolve_ownership_mut(&mut self, type_ctx: &dyn Fn(usize) -> Option<RustTypeDef>) {
+        match self {
+            RustExpr::Entity(..) | RustExpr::PrimitiveLit(..) => {}
+            RustExpr::ArrayLit(elts) | RustExpr::Tuple(elts) => elts
+                .iter_mut()
+                .for_each(|elt| elt.resolve_ownership_mut(type_ctx)),
+            RustExpr::MethodCall(expr, _, args) | RustExpr::FunctionCall(expr, args) => {
+                expr.resolve_ownership_mut(type_ctx);
+                args.iter_mut()
+                    .for_each(|arg| arg.resolve_ownership_mut(type_ctx));
+            }
+            RustExpr::Struct(_, assigns) => assigns
+                .iter_mut()
+                .filter_map(|(_, val)| val.as_deref_mut())
+                .for_each(|val| val.resolve_ownership_mut(type_ctx)),
+            RustExpr::FieldAccess(expr, _)
+            | RustExpr::Deref(expr)
+            | RustExpr::Borrow(expr)
+            | RustExpr::BorrowMut(expr)
+            | RustExpr::Try(expr)
+            | RustExpr::Unsafe(expr)
+            | RustExpr::Operation(RustOp::AsCast(expr, _) | RustOp::Not(expr))
+            | RustExpr::Macro(RustMacro::Assert(_, expr)) => expr.resolve_ownership_mut(type_ctx),
+            RustExpr::Operation(RustOp::InfixOp(_, lhs, rhs))
+            | RustExpr::RangeExclusive(lhs, rhs)
+            | RustExpr::Index(lhs, rhs) => {
+                lhs.resolve_ownership_mut(type_ctx);
+                rhs.resolve_ownership_mut(type_ctx);
+            }
+            RustExpr::Slice(expr, start, stop) => {
+                expr.resolve_ownership_mut(type_ctx);
+                start.resolve_ownership_mut(type_ctx);
+                stop.resolve_ownership_mut(type_ctx);
+            }
+            RustExpr::BlockScope(stmts, tail) => {
+                RustStmt::resolve_ownership_all(stmts, type_ctx);
+                tail.resolve_ownership_mut(type_ctx);
+            }
+            RustExpr::Control(ctrl) => ctrl.resolve_ownership_mut(type_ctx),
+            RustExpr::Closure(RustClosure(_, body)) => match body {
+                ClosureBody::Expression(expr) => expr.resolve_ownership_mut(type_ctx),
+                ClosureBody::Statements(stmts) => RustStmt::resolve_ownership_all(stmts, type_ctx),
+            },
+            RustExpr::Macro(RustMacro::Matches(expr, _, guard)) => {
+                expr.resolve_ownership_mut(type_ctx);
+                if let Some(guard) = guard {
+                    guard.resolve_ownership_mut(type_ctx);
+                }
+            }
+            RustExpr::Owned(kind, expr) => {
+                if let OwnedKind::Unresolved(lens) = kind {
+                    *kind = lens
+                        .resolve_with(type_ctx)
+                        .map_or(OwnedKind::Cloned, |t| OwnedKind::for_type(&t));
+                }
+                expr.resolve_ownership_mut(type_ctx);
+            }
+        }
+    }
+
+    /// Returns the logical negation of a boolean expression, inverting (in)equality comparisons and
+    /// cancelling double-negations rather than prepending `!` where possible.
+    pub fn negate(self) -> RustExpr {
+        match self {
+            RustExpr::PrimitiveLit(RustPrimLit::Boolean(b)) => {
+                RustExpr::PrimitiveLit(RustPrimLit::Boolean(!b))
+            }
+            RustExpr::Operation(RustOp::InfixOp(Operator::Eq, lhs, rhs)) => {
+                RustExpr::Operation(RustOp::InfixOp(Operator::Neq, lhs, rhs))
+            }
+            RustExpr::Operation(RustOp::InfixOp(Operator::Neq, lhs, rhs)) => {
+                RustExpr::Operation(RustOp::InfixOp(Operator::Eq, lhs, rhs))
+            }
+            RustExpr::Operation(RustOp::Not(inner)) => *inner,
+            other => RustExpr::Operation(RustOp::Not(Box::new(other))),
+        }
+    }
+
     pub fn try_get_primtype(&self) -> Option<PrimType> {
         match self {
             RustExpr::Entity(_) => None,
@@ -1379,6 +2140,10 @@ impl RustExpr {
                     RustNumLit::U16(..) => Some(PrimType::U16),
                     RustNumLit::U32(..) => Some(PrimType::U32),
                     RustNumLit::U64(..) => Some(PrimType::U64),
+                    RustNumLit::I8(..) => Some(PrimType::I8),
+                    RustNumLit::I16(..) => Some(PrimType::I16),
+                    RustNumLit::I32(..) => Some(PrimType::I32),
+                    RustNumLit::I64(..) => Some(PrimType::I64),
                     RustNumLit::Usize(..) => Some(PrimType::Usize),
                 },
                 RustPrimLit::Char(..) => Some(PrimType::Char),
@@ -1439,12 +2204,19 @@ impl RustExpr {
                         None
                     }
                 }
+                RustOp::Not(expr) => expr.try_get_primtype().filter(|pt| *pt == PrimType::Bool),
             },
             RustExpr::BlockScope(_stmts, ret) => ret.try_get_primtype(),
             RustExpr::Control(..)
             | RustExpr::Closure(..)
             | RustExpr::Slice(..)
-            | RustExpr::RangeExclusive(..) => None,
+            | RustExpr::RangeExclusive(..)
+            | RustExpr::Index(..)
+            | RustExpr::Unsafe(..) => None,
+            RustExpr::Macro(RustMacro::Matches(..)) => Some(PrimType::Bool),
+            RustExpr::Macro(RustMacro::Assert(..)) => Some(PrimType::Unit),
+            RustExpr::Owned(OwnedKind::Cloned | OwnedKind::Copied, expr) => expr.try_get_primtype(),
+            RustExpr::Owned(..) => None,
         }
     }
 
@@ -1471,6 +2243,7 @@ impl RustExpr {
                 RustOp::InfixOp(.., lhs, rhs) => lhs.is_pure() && rhs.is_pure() && op.is_sound(),
                 // NOTE - illegal casts like `x as u8` where x >= 256 are language-level errors that are neither pure nor impure
                 RustOp::AsCast(expr, ..) => expr.is_pure() && op.is_sound(),
+                RustOp::Not(expr) => expr.is_pure(),
             },
             RustExpr::BlockScope(stmts, tail) => stmts.is_empty() && tail.is_pure(),
             // NOTE - there may be some pure control expressions but those will be relatively rare as natural occurrences
@@ -1480,10 +2253,170 @@ impl RustExpr {
             RustExpr::Slice(..) => false,
             // NOTE - ranges can be inverted
             RustExpr::RangeExclusive(..) => false,
+            // NOTE - checked indexing can panic, and unchecked indexing can be unsound, when out-of-bounds
+            RustExpr::Index(..) | RustExpr::Unsafe(..) => false,
+            RustExpr::Macro(RustMacro::Matches(expr, _, guard)) => {
+                expr.is_pure() && guard.as_deref().is_none_or(Self::is_pure)
+            }
+            // NOTE - assertions can panic
+            RustExpr::Macro(RustMacro::Assert(..)) => false,
+            RustExpr::Owned(_, expr) => expr.is_pure(),
         }
     }
 }
 
+/// Analysis of the evaluation-order of sub-expressions that may short-circuit out of the enclosing
+/// fn-body (e.g. via `?`), and which are therefore sensitive to being reordered.
+pub(crate) trait ShortCircuitExt {
+    /// Conservatively determines whether the evaluation of `self` may short-circuit.
+    fn may_short_circuit(&self) -> bool;
+
+    /// Checks that no value-producing node within `self` has more than one argument that may short-circuit,
+    /// so that the relative order in which its arguments are evaluated cannot affect program behavior.
+    #[cfg_attr(not(test), allow(dead_code))]
+    fn check_eval_purity(&self) -> bool;
+}
+
+impl ShortCircuitExt for RustStmt {
+    fn may_short_circuit(&self) -> bool {
+        match self {
+            RustStmt::Let(.., expr) | RustStmt::LetPattern(_, expr) | RustStmt::Expr(expr) => {
+                expr.may_short_circuit()
+            }
+            RustStmt::Return(kind, expr) => kind.is_keyword() || expr.may_short_circuit(),
+            // NOTE - control-flow statements may contain early returns or breaks that we don't bother analyzing
+            RustStmt::Control(..) => true,
+        }
+    }
+
+    fn check_eval_purity(&self) -> bool {
+        match self {
+            RustStmt::Let(.., expr)
+            | RustStmt::LetPattern(_, expr)
+            | RustStmt::Expr(expr)
+            | RustStmt::Return(_, expr) => expr.check_eval_purity(),
+            RustStmt::Control(..) => true,
+        }
+    }
+}
+
+impl ShortCircuitExt for RustExpr {
+    fn may_short_circuit(&self) -> bool {
+        match self {
+            RustExpr::Try(..) => true,
+            // NOTE - closure bodies are not evaluated at the point of definition
+            RustExpr::Closure(..) => false,
+            RustExpr::Control(..) => true,
+            RustExpr::BlockScope(stmts, tail) => {
+                stmts.iter().any(RustStmt::may_short_circuit) || tail.may_short_circuit()
+            }
+            _ => self.eval_args().into_iter().any(Self::may_short_circuit),
+        }
+    }
+
+    fn check_eval_purity(&self) -> bool {
+        match self {
+            RustExpr::Closure(..) | RustExpr::Control(..) => true,
+            RustExpr::BlockScope(stmts, tail) => {
+                stmts.iter().all(RustStmt::check_eval_purity) && tail.check_eval_purity()
+            }
+            _ => {
+                let args = self.eval_args();
+                args.iter().filter(|arg| arg.may_short_circuit()).count() <= 1
+                    && args.into_iter().all(Self::check_eval_purity)
+            }
+        }
+    }
+}
+
+impl RustExpr {
+    /// Returns the immediate sub-expressions that are evaluated as part of evaluating `self`.
+    ///
+    /// Closures, control expressions, and block-scopes are treated as having no arguments.
+    fn eval_args(&self) -> Vec<&RustExpr> {
+        match self {
+            RustExpr::Entity(..) | RustExpr::PrimitiveLit(..) => Vec::new(),
+            RustExpr::ArrayLit(elts) | RustExpr::Tuple(elts) => elts.iter().collect(),
+            RustExpr::MethodCall(recv, _, args) => {
+                std::iter::once(recv.as_ref()).chain(args.iter()).collect()
+            }
+            RustExpr::FunctionCall(f, args) => {
+                std::iter::once(f.as_ref()).chain(args.iter()).collect()
+            }
+            RustExpr::Struct(_, assigns) => assigns
+                .iter()
+                .filter_map(|(_, val)| val.as_deref())
+                .collect(),
+            RustExpr::FieldAccess(expr, _)
+            | RustExpr::Deref(expr)
+            | RustExpr::Borrow(expr)
+            | RustExpr::BorrowMut(expr)
+            | RustExpr::Try(expr)
+            | RustExpr::Unsafe(expr)
+            | RustExpr::Owned(_, expr) => vec![expr.as_ref()],
+            RustExpr::Operation(RustOp::InfixOp(_, lhs, rhs))
+            | RustExpr::RangeExclusive(lhs, rhs)
+            | RustExpr::Index(lhs, rhs) => {
+                vec![lhs.as_ref(), rhs.as_ref()]
+            }
+            RustExpr::Operation(RustOp::AsCast(expr, _) | RustOp::Not(expr)) => vec![expr.as_ref()],
+            RustExpr::Slice(expr, start, stop) => {
+                vec![expr.as_ref(), start.as_ref(), stop.as_ref()]
+            }
+            // NOTE - the guard is only evaluated if a pattern matches, but is still an argument for the purposes of short-circuiting
+            RustExpr::Macro(RustMacro::Matches(expr, _, guard)) => std::iter::once(expr.as_ref())
+                .chain(guard.as_deref())
+                .collect(),
+            RustExpr::Macro(RustMacro::Assert(_, cond)) => vec![cond.as_ref()],
+            RustExpr::BlockScope(..) | RustExpr::Control(..) | RustExpr::Closure(..) => Vec::new(),
+        }
+    }
+
+    /// Hoists the impure arguments of a tuple, struct, array, or function-call expression into a sequence of
+    /// `let`-bindings, in their original order, when more than one of its arguments may short-circuit.
+    ///
+    /// Returns `self` unchanged if at most one argument may short-circuit, or if it is not one of the above node-types.
+    pub fn sequence_short_circuits(self) -> RustExpr {
+        fn hoist<'a>(stmts: &mut Vec<RustStmt>, args: impl IntoIterator<Item = &'a mut RustExpr>) {
+            for arg in args {
+                if !arg.is_pure() {
+                    let name = Label::from(format!("__arg{}", stmts.len()));
+                    let value = std::mem::replace(arg, RustExpr::local(name.clone()));
+                    stmts.push(RustStmt::assign(name, value));
+                }
+            }
+        }
+
+        let n_short = match &self {
+            RustExpr::ArrayLit(..)
+            | RustExpr::Tuple(..)
+            | RustExpr::Struct(..)
+            | RustExpr::FunctionCall(..) => self
+                .eval_args()
+                .into_iter()
+                .filter(|arg| arg.may_short_circuit())
+                .count(),
+            _ => 0,
+        };
+        if n_short <= 1 {
+            return self;
+        }
+
+        let mut stmts = Vec::new();
+        let mut this = self;
+        match &mut this {
+            RustExpr::ArrayLit(elts) | RustExpr::Tuple(elts) => hoist(&mut stmts, elts.iter_mut()),
+            RustExpr::Struct(_, assigns) => hoist(
+                &mut stmts,
+                assigns.iter_mut().filter_map(|(_, val)| val.as_deref_mut()),
+            ),
+            RustExpr::FunctionCall(_, args) => hoist(&mut stmts, args.iter_mut()),
+            _ => unreachable!("non-zero short-circuit count for unexpected node-type"),
+        }
+        RustExpr::BlockScope(stmts, Box::new(this))
+    }
+}
+
 impl ToFragmentExt for RustExpr {
     // REVIEW - make sure we aren't leaving anything by the wayside
     fn to_fragment_precedence(&self, prec: Precedence) -> Fragment {
@@ -1507,7 +2440,8 @@ impl ToFragmentExt for RustExpr {
                 .to_fragment_precedence(Precedence::Projection)
                 .intervene(Fragment::Char('.'), name.to_fragment()),
             RustExpr::FunctionCall(f, args) => cond_paren(
-                f.to_fragment_precedence(prec)
+                // the callee binds as tightly as a projection, so e.g. closures are parenthesized
+                f.to_fragment_precedence(Precedence::Projection)
                     .cat(ToFragmentExt::paren_list_prec(args, Precedence::Top)),
                 prec,
                 Precedence::Calculus,
@@ -1576,6 +2510,30 @@ impl ToFragmentExt for RustExpr {
                 prec,
                 Precedence::Top,
             ),
+            RustExpr::Index(expr, ix) => expr.to_fragment_precedence(Precedence::Projection).cat(
+                ix.to_fragment_precedence(Precedence::Top)
+                    .delimit(Fragment::Char('['), Fragment::Char(']')),
+            ),
+            RustExpr::Macro(mac) => mac.to_fragment(),
+            RustExpr::Unsafe(expr) => Fragment::string("unsafe ").cat(
+                expr.to_fragment_precedence(Precedence::Top)
+                    .delimit(Fragment::string("{ "), Fragment::string(" }")),
+            ),
+            RustExpr::Owned(kind, expr) => match kind {
+                OwnedKind::Cloned => cond_paren(
+                    expr.to_fragment_precedence(Precedence::Projection)
+                        .cat(Fragment::string(".clone()")),
+                    prec,
+                    Precedence::Projection,
+                ),
+                OwnedKind::Copied => expr.to_fragment_precedence(prec),
+                OwnedKind::Deref => {
+                    Fragment::Char('*').cat(expr.to_fragment_precedence(Precedence::Prefix))
+                }
+                OwnedKind::Unresolved(lens) => {
+                    panic!("unresolved ownership for {lens:?} (missing call to resolve_ownership?)")
+                }
+            },
         }
     }
 }
@@ -1611,6 +2569,9 @@ impl From<bool> for ReturnKind {
 #[derive(Clone, Debug)]
 pub(crate) enum RustStmt {
     Let(Mut, Label, Option<RustType>, RustExpr),
+    /// Destructuring `let` over an irrefutable pattern
+    #[cfg_attr(not(test), allow(dead_code))]
+    LetPattern(RustPattern, RustExpr),
     Expr(RustExpr),
     Return(ReturnKind, RustExpr), // bool: true for explicit return, false for implicit return
     Control(RustControl),
@@ -1626,6 +2587,57 @@ impl RustStmt {
         Self::Let(Mut::Mutable, name.into(), None, rhs)
     }
 
+    /// Constructs an early-return guard `if !<cond> { return Err(<err>); }`, for checking a predicate
+    /// without nesting the code that follows it inside an `if`-block.
+    pub fn guard(cond: RustExpr, err: RustExpr) -> Self {
+        Self::Control(RustControl::If(
+            cond.negate(),
+            vec![RustStmt::Return(ReturnKind::Keyword, RustExpr::err(err))],
+            None,
+        ))
+    }
+
+    /// Constructs a statement binding each element of a fixed-size array, in order, to the corresponding
+    /// identifier in `names` (e.g. `let [hi, lo] = bytes;`), as an alternative to positional indexing.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn destructure_array<Name: Into<Label>>(
+        names: impl IntoIterator<Item = Name>,
+        array: RustExpr,
+    ) -> Self {
+        let pat = RustPattern::ArrayLiteral(
+            names
+                .into_iter()
+                .map(|name| RustPattern::CatchAll(Some(name.into())))
+                .collect(),
+        );
+        Self::LetPattern(pat, array)
+    }
+
+    /// Returns the identifiers that are newly bound by this statement, in order of occurrence.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn bound_vars(&self) -> Vec<&Label> {
+        match self {
+            RustStmt::Let(_, name, ..) => vec![name],
+            RustStmt::LetPattern(pat, _) => pat.bound_vars(),
+            RustStmt::Expr(..) | RustStmt::Return(..) | RustStmt::Control(..) => Vec::new(),
+        }
+    }
+
+    fn resolve_ownership_all(
+        stmts: &mut [RustStmt],
+        type_ctx: &dyn Fn(usize) -> Option<RustTypeDef>,
+    ) {
+        for stmt in stmts.iter_mut() {
+            match stmt {
+                RustStmt::Let(.., expr)
+                | RustStmt::LetPattern(_, expr)
+                | RustStmt::Expr(expr)
+                | RustStmt::Return(_, expr) => expr.resolve_ownership_mut(type_ctx),
+                RustStmt::Control(ctrl) => ctrl.resolve_ownership_mut(type_ctx),
+            }
+        }
+    }
+
     pub fn assign_and_forget(rhs: RustExpr) -> Option<Self> {
         if rhs.is_pure() {
             None
@@ -1646,6 +2658,46 @@ pub(crate) enum RustControl {
     Break, // no support for break values or loop labels, yet
 }
 
+impl RustControl {
+    fn resolve_ownership_mut(&mut self, type_ctx: &dyn Fn(usize) -> Option<RustTypeDef>) {
+        match self {
+            RustControl::Loop(body) => RustStmt::resolve_ownership_all(body, type_ctx),
+            RustControl::While(expr, body)
+            | RustControl::ForIter(_, expr, body)
+            | RustControl::ForRange0(_, expr, body) => {
+                expr.resolve_ownership_mut(type_ctx);
+                RustStmt::resolve_ownership_all(body, type_ctx);
+            }
+            RustControl::If(cond, b_then, b_else) => {
+                cond.resolve_ownership_mut(type_ctx);
+                RustStmt::resolve_ownership_all(b_then, type_ctx);
+                if let Some(b_else) = b_else {
+                    RustStmt::resolve_ownership_all(b_else, type_ctx);
+                }
+            }
+            RustControl::Match(expr, body) => {
+                expr.resolve_ownership_mut(type_ctx);
+                let cases = match body {
+                    RustMatchBody::Irrefutable(cases) => cases,
+                    RustMatchBody::Refutable(cases, catchall) => {
+                        if let RustCatchAll::ReturnErrorValue { value } = catchall {
+                            value.resolve_ownership_mut(type_ctx);
+                        }
+                        cases
+                    }
+                };
+                for (lhs, stmts) in cases.iter_mut() {
+                    if let MatchCaseLHS::WithGuard(_, guard) = lhs {
+                        guard.resolve_ownership_mut(type_ctx);
+                    }
+                    RustStmt::resolve_ownership_all(stmts, type_ctx);
+                }
+            }
+            RustControl::Break => {}
+        }
+    }
+}
+
 pub(crate) type RustMatchCase = (MatchCaseLHS, Vec<RustStmt>);
 
 #[derive(Clone, Debug)]
@@ -1732,6 +2784,8 @@ pub(crate) enum RustPattern {
     Fill,                                   // `..`
     CatchAll(Option<Label>),                // None <- `_`, Some("x") for `x`
     Variant(Constructor, Box<RustPattern>), // FIXME - need to attach enum scope
+    Or(Vec<RustPattern>),                   // `p0 | p1 | ...`
+    PrimRange(RustPrimLit, RustPrimLit),    // `lo..=hi`
 }
 
 #[derive(Debug, Clone)]
@@ -1742,6 +2796,26 @@ pub(crate) enum Constructor {
     Compound(Label, Label),
 }
 
+impl RustPattern {
+    /// Returns the identifiers bound by this pattern, in left-to-right order.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub fn bound_vars(&self) -> Vec<&Label> {
+        match self {
+            RustPattern::PrimLiteral(..)
+            | RustPattern::PrimRange(..)
+            | RustPattern::Fill
+            | RustPattern::CatchAll(None) => Vec::new(),
+            RustPattern::CatchAll(Some(lab)) => vec![lab],
+            RustPattern::TupleLiteral(pats) | RustPattern::ArrayLiteral(pats) => {
+                pats.iter().flat_map(RustPattern::bound_vars).collect()
+            }
+            RustPattern::Variant(_, inner) => inner.bound_vars(),
+            // every alternative binds the same identifiers
+            RustPattern::Or(alts) => alts.first().map_or_else(Vec::new, RustPattern::bound_vars),
+        }
+    }
+}
+
 impl From<Constructor> for RustEntity {
     fn from(value: Constructor) -> Self {
         match value {
@@ -1764,6 +2838,10 @@ impl ToFragment for RustPattern {
     fn to_fragment(&self) -> Fragment {
         match self {
             RustPattern::PrimLiteral(pl) => pl.to_fragment(),
+            RustPattern::PrimRange(lo, hi) => lo
+                .to_fragment()
+                .cat(Fragment::string("..="))
+                .cat(hi.to_fragment()),
             RustPattern::TupleLiteral(tup) => RustPattern::paren_list(tup),
             RustPattern::ArrayLiteral(tup) => RustPattern::brace_list(tup),
             RustPattern::Variant(constr, inner) => {
@@ -1778,6 +2856,10 @@ impl ToFragment for RustPattern {
             RustPattern::Fill => Fragment::String("..".into()),
             RustPattern::CatchAll(None) => Fragment::Char('_'),
             RustPattern::CatchAll(Some(lab)) => Fragment::String(lab.clone()),
+            RustPattern::Or(alts) => Fragment::seq(
+                alts.iter().map(RustPattern::to_fragment),
+                Some(Fragment::string(" | ")),
+            ),
         }
     }
 }
@@ -1854,6 +2936,11 @@ impl ToFragment for RustStmt {
             .cat(Fragment::string(" = "))
             .cat(value.to_fragment_precedence(Precedence::TOP))
             .cat(Fragment::Char(';')),
+            RustStmt::LetPattern(pat, value) => Fragment::string("let ")
+                .cat(pat.to_fragment())
+                .cat(Fragment::string(" = "))
+                .cat(value.to_fragment_precedence(Precedence::TOP))
+                .cat(Fragment::Char(';')),
             RustStmt::Expr(expr) => expr
                 .to_fragment_precedence(Precedence::TOP)
                 .cat(Fragment::Char(';')),
@@ -1986,6 +3073,402 @@ mod test {
         expect_fragment(&rt, "Vec<(Label, TypeRef)>");
     }
 
+    #[test]
+    fn boxed_type() {
+        let rt = RustType::box_of(RustType::imported("Foo"));
+        expect_fragment(&rt, "Box<Foo>");
+        assert!(!rt.is_copy());
+        assert_eq!(
+            Lens::FieldAccess(
+                ground(RustType::box_of(RustType::anon_tuple(
+                    [PrimType::U8.into()]
+                ))),
+                SubIdent::ByIndex(0)
+            )
+            .resolve(),
+            Some(PrimType::U8.into())
+        );
+    }
+
+    #[test]
+    fn slice_type() {
+        let rt = RustType::borrow_of(
+            None,
+            Mut::Immutable,
+            RustType::slice_of(PrimType::U8.into()),
+        );
+        expect_fragment(&rt, "&[u8]");
+        assert!(rt.is_copy());
+        assert_eq!(
+            Lens::ElemOf(ground(rt)).resolve(),
+            Some(PrimType::U8.into())
+        );
+    }
+
+    #[test]
+    fn cow_type() {
+        let lt = RustLt::Parametric("'a".into());
+        let rt = RustType::cow_of(Some(lt), RustType::slice_of(PrimType::U8.into()));
+        expect_fragment(&rt, "Cow<'a, [u8]>");
+        assert!(!rt.is_copy());
+        assert_eq!(
+            Lens::ElemOf(ground(rt)).resolve(),
+            Some(PrimType::U8.into())
+        );
+        expect_fragment(
+            &RustType::cow_of(None, RustType::imported("str")),
+            "Cow<str>",
+        );
+    }
+
+    #[test]
+    fn dyn_trait_type() {
+        let lt = RustLt::Parametric("'a".into());
+        let rt = RustType::dyn_trait_of("Decode", Some(lt.clone()));
+        expect_fragment(&rt, "Box<dyn Decode + 'a>");
+        assert!(!rt.is_copy());
+        assert_eq!(
+            RustType::vec_of(rt)
+                .lifetimes()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec![lt]
+        );
+        let rt = RustType::dyn_trait_of("Decode", None);
+        expect_fragment(&rt, "Box<dyn Decode>");
+        assert!(rt.lifetimes().is_empty());
+    }
+
+    #[test]
+    fn array_type() {
+        let rt = RustType::array_of(PrimType::U16.into(), 4);
+        expect_fragment(&rt, "[u16; 4]");
+        assert!(rt.is_copy());
+        assert!(!RustType::array_of(RustType::vec_of(PrimType::U8.into()), 2).is_copy());
+        assert_eq!(
+            Lens::ElemOf(ground(rt)).resolve(),
+            Some(PrimType::U16.into())
+        );
+    }
+
+    #[test]
+    fn nonzero_type() {
+        let rt = RustType::from(PrimType::NonZeroU16);
+        expect_fragment(&rt, "NonZeroU16");
+        assert!(rt.is_copy());
+        assert_eq!(PrimType::U16.to_nonzero(), Some(PrimType::NonZeroU16));
+        assert_eq!(PrimType::I16.to_nonzero(), None);
+    }
+
+    #[test]
+    fn iter_owned_copied() {
+        let expr = RustExpr::local("xs").iter_owned(PrimType::U8.into());
+        expect_fragment(&expr, "xs.iter().copied()");
+    }
+
+    #[test]
+    fn iter_owned_cloned() {
+        let expr = RustExpr::local("xs").iter_owned(RustType::imported("String"));
+        expect_fragment(&expr, "xs.iter().cloned()");
+    }
+
+    #[test]
+    fn copy_types() {
+        assert!(RustType::from(PrimType::U16).is_copy());
+        assert!(RustType::anon_tuple([PrimType::U8.into(), PrimType::Char.into()]).is_copy());
+        assert!(
+            RustType::borrow_of(None, Mut::Immutable, RustType::vec_of(PrimType::U8.into()))
+                .is_copy()
+        );
+        assert!(!RustType::borrow_of(None, Mut::Mutable, PrimType::U8.into()).is_copy());
+        assert!(!RustType::vec_of(PrimType::U8.into()).is_copy());
+        assert!(
+            !RustType::anon_tuple([PrimType::U8.into(), RustType::defined(0, "Foo")]).is_copy()
+        );
+    }
+
+    fn ground(t: impl Into<RustType>) -> Box<Lens<RustType>> {
+        Box::new(Lens::Ground(t.into()))
+    }
+
+    #[test]
+    fn lens_elem_of() {
+        let vec_t = RustType::vec_of(PrimType::U16.into());
+        assert_eq!(
+            Lens::ElemOf(ground(vec_t.clone())).resolve(),
+            Some(PrimType::U16.into())
+        );
+        assert_eq!(
+            Lens::ElemOf(ground(RustType::borrow_of(None, Mut::Immutable, vec_t))).resolve(),
+            Some(PrimType::U16.into())
+        );
+        assert_eq!(Lens::ElemOf(ground(PrimType::U8)).resolve(), None);
+    }
+
+    #[test]
+    fn lens_param_of() {
+        let mut params = UseParams::new();
+        params.push_type(RustType::from(PrimType::Char));
+        let option_t = RustType::verbatim("Option", Some(params));
+        assert_eq!(
+            Lens::ParamOf(ground(option_t.clone()), 0).resolve(),
+            Some(PrimType::Char.into())
+        );
+        assert_eq!(Lens::ParamOf(ground(option_t), 1).resolve(), None);
+        let result_t = RustType::result_of(PrimType::U32.into(), RustType::imported("ParseError"));
+        assert_eq!(
+            Lens::ParamOf(ground(result_t.clone()), 0).resolve(),
+            Some(PrimType::U32.into())
+        );
+        assert_eq!(
+            Lens::ParamOf(ground(result_t), 1).resolve(),
+            Some(RustType::imported("ParseError"))
+        );
+        assert_eq!(Lens::ParamOf(ground(PrimType::Bool), 0).resolve(), None);
+    }
+
+    #[test]
+    fn lens_field_access() {
+        let tuple_t =
+            RustType::anon_tuple([PrimType::U8.into(), RustType::vec_of(PrimType::U8.into())]);
+        let snd = Lens::FieldAccess(ground(tuple_t), SubIdent::ByIndex(1));
+        assert_eq!(snd.resolve(), Some(RustType::vec_of(PrimType::U8.into())));
+        assert_eq!(
+            Lens::ElemOf(Box::new(snd)).resolve(),
+            Some(PrimType::U8.into())
+        );
+
+        let record_t = RustType::defined(0, "Header");
+        let lookup = |ix: usize| {
+            (ix == 0).then(|| {
+                RustTypeDef::Struct(RustStruct::Record(vec![
+                    (Label::from("magic"), PrimType::U32.into()),
+                    (Label::from("version"), PrimType::U16.into()),
+                ]))
+            })
+        };
+        let version =
+            Lens::FieldAccess(ground(record_t.clone()), SubIdent::ByName("version".into()));
+        assert_eq!(version.resolve_with(&lookup), Some(PrimType::U16.into()));
+        assert_eq!(version.resolve(), None);
+        let missing = Lens::FieldAccess(ground(record_t), SubIdent::ByName("length".into()));
+        assert_eq!(missing.resolve_with(&lookup), None);
+        assert_eq!(
+            Lens::FieldAccess(ground(PrimType::U8), SubIdent::ByIndex(0)).resolve(),
+            None
+        );
+    }
+
+    #[test]
+    fn resolve_ownership_copy_field() {
+        let record_t = RustType::defined(0, "Header");
+        let lookup = |ix: usize| {
+            (ix == 0).then(|| {
+                RustTypeDef::Struct(RustStruct::Record(vec![
+                    (Label::from("version"), PrimType::U16.into()),
+                    (
+                        Label::from("tag"),
+                        RustType::borrow_of(None, Mut::Immutable, PrimType::U8.into()),
+                    ),
+                ]))
+            })
+        };
+        let version = RustExpr::local("header")
+            .field("version")
+            .owned(Lens::FieldAccess(
+                ground(record_t.clone()),
+                SubIdent::ByName("version".into()),
+            ));
+        expect_fragment(&version.resolve_ownership(&lookup), "header.version");
+        let tag = RustExpr::local("header")
+            .field("tag")
+            .owned(Lens::FieldAccess(
+                ground(record_t),
+                SubIdent::ByName("tag".into()),
+            ));
+        expect_fragment(&tag.resolve_ownership(&lookup), "*header.tag");
+    }
+
+    #[test]
+    fn resolve_ownership_non_copy_field() {
+        let tuple_t =
+            RustType::anon_tuple([PrimType::U8.into(), RustType::vec_of(PrimType::U8.into())]);
+        let snd = RustExpr::local("pair")
+            .nth(1)
+            .owned(Lens::FieldAccess(ground(tuple_t), SubIdent::ByIndex(1)));
+        // nested occurrences are resolved as well
+        let expr = RustExpr::Tuple(vec![RustExpr::local("x"), snd]);
+        expect_fragment(&expr.resolve_ownership(&|_| None), "(x, pair.1.clone())");
+        // unresolvable lenses fall back on cloning
+        let opaque = RustExpr::local("header")
+            .field("data")
+            .owned(Lens::FieldAccess(
+                ground(RustType::defined(0, "Header")),
+                SubIdent::ByName("data".into()),
+            ));
+        expect_fragment(&opaque.resolve_ownership(&|_| None), "header.data.clone()");
+    }
+
+    #[test]
+    #[should_panic(expected = "unresolved ownership")]
+    fn unresolved_ownership_panics() {
+        let expr = RustExpr::local("xs").owned(Lens::Ground(RustType::vec_of(PrimType::U8.into())));
+        expr.to_fragment();
+    }
+
+    #[test]
+    fn destructure_array_stmt() {
+        let stmt = RustStmt::destructure_array(["a", "b"], RustExpr::local("bytes"));
+        assert_eq!(&format!("{}", stmt.to_fragment()), "let [a, b] = bytes;");
+        assert_eq!(stmt.bound_vars(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn struct_builder_items() {
+        let fields = vec![
+            (
+                Label::from("x"),
+                RustType::Atom(AtomType::Prim(PrimType::U8)),
+            ),
+            (
+                Label::from("y"),
+                RustType::Atom(AtomType::Prim(PrimType::U16)),
+            ),
+            (
+                Label::from("type"),
+                RustType::vec_of(RustType::Atom(AtomType::Prim(PrimType::U8))),
+            ),
+        ];
+        let [def, methods] = RustItem::struct_builder(&Label::from("Foo"), &fields);
+        assert_eq!(
+            format!("{}", def.to_fragment()),
+            "#[derive(Debug, Clone, Default)]\npub struct FooBuilder {\nx: Option<u8>,\ny: Option<u16>,\nr#type: Option<Vec<u8>>\n}"
+        );
+        let methods = format!("{}", methods.to_fragment());
+        assert!(methods.starts_with("impl FooBuilder {\n"));
+        assert!(methods.contains(
+            "pub fn y(self, value: u16) -> FooBuilder {\nFooBuilder { x: self.x, y: Some(value), r#type: self.r#type }\n}"
+        ));
+        assert!(methods.contains(
+            "pub fn build(self) -> Option<Foo> {\nSome(Foo { x: self.x?, y: self.y?, r#type: self.r#type? })\n}"
+        ));
+    }
+
+    #[test]
+    fn assert_stmt() {
+        let cond = RustExpr::Operation(RustOp::op_eq(RustExpr::local("x"), RustExpr::u8lit(0)));
+        let debug = RustStmt::Expr(cond.clone().assert(true));
+        assert_eq!(
+            format!("{}", debug.to_fragment()),
+            "debug_assert!(x == 0u8);"
+        );
+        let always = RustStmt::Expr(cond.assert(false));
+        assert_eq!(format!("{}", always.to_fragment()), "assert!(x == 0u8);");
+    }
+
+    #[test]
+    fn guard_stmt() {
+        let err = RustExpr::scoped(["ParseError"], "FailToken");
+        let eq_guard = RustStmt::guard(
+            RustExpr::Operation(RustOp::op_eq(RustExpr::local("b"), RustExpr::u8lit(0))),
+            err.clone(),
+        );
+        assert_eq!(
+            format!("{}", eq_guard.to_fragment()),
+            "if b != 0u8 {\nreturn Err(ParseError::FailToken);\n}"
+        );
+        let call_guard = RustStmt::guard(RustExpr::local("is_valid").call(), err);
+        assert_eq!(
+            format!("{}", call_guard.to_fragment()),
+            "if !(is_valid()) {\nreturn Err(ParseError::FailToken);\n}"
+        );
+    }
+
+    #[test]
+    fn matches_with_guard() {
+        let some_y = RustPattern::Variant(
+            Constructor::Simple(Label::from("Some")),
+            Box::new(RustPattern::CatchAll(Some(Label::from("y")))),
+        );
+        let guard = RustExpr::infix(RustExpr::local("y"), Operator::Gt, RustExpr::u8lit(0));
+        let guarded = RustExpr::local("x").matches([some_y], Some(guard));
+        assert_eq!(
+            format!("{}", guarded.to_fragment()),
+            "matches!(x, Some(y) if y > 0u8)"
+        );
+        assert!(!guarded.may_short_circuit());
+
+        let alts = RustExpr::local("x").matches(
+            [
+                RustPattern::PrimLiteral(RustPrimLit::Numeric(RustNumLit::U8(1))),
+                RustPattern::PrimLiteral(RustPrimLit::Numeric(RustNumLit::U8(2))),
+            ],
+            Some(RustExpr::local("check").call().wrap_try()),
+        );
+        assert_eq!(
+            format!("{}", alts.to_fragment()),
+            "matches!(x, 1u8 | 2u8 if (check())?)"
+        );
+        assert!(alts.may_short_circuit());
+    }
+
+    #[test]
+    fn index_expr() {
+        let safe = RustExpr::local("buf").index(RustExpr::local("ix"), &GenOptions::default());
+        assert_eq!(format!("{}", safe.to_fragment()), "buf[ix]");
+        let unchecked = RustExpr::local("buf").index(
+            RustExpr::local("ix"),
+            &GenOptions {
+                use_unchecked_indexing: true,
+                ..GenOptions::default()
+            },
+        );
+        assert_eq!(
+            format!("{}", unchecked.to_fragment()),
+            "unsafe { buf.get_unchecked(ix) }"
+        );
+    }
+
+    #[test]
+    fn safe_index_expr() {
+        let copied = RustExpr::local("buf").safe_index(RustExpr::local("ix"), PrimType::U16.into());
+        expect_fragment(&copied, "buf.get(ix).copied()");
+        let cloned = RustExpr::local("rows")
+            .safe_index(RustExpr::local("ix"), RustType::vec_of(PrimType::U8.into()));
+        expect_fragment(&cloned, "rows.get(ix).cloned()");
+    }
+
+    #[test]
+    fn program_blank_lines() {
+        let mut program = RustProgram::from_iter([]);
+        program.add_module_attr(ModuleAttr::Allow(AllowAttr::from(Label::from("dead_code"))));
+        let rendered = program.render();
+        assert!(!rendered.contains("\n\n\n"));
+        assert_eq!(collapse_blank_lines("a\n\n\n\nb\n\nc\n"), "a\n\nb\n\nc\n");
+    }
+
+    #[test]
+    fn emit_multi_files() {
+        let out_dir =
+            std::env::temp_dir().join(format!("doodle-emit-multi-{}", std::process::id()));
+        let mut program = RustProgram::from_iter([]);
+        program.add_submodule(RustSubmodule::new("alpha").with_content(RustProgram::from_iter([])));
+        program
+            .add_submodule(RustSubmodule::new_pub("beta").with_content(RustProgram::from_iter([])));
+        program.add_submodule(RustSubmodule::new("external"));
+        program.emit_multi(&out_dir).unwrap();
+
+        let mut files = std::fs::read_dir(&out_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, ["alpha.rs", "beta.rs", "mod.rs"]);
+        let root = std::fs::read_to_string(out_dir.join("mod.rs")).unwrap();
+        assert!(root.contains("mod alpha;\npub mod beta;\nmod external;\n"));
+        std::fs::remove_dir_all(&out_dir).unwrap();
+    }
+
     #[test]
     fn sample_expr() {
         let re = RustExpr::local("this").call_method_with(
@@ -1994,4 +3477,37 @@ mod test {
         );
         expect_fragment(&re, "this.append(&mut other)")
     }
+
+    #[test]
+    fn hoist_multiple_try_args() {
+        let tuple = RustExpr::Tuple(vec![
+            RustExpr::local("parse_a").call().wrap_try(),
+            RustExpr::local("x"),
+            RustExpr::local("parse_b").call().wrap_try(),
+        ]);
+        assert!(!tuple.check_eval_purity());
+        let hoisted = tuple.sequence_short_circuits();
+        assert!(hoisted.check_eval_purity());
+        match &hoisted {
+            RustExpr::BlockScope(stmts, _) => assert_eq!(stmts.len(), 2),
+            other => panic!("expected block-scope, found {other:?}"),
+        }
+        expect_fragment(
+            &hoisted,
+            "{\nlet __arg0 = (parse_a())?;\nlet __arg1 = (parse_b())?;\n(__arg0, x, __arg1)\n}",
+        );
+    }
+
+    #[test]
+    fn single_try_arg_unchanged() {
+        let tuple = RustExpr::Tuple(vec![
+            RustExpr::local("parse_a").call().wrap_try(),
+            RustExpr::local("x"),
+        ]);
+        assert!(tuple.check_eval_purity());
+        assert!(matches!(
+            tuple.sequence_short_circuits(),
+            RustExpr::Tuple(..)
+        ));
+    }
 }
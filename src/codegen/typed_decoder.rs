@@ -11,7 +11,7 @@ use super::{
     GTFormat,
 };
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub(crate) struct TypedDecoderExt<TypeRep> {
     dec: TypedDecoder<TypeRep>,
     args: Option<Vec<(Label, TypeRep)>>,
@@ -47,21 +47,35 @@ impl<TypeRep> From<TypedDecoder<TypeRep>> for TypedDecoderExt<TypeRep> {
 }
 
 /// Decoders with a fixed amount of lookahead
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub(crate) enum TypedDecoder<TypeRep> {
     Call(TypeRep, usize, Vec<(Label, TypedExpr<TypeRep>)>),
     Fail,
     EndOfInput,
     Align(usize),
+    AbsolutePos,
+    SlicePos,
     Byte(ByteSet),
     Variant(TypeRep, Label, Box<TypedDecoderExt<TypeRep>>),
+    Newtype(
+        TypeRep,
+        Label,
+        Box<TypedDecoderExt<TypeRep>>,
+        Option<TypedExpr<TypeRep>>,
+    ),
     Parallel(TypeRep, Vec<TypedDecoderExt<TypeRep>>),
     Branch(TypeRep, MatchTree, Vec<TypedDecoderExt<TypeRep>>),
     Tuple(TypeRep, Vec<TypedDecoderExt<TypeRep>>),
     Record(TypeRep, Vec<(Label, TypedDecoderExt<TypeRep>)>),
     Repeat0While(TypeRep, MatchTree, Box<TypedDecoderExt<TypeRep>>),
     Repeat1Until(TypeRep, MatchTree, Box<TypedDecoderExt<TypeRep>>),
-    RepeatCount(TypeRep, TypedExpr<TypeRep>, Box<TypedDecoderExt<TypeRep>>),
+    /// As with [`Decoder::RepeatCount`](crate::decoder::Decoder::RepeatCount), the final element has its own decoder if its lookahead differs
+    RepeatCount(
+        TypeRep,
+        TypedExpr<TypeRep>,
+        Box<TypedDecoderExt<TypeRep>>,
+        Option<Box<TypedDecoderExt<TypeRep>>>,
+    ),
     RepeatBetween(
         TypeRep,
         MatchTree,
@@ -71,11 +85,19 @@ pub(crate) enum TypedDecoder<TypeRep> {
     ),
     RepeatUntilLast(TypeRep, TypedExpr<TypeRep>, Box<TypedDecoderExt<TypeRep>>),
     RepeatUntilSeq(TypeRep, TypedExpr<TypeRep>, Box<TypedDecoderExt<TypeRep>>),
+    ForEach(
+        TypeRep,
+        TypedExpr<TypeRep>,
+        Label,
+        Box<TypedDecoderExt<TypeRep>>,
+    ),
+    RepeatWhileInput(TypeRep, Box<TypedDecoderExt<TypeRep>>),
     Peek(TypeRep, Box<TypedDecoderExt<TypeRep>>),
     PeekNot(TypeRep, Box<TypedDecoderExt<TypeRep>>),
     Slice(TypeRep, TypedExpr<TypeRep>, Box<TypedDecoderExt<TypeRep>>),
     Bits(TypeRep, Box<TypedDecoderExt<TypeRep>>),
     WithRelativeOffset(TypeRep, TypedExpr<TypeRep>, Box<TypedDecoderExt<TypeRep>>),
+    FollowOffset(TypeRep, TypedExpr<TypeRep>, Box<TypedDecoderExt<TypeRep>>),
     Map(TypeRep, Box<TypedDecoderExt<TypeRep>>, TypedExpr<TypeRep>),
     Compute(TypeRep, TypedExpr<TypeRep>),
     Let(
@@ -101,12 +123,18 @@ pub(crate) enum TypedDecoder<TypeRep> {
 #[derive(Clone, Debug)]
 pub(crate) struct TypedProgram<TypeRep> {
     pub decoders: Vec<(TypedDecoderExt<TypeRep>, TypeRep)>,
+    /// Whether each decoder was compiled from a named format that can never fail (see [`Format::is_infallible`])
+    pub infallible: Vec<bool>,
 }
 
 impl TypedProgram<GenType> {
     fn new() -> Self {
         let decoders = Vec::new();
-        TypedProgram { decoders }
+        let infallible = Vec::new();
+        TypedProgram {
+            decoders,
+            infallible,
+        }
     }
 }
 
@@ -149,7 +177,7 @@ impl<'a> GTCompiler<'a> {
             Some(t) => t.into_owned(),
         };
         // decoder
-        compiler.queue_compile(t, format, None, Rc::new(Next::Empty));
+        compiler.queue_compile(t, format, None, Rc::new(Next::Empty), false);
         while let Some((f, args, next, n)) = compiler.compile_queue.pop() {
             let d = compiler.compile_gt_format(f, args, next)?;
             compiler.program.decoders[n].0 = d;
@@ -163,13 +191,53 @@ impl<'a> GTCompiler<'a> {
         f: &'a GTFormat,
         args: Option<Vec<(Label, GenType)>>,
         next: Rc<Next<'a>>,
+        infallible: bool,
     ) -> usize {
         let n = self.program.decoders.len();
         self.program.decoders.push((TypedDecoder::Fail.into(), t));
+        self.program.infallible.push(infallible);
         self.compile_queue.push((f, args, next, n));
         n
     }
 
+    // NOTE - split out of `compile_gt_format` to keep its (recursive) stack frame from growing
+    #[inline(never)]
+    fn compile_for_each(
+        &mut self,
+        gt: &GenType,
+        expr: &TypedExpr<GenType>,
+        name: &Label,
+        a: &'a GTFormat,
+    ) -> AResult<TypedDecoder<GenType>> {
+        // NOTE - each iteration is independent of whatever follows the entire loop
+        let da = Box::new(self.compile_gt_format(a, None, Rc::new(Next::Empty))?);
+        Ok(TypedDecoder::ForEach(
+            gt.clone(),
+            expr.clone(),
+            name.clone(),
+            da,
+        ))
+    }
+
+    // NOTE - split out of `compile_gt_format` to keep its (recursive) stack frame from growing
+    #[inline(never)]
+    fn compile_newtype(
+        &mut self,
+        gt: &GenType,
+        name: &Label,
+        a: &'a GTFormat,
+        validate: Option<&TypedExpr<GenType>>,
+        next: Rc<Next<'a>>,
+    ) -> AResult<TypedDecoder<GenType>> {
+        let da = Box::new(self.compile_gt_format(a, None, next)?);
+        Ok(TypedDecoder::Newtype(
+            gt.clone(),
+            name.clone(),
+            da,
+            validate.cloned(),
+        ))
+    }
+
     fn compile_gt_format(
         &mut self,
         format: &'a GTFormat,
@@ -197,8 +265,8 @@ impl<'a> GTCompiler<'a> {
                     )
                 };
 
-                let _f = self.module.get_format(*level);
-                let next = if _f.depends_on_next(self.module) {
+                let f = self.module.get_format(*level);
+                let next = if f.depends_on_next(self.module) {
                     next
                 } else {
                     Rc::new(Next::Empty)
@@ -206,7 +274,13 @@ impl<'a> GTCompiler<'a> {
                 let n = if let Some(n) = self.decoder_map.get(&(*level, next.clone())) {
                     *n
                 } else {
-                    let n = self.queue_compile(gt.clone(), deref, sig_args, next.clone());
+                    let n = self.queue_compile(
+                        gt.clone(),
+                        deref,
+                        sig_args,
+                        next.clone(),
+                        f.is_infallible(),
+                    );
                     self.decoder_map.insert((*level, next.clone()), n);
                     n
                 };
@@ -216,6 +290,8 @@ impl<'a> GTCompiler<'a> {
             GTFormat::Fail => Ok(TypedDecoder::Fail),
             GTFormat::EndOfInput => Ok(TypedDecoder::EndOfInput),
             GTFormat::Align(n) => Ok(TypedDecoder::Align(*n)),
+            GTFormat::AbsolutePos => Ok(TypedDecoder::AbsolutePos),
+            GTFormat::SlicePos => Ok(TypedDecoder::SlicePos),
             GTFormat::Byte(bs) => Ok(TypedDecoder::Byte(*bs)),
             GTFormat::Variant(gt, label, f) => {
                 let d = self.compile_gt_format(f, None, next.clone())?;
@@ -225,6 +301,9 @@ impl<'a> GTCompiler<'a> {
                     Box::new(d),
                 ))
             }
+            GTFormat::Newtype(gt, name, f, validate) => {
+                self.compile_newtype(gt, name, f, validate.as_ref(), next)
+            }
             GTFormat::Union(gt, branches) => {
                 let mut fs = Vec::with_capacity(branches.len());
                 let mut ds = Vec::with_capacity(branches.len());
@@ -232,10 +311,9 @@ impl<'a> GTCompiler<'a> {
                     ds.push(self.compile_gt_format(f, None, next.clone())?);
                     fs.push(f.clone().into());
                 }
-                if let Some(tree) = MatchTree::build(self.module, &fs, next) {
-                    Ok(TypedDecoder::Branch(gt.clone(), tree, ds))
-                } else {
-                    Err(anyhow!("cannot build match tree for {:?}", format))
+                match MatchTree::build(self.module, &fs, next, MatchTree::MAX_DEPTH) {
+                    Ok(tree) => Ok(TypedDecoder::Branch(gt.clone(), tree, ds)),
+                    Err(ambiguity) => Err(anyhow!("cannot build match tree: {ambiguity}")),
                 }
             }
             GTFormat::UnionNondet(gt, branches) => {
@@ -284,7 +362,12 @@ impl<'a> GTCompiler<'a> {
                 let astar = TypedFormat::Repeat(gt.clone(), a.clone());
                 let fa = TypedFormat::tuple(vec![(**a).clone(), astar]);
                 let fb = TypedFormat::EMPTY;
-                if let Some(tree) = MatchTree::build(self.module, &[fa.into(), fb.into()], next) {
+                if let Ok(tree) = MatchTree::build(
+                    self.module,
+                    &[fa.into(), fb.into()],
+                    next,
+                    MatchTree::MAX_DEPTH,
+                ) {
                     Ok(TypedDecoder::Repeat0While(gt.clone(), tree, Box::new(da)))
                 } else {
                     Err(anyhow!("cannot build match tree for {:?}", format))
@@ -302,16 +385,33 @@ impl<'a> GTCompiler<'a> {
                 let astar = TypedFormat::Repeat(gt.clone(), a.clone());
                 let fa = TypedFormat::EMPTY;
                 let fb = TypedFormat::tuple(vec![(**a).clone(), astar]);
-                if let Some(tree) = MatchTree::build(self.module, &[fa.into(), fb.into()], next) {
+                if let Ok(tree) = MatchTree::build(
+                    self.module,
+                    &[fa.into(), fb.into()],
+                    next,
+                    MatchTree::MAX_DEPTH,
+                ) {
                     Ok(TypedDecoder::Repeat1Until(gt.clone(), tree, Box::new(da)))
                 } else {
                     Err(anyhow!("cannot build match tree for {:?}", format))
                 }
             }
             GTFormat::RepeatCount(gt, expr, a) => {
-                // FIXME probably not right
-                let da = Box::new(self.compile_gt_format(a, None, next)?);
-                Ok(TypedDecoder::RepeatCount(gt.clone(), expr.clone(), da))
+                // as with the untyped compiler, see `Compiler::compile_format`
+                let body_next = Rc::new(Next::Cat(MaybeTyped::Typed(a), Rc::new(Next::Empty)));
+                let da = self.compile_gt_format(a, None, body_next)?;
+                let da_last = if Format::from((**a).clone()).depends_on_next(self.module) {
+                    let da_last = self.compile_gt_format(a, None, next)?;
+                    (da_last != da).then(|| Box::new(da_last))
+                } else {
+                    None
+                };
+                Ok(TypedDecoder::RepeatCount(
+                    gt.clone(),
+                    expr.clone(),
+                    Box::new(da),
+                    da_last,
+                ))
             }
             GTFormat::RepeatBetween(gt, min_expr, max_expr, a) => {
                 // FIXME - preliminary support only for exact-bound limit values
@@ -344,7 +444,9 @@ impl<'a> GTCompiler<'a> {
                         );
                         branches.push(f_count.into());
                     }
-                    let Some(tree) = MatchTree::build(self.module, &branches[..], next) else {
+                    let Ok(tree) =
+                        MatchTree::build(self.module, &branches[..], next, MatchTree::MAX_DEPTH)
+                    else {
                         panic!("cannot build match tree for {:?}", format)
                     };
                     tree
@@ -358,7 +460,9 @@ impl<'a> GTCompiler<'a> {
                 ))
             }
             GTFormat::RepeatUntilLast(gt, expr, a) => {
-                // FIXME probably not right
+                if a.is_non_progressing() {
+                    return Err(anyhow!("cannot repeat non-progressing format: {a:?}"));
+                }
                 let da = Box::new(self.compile_gt_format(a, None, next)?);
                 Ok(TypedDecoder::RepeatUntilLast(gt.clone(), expr.clone(), da))
             }
@@ -367,6 +471,18 @@ impl<'a> GTCompiler<'a> {
                 let da = Box::new(self.compile_gt_format(a, None, next)?);
                 Ok(TypedDecoder::RepeatUntilSeq(gt.clone(), expr.clone(), da))
             }
+            GTFormat::ForEach(gt, expr, name, a) => self.compile_for_each(gt, expr, name, a),
+            GTFormat::RepeatWhileInput(gt, a) => {
+                if a.is_nullable() {
+                    return Err(anyhow!("cannot repeat nullable format: {a:?}"));
+                }
+                let da = self.compile_gt_format(
+                    a,
+                    None,
+                    Rc::new(Next::Repeat(MaybeTyped::Typed(a), Rc::new(Next::Empty))),
+                )?;
+                Ok(TypedDecoder::RepeatWhileInput(gt.clone(), Box::new(da)))
+            }
             GTFormat::Peek(gt, a) => {
                 let da = Box::new(self.compile_gt_format(a, None, Rc::new(Next::Empty))?);
                 Ok(TypedDecoder::Peek(gt.clone(), da))
@@ -403,6 +519,11 @@ impl<'a> GTCompiler<'a> {
                     da,
                 ))
             }
+            GTFormat::FollowOffset(gt, expr, a) => {
+                // NOTE - the target is decoded without regard for what follows it, which is conservative since the stream has jumped
+                let da = Box::new(self.compile_gt_format(a, None, Rc::new(Next::Empty))?);
+                Ok(TypedDecoder::FollowOffset(gt.clone(), expr.clone(), da))
+            }
             GTFormat::Map(gt, a, expr) => {
                 let da = Box::new(self.compile_gt_format(a, None, next.clone())?);
                 Ok(TypedDecoder::Map(gt.clone(), da, expr.clone()))
@@ -56,8 +56,16 @@ pub enum TypedFormat<TypeRep> {
     Fail,
     EndOfInput,
     Align(usize),
+    AbsolutePos,
+    SlicePos,
     Byte(ByteSet),
     Variant(TypeRep, Label, Box<TypedFormat<TypeRep>>),
+    Newtype(
+        TypeRep,
+        Label,
+        Box<TypedFormat<TypeRep>>,
+        Option<TypedExpr<TypeRep>>,
+    ),
     Union(TypeRep, Vec<TypedFormat<TypeRep>>),
     UnionNondet(TypeRep, Vec<TypedFormat<TypeRep>>),
     Tuple(TypeRep, Vec<TypedFormat<TypeRep>>),
@@ -73,11 +81,19 @@ pub enum TypedFormat<TypeRep> {
     ),
     RepeatUntilLast(TypeRep, TypedExpr<TypeRep>, Box<TypedFormat<TypeRep>>),
     RepeatUntilSeq(TypeRep, TypedExpr<TypeRep>, Box<TypedFormat<TypeRep>>),
+    ForEach(
+        TypeRep,
+        TypedExpr<TypeRep>,
+        Label,
+        Box<TypedFormat<TypeRep>>,
+    ),
+    RepeatWhileInput(TypeRep, Box<TypedFormat<TypeRep>>),
     Peek(TypeRep, Box<TypedFormat<TypeRep>>),
     PeekNot(TypeRep, Box<TypedFormat<TypeRep>>),
     Slice(TypeRep, TypedExpr<TypeRep>, Box<TypedFormat<TypeRep>>),
     Bits(TypeRep, Box<TypedFormat<TypeRep>>),
     WithRelativeOffset(TypeRep, TypedExpr<TypeRep>, Box<TypedFormat<TypeRep>>),
+    FollowOffset(TypeRep, TypedExpr<TypeRep>, Box<TypedFormat<TypeRep>>),
     Map(TypeRep, Box<TypedFormat<TypeRep>>, TypedExpr<TypeRep>),
     Compute(TypeRep, TypedExpr<TypeRep>),
     Let(
@@ -116,8 +132,10 @@ impl TypedFormat<GenType> {
             }
 
             TypedFormat::Align(n) => Bounds::new(0, Some(n - 1)),
+            TypedFormat::AbsolutePos | TypedFormat::SlicePos => Bounds::exact(0),
             TypedFormat::Byte(_) => Bounds::exact(1),
             TypedFormat::Variant(_, _, f) => f.lookahead_bounds(),
+            TypedFormat::Newtype(_, _, f, _) => f.lookahead_bounds(),
             TypedFormat::Union(_, branches) | TypedFormat::UnionNondet(_, branches) => branches
                 .iter()
                 .map(TypedFormat::lookahead_bounds)
@@ -143,15 +161,17 @@ impl TypedFormat<GenType> {
                 f.lookahead_bounds() * Bounds::new(1, None)
             }
 
-            TypedFormat::Repeat(_, _f) | TypedFormat::RepeatUntilSeq(_, _, _f) => {
-                Bounds::new(0, None)
-            }
+            TypedFormat::Repeat(_, _f)
+            | TypedFormat::RepeatUntilSeq(_, _, _f)
+            | TypedFormat::ForEach(_, _, _, _f)
+            | TypedFormat::RepeatWhileInput(_, _f) => Bounds::new(0, None),
 
             TypedFormat::Slice(_, t_expr, _) => t_expr.bounds(),
 
             TypedFormat::Bits(_, f) => f.lookahead_bounds().bits_to_bytes(),
 
-            TypedFormat::WithRelativeOffset(_, offset_expr, inner) => {
+            TypedFormat::WithRelativeOffset(_, offset_expr, inner)
+            | TypedFormat::FollowOffset(_, offset_expr, inner) => {
                 offset_expr.bounds() + inner.lookahead_bounds()
             }
 
@@ -180,8 +200,10 @@ impl TypedFormat<GenType> {
             | TypedFormat::Fail => Bounds::exact(0),
 
             TypedFormat::Align(n) => Bounds::new(0, Some(n - 1)),
+            TypedFormat::AbsolutePos | TypedFormat::SlicePos => Bounds::exact(0),
             TypedFormat::Byte(_) => Bounds::exact(1),
             TypedFormat::Variant(_, _, f) => f.match_bounds(),
+            TypedFormat::Newtype(_, _, f, _) => f.match_bounds(),
             TypedFormat::Union(_, branches) | TypedFormat::UnionNondet(_, branches) => branches
                 .iter()
                 .map(TypedFormat::match_bounds)
@@ -207,9 +229,10 @@ impl TypedFormat<GenType> {
                 f.match_bounds() * Bounds::new(1, None)
             }
 
-            TypedFormat::Repeat(_, _f) | TypedFormat::RepeatUntilSeq(_, _, _f) => {
-                Bounds::new(0, None)
-            }
+            TypedFormat::Repeat(_, _f)
+            | TypedFormat::RepeatUntilSeq(_, _, _f)
+            | TypedFormat::ForEach(_, _, _, _f)
+            | TypedFormat::RepeatWhileInput(_, _f) => Bounds::new(0, None),
 
             TypedFormat::Slice(_, t_expr, _) => t_expr.bounds(),
 
@@ -217,6 +240,10 @@ impl TypedFormat<GenType> {
 
             TypedFormat::WithRelativeOffset(_, _, _) => Bounds::exact(0),
 
+            TypedFormat::FollowOffset(_, offset_expr, inner) => {
+                offset_expr.bounds() + inner.match_bounds()
+            }
+
             TypedFormat::Map(_, f, _)
             | TypedFormat::Dynamic(_, _, _, f)
             | TypedFormat::Let(_, _, _, f) => f.match_bounds(),
@@ -231,8 +258,16 @@ impl TypedFormat<GenType> {
         }
     }
 
+    pub(crate) fn min_length(&self) -> usize {
+        self.match_bounds().min
+    }
+
     pub(crate) fn is_nullable(&self) -> bool {
-        self.match_bounds().min == 0
+        self.min_length() == 0
+    }
+
+    pub(crate) fn is_non_progressing(&self) -> bool {
+        self.match_bounds().max == Some(0)
     }
 
     pub(crate) fn tuple(elts: Vec<TypedFormat<GenType>>) -> Self {
@@ -253,10 +288,14 @@ impl TypedFormat<GenType> {
             TypedFormat::EndOfInput | TypedFormat::Align(_) => {
                 Some(Cow::Owned(GenType::from(RustType::UNIT)))
             }
+            TypedFormat::AbsolutePos | TypedFormat::SlicePos => {
+                Some(Cow::Owned(GenType::from(PrimType::U32)))
+            }
             TypedFormat::Byte(_) => Some(Cow::Owned(GenType::from(PrimType::U8))),
 
             TypedFormat::FormatCall(gt, ..)
             | TypedFormat::Variant(gt, ..)
+            | TypedFormat::Newtype(gt, ..)
             | TypedFormat::Union(gt, ..)
             | TypedFormat::UnionNondet(gt, ..)
             | TypedFormat::Tuple(gt, ..)
@@ -267,11 +306,14 @@ impl TypedFormat<GenType> {
             | TypedFormat::RepeatBetween(gt, ..)
             | TypedFormat::RepeatUntilLast(gt, ..)
             | TypedFormat::RepeatUntilSeq(gt, ..)
+            | TypedFormat::ForEach(gt, ..)
+            | TypedFormat::RepeatWhileInput(gt, ..)
             | TypedFormat::Peek(gt, ..)
             | TypedFormat::PeekNot(gt, ..)
             | TypedFormat::Slice(gt, ..)
             | TypedFormat::Bits(gt, ..)
             | TypedFormat::WithRelativeOffset(gt, ..)
+            | TypedFormat::FollowOffset(gt, ..)
             | TypedFormat::Map(gt, ..)
             | TypedFormat::Compute(gt, ..)
             | TypedFormat::Let(gt, ..)
@@ -296,6 +338,10 @@ pub enum TypedExpr<TypeRep> {
     U16(u16),
     U32(u32),
     U64(u64),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
     Tuple(TypeRep, Vec<TypedExpr<TypeRep>>),
     TupleProj(TypeRep, Box<TypedExpr<TypeRep>>, usize),
     Record(TypeRep, Vec<(Label, TypedExpr<TypeRep>)>),
@@ -327,6 +373,9 @@ pub enum TypedExpr<TypeRep> {
     AsU32(Box<TypedExpr<TypeRep>>),
     AsU64(Box<TypedExpr<TypeRep>>),
     AsChar(Box<TypedExpr<TypeRep>>),
+    AsSigned8(Box<TypedExpr<TypeRep>>),
+    AsSigned16(Box<TypedExpr<TypeRep>>),
+    AsSigned32(Box<TypedExpr<TypeRep>>),
 
     U16Be(Box<TypedExpr<TypeRep>>),
     U16Le(Box<TypedExpr<TypeRep>>),
@@ -334,8 +383,13 @@ pub enum TypedExpr<TypeRep> {
     U32Le(Box<TypedExpr<TypeRep>>),
     U64Be(Box<TypedExpr<TypeRep>>),
     U64Le(Box<TypedExpr<TypeRep>>),
+    I16Be(Box<TypedExpr<TypeRep>>),
+    I16Le(Box<TypedExpr<TypeRep>>),
+    I32Be(Box<TypedExpr<TypeRep>>),
+    I32Le(Box<TypedExpr<TypeRep>>),
 
     SeqLength(Box<TypedExpr<TypeRep>>),
+    SeqIx(TypeRep, Box<TypedExpr<TypeRep>>, Box<TypedExpr<TypeRep>>),
     SubSeq(
         TypeRep,
         Box<TypedExpr<TypeRep>>,
@@ -395,6 +449,18 @@ impl TypedExpr<GenType> {
             TypedExpr::U64Be(_) | TypedExpr::U64Le(_) | TypedExpr::AsU64(_) | TypedExpr::U64(_) => {
                 Some(Cow::Owned(GenType::from(PrimType::U64)))
             }
+            TypedExpr::AsSigned8(_) | TypedExpr::I8(_) => {
+                Some(Cow::Owned(GenType::from(PrimType::I8)))
+            }
+            TypedExpr::I16Be(_)
+            | TypedExpr::I16Le(_)
+            | TypedExpr::AsSigned16(_)
+            | TypedExpr::I16(_) => Some(Cow::Owned(GenType::from(PrimType::I16))),
+            TypedExpr::I32Be(_)
+            | TypedExpr::I32Le(_)
+            | TypedExpr::AsSigned32(_)
+            | TypedExpr::I32(_) => Some(Cow::Owned(GenType::from(PrimType::I32))),
+            TypedExpr::I64(_) => Some(Cow::Owned(GenType::from(PrimType::I64))),
             TypedExpr::AsChar(_) => Some(Cow::Owned(GenType::from(PrimType::Char))),
             TypedExpr::Lambda(..) => None,
             TypedExpr::Var(gt, _)
@@ -407,6 +473,7 @@ impl TypedExpr<GenType> {
             | TypedExpr::Match(gt, _, _)
             | TypedExpr::IntRel(gt, _, _, _)
             | TypedExpr::Arith(gt, _, _, _)
+            | TypedExpr::SeqIx(gt, _, _)
             | TypedExpr::SubSeq(gt, _, _, _)
             | TypedExpr::SubSeqInflate(gt, _, _, _)
             | TypedExpr::FlatMap(gt, _, _)
@@ -427,10 +494,18 @@ pub enum TypedPattern<TypeRep> {
     U16(u16),
     U32(u32),
     U64(u64),
+    U8Range(u8, u8),
+    U16Range(u16, u16),
+    U32Range(u32, u32),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
     Char(char),
     Tuple(TypeRep, Vec<TypedPattern<TypeRep>>),
     Variant(TypeRep, Label, Box<TypedPattern<TypeRep>>),
     Seq(TypeRep, Vec<TypedPattern<TypeRep>>),
+    Or(TypeRep, Vec<TypedPattern<TypeRep>>),
 }
 
 mod __impls {
@@ -498,6 +573,10 @@ mod __impls {
                 TypedExpr::U16(n) => Expr::U16(n),
                 TypedExpr::U32(n) => Expr::U32(n),
                 TypedExpr::U64(n) => Expr::U64(n),
+                TypedExpr::I8(n) => Expr::I8(n),
+                TypedExpr::I16(n) => Expr::I16(n),
+                TypedExpr::I32(n) => Expr::I32(n),
+                TypedExpr::I64(n) => Expr::I64(n),
                 TypedExpr::Tuple(_, t_elts) => Expr::Tuple(revec(t_elts)),
                 TypedExpr::TupleProj(_, tup, ix) => Expr::TupleProj(rebox(tup), ix),
                 TypedExpr::Record(_, t_flds) => Expr::Record(revec_pair(t_flds)),
@@ -515,13 +594,21 @@ mod __impls {
                 TypedExpr::AsU32(x) => Expr::AsU32(rebox(x)),
                 TypedExpr::AsU64(x) => Expr::AsU64(rebox(x)),
                 TypedExpr::AsChar(x) => Expr::AsChar(rebox(x)),
+                TypedExpr::AsSigned8(x) => Expr::AsSigned8(rebox(x)),
+                TypedExpr::AsSigned16(x) => Expr::AsSigned16(rebox(x)),
+                TypedExpr::AsSigned32(x) => Expr::AsSigned32(rebox(x)),
                 TypedExpr::U16Be(x) => Expr::U16Be(rebox(x)),
                 TypedExpr::U16Le(x) => Expr::U16Le(rebox(x)),
                 TypedExpr::U32Be(x) => Expr::U32Be(rebox(x)),
                 TypedExpr::U32Le(x) => Expr::U32Le(rebox(x)),
                 TypedExpr::U64Be(x) => Expr::U64Be(rebox(x)),
                 TypedExpr::U64Le(x) => Expr::U64Le(rebox(x)),
+                TypedExpr::I16Be(x) => Expr::I16Be(rebox(x)),
+                TypedExpr::I16Le(x) => Expr::I16Le(rebox(x)),
+                TypedExpr::I32Be(x) => Expr::I32Be(rebox(x)),
+                TypedExpr::I32Le(x) => Expr::I32Le(rebox(x)),
                 TypedExpr::SeqLength(x) => Expr::SeqLength(rebox(x)),
+                TypedExpr::SeqIx(_, seq, index) => Expr::SeqIx(rebox(seq), rebox(index)),
                 TypedExpr::SubSeq(_, seq, start, len) => {
                     Expr::SubSeq(rebox(seq), rebox(start), rebox(len))
                 }
@@ -553,8 +640,13 @@ mod __impls {
                 TypedFormat::Fail => Format::Fail,
                 TypedFormat::EndOfInput => Format::EndOfInput,
                 TypedFormat::Align(n) => Format::Align(n),
+                TypedFormat::AbsolutePos => Format::AbsolutePos,
+                TypedFormat::SlicePos => Format::SlicePos,
                 TypedFormat::Byte(b) => Format::Byte(b),
                 TypedFormat::Variant(_, lbl, inner) => Format::Variant(lbl, rebox(inner)),
+                TypedFormat::Newtype(_, name, inner, validate) => {
+                    Format::Newtype(name, rebox(inner), validate.map(Expr::from))
+                }
                 TypedFormat::Union(_, branches) => {
                     Format::Union(branches.into_iter().map(Format::from).collect())
                 }
@@ -577,6 +669,10 @@ mod __impls {
                 TypedFormat::RepeatUntilSeq(_, lambda, inner) => {
                     Format::RepeatUntilSeq(Expr::from(lambda), rebox(inner))
                 }
+                TypedFormat::ForEach(_, seq, name, inner) => {
+                    Format::ForEach(Expr::from(seq), name, rebox(inner))
+                }
+                TypedFormat::RepeatWhileInput(_, inner) => Format::RepeatWhileInput(rebox(inner)),
                 TypedFormat::Peek(_, inner) => Format::Peek(rebox(inner)),
                 TypedFormat::PeekNot(_, inner) => Format::PeekNot(rebox(inner)),
                 TypedFormat::Slice(_, sz, inner) => Format::Slice(Expr::from(sz), rebox(inner)),
@@ -584,6 +680,9 @@ mod __impls {
                 TypedFormat::WithRelativeOffset(_, ofs, inner) => {
                     Format::WithRelativeOffset(ofs.into(), rebox(inner))
                 }
+                TypedFormat::FollowOffset(_, ofs, inner) => {
+                    Format::FollowOffset(ofs.into(), rebox(inner))
+                }
                 TypedFormat::Map(_, inner, lambda) => Format::Map(rebox(inner), Expr::from(lambda)),
                 TypedFormat::Compute(_, expr) => Format::Compute(Expr::from(expr)),
                 TypedFormat::Let(_, name, val, inner) => {
@@ -624,10 +723,18 @@ mod __impls {
                 TypedPattern::U16(n) => Pattern::U16(n),
                 TypedPattern::U32(n) => Pattern::U32(n),
                 TypedPattern::U64(n) => Pattern::U64(n),
+                TypedPattern::U8Range(lo, hi) => Pattern::U8Range(lo, hi),
+                TypedPattern::U16Range(lo, hi) => Pattern::U16Range(lo, hi),
+                TypedPattern::U32Range(lo, hi) => Pattern::U32Range(lo, hi),
+                TypedPattern::I8(n) => Pattern::I8(n),
+                TypedPattern::I16(n) => Pattern::I16(n),
+                TypedPattern::I32(n) => Pattern::I32(n),
+                TypedPattern::I64(n) => Pattern::I64(n),
                 TypedPattern::Char(c) => Pattern::Char(c),
                 TypedPattern::Tuple(_, elts) => Pattern::Tuple(revec(elts)),
                 TypedPattern::Variant(_, name, inner) => Pattern::Variant(name, rebox(inner)),
                 TypedPattern::Seq(_, elts) => Pattern::Seq(revec(elts)),
+                TypedPattern::Or(_, alts) => Pattern::Or(revec(alts)),
             }
         }
     }
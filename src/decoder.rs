@@ -2,17 +2,18 @@ use crate::byte_set::ByteSet;
 use crate::error::{ParseError, ParseResult};
 use crate::read::ReadCtxt;
 use crate::{
-    pattern::Pattern, Arith, DynFormat, Expr, Format, FormatModule, IntRel, MatchTree, Next,
-    TypeScope, ValueType,
+    pattern::Pattern, Arith, BaseType, DynFormat, Expr, Format, FormatModule, IntRel, MatchTree,
+    Next, TypeScope, ValueType,
 };
 use crate::{IntoLabel, Label, MaybeTyped};
 use anyhow::{anyhow, Result as AResult};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
-#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 #[serde(tag = "tag", content = "data")]
 pub enum Value {
     Bool(bool),
@@ -20,6 +21,10 @@ pub enum Value {
     U16(u16),
     U32(u32),
     U64(u64),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
     Char(char),
     Tuple(Vec<Value>),
     Record(Vec<(Label, Value)>),
@@ -27,6 +32,11 @@ pub enum Value {
     Seq(Vec<Value>),
     Mapped(Box<Value>, Box<Value>),
     Branch(usize, Box<Value>),
+    /// An optional value, as produced by [`Value::flatten_singletons`] from `some` and `none` variants
+    Option(Option<Box<Value>>),
+    /// A sub-table decoded once and shared between every offset link to it, as produced by
+    /// [`Program::run_memoized`]
+    Shared(Rc<Value>),
 }
 
 impl Value {
@@ -49,6 +59,13 @@ impl Value {
             (Pattern::U16(i0), Value::U16(i1)) => i0 == i1,
             (Pattern::U32(i0), Value::U32(i1)) => i0 == i1,
             (Pattern::U64(i0), Value::U64(i1)) => i0 == i1,
+            (Pattern::U8Range(lo, hi), Value::U8(i1)) => (*lo..=*hi).contains(i1),
+            (Pattern::U16Range(lo, hi), Value::U16(i1)) => (*lo..=*hi).contains(i1),
+            (Pattern::U32Range(lo, hi), Value::U32(i1)) => (*lo..=*hi).contains(i1),
+            (Pattern::I8(i0), Value::I8(i1)) => i0 == i1,
+            (Pattern::I16(i0), Value::I16(i1)) => i0 == i1,
+            (Pattern::I32(i0), Value::I32(i1)) => i0 == i1,
+            (Pattern::I64(i0), Value::I64(i1)) => i0 == i1,
             (Pattern::Char(c0), Value::Char(c1)) => c0 == c1,
             (Pattern::Tuple(ps), Value::Tuple(vs)) | (Pattern::Seq(ps), Value::Seq(vs))
                 if ps.len() == vs.len() =>
@@ -63,6 +80,17 @@ impl Value {
             (Pattern::Variant(label0, p), Value::Variant(label1, v)) if label0 == label1 => {
                 v.matches_inner(scope, p)
             }
+            (Pattern::Or(ps), head) => {
+                let len = scope.entries.len();
+                for p in ps {
+                    if head.matches_inner(scope, p) {
+                        return true;
+                    }
+                    scope.entries.truncate(len);
+                }
+                false
+            }
+            (pattern, Value::Shared(v)) => v.matches_inner(scope, pattern),
             _ => false,
         }
     }
@@ -86,6 +114,233 @@ impl Value {
         match self {
             Value::Mapped(_orig, v) => v.coerce_mapped_value(),
             Value::Branch(_n, v) => v.coerce_mapped_value(),
+            Value::Shared(v) => v.coerce_mapped_value(),
+            v => v,
+        }
+    }
+
+    fn coerce_mapped_value_mut(&mut self) -> &mut Self {
+        match self {
+            Value::Mapped(_orig, v) => v.coerce_mapped_value_mut(),
+            Value::Branch(_n, v) => v.coerce_mapped_value_mut(),
+            Value::Shared(v) => Rc::make_mut(v).coerce_mapped_value_mut(),
+            v => v,
+        }
+    }
+
+    fn step(&self, accessor: &Accessor) -> Option<&Self> {
+        match (self.coerce_mapped_value(), accessor) {
+            (Value::Record(fields), Accessor::Field(label)) => {
+                fields.iter().find(|(l, _)| l == label).map(|(_, v)| v)
+            }
+            (Value::Tuple(vs) | Value::Seq(vs), Accessor::Index(ix)) => vs.get(*ix),
+            (Value::Variant(l, v), Accessor::Variant(label)) if l == label => Some(v),
+            _ => None,
+        }
+    }
+
+    fn step_mut(&mut self, accessor: &Accessor) -> Option<&mut Self> {
+        match (self.coerce_mapped_value_mut(), accessor) {
+            (Value::Record(fields), Accessor::Field(label)) => {
+                fields.iter_mut().find(|(l, _)| l == label).map(|(_, v)| v)
+            }
+            (Value::Tuple(vs) | Value::Seq(vs), Accessor::Index(ix)) => vs.get_mut(*ix),
+            (Value::Variant(l, v), Accessor::Variant(label)) if l == label => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value found by following `path` from `self`, if every step of the path is valid.
+    ///
+    /// Mapped values are traversed through their image, and branch-indices are ignored.
+    pub fn get(&self, path: &[Accessor]) -> Option<&Self> {
+        path.iter().try_fold(self, |v, acc| v.step(acc))
+    }
+
+    /// Mutable counterpart to [`Value::get`].
+    pub fn get_mut(&mut self, path: &[Accessor]) -> Option<&mut Self> {
+        path.iter().try_fold(self, |v, acc| v.step_mut(acc))
+    }
+
+    /// Replaces the value found by following `path` from `self` with `new`.
+    ///
+    /// Returns an error indicating the first invalid step if `path` does not lead to a value.
+    pub fn set(&mut self, path: &[Accessor], new: Value) -> Result<(), PathError> {
+        let mut target = self;
+        for (position, acc) in path.iter().enumerate() {
+            target = target.step_mut(acc).ok_or_else(|| PathError {
+                position,
+                accessor: acc.clone(),
+            })?;
+        }
+        *target = new;
+        Ok(())
+    }
+
+    /// Condenses a sequence into a flat stream of its non-unit leaves.
+    ///
+    /// Every element of a [`Value::Seq`] that is itself a sequence (after looking through mapped values
+    /// and branches) is replaced in-place by the elements of its own condensed stream, and every element
+    /// equal to [`Value::UNIT`] is dropped, so that `[[1], 2, ()]` condenses to `[1, 2]`. Other elements,
+    /// including tuples and records containing sequences, are kept as-is. Values that are not sequences
+    /// are returned unchanged.
+    pub fn condense_stream(&self) -> Value {
+        fn push_leaves(elems: &[Value], out: &mut Vec<Value>) {
+            for elem in elems {
+                match elem.coerce_mapped_value() {
+                    Value::Seq(inner) => push_leaves(inner, out),
+                    v if *v == Value::UNIT => {}
+                    _ => out.push(elem.clone()),
+                }
+            }
+        }
+        match self.coerce_mapped_value() {
+            Value::Seq(elems) => {
+                let mut out = Vec::with_capacity(elems.len());
+                push_leaves(elems, &mut out);
+                Value::Seq(out)
+            }
+            _ => self.clone(),
+        }
+    }
+
+    /// Returns `true` if `self` and `other` are equal after condensing both with [`Value::condense_stream`].
+    pub fn stream_eq(&self, other: &Value) -> bool {
+        self.condense_stream() == other.condense_stream()
+    }
+
+    /// Lists the points at which `self` and `other` differ, each annotated with the path leading to it.
+    ///
+    /// Tuples and sequences of the same length, records with the same field names, and variants with
+    /// the same label are compared element-by-element; any other pair of unequal values is reported
+    /// as a single difference. As with [`Value::get`], mapped values are compared through their image,
+    /// and branch-indices are ignored.
+    pub fn diff(&self, other: &Value) -> Vec<ValueDiff> {
+        fn go(path: &mut Vec<Accessor>, x: &Value, y: &Value, out: &mut Vec<ValueDiff>) {
+            let mut descend = |path: &mut Vec<Accessor>, acc: Accessor, x, y| {
+                path.push(acc);
+                go(path, x, y, out);
+                path.pop();
+            };
+            match (x.coerce_mapped_value(), y.coerce_mapped_value()) {
+                (Value::Tuple(xs), Value::Tuple(ys)) | (Value::Seq(xs), Value::Seq(ys))
+                    if xs.len() == ys.len() =>
+                {
+                    for (ix, (x, y)) in Iterator::zip(xs.iter(), ys.iter()).enumerate() {
+                        descend(path, Accessor::Index(ix), x, y);
+                    }
+                }
+                (Value::Record(xs), Value::Record(ys))
+                    if xs.len() == ys.len()
+                        && Iterator::zip(xs.iter(), ys.iter())
+                            .all(|((l0, _), (l1, _))| l0 == l1) =>
+                {
+                    for ((label, x), (_, y)) in Iterator::zip(xs.iter(), ys.iter()) {
+                        descend(path, Accessor::Field(label.clone()), x, y);
+                    }
+                }
+                (Value::Variant(l0, x), Value::Variant(l1, y)) if l0 == l1 => {
+                    descend(path, Accessor::Variant(l0.clone()), x, y);
+                }
+                (x, y) if x == y => {}
+                (x, y) => out.push(ValueDiff {
+                    path: path.clone(),
+                    left: x.clone(),
+                    right: y.clone(),
+                }),
+            }
+        }
+
+        let mut out = Vec::new();
+        go(&mut Vec::new(), self, other, &mut out);
+        out
+    }
+
+    /// Converts `self` into a JSON value that mirrors its structure, independently of the serde
+    /// representation derived for `Value` itself.
+    ///
+    /// Records become objects keyed by field name, tuples and sequences become arrays, and variants
+    /// become objects of the form `{"tag": label, "value": ...}`. Numbers and booleans are kept as-is,
+    /// characters become single-character strings, and options become `null` or their contents. Mapped
+    /// values are represented by their image, shared values by their contents, and branch-indices are dropped.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value as Json;
+        match self {
+            Value::Bool(b) => Json::Bool(*b),
+            Value::U8(n) => Json::from(*n),
+            Value::U16(n) => Json::from(*n),
+            Value::U32(n) => Json::from(*n),
+            Value::U64(n) => Json::from(*n),
+            Value::I8(n) => Json::from(*n),
+            Value::I16(n) => Json::from(*n),
+            Value::I32(n) => Json::from(*n),
+            Value::I64(n) => Json::from(*n),
+            Value::Char(c) => Json::String(c.to_string()),
+            Value::Tuple(vs) | Value::Seq(vs) => {
+                Json::Array(vs.iter().map(Value::to_json).collect())
+            }
+            Value::Record(fields) => Json::Object(
+                fields
+                    .iter()
+                    .map(|(label, v)| (label.to_string(), v.to_json()))
+                    .collect(),
+            ),
+            Value::Variant(label, v) => serde_json::json!({
+                "tag": label.as_ref(),
+                "value": v.to_json(),
+            }),
+            Value::Mapped(_, v) | Value::Branch(_, v) => v.to_json(),
+            Value::Option(v) => v.as_deref().map_or(Json::Null, Value::to_json),
+            Value::Shared(v) => v.to_json(),
+        }
+    }
+
+    /// Removes the variant wrappers whose labels are listed in `labels`, recursively.
+    ///
+    /// A variant labelled `some` or `none` (as yielded by [`helper::optional`](crate::helper::optional))
+    /// becomes a [`Value::Option`]; one with any other listed label is replaced by its inner value. A branch
+    /// whose variant is removed is replaced by the result, as its index no longer refers to a variant.
+    pub fn flatten_singletons(self, labels: &[&str]) -> Value {
+        let is_listed = |label: &Label| labels.contains(&label.as_ref());
+        let flatten_variant = |label: &Label, v: Value| match label.as_ref() {
+            "some" => Value::Option(Some(Box::new(v.flatten_singletons(labels)))),
+            "none" => Value::Option(None),
+            _ => v.flatten_singletons(labels),
+        };
+        match self {
+            Value::Tuple(vs) => Value::Tuple(
+                vs.into_iter()
+                    .map(|v| v.flatten_singletons(labels))
+                    .collect(),
+            ),
+            Value::Seq(vs) => Value::Seq(
+                vs.into_iter()
+                    .map(|v| v.flatten_singletons(labels))
+                    .collect(),
+            ),
+            Value::Record(fields) => Value::Record(
+                fields
+                    .into_iter()
+                    .map(|(l, v)| (l, v.flatten_singletons(labels)))
+                    .collect(),
+            ),
+            Value::Variant(label, v) if is_listed(&label) => flatten_variant(&label, *v),
+            Value::Variant(label, v) => {
+                Value::Variant(label, Box::new(v.flatten_singletons(labels)))
+            }
+            Value::Mapped(orig, v) => Value::Mapped(
+                Box::new(orig.flatten_singletons(labels)),
+                Box::new(v.flatten_singletons(labels)),
+            ),
+            Value::Branch(n, v) => match *v {
+                Value::Variant(label, v) if is_listed(&label) => flatten_variant(&label, *v),
+                v => Value::Branch(n, Box::new(v.flatten_singletons(labels))),
+            },
+            Value::Option(v) => Value::Option(v.map(|v| Box::new(v.flatten_singletons(labels)))),
+            Value::Shared(v) => {
+                Value::Shared(Rc::new(Rc::unwrap_or_clone(v).flatten_singletons(labels)))
+            }
             v => v,
         }
     }
@@ -94,10 +349,59 @@ impl Value {
         match self {
             Value::Mapped(_orig, v) => v.extract_mapped_value(),
             Value::Branch(_n, v) => v.extract_mapped_value(),
+            Value::Shared(v) => Rc::unwrap_or_clone(v).extract_mapped_value(),
             v => v,
         }
     }
 
+    /// Estimates the number of bytes of heap memory owned by this value, not counting the
+    /// `size_of::<Value>()` bytes of the value itself.
+    ///
+    /// Accounts for the full capacity of every `Vec`, each `Box` indirection, and any owned (non-static)
+    /// labels, but not for allocator overhead. Shared values are counted in full at each reference to them.
+    pub fn heap_size(&self) -> usize {
+        fn label_size(label: &Label) -> usize {
+            match label {
+                Label::Borrowed(_) => 0,
+                Label::Owned(s) => s.capacity(),
+            }
+        }
+
+        fn boxed_size(v: &Value) -> usize {
+            std::mem::size_of::<Value>() + v.heap_size()
+        }
+
+        match self {
+            Value::Bool(_)
+            | Value::U8(_)
+            | Value::U16(_)
+            | Value::U32(_)
+            | Value::U64(_)
+            | Value::I8(_)
+            | Value::I16(_)
+            | Value::I32(_)
+            | Value::I64(_)
+            | Value::Char(_) => 0,
+            Value::Tuple(vs) | Value::Seq(vs) => {
+                vs.capacity() * std::mem::size_of::<Value>()
+                    + vs.iter().map(Value::heap_size).sum::<usize>()
+            }
+            Value::Record(fields) => {
+                fields.capacity() * std::mem::size_of::<(Label, Value)>()
+                    + fields
+                        .iter()
+                        .map(|(l, v)| label_size(l) + v.heap_size())
+                        .sum::<usize>()
+            }
+            Value::Variant(label, v) => label_size(label) + boxed_size(v),
+            Value::Mapped(orig, v) => boxed_size(orig) + boxed_size(v),
+            Value::Branch(_n, v) => boxed_size(v),
+            Value::Option(v) => v.as_deref().map_or(0, boxed_size),
+            // the allocation of an `Rc` also holds its strong and weak counts
+            Value::Shared(v) => 2 * std::mem::size_of::<usize>() + boxed_size(v),
+        }
+    }
+
     fn record_proj(&self, label: &str) -> &Self {
         match self {
             Value::Record(fields) => match fields.iter().find(|(l, _)| label == l) {
@@ -116,6 +420,182 @@ impl Value {
     }
 }
 
+/// Single step of a path into a nested `Value`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Accessor {
+    /// Named field of a record
+    Field(Label),
+    /// Positional element of a tuple or sequence
+    Index(usize),
+    /// Contents of a variant, provided its name matches
+    Variant(Label),
+}
+
+/// Point at which two values differ, as reported by [`Value::diff`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueDiff {
+    /// Path from the root of both values to the differing sub-values
+    pub path: Vec<Accessor>,
+    pub left: Value,
+    pub right: Value,
+}
+
+impl std::fmt::Display for ValueDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at {:?}: {:?} != {:?}", self.path, self.left, self.right)
+    }
+}
+
+/// Error for a path that does not lead to any value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathError {
+    /// Index of the first step of the path that could not be followed
+    pub position: usize,
+    pub accessor: Accessor,
+}
+
+impl std::fmt::Display for PathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid path: cannot follow {:?} at step {}",
+            self.accessor, self.position
+        )
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// Error for a `Value` that does not conform to a `ValueType`
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchemaError {
+    /// Path from the root of the validated value to the non-conforming sub-value
+    pub path: Vec<Accessor>,
+    pub kind: SchemaErrorKind,
+}
+
+/// Reason for a [`SchemaError`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchemaErrorKind {
+    /// The value is of a different kind than the type (e.g. a tuple where a record was expected)
+    Mismatch { expected: ValueType, found: Value },
+    /// A tuple or record with the wrong number of elements or fields
+    Arity { expected: usize, found: usize },
+    /// A record field whose name differs from the field declared at the same position
+    FieldName { expected: Label, found: Label },
+    /// A variant whose name is not among those declared by the union type
+    UnknownVariant(Label),
+}
+
+impl std::fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            SchemaErrorKind::Mismatch { expected, found } => {
+                write!(f, "expected value of type {expected:?}, found {found:?}")?
+            }
+            SchemaErrorKind::Arity { expected, found } => {
+                write!(f, "expected {expected} elements, found {found}")?
+            }
+            SchemaErrorKind::FieldName { expected, found } => {
+                write!(f, "expected field {expected}, found {found}")?
+            }
+            SchemaErrorKind::UnknownVariant(label) => write!(f, "undeclared variant {label}")?,
+        }
+        write!(f, " at {:?}", self.path)
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl ValueType {
+    /// Checks that `value` conforms to `self`, ignoring any `Mapped` and `Branch` wrappers left over from decoding.
+    ///
+    /// Record fields must appear with the declared names in the declared order, variants must be declared by
+    /// the union type, and every element of a sequence must conform to its element type.
+    pub fn validate(&self, value: &Value) -> Result<(), SchemaError> {
+        let mut path = Vec::new();
+        self.validate_at(value, &mut path)
+            .map_err(|kind| SchemaError { path, kind })
+    }
+
+    fn validate_at(&self, value: &Value, path: &mut Vec<Accessor>) -> Result<(), SchemaErrorKind> {
+        let value = value.coerce_mapped_value();
+        let mismatch = || SchemaErrorKind::Mismatch {
+            expected: self.clone(),
+            found: value.clone(),
+        };
+        match (self, value) {
+            (ValueType::Any, _) => Ok(()),
+            (ValueType::Newtype(_, t), v) => t.validate_at(v, path),
+            (ValueType::Base(b), v) => match (b, v) {
+                (BaseType::Bool, Value::Bool(_))
+                | (BaseType::U8, Value::U8(_))
+                | (BaseType::U16, Value::U16(_))
+                | (BaseType::U32, Value::U32(_))
+                | (BaseType::U64, Value::U64(_))
+                | (BaseType::I8, Value::I8(_))
+                | (BaseType::I16, Value::I16(_))
+                | (BaseType::I32, Value::I32(_))
+                | (BaseType::I64, Value::I64(_))
+                | (BaseType::Char, Value::Char(_)) => Ok(()),
+                _ => Err(mismatch()),
+            },
+            (ValueType::Tuple(ts), Value::Tuple(vs)) => {
+                if ts.len() != vs.len() {
+                    return Err(SchemaErrorKind::Arity {
+                        expected: ts.len(),
+                        found: vs.len(),
+                    });
+                }
+                for (ix, (t, v)) in Iterator::zip(ts.iter(), vs.iter()).enumerate() {
+                    path.push(Accessor::Index(ix));
+                    t.validate_at(v, path)?;
+                    path.pop();
+                }
+                Ok(())
+            }
+            (ValueType::Record(fts), Value::Record(fvs)) => {
+                if fts.len() != fvs.len() {
+                    return Err(SchemaErrorKind::Arity {
+                        expected: fts.len(),
+                        found: fvs.len(),
+                    });
+                }
+                for ((lt, t), (lv, v)) in Iterator::zip(fts.iter(), fvs.iter()) {
+                    if lt != lv {
+                        return Err(SchemaErrorKind::FieldName {
+                            expected: lt.clone(),
+                            found: lv.clone(),
+                        });
+                    }
+                    path.push(Accessor::Field(lv.clone()));
+                    t.validate_at(v, path)?;
+                    path.pop();
+                }
+                Ok(())
+            }
+            (ValueType::Union(branches), Value::Variant(label, v)) => match branches.get(label) {
+                Some(t) => {
+                    path.push(Accessor::Variant(label.clone()));
+                    t.validate_at(v, path)?;
+                    path.pop();
+                    Ok(())
+                }
+                None => Err(SchemaErrorKind::UnknownVariant(label.clone())),
+            },
+            (ValueType::Seq(t), Value::Seq(vs)) => {
+                for (ix, v) in vs.iter().enumerate() {
+                    path.push(Accessor::Index(ix));
+                    t.validate_at(v, path)?;
+                    path.pop();
+                }
+                Ok(())
+            }
+            _ => Err(mismatch()),
+        }
+    }
+}
+
 impl Value {
     pub const UNIT: Value = Value::Tuple(Vec::new());
 
@@ -132,12 +612,77 @@ impl Value {
         Value::Variant(label.into(), value.into())
     }
 
+    // NOTE - the accessors below look through mapped values and branches, as does `Value::get`
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.coerce_mapped_value() {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(&self) -> Option<u8> {
+        match self.coerce_mapped_value() {
+            Value::U8(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_u16(&self) -> Option<u16> {
+        match self.coerce_mapped_value() {
+            Value::U16(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        match self.coerce_mapped_value() {
+            Value::U32(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.coerce_mapped_value() {
+            Value::U64(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_seq(&self) -> Option<&[Value]> {
+        match self.coerce_mapped_value() {
+            Value::Seq(vs) => Some(vs),
+            _ => None,
+        }
+    }
+
+    pub fn as_record(&self) -> Option<&[(Label, Value)]> {
+        match self.coerce_mapped_value() {
+            Value::Record(fields) => Some(fields),
+            _ => None,
+        }
+    }
+
+    /// Returns the field named `name`, if `self` is a record with such a field.
+    ///
+    /// Shorthand for `self.get(&[Accessor::Field(name.into())])`.
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        self.as_record()?
+            .iter()
+            .find(|(label, _)| label == name)
+            .map(|(_, v)| v)
+    }
+
     pub(crate) fn unwrap_usize(self) -> usize {
         match self {
             Value::U8(n) => usize::from(n),
             Value::U16(n) => usize::from(n),
             Value::U32(n) => usize::try_from(n).unwrap(),
             Value::U64(n) => usize::try_from(n).unwrap(),
+            Value::I8(n) => usize::try_from(n).unwrap(),
+            Value::I16(n) => usize::try_from(n).unwrap(),
+            Value::I32(n) => usize::try_from(n).unwrap(),
+            Value::I64(n) => usize::try_from(n).unwrap(),
             _ => panic!("value is not a number"),
         }
     }
@@ -166,28 +711,159 @@ impl Value {
     }
 }
 
+/// Treatment of integer overflow in arithmetic expressions evaluated while parsing
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ArithMode {
+    /// Overflow is a parse failure
+    #[default]
+    Checked,
+    /// Results wrap around at the bounds of the type, as do shift amounts
+    Wrapping,
+    /// Results are clamped to the bounds of the type, and shifts by at least the width of the type shift out
+    /// every bit (leaving only copies of the sign-bit, for a right-shift of a negative value)
+    Saturating,
+}
+
+/// Marker for arithmetic that overflows (or divides by zero) in the evaluation of an expression
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Overflow;
+
+/// Integer arithmetic whose overflow behavior is determined by an [`ArithMode`]
+///
+/// Division by zero is an error in every mode.
+pub(crate) trait ModeArith: Sized {
+    fn add_in(self, mode: ArithMode, y: Self) -> Result<Self, Overflow>;
+    fn sub_in(self, mode: ArithMode, y: Self) -> Result<Self, Overflow>;
+    fn mul_in(self, mode: ArithMode, y: Self) -> Result<Self, Overflow>;
+    fn div_in(self, mode: ArithMode, y: Self) -> Result<Self, Overflow>;
+    fn rem_in(self, mode: ArithMode, y: Self) -> Result<Self, Overflow>;
+    fn shl_in(self, mode: ArithMode, places: u32) -> Result<Self, Overflow>;
+    fn shr_in(self, mode: ArithMode, places: u32) -> Result<Self, Overflow>;
+}
+
+macro_rules! impl_mode_arith {
+    ($($t:ty),*) => {$(
+        impl ModeArith for $t {
+            fn add_in(self, mode: ArithMode, y: Self) -> Result<Self, Overflow> {
+                match mode {
+                    ArithMode::Checked => self.checked_add(y).ok_or(Overflow),
+                    ArithMode::Wrapping => Ok(self.wrapping_add(y)),
+                    ArithMode::Saturating => Ok(self.saturating_add(y)),
+                }
+            }
+
+            fn sub_in(self, mode: ArithMode, y: Self) -> Result<Self, Overflow> {
+                match mode {
+                    ArithMode::Checked => self.checked_sub(y).ok_or(Overflow),
+                    ArithMode::Wrapping => Ok(self.wrapping_sub(y)),
+                    ArithMode::Saturating => Ok(self.saturating_sub(y)),
+                }
+            }
+
+            fn mul_in(self, mode: ArithMode, y: Self) -> Result<Self, Overflow> {
+                match mode {
+                    ArithMode::Checked => self.checked_mul(y).ok_or(Overflow),
+                    ArithMode::Wrapping => Ok(self.wrapping_mul(y)),
+                    ArithMode::Saturating => Ok(self.saturating_mul(y)),
+                }
+            }
+
+            fn div_in(self, mode: ArithMode, y: Self) -> Result<Self, Overflow> {
+                match mode {
+                    _ if y == 0 => Err(Overflow),
+                    ArithMode::Checked => self.checked_div(y).ok_or(Overflow),
+                    ArithMode::Wrapping => Ok(self.wrapping_div(y)),
+                    ArithMode::Saturating => Ok(self.saturating_div(y)),
+                }
+            }
+
+            fn rem_in(self, mode: ArithMode, y: Self) -> Result<Self, Overflow> {
+                match mode {
+                    _ if y == 0 => Err(Overflow),
+                    ArithMode::Checked => self.checked_rem(y).ok_or(Overflow),
+                    // the only overflowing remainder, `MIN % -1`, is mathematically zero
+                    ArithMode::Wrapping | ArithMode::Saturating => Ok(self.wrapping_rem(y)),
+                }
+            }
+
+            fn shl_in(self, mode: ArithMode, places: u32) -> Result<Self, Overflow> {
+                match mode {
+                    ArithMode::Checked => self.checked_shl(places).ok_or(Overflow),
+                    ArithMode::Wrapping => Ok(self.wrapping_shl(places)),
+                    ArithMode::Saturating => Ok(self.checked_shl(places).unwrap_or(0)),
+                }
+            }
+
+            fn shr_in(self, mode: ArithMode, places: u32) -> Result<Self, Overflow> {
+                match mode {
+                    ArithMode::Checked => self.checked_shr(places).ok_or(Overflow),
+                    ArithMode::Wrapping => Ok(self.wrapping_shr(places)),
+                    // NOTE - shifting by one less than the width, and then once more, fills every bit with the sign-bit (zero for unsigned types)
+                    ArithMode::Saturating => {
+                        Ok(self.checked_shr(places).unwrap_or((self >> (Self::BITS - 1)) >> 1))
+                    }
+                }
+            }
+        }
+    )*};
+}
+
+impl_mode_arith!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+/// Converts a signed shift-amount into a number of places, which is an error in every mode if negative.
+///
+/// Amounts too large to represent are clamped, as they are shifts past the width of any type regardless.
+pub(crate) fn signed_places(places: i64) -> Result<u32, Overflow> {
+    if places < 0 {
+        Err(Overflow)
+    } else {
+        Ok(u32::try_from(places).unwrap_or(u32::MAX))
+    }
+}
+
 impl Expr {
     pub fn eval<'a>(&'a self, scope: &'a Scope<'a>) -> Cow<'a, Value> {
-        match self {
+        self.try_eval(scope, ArithMode::Checked)
+            .unwrap_or_else(|Overflow| panic!("arithmetic overflow"))
+    }
+
+    /// Evaluates an expression, handling any arithmetic overflow according to `mode`.
+    ///
+    /// Division by zero, out-of-range casts and, in [`ArithMode::Checked`], overflowing arithmetic
+    /// result in an `Err`.
+    fn try_eval<'a>(
+        &'a self,
+        scope: &'a Scope<'a>,
+        mode: ArithMode,
+    ) -> Result<Cow<'a, Value>, Overflow> {
+        Ok(match self {
             Expr::Var(name) => Cow::Borrowed(scope.get_value_by_name(name)),
             Expr::Bool(b) => Cow::Owned(Value::Bool(*b)),
             Expr::U8(i) => Cow::Owned(Value::U8(*i)),
             Expr::U16(i) => Cow::Owned(Value::U16(*i)),
             Expr::U32(i) => Cow::Owned(Value::U32(*i)),
             Expr::U64(i) => Cow::Owned(Value::U64(*i)),
+            Expr::I8(i) => Cow::Owned(Value::I8(*i)),
+            Expr::I16(i) => Cow::Owned(Value::I16(*i)),
+            Expr::I32(i) => Cow::Owned(Value::I32(*i)),
+            Expr::I64(i) => Cow::Owned(Value::I64(*i)),
             Expr::Tuple(exprs) => Cow::Owned(Value::Tuple(
-                exprs.iter().map(|expr| expr.eval_value(scope)).collect(),
+                exprs
+                    .iter()
+                    .map(|expr| expr.try_eval_value(scope, mode))
+                    .collect::<Result<_, _>>()?,
             )),
-            Expr::TupleProj(head, index) => match head.eval(scope) {
+            Expr::TupleProj(head, index) => match head.try_eval(scope, mode)? {
                 Cow::Owned(v) => Cow::Owned(v.coerce_mapped_value().tuple_proj(*index).clone()),
                 Cow::Borrowed(v) => Cow::Borrowed(v.coerce_mapped_value().tuple_proj(*index)),
             },
             Expr::Record(fields) => Cow::Owned(Value::record(
                 fields
                     .iter()
-                    .map(|(label, expr)| (label.clone(), expr.eval_value(scope))),
+                    .map(|(label, expr)| Ok((label.clone(), expr.try_eval_value(scope, mode)?)))
+                    .collect::<Result<Vec<_>, _>>()?,
             )),
-            Expr::RecordProj(head, label) => match head.eval(scope) {
+            Expr::RecordProj(head, label) => match head.try_eval(scope, mode)? {
                 Cow::Owned(v) => {
                     Cow::Owned(v.coerce_mapped_value().record_proj(label.as_ref()).clone())
                 }
@@ -195,242 +871,447 @@ impl Expr {
                     Cow::Borrowed(v.coerce_mapped_value().record_proj(label.as_ref()))
                 }
             },
-            Expr::Variant(label, expr) => {
-                Cow::Owned(Value::variant(label.clone(), expr.eval_value(scope)))
-            }
+            Expr::Variant(label, expr) => Cow::Owned(Value::variant(
+                label.clone(),
+                expr.try_eval_value(scope, mode)?,
+            )),
             Expr::Seq(exprs) => Cow::Owned(Value::Seq(
-                exprs.iter().map(|expr| expr.eval_value(scope)).collect(),
+                exprs
+                    .iter()
+                    .map(|expr| expr.try_eval_value(scope, mode))
+                    .collect::<Result<_, _>>()?,
             )),
             Expr::Match(head, branches) => {
-                let head = head.eval(scope);
+                let head = head.try_eval(scope, mode)?;
                 for (pattern, expr) in branches {
                     if let Some(pattern_scope) = head.matches(scope, pattern) {
-                        let value = expr.eval_value(&Scope::Multi(&pattern_scope));
-                        return Cow::Owned(value);
+                        let value = expr.try_eval_value(&Scope::Multi(&pattern_scope), mode)?;
+                        return Ok(Cow::Owned(value));
                     }
                 }
                 panic!("non-exhaustive patterns");
             }
             Expr::Lambda(_, _) => panic!("cannot eval lambda"),
 
-            Expr::IntRel(IntRel::Eq, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
+            Expr::IntRel(IntRel::Eq, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::Bool(x == y),
                     (Value::U16(x), Value::U16(y)) => Value::Bool(x == y),
                     (Value::U32(x), Value::U32(y)) => Value::Bool(x == y),
                     (Value::U64(x), Value::U64(y)) => Value::Bool(x == y),
+                    (Value::I8(x), Value::I8(y)) => Value::Bool(x == y),
+                    (Value::I16(x), Value::I16(y)) => Value::Bool(x == y),
+                    (Value::I32(x), Value::I32(y)) => Value::Bool(x == y),
+                    (Value::I64(x), Value::I64(y)) => Value::Bool(x == y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::IntRel(IntRel::Ne, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
+                },
+            ),
+            Expr::IntRel(IntRel::Ne, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::Bool(x != y),
                     (Value::U16(x), Value::U16(y)) => Value::Bool(x != y),
                     (Value::U32(x), Value::U32(y)) => Value::Bool(x != y),
                     (Value::U64(x), Value::U64(y)) => Value::Bool(x != y),
+                    (Value::I8(x), Value::I8(y)) => Value::Bool(x != y),
+                    (Value::I16(x), Value::I16(y)) => Value::Bool(x != y),
+                    (Value::I32(x), Value::I32(y)) => Value::Bool(x != y),
+                    (Value::I64(x), Value::I64(y)) => Value::Bool(x != y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::IntRel(IntRel::Lt, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
+                },
+            ),
+            Expr::IntRel(IntRel::Lt, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::Bool(x < y),
                     (Value::U16(x), Value::U16(y)) => Value::Bool(x < y),
                     (Value::U32(x), Value::U32(y)) => Value::Bool(x < y),
                     (Value::U64(x), Value::U64(y)) => Value::Bool(x < y),
+                    (Value::I8(x), Value::I8(y)) => Value::Bool(x < y),
+                    (Value::I16(x), Value::I16(y)) => Value::Bool(x < y),
+                    (Value::I32(x), Value::I32(y)) => Value::Bool(x < y),
+                    (Value::I64(x), Value::I64(y)) => Value::Bool(x < y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::IntRel(IntRel::Gt, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
+                },
+            ),
+            Expr::IntRel(IntRel::Gt, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::Bool(x > y),
                     (Value::U16(x), Value::U16(y)) => Value::Bool(x > y),
                     (Value::U32(x), Value::U32(y)) => Value::Bool(x > y),
                     (Value::U64(x), Value::U64(y)) => Value::Bool(x > y),
+                    (Value::I8(x), Value::I8(y)) => Value::Bool(x > y),
+                    (Value::I16(x), Value::I16(y)) => Value::Bool(x > y),
+                    (Value::I32(x), Value::I32(y)) => Value::Bool(x > y),
+                    (Value::I64(x), Value::I64(y)) => Value::Bool(x > y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::IntRel(IntRel::Lte, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
+                },
+            ),
+            Expr::IntRel(IntRel::Lte, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::Bool(x <= y),
                     (Value::U16(x), Value::U16(y)) => Value::Bool(x <= y),
                     (Value::U32(x), Value::U32(y)) => Value::Bool(x <= y),
                     (Value::U64(x), Value::U64(y)) => Value::Bool(x <= y),
+                    (Value::I8(x), Value::I8(y)) => Value::Bool(x <= y),
+                    (Value::I16(x), Value::I16(y)) => Value::Bool(x <= y),
+                    (Value::I32(x), Value::I32(y)) => Value::Bool(x <= y),
+                    (Value::I64(x), Value::I64(y)) => Value::Bool(x <= y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::IntRel(IntRel::Gte, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
+                },
+            ),
+            Expr::IntRel(IntRel::Gte, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::Bool(x >= y),
                     (Value::U16(x), Value::U16(y)) => Value::Bool(x >= y),
                     (Value::U32(x), Value::U32(y)) => Value::Bool(x >= y),
                     (Value::U64(x), Value::U64(y)) => Value::Bool(x >= y),
+                    (Value::I8(x), Value::I8(y)) => Value::Bool(x >= y),
+                    (Value::I16(x), Value::I16(y)) => Value::Bool(x >= y),
+                    (Value::I32(x), Value::I32(y)) => Value::Bool(x >= y),
+                    (Value::I64(x), Value::I64(y)) => Value::Bool(x >= y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::Arith(Arith::Add, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
-                    (Value::U8(x), Value::U8(y)) => Value::U8(u8::checked_add(x, y).unwrap()),
-                    (Value::U16(x), Value::U16(y)) => Value::U16(u16::checked_add(x, y).unwrap()),
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_add(x, y).unwrap()),
-                    (Value::U64(x), Value::U64(y)) => Value::U64(u64::checked_add(x, y).unwrap()),
+                },
+            ),
+            Expr::Arith(Arith::Add, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.add_in(mode, y)?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.add_in(mode, y)?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.add_in(mode, y)?),
+                    (Value::U64(x), Value::U64(y)) => Value::U64(x.add_in(mode, y)?),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x.add_in(mode, y)?),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x.add_in(mode, y)?),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x.add_in(mode, y)?),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x.add_in(mode, y)?),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::Arith(Arith::Sub, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
-                    (Value::U8(x), Value::U8(y)) => Value::U8(u8::checked_sub(x, y).unwrap()),
-                    (Value::U16(x), Value::U16(y)) => Value::U16(u16::checked_sub(x, y).unwrap()),
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_sub(x, y).unwrap()),
-                    (Value::U64(x), Value::U64(y)) => Value::U64(u64::checked_sub(x, y).unwrap()),
+                },
+            ),
+            Expr::Arith(Arith::Sub, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.sub_in(mode, y)?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.sub_in(mode, y)?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.sub_in(mode, y)?),
+                    (Value::U64(x), Value::U64(y)) => Value::U64(x.sub_in(mode, y)?),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x.sub_in(mode, y)?),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x.sub_in(mode, y)?),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x.sub_in(mode, y)?),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x.sub_in(mode, y)?),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::Arith(Arith::Mul, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
-                    (Value::U8(x), Value::U8(y)) => Value::U8(u8::checked_mul(x, y).unwrap()),
-                    (Value::U16(x), Value::U16(y)) => Value::U16(u16::checked_mul(x, y).unwrap()),
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_mul(x, y).unwrap()),
-                    (Value::U64(x), Value::U64(y)) => Value::U64(u64::checked_mul(x, y).unwrap()),
+                },
+            ),
+            Expr::Arith(Arith::Mul, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.mul_in(mode, y)?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.mul_in(mode, y)?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.mul_in(mode, y)?),
+                    (Value::U64(x), Value::U64(y)) => Value::U64(x.mul_in(mode, y)?),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x.mul_in(mode, y)?),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x.mul_in(mode, y)?),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x.mul_in(mode, y)?),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x.mul_in(mode, y)?),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::Arith(Arith::Div, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
-                    (Value::U8(x), Value::U8(y)) => Value::U8(u8::checked_div(x, y).unwrap()),
-                    (Value::U16(x), Value::U16(y)) => Value::U16(u16::checked_div(x, y).unwrap()),
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_div(x, y).unwrap()),
-                    (Value::U64(x), Value::U64(y)) => Value::U64(u64::checked_div(x, y).unwrap()),
+                },
+            ),
+            Expr::Arith(Arith::Div, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.div_in(mode, y)?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.div_in(mode, y)?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.div_in(mode, y)?),
+                    (Value::U64(x), Value::U64(y)) => Value::U64(x.div_in(mode, y)?),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x.div_in(mode, y)?),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x.div_in(mode, y)?),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x.div_in(mode, y)?),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x.div_in(mode, y)?),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::Arith(Arith::Rem, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
-                    (Value::U8(x), Value::U8(y)) => Value::U8(u8::checked_rem(x, y).unwrap()),
-                    (Value::U16(x), Value::U16(y)) => Value::U16(u16::checked_rem(x, y).unwrap()),
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_rem(x, y).unwrap()),
-                    (Value::U64(x), Value::U64(y)) => Value::U64(u64::checked_rem(x, y).unwrap()),
+                },
+            ),
+            Expr::Arith(Arith::Rem, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.rem_in(mode, y)?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.rem_in(mode, y)?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.rem_in(mode, y)?),
+                    (Value::U64(x), Value::U64(y)) => Value::U64(x.rem_in(mode, y)?),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x.rem_in(mode, y)?),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x.rem_in(mode, y)?),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x.rem_in(mode, y)?),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x.rem_in(mode, y)?),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::Arith(Arith::BitAnd, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
+                },
+            ),
+            Expr::Arith(Arith::BitAnd, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::U8(x & y),
                     (Value::U16(x), Value::U16(y)) => Value::U16(x & y),
                     (Value::U32(x), Value::U32(y)) => Value::U32(x & y),
                     (Value::U64(x), Value::U64(y)) => Value::U64(x & y),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x & y),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x & y),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x & y),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x & y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::Arith(Arith::BitOr, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
+                },
+            ),
+            Expr::Arith(Arith::BitOr, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::U8(x | y),
                     (Value::U16(x), Value::U16(y)) => Value::U16(x | y),
                     (Value::U32(x), Value::U32(y)) => Value::U32(x | y),
                     (Value::U64(x), Value::U64(y)) => Value::U64(x | y),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x | y),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x | y),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x | y),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x | y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::Arith(Arith::Shl, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
-                    (Value::U8(x), Value::U8(y)) => {
-                        Value::U8(u8::checked_shl(x, u32::from(y)).unwrap())
+                },
+            ),
+            Expr::Arith(Arith::BitXor, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x ^ y),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x ^ y),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x ^ y),
+                    (Value::U64(x), Value::U64(y)) => Value::U64(x ^ y),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x ^ y),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x ^ y),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x ^ y),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x ^ y),
+                    (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
+                },
+            ),
+            Expr::Arith(Arith::Shl, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.shl_in(mode, u32::from(y))?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.shl_in(mode, u32::from(y))?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.shl_in(mode, y)?),
+                    (Value::U64(x), Value::U64(y)) => {
+                        Value::U64(x.shl_in(mode, u32::try_from(y).unwrap_or(u32::MAX))?)
                     }
-                    (Value::U16(x), Value::U16(y)) => {
-                        Value::U16(u16::checked_shl(x, u32::from(y)).unwrap())
+                    (Value::I8(x), Value::I8(y)) => {
+                        Value::I8(x.shl_in(mode, signed_places(y.into())?)?)
                     }
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_shl(x, y).unwrap()),
-                    (Value::U64(x), Value::U64(y)) => {
-                        Value::U64(u64::checked_shl(x, u32::try_from(y).unwrap()).unwrap())
+                    (Value::I16(x), Value::I16(y)) => {
+                        Value::I16(x.shl_in(mode, signed_places(y.into())?)?)
                     }
-                    (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
-            Expr::Arith(Arith::Shr, x, y) => {
-                Cow::Owned(match (x.eval_value(scope), y.eval_value(scope)) {
-                    (Value::U8(x), Value::U8(y)) => {
-                        Value::U8(u8::checked_shr(x, u32::from(y)).unwrap())
+                    (Value::I32(x), Value::I32(y)) => {
+                        Value::I32(x.shl_in(mode, signed_places(y.into())?)?)
                     }
-                    (Value::U16(x), Value::U16(y)) => {
-                        Value::U16(u16::checked_shr(x, u32::from(y)).unwrap())
+                    (Value::I64(x), Value::I64(y)) => {
+                        Value::I64(x.shl_in(mode, signed_places(y)?)?)
                     }
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_shr(x, y).unwrap()),
+                    (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
+                },
+            ),
+            Expr::Arith(Arith::Shr, x, y) => Cow::Owned(
+                match (
+                    x.try_eval_value(scope, mode)?,
+                    y.try_eval_value(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.shr_in(mode, u32::from(y))?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.shr_in(mode, u32::from(y))?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.shr_in(mode, y)?),
                     (Value::U64(x), Value::U64(y)) => {
-                        Value::U64(u64::checked_shr(x, u32::try_from(y).unwrap()).unwrap())
+                        Value::U64(x.shr_in(mode, u32::try_from(y).unwrap_or(u32::MAX))?)
+                    }
+                    (Value::I8(x), Value::I8(y)) => {
+                        Value::I8(x.shr_in(mode, signed_places(y.into())?)?)
+                    }
+                    (Value::I16(x), Value::I16(y)) => {
+                        Value::I16(x.shr_in(mode, signed_places(y.into())?)?)
+                    }
+                    (Value::I32(x), Value::I32(y)) => {
+                        Value::I32(x.shr_in(mode, signed_places(y.into())?)?)
+                    }
+                    (Value::I64(x), Value::I64(y)) => {
+                        Value::I64(x.shr_in(mode, signed_places(y)?)?)
                     }
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
-                })
-            }
+                },
+            ),
 
-            Expr::AsU8(x) => Cow::Owned(match x.eval_value(scope) {
+            Expr::AsSigned8(x) => Cow::Owned(match x.try_eval_value(scope, mode)? {
+                Value::U8(x) => Value::I8(x as i8),
+                x => panic!("cannot reinterpret {x:?} as I8"),
+            }),
+            Expr::AsSigned16(x) => Cow::Owned(match x.try_eval_value(scope, mode)? {
+                Value::U16(x) => Value::I16(x as i16),
+                x => panic!("cannot reinterpret {x:?} as I16"),
+            }),
+            Expr::AsSigned32(x) => Cow::Owned(match x.try_eval_value(scope, mode)? {
+                Value::U32(x) => Value::I32(x as i32),
+                x => panic!("cannot reinterpret {x:?} as I32"),
+            }),
+            Expr::AsU8(x) => Cow::Owned(match x.try_eval_value(scope, mode)? {
                 Value::U8(x) => Value::U8(x),
-                Value::U16(x) => Value::U8(u8::try_from(x).unwrap()),
-                Value::U32(x) => Value::U8(u8::try_from(x).unwrap()),
-                Value::U64(x) => Value::U8(u8::try_from(x).unwrap()),
+                Value::U16(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
+                Value::U32(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
+                Value::U64(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
+                Value::I8(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
+                Value::I16(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
+                Value::I32(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
+                Value::I64(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
                 x => panic!("cannot convert {x:?} to U8"),
             }),
-            Expr::AsU16(x) => Cow::Owned(match x.eval_value(scope) {
+            Expr::AsU16(x) => Cow::Owned(match x.try_eval_value(scope, mode)? {
                 Value::U8(x) => Value::U16(u16::from(x)),
                 Value::U16(x) => Value::U16(x),
-                Value::U32(x) => Value::U16(u16::try_from(x).unwrap()),
-                Value::U64(x) => Value::U16(u16::try_from(x).unwrap()),
+                Value::U32(x) => Value::U16(u16::try_from(x).map_err(|_| Overflow)?),
+                Value::U64(x) => Value::U16(u16::try_from(x).map_err(|_| Overflow)?),
+                Value::I8(x) => Value::U16(u16::try_from(x).map_err(|_| Overflow)?),
+                Value::I16(x) => Value::U16(u16::try_from(x).map_err(|_| Overflow)?),
+                Value::I32(x) => Value::U16(u16::try_from(x).map_err(|_| Overflow)?),
+                Value::I64(x) => Value::U16(u16::try_from(x).map_err(|_| Overflow)?),
                 x => panic!("cannot convert {x:?} to U16"),
             }),
-            Expr::AsU32(x) => Cow::Owned(match x.eval_value(scope) {
+            Expr::AsU32(x) => Cow::Owned(match x.try_eval_value(scope, mode)? {
                 Value::U8(x) => Value::U32(u32::from(x)),
                 Value::U16(x) => Value::U32(u32::from(x)),
                 Value::U32(x) => Value::U32(x),
-                Value::U64(x) => Value::U32(u32::try_from(x).unwrap()),
+                Value::U64(x) => Value::U32(u32::try_from(x).map_err(|_| Overflow)?),
+                Value::I8(x) => Value::U32(u32::try_from(x).map_err(|_| Overflow)?),
+                Value::I16(x) => Value::U32(u32::try_from(x).map_err(|_| Overflow)?),
+                Value::I32(x) => Value::U32(u32::try_from(x).map_err(|_| Overflow)?),
+                Value::I64(x) => Value::U32(u32::try_from(x).map_err(|_| Overflow)?),
                 x => panic!("cannot convert {x:?} to U32"),
             }),
-            Expr::AsU64(x) => Cow::Owned(match x.eval_value(scope) {
+            Expr::AsU64(x) => Cow::Owned(match x.try_eval_value(scope, mode)? {
                 Value::U8(x) => Value::U64(u64::from(x)),
                 Value::U16(x) => Value::U64(u64::from(x)),
                 Value::U32(x) => Value::U64(u64::from(x)),
                 Value::U64(x) => Value::U64(x),
+                Value::I8(x) => Value::U64(u64::try_from(x).map_err(|_| Overflow)?),
+                Value::I16(x) => Value::U64(u64::try_from(x).map_err(|_| Overflow)?),
+                Value::I32(x) => Value::U64(u64::try_from(x).map_err(|_| Overflow)?),
+                Value::I64(x) => Value::U64(u64::try_from(x).map_err(|_| Overflow)?),
                 x => panic!("cannot convert {x:?} to U64"),
             }),
 
-            Expr::U16Be(bytes) => match bytes.eval_value(scope).unwrap_tuple().as_slice() {
-                [Value::U8(hi), Value::U8(lo)] => {
-                    Cow::Owned(Value::U16(u16::from_be_bytes([*hi, *lo])))
+            Expr::U16Be(bytes) => {
+                match bytes.try_eval_value(scope, mode)?.unwrap_tuple().as_slice() {
+                    [Value::U8(hi), Value::U8(lo)] => {
+                        Cow::Owned(Value::U16(u16::from_be_bytes([*hi, *lo])))
+                    }
+                    _ => panic!("U16Be: expected (U8, U8)"),
                 }
-                _ => panic!("U16Be: expected (U8, U8)"),
-            },
-            Expr::U16Le(bytes) => match bytes.eval_value(scope).unwrap_tuple().as_slice() {
-                [Value::U8(lo), Value::U8(hi)] => {
-                    Cow::Owned(Value::U16(u16::from_le_bytes([*lo, *hi])))
+            }
+            Expr::U16Le(bytes) => {
+                match bytes.try_eval_value(scope, mode)?.unwrap_tuple().as_slice() {
+                    [Value::U8(lo), Value::U8(hi)] => {
+                        Cow::Owned(Value::U16(u16::from_le_bytes([*lo, *hi])))
+                    }
+                    _ => panic!("U16Le: expected (U8, U8)"),
                 }
-                _ => panic!("U16Le: expected (U8, U8)"),
-            },
-            Expr::U32Be(bytes) => match bytes.eval_value(scope).unwrap_tuple().as_slice() {
-                [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d)] => {
-                    Cow::Owned(Value::U32(u32::from_be_bytes([*a, *b, *c, *d])))
+            }
+            Expr::U32Be(bytes) => {
+                match bytes.try_eval_value(scope, mode)?.unwrap_tuple().as_slice() {
+                    [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d)] => {
+                        Cow::Owned(Value::U32(u32::from_be_bytes([*a, *b, *c, *d])))
+                    }
+                    _ => panic!("U32Be: expected (U8, U8, U8, U8)"),
                 }
-                _ => panic!("U32Be: expected (U8, U8, U8, U8)"),
-            },
-            Expr::U32Le(bytes) => match bytes.eval_value(scope).unwrap_tuple().as_slice() {
-                [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d)] => {
-                    Cow::Owned(Value::U32(u32::from_le_bytes([*a, *b, *c, *d])))
+            }
+            Expr::U32Le(bytes) => {
+                match bytes.try_eval_value(scope, mode)?.unwrap_tuple().as_slice() {
+                    [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d)] => {
+                        Cow::Owned(Value::U32(u32::from_le_bytes([*a, *b, *c, *d])))
+                    }
+                    _ => panic!("U32Le: expected (U8, U8, U8, U8)"),
                 }
-                _ => panic!("U32Le: expected (U8, U8, U8, U8)"),
-            },
-            Expr::U64Be(bytes) => match bytes.eval_value(scope).unwrap_tuple().as_slice() {
-                [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d), Value::U8(e), Value::U8(f), Value::U8(g), Value::U8(h)] => {
-                    Cow::Owned(Value::U64(u64::from_be_bytes([
-                        *a, *b, *c, *d, *e, *f, *g, *h,
-                    ])))
+            }
+            Expr::U64Be(bytes) => {
+                match bytes.try_eval_value(scope, mode)?.unwrap_tuple().as_slice() {
+                    [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d), Value::U8(e), Value::U8(f), Value::U8(g), Value::U8(h)] => {
+                        Cow::Owned(Value::U64(u64::from_be_bytes([
+                            *a, *b, *c, *d, *e, *f, *g, *h,
+                        ])))
+                    }
+                    _ => panic!("U32Be: expected (U8, U8, U8, U8, U8, U8, U8, U8)"),
                 }
-                _ => panic!("U32Be: expected (U8, U8, U8, U8, U8, U8, U8, U8)"),
-            },
-            Expr::U64Le(bytes) => match bytes.eval_value(scope).unwrap_tuple().as_slice() {
-                [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d), Value::U8(e), Value::U8(f), Value::U8(g), Value::U8(h)] => {
-                    Cow::Owned(Value::U64(u64::from_le_bytes([
-                        *a, *b, *c, *d, *e, *f, *g, *h,
-                    ])))
+            }
+            Expr::U64Le(bytes) => {
+                match bytes.try_eval_value(scope, mode)?.unwrap_tuple().as_slice() {
+                    [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d), Value::U8(e), Value::U8(f), Value::U8(g), Value::U8(h)] => {
+                        Cow::Owned(Value::U64(u64::from_le_bytes([
+                            *a, *b, *c, *d, *e, *f, *g, *h,
+                        ])))
+                    }
+                    _ => panic!("U32Le: expected (U8, U8, U8, U8, U8, U8, U8, U8)"),
                 }
-                _ => panic!("U32Le: expected (U8, U8, U8, U8, U8, U8, U8, U8)"),
-            },
-            Expr::AsChar(bytes) => Cow::Owned(match bytes.eval_value(scope) {
+            }
+            Expr::I16Be(bytes) => {
+                match bytes.try_eval_value(scope, mode)?.unwrap_tuple().as_slice() {
+                    [Value::U8(hi), Value::U8(lo)] => {
+                        Cow::Owned(Value::I16(i16::from_be_bytes([*hi, *lo])))
+                    }
+                    _ => panic!("I16Be: expected (U8, U8)"),
+                }
+            }
+            Expr::I16Le(bytes) => {
+                match bytes.try_eval_value(scope, mode)?.unwrap_tuple().as_slice() {
+                    [Value::U8(lo), Value::U8(hi)] => {
+                        Cow::Owned(Value::I16(i16::from_le_bytes([*lo, *hi])))
+                    }
+                    _ => panic!("I16Le: expected (U8, U8)"),
+                }
+            }
+            Expr::I32Be(bytes) => {
+                match bytes.try_eval_value(scope, mode)?.unwrap_tuple().as_slice() {
+                    [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d)] => {
+                        Cow::Owned(Value::I32(i32::from_be_bytes([*a, *b, *c, *d])))
+                    }
+                    _ => panic!("I32Be: expected (U8, U8, U8, U8)"),
+                }
+            }
+            Expr::I32Le(bytes) => {
+                match bytes.try_eval_value(scope, mode)?.unwrap_tuple().as_slice() {
+                    [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d)] => {
+                        Cow::Owned(Value::I32(i32::from_le_bytes([*a, *b, *c, *d])))
+                    }
+                    _ => panic!("I32Le: expected (U8, U8, U8, U8)"),
+                }
+            }
+            Expr::AsChar(bytes) => Cow::Owned(match bytes.try_eval_value(scope, mode)? {
                 Value::U8(x) => Value::Char(char::from(x)),
                 Value::U16(x) => {
                     Value::Char(char::from_u32(x as u32).unwrap_or(char::REPLACEMENT_CHARACTER))
@@ -439,23 +1320,48 @@ impl Expr {
                     Value::Char(char::from_u32(x).unwrap_or(char::REPLACEMENT_CHARACTER))
                 }
                 Value::U64(x) => Value::Char(
-                    char::from_u32(u32::try_from(x).unwrap())
+                    char::from_u32(u32::try_from(x).map_err(|_| Overflow)?)
                         .unwrap_or(char::REPLACEMENT_CHARACTER),
                 ),
                 _ => panic!("AsChar: expected U8, U16, U32, or U64"),
             }),
-            Expr::SeqLength(seq) => match seq.eval(scope).coerce_mapped_value().get_sequence() {
+            Expr::SeqLength(seq) => match seq
+                .try_eval(scope, mode)?
+                .coerce_mapped_value()
+                .get_sequence()
+            {
                 Some(values) => {
                     let len = values.len();
                     Cow::Owned(Value::U32(len as u32))
                 }
                 _ => panic!("SeqLength: expected Seq"),
             },
+            Expr::SeqIx(seq, index) => match seq
+                .try_eval(scope, mode)?
+                .coerce_mapped_value()
+                .get_sequence()
+            {
+                Some(values) => {
+                    let index = index.try_eval_value(scope, mode)?.unwrap_usize();
+                    match values.get(index) {
+                        Some(v) => Cow::Owned(v.clone()),
+                        None => panic!(
+                            "SeqIx: index {index} out of bounds for sequence of length {}",
+                            values.len()
+                        ),
+                    }
+                }
+                _ => panic!("SeqIx: expected Seq"),
+            },
             Expr::SubSeq(seq, start, length) => {
-                match seq.eval(scope).coerce_mapped_value().get_sequence() {
+                match seq
+                    .try_eval(scope, mode)?
+                    .coerce_mapped_value()
+                    .get_sequence()
+                {
                     Some(values) => {
-                        let start = start.eval_value(scope).unwrap_usize();
-                        let length = length.eval_value(scope).unwrap_usize();
+                        let start = start.try_eval_value(scope, mode)?.unwrap_usize();
+                        let length = length.try_eval_value(scope, mode)?.unwrap_usize();
                         let values = &values[start..];
                         let values = &values[..length];
                         Cow::Owned({
@@ -467,10 +1373,14 @@ impl Expr {
                 }
             }
             Expr::SubSeqInflate(seq, start, length) => {
-                match seq.eval(scope).coerce_mapped_value().get_sequence() {
+                match seq
+                    .try_eval(scope, mode)?
+                    .coerce_mapped_value()
+                    .get_sequence()
+                {
                     Some(vs0) => {
-                        let start = start.eval_value(scope).unwrap_usize();
-                        let length = length.eval_value(scope).unwrap_usize();
+                        let start = start.try_eval_value(scope, mode)?.unwrap_usize();
+                        let length = length.try_eval_value(scope, mode)?.unwrap_usize();
                         let mut vs = Vec::new();
                         for i in 0..length {
                             if i + start < vs0.len() {
@@ -485,11 +1395,15 @@ impl Expr {
                 }
             }
             Expr::FlatMap(expr, seq) => {
-                match seq.eval(scope).coerce_mapped_value().get_sequence() {
+                match seq
+                    .try_eval(scope, mode)?
+                    .coerce_mapped_value()
+                    .get_sequence()
+                {
                     Some(values) => {
                         let mut vs = Vec::new();
                         for v in values {
-                            if let Value::Seq(vn) = expr.eval_lambda(scope, v) {
+                            if let Value::Seq(vn) = expr.try_eval_lambda(scope, v, mode)? {
                                 vs.extend(vn);
                             } else {
                                 panic!("FlatMap: expected Seq");
@@ -500,30 +1414,33 @@ impl Expr {
                     _ => panic!("FlatMap: expected Seq"),
                 }
             }
-            Expr::FlatMapAccum(expr, accum, _accum_type, seq) => match seq.eval_value(scope) {
-                Value::Seq(values) => {
-                    let mut accum = accum.eval_value(scope);
-                    let mut vs = Vec::new();
-                    for v in values {
-                        let ret = expr.eval_lambda(scope, &Value::Tuple(vec![accum, v]));
-                        accum = match ret.unwrap_tuple().as_mut_slice() {
-                            [accum, Value::Seq(vn)] => {
-                                vs.extend_from_slice(vn);
-                                accum.clone()
-                            }
-                            _ => panic!("FlatMapAccum: expected two values"),
-                        };
+            Expr::FlatMapAccum(expr, accum, _accum_type, seq) => {
+                match seq.try_eval_value(scope, mode)? {
+                    Value::Seq(values) => {
+                        let mut accum = accum.try_eval_value(scope, mode)?;
+                        let mut vs = Vec::new();
+                        for v in values {
+                            let ret =
+                                expr.try_eval_lambda(scope, &Value::Tuple(vec![accum, v]), mode)?;
+                            accum = match ret.unwrap_tuple().as_mut_slice() {
+                                [accum, Value::Seq(vn)] => {
+                                    vs.extend_from_slice(vn);
+                                    accum.clone()
+                                }
+                                _ => panic!("FlatMapAccum: expected two values"),
+                            };
+                        }
+                        Cow::Owned(Value::Seq(vs))
                     }
-                    Cow::Owned(Value::Seq(vs))
+                    _ => panic!("FlatMapAccum: expected Seq"),
                 }
-                _ => panic!("FlatMapAccum: expected Seq"),
-            },
-            Expr::FlatMapList(expr, _ret_type, seq) => match seq.eval_value(scope) {
+            }
+            Expr::FlatMapList(expr, _ret_type, seq) => match seq.try_eval_value(scope, mode)? {
                 Value::Seq(values) => {
                     let mut vs = Vec::new();
                     for v in values {
                         let arg = Value::Tuple(vec![Value::Seq(vs), v]);
-                        if let Value::Seq(vn) = expr.eval_lambda(scope, &arg) {
+                        if let Value::Seq(vn) = expr.try_eval_lambda(scope, &arg, mode)? {
                             vs = match arg {
                                 Value::Tuple(mut args) => match args.remove(0) {
                                     Value::Seq(vs) => vs,
@@ -541,62 +1458,94 @@ impl Expr {
                 _ => panic!("FlatMapList: expected Seq"),
             },
             Expr::Dup(count, expr) => {
-                let count = count.eval_value(scope).unwrap_usize();
-                let v = expr.eval_value(scope);
+                let count = count.try_eval_value(scope, mode)?.unwrap_usize();
+                let v = expr.try_eval_value(scope, mode)?;
                 let mut vs = Vec::new();
                 for _ in 0..count {
                     vs.push(v.clone());
                 }
                 Cow::Owned(Value::Seq(vs))
             }
-        }
+        })
     }
 
-    fn eval_value_ref<'a, 'b: 'a>(&'b self, scope: &'a Scope<'a>) -> Cow<'a, Value> {
-        match self.eval(scope) {
+    fn try_eval_value_ref<'a, 'b: 'a>(
+        &'b self,
+        scope: &'a Scope<'a>,
+        mode: ArithMode,
+    ) -> Result<Cow<'a, Value>, Overflow> {
+        Ok(match self.try_eval(scope, mode)? {
             Cow::Borrowed(vref) => Cow::Borrowed(vref.coerce_mapped_value()),
             Cow::Owned(v) => Cow::Owned(v.extract_mapped_value()),
-        }
+        })
     }
 
-    pub fn eval_value<'a>(&self, scope: &'a Scope<'a>) -> Value {
-        self.eval_value_ref(scope).into_owned()
+    fn try_eval_value<'a>(&self, scope: &'a Scope<'a>, mode: ArithMode) -> Result<Value, Overflow> {
+        Ok(self.try_eval_value_ref(scope, mode)?.into_owned())
     }
 
-    fn eval_lambda<'a>(&self, scope: &'a Scope<'a>, arg: &Value) -> Value {
+    fn try_eval_lambda<'a>(
+        &self,
+        scope: &'a Scope<'a>,
+        arg: &Value,
+        mode: ArithMode,
+    ) -> Result<Value, Overflow> {
         match self {
             Expr::Lambda(name, expr) => {
                 let child_scope = SingleScope::new(scope, name, arg);
-                expr.eval_value(&Scope::Single(child_scope))
+                expr.try_eval_value(&Scope::Single(child_scope), mode)
             }
             _ => panic!("expected Lambda"),
         }
     }
+
+    pub fn eval_value<'a>(&self, scope: &'a Scope<'a>) -> Value {
+        self.try_eval_value(scope, ArithMode::Checked)
+            .unwrap_or_else(|Overflow| panic!("arithmetic overflow"))
+    }
+
+    /// Evaluates a numeric expression denoting a length in bytes, in the nominal type of the expression
+    /// (as the generated parsers do) before converting it to `usize`.
+    ///
+    /// Returns `None` if any step of the computation over- or underflows, rather than panicking.
+    fn eval_length<'a>(&self, scope: &'a Scope<'a>, mode: ArithMode) -> Option<usize> {
+        Some(self.try_eval_value(scope, mode).ok()?.unwrap_usize())
+    }
 }
 
 /// Decoders with a fixed amount of lookahead
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Decoder {
     Call(usize, Vec<(Label, Expr)>),
     Fail,
     EndOfInput,
     Align(usize),
+    AbsolutePos,
+    SlicePos,
     Byte(ByteSet),
     Variant(Label, Box<Decoder>),
+    Newtype(Box<Decoder>, Option<Expr>),
     Parallel(Vec<Decoder>),
     Branch(MatchTree, Vec<Decoder>),
     Tuple(Vec<Decoder>),
     Record(Vec<(Label, Decoder)>),
     While(MatchTree, Box<Decoder>),
     Until(MatchTree, Box<Decoder>),
-    RepeatCount(Expr, Box<Decoder>),
+    RepeatSep(MatchTree, Box<Decoder>, Box<Decoder>),
+    /// Element decoder, along with a distinct decoder for the final element if its lookahead differs
+    RepeatCount(Expr, Box<Decoder>, Option<Box<Decoder>>),
     RepeatUntilLast(Expr, Box<Decoder>),
     RepeatUntilSeq(Expr, Box<Decoder>),
+    ForEach(Expr, Label, Box<Decoder>),
+    RepeatWhileInput(Box<Decoder>),
     Peek(Box<Decoder>),
     PeekNot(Box<Decoder>),
+    UntilSeq(Vec<u8>, Box<Decoder>),
+    Signature(Label, Box<Decoder>, Box<Decoder>),
     Slice(Expr, Box<Decoder>),
     Bits(Box<Decoder>),
     WithRelativeOffset(Expr, Box<Decoder>),
+    FollowOffset(Expr, Box<Decoder>),
     Map(Box<Decoder>, Expr),
     Compute(Expr),
     Let(Label, Expr, Box<Decoder>),
@@ -606,20 +1555,565 @@ pub enum Decoder {
     RepeatBetween(MatchTree, Expr, Expr, Box<Decoder>),
 }
 
+/// Callbacks for consuming the structure of a parse as it occurs, instead of as a fully-materialized [`Value`].
+///
+/// All callbacks default to no-ops, so implementors need only handle the events they are interested in.
+pub trait Visitor {
+    /// Called before the fields of a record are parsed
+    fn enter_record(&mut self) {}
+
+    /// Called with the name and value of each record field, in order, once it has been parsed
+    fn field(&mut self, _name: &Label, _value: &Value) {}
+
+    /// Called after all fields of a record have been parsed
+    fn exit_record(&mut self) {}
+
+    /// Called before the elements of a tuple are parsed
+    fn enter_tuple(&mut self) {}
+
+    /// Called after all elements of a tuple have been parsed
+    fn exit_tuple(&mut self) {}
+
+    /// Called with any value that is not itself streamed as a record or tuple
+    fn value(&mut self, _value: &Value) {}
+}
+
+/// Symbol table for resolving ID-based cross-references between the parts of a decoded value.
+///
+/// Records that contain an *anchor* field are registered as targets, keyed by the kind of the anchor
+/// and the value of that field; record fields registered as *references* are then resolved against those targets.
+#[derive(Debug, Default)]
+pub struct LinkTable {
+    /// `(kind, id_field)` pairs: any record with a field `id_field` is a target of the given kind
+    anchors: Vec<(Label, Label)>,
+    /// `(ref_field, kind)` pairs: any record field `ref_field` refers to a target of the given kind
+    references: Vec<(Label, Label)>,
+    targets: HashMap<(Label, Value), Value>,
+    links: Vec<Link>,
+}
+
+/// A cross-reference from a record field to the target with the matching id, if any
+#[derive(Clone, Debug, PartialEq)]
+pub struct Link {
+    pub field: Label,
+    pub id: Value,
+    /// The record referred to, or `None` if the reference is dangling
+    pub target: Option<Value>,
+}
+
+impl LinkTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers every record containing the field `id_field` as a target of kind `kind`
+    pub fn add_anchor(&mut self, kind: impl IntoLabel, id_field: impl IntoLabel) {
+        self.anchors.push((kind.into(), id_field.into()));
+    }
+
+    /// Registers every record field named `ref_field` as a reference to a target of kind `kind`
+    pub fn add_reference(&mut self, ref_field: impl IntoLabel, kind: impl IntoLabel) {
+        self.references.push((ref_field.into(), kind.into()));
+    }
+
+    /// Returns the links resolved by the most recent linked parse, in the order they occur in the decoded value
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    fn collect_targets(&mut self, value: &Value) {
+        match value.coerce_mapped_value() {
+            Value::Record(fields) => {
+                for (kind, id_field) in self.anchors.iter() {
+                    if let Some((_, id)) = fields.iter().find(|(name, _)| name == id_field) {
+                        let key = (kind.clone(), id.coerce_mapped_value().clone());
+                        self.targets
+                            .insert(key, value.coerce_mapped_value().clone());
+                    }
+                }
+                for (_, v) in fields {
+                    self.collect_targets(v);
+                }
+            }
+            Value::Tuple(vs) | Value::Seq(vs) => vs.iter().for_each(|v| self.collect_targets(v)),
+            Value::Variant(_, v) => self.collect_targets(v),
+            _ => {}
+        }
+    }
+
+    fn resolve_links(&mut self, value: &Value) {
+        match value.coerce_mapped_value() {
+            Value::Record(fields) => {
+                for (name, v) in fields {
+                    if let Some((_, kind)) = self.references.iter().find(|(field, _)| field == name)
+                    {
+                        let id = v.coerce_mapped_value().clone();
+                        let target = self.targets.get(&(kind.clone(), id.clone())).cloned();
+                        self.links.push(Link {
+                            field: name.clone(),
+                            id,
+                            target,
+                        });
+                    }
+                    self.resolve_links(v);
+                }
+            }
+            Value::Tuple(vs) | Value::Seq(vs) => vs.iter().for_each(|v| self.resolve_links(v)),
+            Value::Variant(_, v) => self.resolve_links(v),
+            _ => {}
+        }
+    }
+}
+
+/// Summary of how a parse spent its input, collected by [`Decoder::parse_traced`].
+///
+/// Byte counts are inclusive, so the bytes consumed by a named format also count towards every
+/// named format enclosing it. Branch hits are attributed to the innermost enclosing named format,
+/// or to `None` for unions outside of any named format.
+#[derive(Clone, Debug, Default)]
+pub struct ParseStats {
+    formats: HashMap<String, FormatStats>,
+    branches: HashMap<(Option<String>, usize), usize>,
+    /// Names of the decoders currently being parsed, innermost last
+    stack: Vec<Option<String>>,
+    /// Offset of the most recent failure to propagate out of a decoder call, with the names of the
+    /// formats being parsed at the point it was first encountered (outermost first)
+    failure: Option<(usize, Vec<String>)>,
+}
+
+/// Consumption of a single named format over the course of a parse
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FormatStats {
+    /// Number of times the format was successfully parsed
+    pub calls: usize,
+    /// Total number of bytes consumed by those parses
+    pub bytes: usize,
+}
+
+impl ParseStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the consumption recorded for the format named `name`
+    pub fn format(&self, name: &str) -> FormatStats {
+        self.formats.get(name).copied().unwrap_or_default()
+    }
+
+    /// Returns how many times branch `index` was taken by unions within the format named `name`
+    pub fn branch_hits(&self, name: Option<&str>, index: usize) -> usize {
+        let key = (name.map(String::from), index);
+        self.branches.get(&key).copied().unwrap_or(0)
+    }
+
+    /// Returns the names of the formats that were being parsed, outermost first, when the most recent
+    /// failure was encountered, if any failure propagated out of a named format.
+    pub fn failure_context(&self) -> Option<&[String]> {
+        self.failure.as_ref().map(|(_, names)| names.as_slice())
+    }
+}
+
+/// Parse error annotated with the named formats that were being parsed when it was encountered
+#[derive(Debug)]
+pub struct ContextError {
+    pub error: ParseError,
+    /// Names of the enclosing formats, outermost first
+    pub context: Vec<String>,
+}
+
+impl std::fmt::Display for ContextError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.context.last() {
+            None => write!(f, "{}", self.error),
+            Some(innermost) => write!(
+                f,
+                "{} while parsing {innermost} (in {})",
+                self.error,
+                self.context.join(" > ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ContextError {}
+
+/// Sub-tables decoded through offset links by [`Program::run_memoized`].
+///
+/// Entries are keyed by the decoder being called, the absolute offset the link points to, and the end of
+/// the enclosing slice, so that a sub-table linked from several places is decoded only once.
+#[derive(Clone, Debug, Default)]
+pub struct LinkCache {
+    entries: HashMap<(usize, usize, usize), Rc<Value>>,
+    hits: usize,
+}
+
+impl LinkCache {
+    /// Returns the number of distinct sub-tables that were decoded through offset links
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the number of offset links that were resolved from the cache rather than decoded anew
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Returns the shared values of every sub-table decoded at the absolute offset `offset`
+    pub fn at_offset(&self, offset: usize) -> impl Iterator<Item = &Rc<Value>> + '_ {
+        self.entries
+            .iter()
+            .filter_map(move |((_, start, _), v)| (*start == offset).then_some(v))
+    }
+}
+
+impl std::fmt::Display for ParseStats {
+    /// Lists the named formats by decreasing number of bytes consumed, followed by the branch hit counts.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut formats = self.formats.iter().collect::<Vec<_>>();
+        formats.sort_by(|(n0, s0), (n1, s1)| s1.bytes.cmp(&s0.bytes).then(n0.cmp(n1)));
+        writeln!(f, "formats:")?;
+        for (name, stats) in formats {
+            writeln!(
+                f,
+                "  {name}: {} bytes in {} calls",
+                stats.bytes, stats.calls
+            )?;
+        }
+        let mut branches = self.branches.iter().collect::<Vec<_>>();
+        branches.sort();
+        writeln!(f, "branches:")?;
+        for ((name, index), hits) in branches {
+            let name = name.as_deref().unwrap_or("<top>");
+            writeln!(f, "  {name}[{index}]: {hits}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Golden test vector pairing an input with the value it was decoded into when recorded.
+///
+/// Test vectors can be persisted with `serde` and replayed against later revisions of a format to detect regressions.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TestVector {
+    pub input: Vec<u8>,
+    pub expected: Value,
+}
+
+/// Reason for a [`TestVector`] failing to replay
+#[derive(Debug)]
+pub enum Mismatch {
+    /// The format could not be compiled
+    Compile(anyhow::Error),
+    /// The input no longer parses
+    Parse(ParseError),
+    /// The input parses to a different value than was recorded
+    Value { expected: Value, actual: Value },
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mismatch::Compile(err) => write!(f, "failed to compile format: {err}"),
+            Mismatch::Parse(err) => write!(f, "failed to parse recorded input: {err}"),
+            Mismatch::Value { expected, actual } => {
+                write!(f, "expected value {expected:?}, found {actual:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+impl TestVector {
+    /// Decodes `input` with `format` and records the resulting value as the expected output.
+    pub fn record(module: &FormatModule, format: &Format, input: Vec<u8>) -> AResult<TestVector> {
+        let program = Compiler::compile_program(module, format)?;
+        let (expected, _) = program
+            .run(ReadCtxt::new(&input))
+            .map_err(|err| anyhow!("failed to parse input: {err}"))?;
+        Ok(TestVector { input, expected })
+    }
+
+    /// Decodes the recorded input with `format` and checks that it yields the recorded value.
+    pub fn replay(&self, module: &FormatModule, format: &Format) -> Result<(), Mismatch> {
+        let program = Compiler::compile_program(module, format).map_err(Mismatch::Compile)?;
+        let (actual, _) = program
+            .run(ReadCtxt::new(&self.input))
+            .map_err(Mismatch::Parse)?;
+        if actual == self.expected {
+            Ok(())
+        } else {
+            Err(Mismatch::Value {
+                expected: self.expected.clone(),
+                actual,
+            })
+        }
+    }
+}
+
+/// Side table for deduplicating equal values (typically short strings such as tags and language codes)
+/// into a single shared allocation.
+///
+/// Interning trades time for memory: each value interned is hashed and compared against the existing
+/// entries, and a table entry is kept alive for as long as the interner is, but any number of equal values
+/// can then be held at the cost of a single copy plus one `Rc` per occurrence.
+#[derive(Debug, Default)]
+pub struct Interner {
+    values: HashSet<Rc<Value>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of distinct values interned so far
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns the shared copy of `value`, allocating it first if no equal value has been interned yet.
+    pub fn intern(&mut self, value: Value) -> Rc<Value> {
+        if let Some(shared) = self.values.get(&value) {
+            return shared.clone();
+        }
+        let shared = Rc::new(value);
+        self.values.insert(shared.clone());
+        shared
+    }
+
+    /// Replaces every string-like value (i.e. non-empty sequence of bytes or chars) within `value` by a
+    /// [`Value::Shared`] reference to its interned copy, returning the shared copies in the order they occur.
+    ///
+    /// Values that are already shared are left as they are.
+    pub fn intern_strings(&mut self, value: &mut Value) -> Vec<Rc<Value>> {
+        let mut ret = Vec::new();
+        self.intern_strings_into(value, &mut ret);
+        ret
+    }
+
+    fn intern_strings_into(&mut self, value: &mut Value, acc: &mut Vec<Rc<Value>>) {
+        match value {
+            Value::Seq(vs) if Self::is_string(vs) => {
+                let shared = self.intern(Value::Seq(std::mem::take(vs)));
+                *value = Value::Shared(Rc::clone(&shared));
+                acc.push(shared);
+            }
+            Value::Record(fields) => fields
+                .iter_mut()
+                .for_each(|(_, v)| self.intern_strings_into(v, acc)),
+            Value::Tuple(vs) | Value::Seq(vs) => {
+                vs.iter_mut().for_each(|v| self.intern_strings_into(v, acc))
+            }
+            Value::Variant(_, v) | Value::Mapped(_, v) | Value::Branch(_, v) => {
+                self.intern_strings_into(v, acc)
+            }
+            _ => {}
+        }
+    }
+
+    fn is_string(vs: &[Value]) -> bool {
+        !vs.is_empty()
+            && (vs.iter().all(|v| matches!(v, Value::U8(_)))
+                || vs.iter().all(|v| matches!(v, Value::Char(_))))
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Program {
     pub decoders: Vec<(Decoder, ValueType)>,
+    /// Name of the format each decoder was compiled from, for those compiled from named formats
+    names: Vec<Option<String>>,
+    /// Statistics being collected by an ongoing [`Decoder::parse_traced`], if any
+    stats: RefCell<Option<ParseStats>>,
+    /// Offset-linked sub-tables decoded by an ongoing [`Program::run_memoized`], if any
+    links: RefCell<Option<LinkCache>>,
+    /// Ceiling on the total number of bytes read by an ongoing [`Decoder::parse_with_budget`], if any,
+    /// alongside the number of bytes read so far
+    budget: Cell<Option<(usize, usize)>>,
+    /// Treatment of overflow in the arithmetic expressions evaluated while parsing
+    arith_mode: ArithMode,
 }
 
 impl Program {
     fn new() -> Self {
         let decoders = Vec::new();
-        Program { decoders }
+        let names = Vec::new();
+        let stats = RefCell::new(None);
+        let links = RefCell::new(None);
+        let budget = Cell::new(None);
+        let arith_mode = ArithMode::default();
+        Program {
+            decoders,
+            names,
+            stats,
+            links,
+            budget,
+            arith_mode,
+        }
+    }
+
+    /// Sets the treatment of overflow in the arithmetic expressions evaluated while parsing
+    pub fn set_arith_mode(&mut self, mode: ArithMode) {
+        self.arith_mode = mode;
+    }
+
+    pub(crate) fn arith_mode(&self) -> ArithMode {
+        self.arith_mode
+    }
+
+    /// Evaluates `expr` under the arithmetic mode of this program, failing at `offset` on overflow.
+    fn eval<'a>(
+        &self,
+        expr: &'a Expr,
+        scope: &'a Scope<'a>,
+        offset: usize,
+    ) -> ParseResult<Cow<'a, Value>> {
+        (expr.try_eval(scope, self.arith_mode)).map_err(|Overflow| ParseError::overflow(offset))
+    }
+
+    fn eval_value<'a>(
+        &self,
+        expr: &Expr,
+        scope: &'a Scope<'a>,
+        offset: usize,
+    ) -> ParseResult<Value> {
+        (expr.try_eval_value(scope, self.arith_mode))
+            .map_err(|Overflow| ParseError::overflow(offset))
+    }
+
+    fn eval_lambda<'a>(
+        &self,
+        expr: &Expr,
+        scope: &'a Scope<'a>,
+        arg: &Value,
+        offset: usize,
+    ) -> ParseResult<Value> {
+        (expr.try_eval_lambda(scope, arg, self.arith_mode))
+            .map_err(|Overflow| ParseError::overflow(offset))
+    }
+
+    /// Parses an offset-linked call to decoder `n` (without arguments) from `input`.
+    ///
+    /// When memoization is enabled, the result is a [`Value::Shared`] that is reused by any other link to the
+    /// same position rather than decoded anew.
+    fn call_linked(&self, n: usize, input: ReadCtxt<'_>) -> ParseResult<Value> {
+        let key = (n, input.offset, input.input.len());
+        if let Some(cache) = self.links.borrow_mut().as_mut() {
+            if let Some(v) = cache.entries.get(&key) {
+                cache.hits += 1;
+                return Ok(Value::Shared(Rc::clone(v)));
+            }
+        }
+        let (v, _) = Decoder::Call(n, Vec::new()).parse(self, &Scope::Empty, input)?;
+        match self.links.borrow_mut().as_mut() {
+            Some(cache) => {
+                let v = Rc::new(v);
+                cache.entries.insert(key, Rc::clone(&v));
+                Ok(Value::Shared(v))
+            }
+            None => Ok(v),
+        }
+    }
+
+    fn enter_call(&self, n: usize) {
+        if let Some(stats) = self.stats.borrow_mut().as_mut() {
+            stats.stack.push(self.names[n].clone());
+        }
+    }
+
+    fn exit_call<T>(&self, n: usize, start: usize, res: &ParseResult<(T, ReadCtxt<'_>)>) {
+        if let Some(stats) = self.stats.borrow_mut().as_mut() {
+            if let Err(e) = res {
+                // an error propagating unchanged through enclosing calls keeps its innermost context
+                if stats.failure.as_ref().map(|(offset, _)| *offset) != Some(e.offset()) {
+                    let names = stats.stack.iter().flatten().cloned().collect();
+                    stats.failure = Some((e.offset(), names));
+                }
+            }
+            stats.stack.pop();
+            if let (Some(name), Ok((_, input))) = (&self.names[n], res) {
+                let entry = stats.formats.entry(name.clone()).or_default();
+                entry.calls += 1;
+                entry.bytes += input.offset - start;
+            }
+        }
+    }
+
+    /// Charges `count` bytes read at `offset` against the byte budget of the ongoing parse, if any.
+    fn consume_bytes(&self, count: usize, offset: usize) -> ParseResult<()> {
+        if let Some((budget, used)) = self.budget.get() {
+            if used + count > budget {
+                return Err(ParseError::byte_budget_exceeded(
+                    budget,
+                    offset + (budget - used),
+                ));
+            }
+            self.budget.set(Some((budget, used + count)));
+        }
+        Ok(())
+    }
+
+    fn hit_branch(&self, index: usize) {
+        if let Some(stats) = self.stats.borrow_mut().as_mut() {
+            let name = stats.stack.last().cloned().flatten();
+            *stats.branches.entry((name, index)).or_default() += 1;
+        }
     }
 
     pub fn run<'input>(&self, input: ReadCtxt<'input>) -> ParseResult<(Value, ReadCtxt<'input>)> {
         self.decoders[0].0.parse(self, &Scope::Empty, input)
     }
+
+    /// Runs the top-level decoder as with [`Program::run`], decoding each sub-table that is reached by
+    /// offset links from more than one place only once.
+    ///
+    /// Every such sub-table appears in the result as a [`Value::Shared`] whose allocation is common to all
+    /// the links to that position, and is also held by the table of linked sub-tables returned alongside.
+    pub fn run_memoized<'input>(
+        &self,
+        input: ReadCtxt<'input>,
+    ) -> (ParseResult<(Value, ReadCtxt<'input>)>, LinkCache) {
+        let prev = self.links.replace(Some(LinkCache::default()));
+        let res = self.decoders[0].0.parse(self, &Scope::Empty, input);
+        let cache = self.links.replace(prev).unwrap_or_default();
+        (res, cache)
+    }
+
+    /// Runs the top-level decoder as with [`Program::run`], annotating any error with the named formats
+    /// that were being parsed when it was encountered.
+    pub fn run_with_context<'input>(
+        &self,
+        input: ReadCtxt<'input>,
+    ) -> Result<(Value, ReadCtxt<'input>), ContextError> {
+        let mut stats = ParseStats::new();
+        self.decoders[0]
+            .0
+            .parse_traced(self, &Scope::Empty, input, &mut stats)
+            .map_err(|error| ContextError {
+                error,
+                context: stats.failure_context().unwrap_or_default().to_vec(),
+            })
+    }
+
+    /// Runs the top-level decoder, streaming the events of the parse into `visitor`.
+    pub fn run_visit<'input>(
+        &self,
+        input: ReadCtxt<'input>,
+        visitor: &mut dyn Visitor,
+    ) -> ParseResult<ReadCtxt<'input>> {
+        self.decoders[0]
+            .0
+            .parse_visit(self, &Scope::Empty, input, visitor)
+    }
 }
 
 pub struct Compiler<'a> {
@@ -627,6 +2121,7 @@ pub struct Compiler<'a> {
     program: Program,
     decoder_map: HashMap<(usize, Rc<Next<'a>>), usize>,
     compile_queue: Vec<(&'a Format, Rc<Next<'a>>, usize)>,
+    max_depth: usize,
 }
 
 impl<'a> Compiler<'a> {
@@ -639,6 +2134,7 @@ impl<'a> Compiler<'a> {
             program,
             decoder_map,
             compile_queue,
+            max_depth: MatchTree::MAX_DEPTH,
         }
     }
 
@@ -648,7 +2144,7 @@ impl<'a> Compiler<'a> {
         let scope = TypeScope::new();
         let t = module.infer_format_type(&scope, format)?;
         // decoder
-        compiler.queue_compile(t, format, Rc::new(Next::Empty));
+        compiler.queue_compile(None, t, format, Rc::new(Next::Empty));
         while let Some((f, next, n)) = compiler.compile_queue.pop() {
             let d = compiler.compile_format(f, next)?;
             compiler.program.decoders[n].0 = d;
@@ -656,9 +2152,16 @@ impl<'a> Compiler<'a> {
         Ok(compiler.program)
     }
 
-    fn queue_compile(&mut self, t: ValueType, f: &'a Format, next: Rc<Next<'a>>) -> usize {
+    fn queue_compile(
+        &mut self,
+        name: Option<&str>,
+        t: ValueType,
+        f: &'a Format,
+        next: Rc<Next<'a>>,
+    ) -> usize {
         let n = self.program.decoders.len();
         self.program.decoders.push((Decoder::Fail, t));
+        self.program.names.push(name.map(String::from));
         self.compile_queue.push((f, next, n));
         n
     }
@@ -669,6 +2172,15 @@ impl<'a> Compiler<'a> {
         compiler.compile_format(format, Rc::new(Next::Empty))
     }
 
+    /// Compiles `format` as with [`Compiler::compile_one`], but allowing the match-trees of any
+    /// alternations to look ahead by up to `max_depth` bytes rather than [`MatchTree::MAX_DEPTH`].
+    pub fn compile_one_with_depth(format: &Format, max_depth: usize) -> AResult<Decoder> {
+        let module = FormatModule::new();
+        let mut compiler = Compiler::new(&module);
+        compiler.max_depth = max_depth;
+        compiler.compile_format(format, Rc::new(Next::Empty))
+    }
+
     fn compile_format(&mut self, format: &'a Format, next: Rc<Next<'a>>) -> AResult<Decoder> {
         match format {
             Format::ItemVar(level, arg_exprs) => {
@@ -682,7 +2194,8 @@ impl<'a> Compiler<'a> {
                     *n
                 } else {
                     let t = self.module.get_format_type(*level).clone();
-                    let n = self.queue_compile(t, f, next.clone());
+                    let name = self.module.get_name(*level);
+                    let n = self.queue_compile(Some(name), t, f, next.clone());
                     self.decoder_map.insert((*level, next.clone()), n);
                     n
                 };
@@ -696,20 +2209,25 @@ impl<'a> Compiler<'a> {
             Format::Fail => Ok(Decoder::Fail),
             Format::EndOfInput => Ok(Decoder::EndOfInput),
             Format::Align(n) => Ok(Decoder::Align(*n)),
+            Format::AbsolutePos => Ok(Decoder::AbsolutePos),
+            Format::SlicePos => Ok(Decoder::SlicePos),
             Format::Byte(bs) => Ok(Decoder::Byte(*bs)),
             Format::Variant(label, f) => {
                 let d = self.compile_format(f, next.clone())?;
                 Ok(Decoder::Variant(label.clone(), Box::new(d)))
             }
+            Format::Newtype(_name, f, validate) => {
+                let d = self.compile_format(f, next.clone())?;
+                Ok(Decoder::Newtype(Box::new(d), validate.clone()))
+            }
             Format::Union(branches) => {
                 let mut ds = Vec::with_capacity(branches.len());
                 for f in branches {
                     ds.push(self.compile_format(f, next.clone())?);
                 }
-                if let Some(tree) = MatchTree::build(self.module, branches, next) {
-                    Ok(Decoder::Branch(tree, ds))
-                } else {
-                    Err(anyhow!("cannot build match tree for {:?}", format))
+                match MatchTree::build(self.module, branches, next, self.max_depth) {
+                    Ok(tree) => Ok(Decoder::Branch(tree, ds)),
+                    Err(ambiguity) => Err(anyhow!("cannot build match tree: {ambiguity}")),
                 }
             }
             Format::UnionNondet(branches) => {
@@ -757,7 +2275,7 @@ impl<'a> Compiler<'a> {
                 let astar = Format::Repeat(a.clone());
                 let fa = Format::Tuple(vec![(**a).clone(), astar]);
                 let fb = Format::EMPTY;
-                if let Some(tree) = MatchTree::build(self.module, &[fa, fb], next) {
+                if let Ok(tree) = MatchTree::build(self.module, &[fa, fb], next, self.max_depth) {
                     Ok(Decoder::While(tree, Box::new(da)))
                 } else {
                     Err(anyhow!("cannot build match tree for {:?}", format))
@@ -774,16 +2292,44 @@ impl<'a> Compiler<'a> {
                 let astar = Format::Repeat(a.clone());
                 let fa = Format::EMPTY;
                 let fb = Format::Tuple(vec![(**a).clone(), astar]);
-                if let Some(tree) = MatchTree::build(self.module, &[fa, fb], next) {
+                if let Ok(tree) = MatchTree::build(self.module, &[fa, fb], next, self.max_depth) {
                     Ok(Decoder::Until(tree, Box::new(da)))
                 } else {
                     Err(anyhow!("cannot build match tree for {:?}", format))
                 }
             }
+            Format::RepeatSep(a, sep) => {
+                if a.is_nullable(self.module) {
+                    return Err(anyhow!("cannot repeat nullable format: {a:?}"));
+                }
+                let rest = Rc::new(Next::RepeatSep(
+                    MaybeTyped::Untyped(a),
+                    MaybeTyped::Untyped(sep),
+                    next.clone(),
+                ));
+                let da = self.compile_format(a, rest.clone())?;
+                let dsep =
+                    self.compile_format(sep, Rc::new(Next::Cat(MaybeTyped::Untyped(a), rest)))?;
+                let fa = Format::Tuple(vec![(**sep).clone(), format.clone()]);
+                let fb = Format::EMPTY;
+                if let Ok(tree) = MatchTree::build(self.module, &[fa, fb], next, self.max_depth) {
+                    Ok(Decoder::RepeatSep(tree, Box::new(da), Box::new(dsep)))
+                } else {
+                    Err(anyhow!("cannot build match tree for {:?}", format))
+                }
+            }
             Format::RepeatCount(expr, a) => {
-                // FIXME probably not right
-                let da = Box::new(self.compile_format(a, next)?);
-                Ok(Decoder::RepeatCount(expr.clone(), da))
+                // every element but the last is followed by at least one more element, while the last
+                // is followed by whatever follows the repetition, so the two may need different lookahead
+                let body_next = Rc::new(Next::Cat(MaybeTyped::Untyped(a), Rc::new(Next::Empty)));
+                let da = self.compile_format(a, body_next)?;
+                let da_last = if a.depends_on_next(self.module) {
+                    let da_last = self.compile_format(a, next)?;
+                    (da_last != da).then(|| Box::new(da_last))
+                } else {
+                    None
+                };
+                Ok(Decoder::RepeatCount(expr.clone(), Box::new(da), da_last))
             }
             Format::RepeatBetween(xmin, xmax, a) => {
                 // FIXME - preliminary support only for exact-bound limit values
@@ -811,7 +2357,9 @@ impl<'a> Compiler<'a> {
                         let f_count = Format::RepeatCount(Expr::U32(count as u32), a.clone());
                         branches.push(f_count);
                     }
-                    let Some(tree) = MatchTree::build(self.module, &branches[..], next) else {
+                    let Ok(tree) =
+                        MatchTree::build(self.module, &branches[..], next, self.max_depth)
+                    else {
                         panic!("cannot build match tree for {:?}", format)
                     };
                     tree
@@ -824,7 +2372,10 @@ impl<'a> Compiler<'a> {
                 ))
             }
             Format::RepeatUntilLast(expr, a) => {
-                // FIXME probably not right
+                // NOTE - each iteration is evaluated in the same scope, so an iteration that consumes no input will recur indefinitely
+                if a.is_non_progressing(self.module) {
+                    return Err(anyhow!("cannot repeat non-progressing format: {a:?}"));
+                }
                 let da = Box::new(self.compile_format(a, next)?);
                 Ok(Decoder::RepeatUntilLast(expr.clone(), da))
             }
@@ -833,6 +2384,30 @@ impl<'a> Compiler<'a> {
                 let da = Box::new(self.compile_format(a, next)?);
                 Ok(Decoder::RepeatUntilSeq(expr.clone(), da))
             }
+            Format::UntilSeq(terminator, a) => {
+                if a.is_nullable(self.module) {
+                    return Err(anyhow!(
+                        "cannot repeat non-progressing (nullable) format: {a:?}"
+                    ));
+                }
+                let da = Box::new(self.compile_format(a, Rc::new(Next::Empty))?);
+                Ok(Decoder::UntilSeq(terminator.clone(), da))
+            }
+            Format::ForEach(expr, name, a) => {
+                // NOTE - each iteration is independent of whatever follows the entire loop
+                let da = Box::new(self.compile_format(a, Rc::new(Next::Empty))?);
+                Ok(Decoder::ForEach(expr.clone(), name.clone(), da))
+            }
+            Format::RepeatWhileInput(a) => {
+                if a.is_nullable(self.module) {
+                    return Err(anyhow!("cannot repeat nullable format: {a:?}"));
+                }
+                let da = Box::new(self.compile_format(
+                    a,
+                    Rc::new(Next::Repeat(MaybeTyped::Untyped(a), Rc::new(Next::Empty))),
+                )?);
+                Ok(Decoder::RepeatWhileInput(da))
+            }
             Format::Peek(a) => {
                 let da = Box::new(self.compile_format(a, Rc::new(Next::Empty))?);
                 Ok(Decoder::Peek(da))
@@ -851,6 +2426,18 @@ impl<'a> Compiler<'a> {
                 let da = Box::new(self.compile_format(a, Rc::new(Next::Empty))?);
                 Ok(Decoder::PeekNot(da))
             }
+            Format::Signature(name, magic, body) => {
+                let dmagic = self.compile_format(
+                    magic,
+                    Rc::new(Next::Cat(MaybeTyped::Untyped(body), next.clone())),
+                )?;
+                let dbody = self.compile_format(body, next)?;
+                Ok(Decoder::Signature(
+                    name.clone(),
+                    Box::new(dmagic),
+                    Box::new(dbody),
+                ))
+            }
             Format::Slice(expr, a) => {
                 let da = Box::new(self.compile_format(a, Rc::new(Next::Empty))?);
                 Ok(Decoder::Slice(expr.clone(), da))
@@ -863,6 +2450,11 @@ impl<'a> Compiler<'a> {
                 let da = Box::new(self.compile_format(a, Rc::new(Next::Empty))?);
                 Ok(Decoder::WithRelativeOffset(expr.clone(), da))
             }
+            Format::FollowOffset(expr, a) => {
+                // NOTE - the target is decoded without regard for what follows it, which is conservative since the stream has jumped
+                let da = Box::new(self.compile_format(a, Rc::new(Next::Empty))?);
+                Ok(Decoder::FollowOffset(expr.clone(), da))
+            }
             Format::Map(a, expr) => {
                 let da = Box::new(self.compile_format(a, next.clone())?);
                 Ok(Decoder::Map(da, expr.clone()))
@@ -1049,7 +2641,206 @@ impl<'a> DecoderScope<'a> {
 }
 
 impl Decoder {
-    pub fn parse<'input>(
+    /// Compiles a standalone decoder for `format`, with alternations disambiguated by up to
+    /// `max_depth` bytes of lookahead.
+    pub fn compile_with_depth(format: &Format, max_depth: usize) -> AResult<Decoder> {
+        Compiler::compile_one_with_depth(format, max_depth)
+    }
+
+    /// Parses the input as with [`Decoder::parse`], but rather than returning a value, reports the
+    /// structure of the parse to `visitor`.
+    ///
+    /// Tuples are streamed element-by-element without being materialized. The fields of records are
+    /// materialized individually, as they may be referred to by later fields, but the record as a whole is not.
+    pub fn parse_visit<'input>(
+        &self,
+        program: &Program,
+        scope: &Scope<'_>,
+        input: ReadCtxt<'input>,
+        visitor: &mut dyn Visitor,
+    ) -> ParseResult<ReadCtxt<'input>> {
+        match self {
+            Decoder::Call(n, es) => {
+                let mut new_scope = MultiScope::with_capacity(&Scope::Empty, es.len());
+                for (name, e) in es {
+                    let v = program.eval_value(e, scope, input.offset)?;
+                    new_scope.push_owned(name.clone(), v);
+                }
+                program.decoders[*n].0.parse_visit(
+                    program,
+                    &Scope::Multi(&new_scope),
+                    input,
+                    visitor,
+                )
+            }
+            Decoder::Tuple(fields) => {
+                let mut input = input;
+                visitor.enter_tuple();
+                for f in fields {
+                    input = f.parse_visit(program, scope, input, visitor)?;
+                }
+                visitor.exit_tuple();
+                Ok(input)
+            }
+            Decoder::Record(fields) => {
+                let mut input = input;
+                let mut record_scope = MultiScope::with_capacity(scope, fields.len());
+                visitor.enter_record();
+                for (name, f) in fields {
+                    let (vf, next_input) = f.parse(program, &Scope::Multi(&record_scope), input)?;
+                    visitor.field(name, &vf);
+                    record_scope.push_owned(name.clone(), vf);
+                    input = next_input;
+                }
+                visitor.exit_record();
+                Ok(input)
+            }
+            _ => {
+                let (v, input) = self.parse(program, scope, input)?;
+                visitor.value(&v);
+                Ok(input)
+            }
+        }
+    }
+
+    /// Shrinks an input that this decoder fails to parse down to the shortest prefix that still
+    /// fails with the same kind of error at the same offset.
+    ///
+    /// The prefix length is found by bisection, which assumes that once a prefix reproduces the
+    /// original failure, so does every longer prefix; this holds for the common case of errors that
+    /// are independent of the input that follows them. If the input parses successfully, it is
+    /// returned unchanged.
+    pub fn minimize_failure(&self, program: &Program, input: &[u8]) -> Vec<u8> {
+        let parse = |len: usize| self.parse(program, &Scope::Empty, ReadCtxt::new(&input[..len]));
+        let Err(original) = parse(input.len()) else {
+            return input.to_vec();
+        };
+        let (mut lo, mut hi) = (0, input.len());
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match parse(mid) {
+                Err(e) if e.same_failure(&original) => hi = mid,
+                _ => lo = mid + 1,
+            }
+        }
+        input[..hi].to_vec()
+    }
+
+    /// Parses the input as with [`Decoder::parse`], and then resolves all cross-references registered
+    /// in `links` against the decoded value, in a second pass.
+    ///
+    /// The resolved links are available through [`LinkTable::links`] afterwards.
+    pub fn parse_linked<'input>(
+        &self,
+        program: &Program,
+        scope: &Scope<'_>,
+        input: ReadCtxt<'input>,
+        links: &mut LinkTable,
+    ) -> ParseResult<(Value, ReadCtxt<'input>)> {
+        let (v, input) = self.parse(program, scope, input)?;
+        links.targets.clear();
+        links.links.clear();
+        links.collect_targets(&v);
+        links.resolve_links(&v);
+        Ok((v, input))
+    }
+
+    /// Parses the input as with [`Decoder::parse`], failing with [`ParseError::ByteBudgetExceeded`] once
+    /// more than `max_total_bytes` bytes have been read in total, if it is set.
+    ///
+    /// Bytes that are read more than once (e.g. through several offset-links to the same position) are
+    /// counted each time, so that the budget bounds the work done rather than the size of the input.
+    pub fn parse_with_budget<'input>(
+        &self,
+        program: &Program,
+        scope: &Scope<'_>,
+        input: ReadCtxt<'input>,
+        max_total_bytes: Option<usize>,
+    ) -> ParseResult<(Value, ReadCtxt<'input>)> {
+        let outer = program
+            .budget
+            .replace(max_total_bytes.map(|budget| (budget, 0)));
+        let res = self.parse(program, scope, input);
+        program.budget.set(outer);
+        res
+    }
+
+    /// Parses the input as with [`Decoder::parse`], while accumulating statistics about the named
+    /// formats and union branches it passes through into `stats`.
+    pub fn parse_traced<'input>(
+        &self,
+        program: &Program,
+        scope: &Scope<'_>,
+        input: ReadCtxt<'input>,
+        stats: &mut ParseStats,
+    ) -> ParseResult<(Value, ReadCtxt<'input>)> {
+        let outer = program.stats.replace(Some(std::mem::take(stats)));
+        let res = self.parse(program, scope, input);
+        *stats = program.stats.replace(outer).unwrap_or_default();
+        res
+    }
+
+    /// Parses the input as with [`Decoder::parse`], but fails with [`ParseError::Leftover`] unless
+    /// the decoder consumes all of it.
+    pub fn parse_all(
+        &self,
+        program: &Program,
+        scope: &Scope<'_>,
+        input: ReadCtxt<'_>,
+    ) -> ParseResult<Value> {
+        let (v, input) = self.parse(program, scope, input)?;
+        match input.remaining().len() {
+            0 => Ok(v),
+            count => Err(ParseError::leftover(count, input.offset)),
+        }
+    }
+
+    /// Parses a prefix of the bytes produced by `reader`, as with [`Decoder::parse`], reading no more of
+    /// it than is needed to do so. Returns the parsed value together with the number of bytes consumed.
+    ///
+    /// The input is buffered in chunks of increasing size, and the decoder is re-run over the buffered
+    /// prefix until it succeeds with at least [`MatchTree::MAX_DEPTH`] bytes of lookahead to spare, or
+    /// until `reader` is exhausted, so that bytes past the end of the parsed value are only read as far
+    /// as the buffer happens to extend. Only failures caused by reaching the end of the buffer prompt
+    /// a retry; any other parse error is returned as soon as it is encountered.
+    ///
+    /// As such, a `Peek` or `PeekNot` that looks further ahead than the match-tree lookahead limit may
+    /// observe the end of the buffer rather than that of the whole stream.
+    pub fn parse_reader(
+        &self,
+        program: &Program,
+        scope: &Scope<'_>,
+        reader: &mut impl std::io::Read,
+    ) -> ParseResult<(Value, usize)> {
+        use std::io::Read;
+
+        const INITIAL_CHUNK: u64 = 4096;
+        let mut buffer = Vec::new();
+        let mut chunk = INITIAL_CHUNK;
+        loop {
+            let nbytes = reader
+                .by_ref()
+                .take(chunk)
+                .read_to_end(&mut buffer)
+                .map_err(|err| ParseError::io(err.kind(), buffer.len()))?;
+            let exhausted = (nbytes as u64) < chunk;
+            match self.parse(program, scope, ReadCtxt::new(&buffer)) {
+                Ok((v, input))
+                    if exhausted || input.offset + MatchTree::MAX_DEPTH < buffer.len() =>
+                {
+                    return Ok((v, input.offset));
+                }
+                Ok(_) => {}
+                Err(err) if exhausted => return Err(err),
+                Err(ParseError::Overrun { nbytes, offset }) if offset + nbytes > buffer.len() => {}
+                Err(ParseError::Overbyte { offset }) if offset >= buffer.len() => {}
+                Err(err) => return Err(err),
+            }
+            chunk *= 2;
+        }
+    }
+
+    pub fn parse<'input>(
         &self,
         program: &Program,
         scope: &Scope<'_>,
@@ -1059,12 +2850,15 @@ impl Decoder {
             Decoder::Call(n, es) => {
                 let mut new_scope = MultiScope::with_capacity(&Scope::Empty, es.len());
                 for (name, e) in es {
-                    let v = e.eval_value(scope);
+                    let v = program.eval_value(e, scope, input.offset)?;
                     new_scope.push_owned(name.clone(), v);
                 }
-                program.decoders[*n]
+                program.enter_call(*n);
+                let res = program.decoders[*n]
                     .0
-                    .parse(program, &Scope::Multi(&new_scope), input)
+                    .parse(program, &Scope::Multi(&new_scope), input);
+                program.exit_call(*n, input.offset, &res);
+                res
             }
             Decoder::Fail => Err(ParseError::<Value>::fail(scope, input)),
             Decoder::EndOfInput => match input.read_byte() {
@@ -1078,10 +2872,21 @@ impl Decoder {
                     .ok_or(ParseError::overrun(skip, input.offset))?;
                 Ok((Value::UNIT, input))
             }
+            Decoder::AbsolutePos => {
+                let pos = u32::try_from(input.offset)
+                    .map_err(|_| ParseError::position_overflow(input.offset))?;
+                Ok((Value::U32(pos), input))
+            }
+            Decoder::SlicePos => {
+                let pos = u32::try_from(input.slice_offset())
+                    .map_err(|_| ParseError::position_overflow(input.offset))?;
+                Ok((Value::U32(pos), input))
+            }
             Decoder::Byte(bs) => {
                 let (b, input) = input
                     .read_byte()
                     .ok_or(ParseError::overbyte(input.offset))?;
+                program.consume_bytes(1, input.offset - 1)?;
                 if bs.contains(b) {
                     Ok((Value::U8(b), input))
                 } else {
@@ -1092,11 +2897,24 @@ impl Decoder {
                 let (v, input) = d.parse(program, scope, input)?;
                 Ok((Value::Variant(label.clone(), Box::new(v)), input))
             }
+            Decoder::Newtype(d, validate) => {
+                let (v, next_input) = d.parse(program, scope, input)?;
+                if let Some(expr) = validate {
+                    if !program
+                        .eval_lambda(expr, scope, &v, input.offset)?
+                        .unwrap_bool()
+                    {
+                        return Err(ParseError::<Value>::fail(scope, input));
+                    }
+                }
+                Ok((v, next_input))
+            }
             Decoder::Branch(tree, branches) => {
                 let index = tree.matches(input).ok_or(ParseError::NoValidBranch {
                     offset: input.offset,
                 })?;
                 let d = &branches[index];
+                program.hit_branch(index);
                 let (v, input) = d.parse(program, scope, input)?;
                 Ok((Value::Branch(index, Box::new(v)), input))
             }
@@ -1104,6 +2922,7 @@ impl Decoder {
                 for (index, d) in branches.iter().enumerate() {
                     let res = d.parse(program, scope, input);
                     if let Ok((v, input)) = res {
+                        program.hit_branch(index);
                         return Ok((Value::Branch(index, Box::new(v)), input));
                     }
                 }
@@ -1158,11 +2977,32 @@ impl Decoder {
                 }
                 Ok((Value::Seq(v), input))
             }
-            Decoder::RepeatCount(expr, a) => {
+            Decoder::RepeatSep(tree, a, sep) => {
+                let (va, mut input) = a.parse(program, scope, input)?;
+                let mut v = vec![va];
+                while tree.matches(input).ok_or(ParseError::NoValidBranch {
+                    offset: input.offset,
+                })? == 0
+                {
+                    let (_, next_input) = sep.parse(program, scope, input)?;
+                    let (va, next_input) = a.parse(program, scope, next_input)?;
+                    input = next_input;
+                    v.push(va);
+                }
+                Ok((Value::Seq(v), input))
+            }
+            Decoder::RepeatCount(expr, a, a_last) => {
                 let mut input = input;
-                let count = expr.eval_value(scope).unwrap_usize();
-                let mut v = Vec::with_capacity(count);
-                for _ in 0..count {
+                let count = program
+                    .eval_value(expr, scope, input.offset)?
+                    .unwrap_usize();
+                // NOTE - `count` may be arbitrarily large for malformed input, so we avoid pre-allocating more than the remaining input could possibly fill
+                let mut v = Vec::with_capacity(Ord::min(count, input.remaining().len()));
+                for ix in 0..count {
+                    let a = match a_last {
+                        Some(a_last) if ix + 1 == count => a_last,
+                        _ => a,
+                    };
                     let (va, next_input) = a.parse(program, scope, input)?;
                     input = next_input;
                     v.push(va);
@@ -1171,8 +3011,8 @@ impl Decoder {
             }
             Decoder::RepeatBetween(tree, min, max, a) => {
                 let mut input = input;
-                let min = min.eval_value(scope).unwrap_usize();
-                let max = max.eval_value(scope).unwrap_usize();
+                let min = program.eval_value(min, scope, input.offset)?.unwrap_usize();
+                let max = program.eval_value(max, scope, input.offset)?.unwrap_usize();
                 let mut v = Vec::new();
                 loop {
                     if tree.matches(input).ok_or(ParseError::NoValidBranch {
@@ -1196,15 +3036,37 @@ impl Decoder {
                 let mut v = Vec::new();
                 loop {
                     let (va, next_input) = a.parse(program, scope, input)?;
+                    let progressed = next_input.offset > input.offset;
                     input = next_input;
-                    let done = expr.eval_lambda(scope, &va).unwrap_bool();
+                    let done = program
+                        .eval_lambda(expr, scope, &va, input.offset)?
+                        .unwrap_bool();
                     v.push(va);
                     if done {
                         break;
+                    } else if !progressed {
+                        // an empty non-terminal iteration would be repeated identically forever
+                        return Err(ParseError::<Value>::fail(scope, input));
                     }
                 }
                 Ok((Value::Seq(v), input))
             }
+            Decoder::UntilSeq(terminator, a) => {
+                let mut input = input;
+                let mut v = Vec::new();
+                loop {
+                    if input.remaining().starts_with(terminator) {
+                        let (_, input) = input.split_at(terminator.len()).unwrap();
+                        return Ok((Value::Seq(v), input));
+                    }
+                    if input.remaining().is_empty() {
+                        return Err(ParseError::overbyte(input.offset));
+                    }
+                    let (va, next_input) = a.parse(program, scope, input)?;
+                    input = next_input;
+                    v.push(va);
+                }
+            }
             Decoder::RepeatUntilSeq(expr, a) => {
                 let mut input = input;
                 let mut v = Vec::new();
@@ -1213,7 +3075,9 @@ impl Decoder {
                     input = next_input;
                     v.push(va);
                     let vs = Value::Seq(v);
-                    let done = expr.eval_lambda(scope, &vs).unwrap_bool();
+                    let done = program
+                        .eval_lambda(expr, scope, &vs, input.offset)?
+                        .unwrap_bool();
                     v = match vs {
                         Value::Seq(v) => v,
                         _ => unreachable!(),
@@ -1224,6 +3088,31 @@ impl Decoder {
                 }
                 Ok((Value::Seq(v), input))
             }
+            Decoder::ForEach(expr, name, a) => {
+                let mut input = input;
+                let seq = program.eval(expr, scope, input.offset)?;
+                let Some(elems) = seq.coerce_mapped_value().get_sequence() else {
+                    panic!("ForEach: expected Seq")
+                };
+                let mut v = Vec::with_capacity(elems.len());
+                for elem in elems {
+                    let elem_scope = SingleScope::new(scope, name, elem);
+                    let (va, next_input) = a.parse(program, &Scope::Single(elem_scope), input)?;
+                    input = next_input;
+                    v.push(va);
+                }
+                Ok((Value::Seq(v), input))
+            }
+            Decoder::RepeatWhileInput(a) => {
+                let mut input = input;
+                let mut v = Vec::new();
+                while !input.remaining().is_empty() {
+                    let (va, next_input) = a.parse(program, scope, input)?;
+                    input = next_input;
+                    v.push(va);
+                }
+                Ok((Value::Seq(v), input))
+            }
             Decoder::Peek(a) => {
                 let (v, _next_input) = a.parse(program, scope, input)?;
                 Ok((v, input))
@@ -1235,8 +3124,16 @@ impl Decoder {
                     Ok((Value::Tuple(vec![]), input))
                 }
             }
+            Decoder::Signature(name, magic, body) => {
+                let (_, input) = magic
+                    .parse(program, scope, input)
+                    .map_err(|_| ParseError::wrong_format(name.clone(), input.offset))?;
+                body.parse(program, scope, input)
+            }
             Decoder::Slice(expr, a) => {
-                let size = expr.eval_value(scope).unwrap_usize();
+                let size = expr
+                    .eval_length(scope, program.arith_mode)
+                    .ok_or(ParseError::overflow(input.offset))?;
                 let (slice, input) = input
                     .split_at(size)
                     .ok_or(ParseError::overrun(size, input.offset))?;
@@ -1250,38 +3147,57 @@ impl Decoder {
                         bits.push((b & (1 << i)) >> i);
                     }
                 }
-                let (v, bits) = a.parse(program, scope, ReadCtxt::new(&bits))?;
+                // the budget is charged for the underlying bytes rather than for each bit
+                let budget = program.budget.take();
+                let res = a.parse(program, scope, ReadCtxt::new(&bits));
+                program.budget.set(budget);
+                let (v, bits) = res?;
                 let bytes_remain = bits.remaining().len() >> 3;
                 let bytes_read = input.remaining().len() - bytes_remain;
+                program.consume_bytes(bytes_read, input.offset)?;
                 let (_, input) = input
                     .split_at(bytes_read)
                     .ok_or(ParseError::overrun(bytes_read, input.offset))?;
                 Ok((v, input))
             }
             Decoder::WithRelativeOffset(expr, a) => {
-                let offset = expr.eval_value(scope).unwrap_usize();
+                let offset = program
+                    .eval_value(expr, scope, input.offset)?
+                    .unwrap_usize();
                 let (_, slice) = input
                     .split_at(offset)
                     .ok_or(ParseError::overrun(offset, input.offset))?;
-                let (v, _) = a.parse(program, scope, slice)?;
+                let v = match a.as_ref() {
+                    Decoder::Call(n, args) if args.is_empty() => program.call_linked(*n, slice)?,
+                    _ => a.parse(program, scope, slice)?.0,
+                };
                 Ok((v, input))
             }
+            Decoder::FollowOffset(expr, a) => {
+                let offset = program
+                    .eval_value(expr, scope, input.offset)?
+                    .unwrap_usize();
+                let (_, input) = input
+                    .split_at(offset)
+                    .ok_or(ParseError::overrun(offset, input.offset))?;
+                a.parse(program, scope, input)
+            }
             Decoder::Map(d, expr) => {
                 let (orig, input) = d.parse(program, scope, input)?;
-                let v = expr.eval_lambda(scope, &orig);
+                let v = program.eval_lambda(expr, scope, &orig, input.offset)?;
                 Ok((Value::Mapped(Box::new(orig), Box::new(v)), input))
             }
             Decoder::Compute(expr) => {
-                let v = expr.eval_value(scope);
+                let v = program.eval_value(expr, scope, input.offset)?;
                 Ok((v, input))
             }
             Decoder::Let(name, expr, d) => {
-                let v = expr.eval_value(scope);
+                let v = program.eval_value(expr, scope, input.offset)?;
                 let let_scope = SingleScope::new(scope, name, &v);
                 d.parse(program, &Scope::Single(let_scope), input)
             }
             Decoder::Match(head, branches) => {
-                let head = head.eval(scope);
+                let head = program.eval(head, scope, input.offset)?;
                 for (index, (pattern, decoder)) in branches.iter().enumerate() {
                     if let Some(pattern_scope) = head.matches(scope, pattern) {
                         let (v, input) =
@@ -1292,12 +3208,13 @@ impl Decoder {
                 panic!("non-exhaustive patterns");
             }
             Decoder::Dynamic(name, DynFormat::Huffman(lengths_expr, opt_values_expr), d) => {
-                let lengths_val = lengths_expr.eval(scope);
+                let lengths_val = program.eval(lengths_expr, scope, input.offset)?;
                 let lengths = value_to_vec_usize(lengths_val.as_ref());
                 let lengths = match opt_values_expr {
                     None => lengths,
                     Some(e) => {
-                        let values = value_to_vec_usize(e.eval(scope).as_ref());
+                        let values =
+                            value_to_vec_usize(program.eval(e, scope, input.offset)?.as_ref());
                         let mut new_lengths = [0].repeat(values.len());
                         for i in 0..lengths.len() {
                             new_lengths[values[i]] = lengths[i];
@@ -1385,16 +3302,29 @@ fn is_bit(b: bool) -> Format {
 mod tests {
     use super::*;
     use crate::helper::*;
+    use crate::{BaseType, BranchExplanation};
 
     fn accepts(d: &Decoder, input: &[u8], tail: &[u8], expect: Value) {
         let program = Program::new();
         let (val, remain) = d
             .parse(&program, &Scope::Empty, ReadCtxt::new(input))
             .unwrap();
-        assert_eq!(val, expect);
+        assert_value_eq(&val, &expect);
         assert_eq!(remain.remaining(), tail);
     }
 
+    /// Asserts that `actual == expected`, reporting only the points at which they differ otherwise.
+    fn assert_value_eq(actual: &Value, expected: &Value) {
+        if actual != expected {
+            let diffs = actual.diff(expected);
+            if diffs.is_empty() {
+                panic!("values differ only in pre-mapped values or branch-indices:\n{actual:?}\n!=\n{expected:?}");
+            }
+            let lines: Vec<String> = diffs.iter().map(ValueDiff::to_string).collect();
+            panic!("values differ:\n{}", lines.join("\n"));
+        }
+    }
+
     fn rejects(d: &Decoder, input: &[u8]) {
         let program = Program::new();
         assert!(d
@@ -1512,6 +3442,57 @@ mod tests {
         assert!(Compiler::compile_one(&f).is_err());
     }
 
+    #[test]
+    fn compile_alt_repeat_count_prefix() {
+        let zeroes = || repeat_count(Expr::U8(2), is_byte(0x00));
+        let tuple_a = Format::Tuple(vec![zeroes(), is_byte(0x01)]);
+        let tuple_b = Format::Tuple(vec![zeroes(), is_byte(0x02)]);
+        let f = alts([("a", tuple_a), ("b", tuple_b)]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let zeroes_value = Value::Seq(vec![Value::U8(0x00), Value::U8(0x00)]);
+        accepts(
+            &d,
+            &[0x00, 0x00, 0x01],
+            &[],
+            Value::Branch(
+                0,
+                Box::new(Value::variant(
+                    "a",
+                    Value::Tuple(vec![zeroes_value.clone(), Value::U8(0x01)]),
+                )),
+            ),
+        );
+        accepts(
+            &d,
+            &[0x00, 0x00, 0x02],
+            &[],
+            Value::Branch(
+                1,
+                Box::new(Value::variant(
+                    "b",
+                    Value::Tuple(vec![zeroes_value, Value::U8(0x02)]),
+                )),
+            ),
+        );
+        rejects(&d, &[0x00, 0x00, 0x03]);
+        rejects(&d, &[0x00, 0x01]);
+    }
+
+    #[test]
+    fn compile_alt_repeat_count_ambiguous() {
+        let zeroes_then_one = |n| {
+            Format::Tuple(vec![
+                repeat_count(Expr::U8(n), is_byte(0x00)),
+                is_byte(0x01),
+            ])
+        };
+        let f = alts([("a", zeroes_then_one(2)), ("b", zeroes_then_one(2))]);
+        assert!(Compiler::compile_one(&f).is_err());
+        // distinct constant counts are told apart by the byte following the shorter run
+        let f = alts([("a", zeroes_then_one(2)), ("b", zeroes_then_one(3))]);
+        assert!(Compiler::compile_one(&f).is_ok());
+    }
+
     #[test]
     fn compile_alt_fail() {
         let f = alts([("a", Format::Fail), ("b", Format::Fail)]);
@@ -1711,77 +3692,697 @@ mod tests {
     }
 
     #[test]
-    fn compile_repeat_alt_repeat1_slow() {
-        let f = repeat(alts([
-            ("a", repeat1(is_byte(0x00))),
-            ("b", is_byte(0x01)),
-            ("c", is_byte(0x02)),
-        ]));
-        assert!(Compiler::compile_one(&f).is_err());
+    fn compile_with_depth() {
+        let prefix = [0x00; 39];
+        let f = alts([
+            ("a", tuple([is_bytes(&prefix), is_byte(0x01)])),
+            ("b", tuple([is_bytes(&prefix), is_byte(0x02)])),
+        ]);
+        assert!(Decoder::compile_with_depth(&f, 32).is_err());
+        let d = Decoder::compile_with_depth(&f, 48).unwrap();
+        let mut input = prefix.to_vec();
+        input.push(0x02);
+        let (val, _) = d
+            .parse(&Program::new(), &Scope::Empty, ReadCtxt::new(&input))
+            .unwrap();
+        assert!(matches!(val, Value::Branch(1, _)));
     }
 
     #[test]
-    fn compile_repeat() {
-        let f = repeat(is_byte(0x00));
+    fn parse_all_rejects_leftover() {
+        let f = tuple([is_byte(0x00), is_byte(0x01)]);
         let d = Compiler::compile_one(&f).unwrap();
-        accepts(&d, &[], &[], Value::Seq(vec![]));
-        accepts(&d, &[0xFF], &[0xFF], Value::Seq(vec![]));
-        accepts(&d, &[0x00], &[], Value::Seq(vec![Value::U8(0x00)]));
-        accepts(
-            &d,
-            &[0x00, 0x00],
-            &[],
-            Value::Seq(vec![Value::U8(0x00), Value::U8(0x00)]),
+        let program = Program::new();
+        let val = d
+            .parse_all(&program, &Scope::Empty, ReadCtxt::new(&[0x00, 0x01]))
+            .unwrap();
+        assert_eq!(val, Value::Tuple(vec![Value::U8(0x00), Value::U8(0x01)]));
+        let Err(err) = d.parse_all(
+            &program,
+            &Scope::Empty,
+            ReadCtxt::new(&[0x00, 0x01, 0x02, 0x03]),
+        ) else {
+            panic!("expected leftover input to be rejected")
+        };
+        assert!(matches!(
+            err,
+            ParseError::Leftover {
+                count: 2,
+                offset: 2
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_reader_reads_prefix() {
+        struct Counting<'a>(&'a [u8], usize);
+        impl std::io::Read for Counting<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = std::io::Read::read(&mut self.0, buf)?;
+                self.1 += n;
+                Ok(n)
+            }
+        }
+
+        let f = record([
+            ("magic", is_bytes(b"HDR")),
+            ("len", Format::Byte(ByteSet::full())),
+            (
+                "data",
+                repeat_count(var("len"), Format::Byte(ByteSet::full())),
+            ),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+
+        // the header is parsed without reading the whole (large) input
+        let mut input = b"HDR\x02\xAA\xBB".to_vec();
+        input.resize(1 << 20, 0xFF);
+        let mut reader = Counting(&input, 0);
+        let (v, consumed) = d
+            .parse_reader(&program, &Scope::Empty, &mut reader)
+            .unwrap();
+        assert_eq!(consumed, 6);
+        assert!(reader.1 < input.len());
+        assert_eq!(
+            v.get(&[Accessor::Field("len".into())]).unwrap(),
+            &Value::U8(2)
         );
+
+        // a value larger than the initial buffer is still parsed in full
+        let f_long = record([
+            (
+                "len",
+                map(
+                    tuple([Format::Byte(ByteSet::full()), Format::Byte(ByteSet::full())]),
+                    lambda("x", Expr::U16Be(Box::new(var("x")))),
+                ),
+            ),
+            (
+                "data",
+                repeat_count(var("len"), Format::Byte(ByteSet::full())),
+            ),
+        ]);
+        let d_long = Compiler::compile_one(&f_long).unwrap();
+        let mut input = vec![0x30, 0x00];
+        input.resize(2 + 0x3000 + 0x100, 0x00);
+        let (_, consumed) = d_long
+            .parse_reader(&program, &Scope::Empty, &mut input.as_slice())
+            .unwrap();
+        assert_eq!(consumed, 2 + 0x3000);
+
+        // running out of input is still an error
+        assert!(matches!(
+            d.parse_reader(&program, &Scope::Empty, &mut &b"HDR\x05\x00"[..]),
+            Err(ParseError::Overbyte { offset: 5 })
+        ));
+
+        // any other failure is reported without reading further
+        let mut input = b"BAD".to_vec();
+        input.resize(1 << 20, 0xFF);
+        let mut reader = Counting(&input, 0);
+        assert!(matches!(
+            d.parse_reader(&program, &Scope::Empty, &mut reader),
+            Err(ParseError::Unexpected { found: b'B', .. })
+        ));
+        assert_eq!(reader.1, 4096);
     }
 
     #[test]
-    fn compile_repeat_repeat() {
-        let f = repeat(repeat(is_byte(0x00)));
-        assert!(Compiler::compile_one(&f).is_err());
+    fn value_diff_names_field() {
+        let x = Value::record([
+            ("a", Value::U8(1)),
+            (
+                "b",
+                Value::Seq(vec![Value::U16(2), Value::record([("c", Value::U8(3))])]),
+            ),
+        ]);
+        let y = Value::record([
+            ("a", Value::U8(1)),
+            (
+                "b",
+                Value::Seq(vec![Value::U16(2), Value::record([("c", Value::U8(4))])]),
+            ),
+        ]);
+        assert_eq!(x.diff(&x), vec![]);
+        assert_eq!(
+            x.diff(&y),
+            vec![ValueDiff {
+                path: vec![
+                    Accessor::Field("b".into()),
+                    Accessor::Index(1),
+                    Accessor::Field("c".into()),
+                ],
+                left: Value::U8(3),
+                right: Value::U8(4),
+            }]
+        );
+        // sequences of different lengths are reported whole
+        let z = Value::record([("a", Value::U8(1)), ("b", Value::Seq(vec![]))]);
+        let diffs = x.diff(&z);
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path, vec![Accessor::Field("b".into())]);
     }
 
     #[test]
-    fn compile_cat_repeat() {
-        let f = Format::Tuple(vec![repeat(is_byte(0x00)), repeat(is_byte(0xFF))]);
+    fn compile_if_remaining() {
+        let any_byte = Format::Byte(ByteSet::full());
+        let header = Format::Slice(
+            var("len"),
+            Box::new(record([
+                ("version", any_byte.clone()),
+                (
+                    "extra",
+                    if_remaining(2, tuple([any_byte.clone(), any_byte.clone()])),
+                ),
+            ])),
+        );
+        let f = record([("len", any_byte), ("header", header)]);
         let d = Compiler::compile_one(&f).unwrap();
-        accepts(
-            &d,
-            &[],
-            &[],
-            Value::Tuple(vec![Value::Seq(vec![]), Value::Seq(vec![])]),
+        let program = Program::new();
+        let extra = |input: &[u8]| {
+            let (v, _) = d
+                .parse(&program, &Scope::Empty, ReadCtxt::new(input))
+                .unwrap();
+            let extra = v
+                .get(&[
+                    Accessor::Field("header".into()),
+                    Accessor::Field("extra".into()),
+                ])
+                .unwrap()
+                .clone();
+            extra
+                .flatten_singletons(&["some", "none"])
+                .extract_mapped_value()
+        };
+        assert_eq!(
+            extra(&[0x03, 0x01, 0xAA, 0xBB]),
+            Value::Option(Some(Box::new(Value::Tuple(vec![
+                Value::U8(0xAA),
+                Value::U8(0xBB)
+            ]))))
         );
-        accepts(
-            &d,
-            &[0x00],
-            &[],
-            Value::Tuple(vec![Value::Seq(vec![Value::U8(0x00)]), Value::Seq(vec![])]),
+        // the slice is too short for the extra field, regardless of the bytes following it
+        assert_eq!(extra(&[0x02, 0x01, 0xAA, 0xBB]), Value::Option(None));
+        assert_eq!(extra(&[0x01, 0x01]), Value::Option(None));
+    }
+
+    #[test]
+    fn compile_where_lambda() {
+        let u16be = map(
+            tuple([Format::Byte(ByteSet::full()), Format::Byte(ByteSet::full())]),
+            lambda("x", Expr::U16Be(Box::new(var("x")))),
         );
-        accepts(
-            &d,
-            &[0xFF],
-            &[],
-            Value::Tuple(vec![Value::Seq(vec![]), Value::Seq(vec![Value::U8(0xFF)])]),
+        let expect_2 = where_lambda(u16be, "version", expr_eq(var("version"), Expr::U16(2)));
+        let d = Compiler::compile_one(&expect_2).unwrap();
+        let program = Program::new();
+        let (v, _) = d
+            .parse(&program, &Scope::Empty, ReadCtxt::new(&[0x00, 0x02]))
+            .unwrap();
+        assert_eq!(v.coerce_mapped_value(), &Value::U16(2));
+        rejects(&d, &[0x00, 0x03]);
+        rejects(&d, &[0x02, 0x00]);
+    }
+
+    #[test]
+    fn compile_map_compose() {
+        let u16be = lambda("x", Expr::U16Be(Box::new(var("x"))));
+        let to_char = lambda("c", as_char(var("c")));
+        let f = map(
+            tuple([Format::Byte(ByteSet::full()), Format::Byte(ByteSet::full())]),
+            compose(to_char, u16be),
         );
+        let d = Compiler::compile_one(&f).unwrap();
         accepts(
             &d,
-            &[0x00, 0xFF],
+            &[0x00, 0x41],
             &[],
-            Value::Tuple(vec![
-                Value::Seq(vec![Value::U8(0x00)]),
-                Value::Seq(vec![Value::U8(0xFF)]),
-            ]),
-        );
-        accepts(
-            &d,
-            &[0x00, 0xFF, 0x00],
-            &[0x00],
-            Value::Tuple(vec![
-                Value::Seq(vec![Value::U8(0x00)]),
-                Value::Seq(vec![Value::U8(0xFF)]),
-            ]),
+            Value::Mapped(
+                Box::new(Value::Tuple(vec![Value::U8(0x00), Value::U8(0x41)])),
+                Box::new(Value::Char('A')),
+            ),
         );
-        accepts(
+    }
+
+    #[test]
+    fn compile_length_prefixed_bytes() {
+        let f = tuple([
+            length_prefixed_bytes(Format::Byte(ByteSet::full())),
+            is_byte(0xFF),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let blob = |input: &[u8]| {
+            let (v, _) = d
+                .parse(&program, &Scope::Empty, ReadCtxt::new(input))
+                .unwrap();
+            v.tuple_proj(0).coerce_mapped_value().clone()
+        };
+        assert_eq!(blob(&[0x00, 0xFF]), Value::Seq(vec![]));
+        assert_eq!(
+            blob(&[0x02, 0xFF, 0x00, 0xFF]),
+            Value::Seq(vec![Value::U8(0xFF), Value::U8(0x00)])
+        );
+        // the blob may not extend past the end of the input
+        rejects(&d, &[0x04, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn factor_union_prefix() {
+        let any_byte = Format::Byte(ByteSet::full());
+        let prefix = || {
+            vec![
+                ("format", tuple([any_byte.clone(), any_byte.clone()])),
+                ("length", any_byte.clone()),
+            ]
+        };
+        let branch = |tag: u8, field: &'static str| {
+            let mut fields = prefix();
+            fields.push(("tag", is_byte(tag)));
+            fields.push((field, repeat_count(var("length"), any_byte.clone())));
+            Format::record(fields)
+        };
+        let f = Format::alts([
+            ("v0", branch(0, "data")),
+            ("v1", branch(1, "words")),
+            ("v2", branch(2, "bytes")),
+        ]);
+        let tail = |tag: u8, field: &'static str| {
+            Format::record([
+                ("tag", is_byte(tag)),
+                (field, repeat_count(var("length"), any_byte.clone())),
+            ])
+        };
+        let mut expected = prefix();
+        expected.push((
+            "tail",
+            Format::alts([
+                ("v0", tail(0, "data")),
+                ("v1", tail(1, "words")),
+                ("v2", tail(2, "bytes")),
+            ]),
+        ));
+        let factored = f.factor_union_prefix();
+        assert_eq!(factored, Format::record(expected));
+
+        let d = Compiler::compile_one(&factored).unwrap();
+        let (v, _) = d
+            .parse(
+                &Program::new(),
+                &Scope::Empty,
+                ReadCtxt::new(&[0x00, 0x01, 0x02, 0x01, 0xAA, 0xBB]),
+            )
+            .unwrap();
+        assert_eq!(v.field("length").and_then(Value::as_u8), Some(2));
+        assert_eq!(
+            v.get(&[
+                Accessor::Field("tail".into()),
+                Accessor::Variant("v1".into()),
+                Accessor::Field("words".into()),
+            ]),
+            Some(&Value::Seq(vec![Value::U8(0xAA), Value::U8(0xBB)]))
+        );
+
+        // nothing to factor out
+        let g = Format::Union(vec![is_byte(0x00), is_byte(0x01)]);
+        assert_eq!(g.clone().factor_union_prefix(), g);
+
+        // a shared field named `tail` would collide with the factored-out union
+        let h = Format::Union(vec![
+            Format::record([("tail", any_byte.clone()), ("x", is_byte(0x00))]),
+            Format::record([("tail", any_byte), ("y", is_byte(0x01))]),
+        ]);
+        assert_eq!(h.clone().factor_union_prefix(), h);
+    }
+
+    #[test]
+    fn value_accessors() {
+        let v = Value::Mapped(
+            Box::new(Value::UNIT),
+            Box::new(Value::record([
+                ("num_tables", Value::Branch(0, Box::new(Value::U16(9)))),
+                ("flags", Value::Seq(vec![Value::Bool(true)])),
+            ])),
+        );
+        let num_tables = || v.field("num_tables")?.as_u16();
+        assert_eq!(num_tables(), Some(9));
+        assert_eq!(v.field("num_tables").and_then(Value::as_u32), None);
+        assert_eq!(v.field("missing"), None);
+        assert_eq!(
+            v.field("flags")
+                .and_then(Value::as_seq)
+                .and_then(|flags| flags[0].as_bool()),
+            Some(true)
+        );
+        assert_eq!(v.as_record().map(<[_]>::len), Some(2));
+        assert_eq!(Value::U8(1).as_record(), None);
+        assert_eq!(Value::U8(1).field("x"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn value_to_json() {
+        let v = Value::record([
+            (
+                "version",
+                Value::Mapped(Box::new(Value::U8(1)), Box::new(Value::U16(0x0100))),
+            ),
+            ("offset", Value::I32(-4)),
+            ("name", Value::Seq(vec![Value::Char('h'), Value::Char('i')])),
+            (
+                "kind",
+                Value::Branch(
+                    1,
+                    Box::new(Value::Variant(
+                        "pair".into(),
+                        Box::new(Value::Tuple(vec![Value::Bool(true), Value::U64(7)])),
+                    )),
+                ),
+            ),
+            ("extra", Value::Option(None)),
+        ]);
+        assert_eq!(
+            v.to_json(),
+            serde_json::json!({
+                "version": 256,
+                "offset": -4,
+                "name": ["h", "i"],
+                "kind": { "tag": "pair", "value": [true, 7] },
+                "extra": null,
+            })
+        );
+        // fields are kept in the order of the record rather than sorted by name
+        let keys = v
+            .to_json()
+            .as_object()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(keys, ["version", "offset", "name", "kind", "extra"]);
+    }
+
+    #[test]
+    fn compile_seq_ix() {
+        let any_byte = Format::Byte(ByteSet::full());
+        let f = record([
+            ("xs", repeat_count(Expr::U8(3), any_byte.clone())),
+            ("len", Format::Compute(seq_length(var("xs")))),
+            ("empty", Format::Compute(seq_is_empty(var("xs")))),
+            (
+                "ys",
+                repeat_count(seq_ix(var("xs"), Expr::U32(2)), any_byte),
+            ),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[0x05, 0x06, 0x01, 0xAA, 0xBB],
+            &[0xBB],
+            Value::record([
+                (
+                    "xs",
+                    Value::Seq(vec![Value::U8(0x05), Value::U8(0x06), Value::U8(0x01)]),
+                ),
+                ("len", Value::U32(3)),
+                ("empty", Value::Bool(false)),
+                ("ys", Value::Seq(vec![Value::U8(0xAA)])),
+            ]),
+        );
+    }
+
+    #[test]
+    fn compile_or_pattern() {
+        let f = record([
+            ("b", Format::Byte(ByteSet::full())),
+            (
+                "is_newline",
+                Format::Match(
+                    var("b"),
+                    vec![
+                        (
+                            Pattern::or([Pattern::U8(0x0A), Pattern::U8(0x0D)]),
+                            Format::Compute(Expr::Bool(true)),
+                        ),
+                        (Pattern::Wildcard, Format::Compute(Expr::Bool(false))),
+                    ],
+                ),
+            ),
+            (
+                "other",
+                Format::Compute(expr_match(
+                    Expr::Tuple(vec![Expr::U8(0), var("b")]),
+                    [
+                        (
+                            Pattern::or([
+                                Pattern::Tuple(vec![Pattern::U8(0x0A), bind("x")]),
+                                Pattern::Tuple(vec![bind("x"), Pattern::U8(0x0D)]),
+                            ]),
+                            var("x"),
+                        ),
+                        (Pattern::Wildcard, Expr::U8(0xFF)),
+                    ],
+                )),
+            ),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let parse = |input: &[u8]| {
+            let (val, _) = d
+                .parse(&program, &Scope::Empty, ReadCtxt::new(input))
+                .unwrap();
+            ["is_newline", "other"].map(|name| {
+                val.get(&[Accessor::Field(name.into())])
+                    .unwrap()
+                    .coerce_mapped_value()
+                    .clone()
+            })
+        };
+        assert_eq!(parse(&[0x0A]), [Value::Bool(true), Value::U8(0xFF)]);
+        assert_eq!(parse(&[0x0D]), [Value::Bool(true), Value::U8(0)]);
+        assert_eq!(parse(&[0x20]), [Value::Bool(false), Value::U8(0xFF)]);
+    }
+
+    #[test]
+    fn compile_u8_range_pattern() {
+        let f = record([
+            ("b", Format::Byte(ByteSet::full())),
+            (
+                "digit",
+                Format::Match(
+                    var("b"),
+                    vec![
+                        (
+                            Pattern::U8Range(0x30, 0x39),
+                            Format::Compute(sub(var("b"), Expr::U8(0x30))),
+                        ),
+                        (Pattern::Wildcard, Format::Fail),
+                    ],
+                ),
+            ),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        for (input, expected) in [(b'0', 0), (b'5', 5), (b'9', 9)] {
+            let (val, _) = d
+                .parse(&program, &Scope::Empty, ReadCtxt::new(&[input]))
+                .unwrap();
+            assert_eq!(
+                val.get(&[Accessor::Field("digit".into())])
+                    .unwrap()
+                    .coerce_mapped_value(),
+                &Value::U8(expected)
+            );
+        }
+        rejects(&d, b"/");
+        rejects(&d, b":");
+    }
+
+    #[test]
+    fn compile_until_seq() {
+        let f = tuple([
+            until_seq(b"\r\n", Format::Byte(ByteSet::full())),
+            is_byte(b'!'),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let line = |bytes: &[u8]| {
+            Value::Tuple(vec![
+                Value::Seq(bytes.iter().copied().map(Value::U8).collect()),
+                Value::U8(b'!'),
+            ])
+        };
+        accepts(&d, b"ab\r\n!", &[], line(b"ab"));
+        accepts(&d, b"\r\n!?", b"?", line(b""));
+        accepts(&d, b"a\r\r\n!", &[], line(b"a\r"));
+        rejects(&d, b"ab\r\n?");
+    }
+
+    #[test]
+    fn compile_until_seq_missing_terminator() {
+        let f = until_seq(&[0xFF, 0xD9], Format::Byte(ByteSet::full()));
+        let d = Compiler::compile_one(&f).unwrap();
+        let Err(err) = d.parse(
+            &Program::new(),
+            &Scope::Empty,
+            ReadCtxt::new(&[0x00, 0xFF, 0xD8]),
+        ) else {
+            panic!("expected missing terminator to be rejected")
+        };
+        assert!(matches!(err, ParseError::Overbyte { offset: 3 }));
+    }
+
+    #[test]
+    fn flatten_singletons_options() {
+        let f = record([
+            ("x", optional(is_byte(0x00))),
+            ("y", optional(is_byte(0x01))),
+            ("z", alts([("tag", is_byte(0x02))])),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let (v, _) = d
+            .parse(&Program::new(), &Scope::Empty, ReadCtxt::new(&[0x00, 0x02]))
+            .unwrap();
+        assert_eq!(
+            v.clone().flatten_singletons(&["some", "none", "tag"]),
+            Value::record([
+                ("x", Value::Option(Some(Box::new(Value::U8(0x00))))),
+                ("y", Value::Option(None)),
+                ("z", Value::U8(0x02)),
+            ])
+        );
+        assert_eq!(
+            v.flatten_singletons(&["tag"]),
+            Value::record([
+                (
+                    "x",
+                    Value::Branch(0, Box::new(Value::variant("some", Value::U8(0x00))))
+                ),
+                (
+                    "y",
+                    Value::Branch(1, Box::new(Value::variant("none", Value::UNIT)))
+                ),
+                ("z", Value::U8(0x02)),
+            ])
+        );
+    }
+
+    #[test]
+    fn compile_alt_ambiguous_message() {
+        let f = alts([
+            ("a", tuple([is_byte(0x00), is_byte(0x01)])),
+            ("b", tuple([is_byte(0x00), is_byte(0x01)])),
+            ("c", is_byte(0x02)),
+        ]);
+        let Err(err) = Compiler::compile_one(&f) else {
+            panic!("expected ambiguous union to be rejected")
+        };
+        assert_eq!(
+            err.to_string(),
+            "cannot build match tree: branches 'a' and 'b' are ambiguous after prefix [0x00, 0x01]"
+        );
+
+        let f = alts([
+            ("a", tuple([is_bytes(&[0x00; 4]), is_byte(0x01)])),
+            ("b", tuple([is_bytes(&[0x00; 4]), is_byte(0x02)])),
+        ]);
+        let Err(err) = Decoder::compile_with_depth(&f, 2) else {
+            panic!("expected lookahead limit to be reached")
+        };
+        assert_eq!(
+            err.to_string(),
+            "cannot build match tree: branches 'a' and 'b' are ambiguous after prefix [0x00, 0x00] (lookahead limit reached)"
+        );
+    }
+
+    #[test]
+    fn compile_repeat_sep() {
+        let digit = byte_in(b'0'..=b'9');
+        let f = repeat_sep(digit, is_byte(b','));
+        let d = Compiler::compile_one(&f).unwrap();
+        let digits = |bytes: &[u8]| Value::Seq(bytes.iter().copied().map(Value::U8).collect());
+        accepts(&d, b"1,2,3", &[], digits(b"123"));
+        accepts(&d, b"7", &[], digits(b"7"));
+        accepts(&d, b"12", b"2", digits(b"1"));
+        rejects(&d, b"");
+        rejects(&d, b",1");
+        rejects(&d, b"1,2,");
+
+        let f = repeat_sep(Format::EMPTY, is_byte(b','));
+        assert!(Compiler::compile_one(&f).is_err());
+    }
+
+    #[test]
+    fn compile_repeat_alt_repeat1_slow() {
+        let f = repeat(alts([
+            ("a", repeat1(is_byte(0x00))),
+            ("b", is_byte(0x01)),
+            ("c", is_byte(0x02)),
+        ]));
+        assert!(Compiler::compile_one(&f).is_err());
+    }
+
+    #[test]
+    fn compile_repeat() {
+        let f = repeat(is_byte(0x00));
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(&d, &[], &[], Value::Seq(vec![]));
+        accepts(&d, &[0xFF], &[0xFF], Value::Seq(vec![]));
+        accepts(&d, &[0x00], &[], Value::Seq(vec![Value::U8(0x00)]));
+        accepts(
+            &d,
+            &[0x00, 0x00],
+            &[],
+            Value::Seq(vec![Value::U8(0x00), Value::U8(0x00)]),
+        );
+    }
+
+    #[test]
+    fn compile_repeat_repeat() {
+        let f = repeat(repeat(is_byte(0x00)));
+        assert!(Compiler::compile_one(&f).is_err());
+    }
+
+    #[test]
+    fn compile_cat_repeat() {
+        let f = Format::Tuple(vec![repeat(is_byte(0x00)), repeat(is_byte(0xFF))]);
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[],
+            &[],
+            Value::Tuple(vec![Value::Seq(vec![]), Value::Seq(vec![])]),
+        );
+        accepts(
+            &d,
+            &[0x00],
+            &[],
+            Value::Tuple(vec![Value::Seq(vec![Value::U8(0x00)]), Value::Seq(vec![])]),
+        );
+        accepts(
+            &d,
+            &[0xFF],
+            &[],
+            Value::Tuple(vec![Value::Seq(vec![]), Value::Seq(vec![Value::U8(0xFF)])]),
+        );
+        accepts(
+            &d,
+            &[0x00, 0xFF],
+            &[],
+            Value::Tuple(vec![
+                Value::Seq(vec![Value::U8(0x00)]),
+                Value::Seq(vec![Value::U8(0xFF)]),
+            ]),
+        );
+        accepts(
+            &d,
+            &[0x00, 0xFF, 0x00],
+            &[0x00],
+            Value::Tuple(vec![
+                Value::Seq(vec![Value::U8(0x00)]),
+                Value::Seq(vec![Value::U8(0xFF)]),
+            ]),
+        );
+        accepts(
             &d,
             &[0x7F],
             &[0x7F],
@@ -1954,63 +4555,2081 @@ mod tests {
     }
 
     #[test]
-    fn compile_repeat1() {
-        let f = repeat1(is_byte(0x00));
+    fn compile_cond_field() {
+        let f = record([
+            ("version", Format::Byte(ByteSet::full())),
+            ("value", is_byte(0x00)),
+            (
+                "extra",
+                cond_field(expr_gte(var("version"), Expr::U8(1)), is_byte(0xFF)),
+            ),
+        ]);
         let d = Compiler::compile_one(&f).unwrap();
-        rejects(&d, &[]);
-        rejects(&d, &[0xFF]);
-        accepts(&d, &[0x00], &[], Value::Seq(vec![Value::U8(0x00)]));
         accepts(
             &d,
-            &[0x00, 0xFF],
+            &[0x00, 0x00, 0xFF],
             &[0xFF],
-            Value::Seq(vec![Value::U8(0x00)]),
+            Value::record([
+                ("version", Value::U8(0)),
+                ("value", Value::U8(0)),
+                (
+                    "extra",
+                    Value::Branch(1, Box::new(Value::variant("none", Value::UNIT))),
+                ),
+            ]),
         );
         accepts(
             &d,
-            &[0x00, 0x00],
+            &[0x01, 0x00, 0xFF],
             &[],
-            Value::Seq(vec![Value::U8(0x00), Value::U8(0x00)]),
+            Value::record([
+                ("version", Value::U8(1)),
+                ("value", Value::U8(0)),
+                (
+                    "extra",
+                    Value::Branch(0, Box::new(Value::variant("some", Value::U8(0xFF)))),
+                ),
+            ]),
         );
+        rejects(&d, &[0x02, 0x00]);
     }
 
     #[test]
-    fn compile_align1() {
-        let f = Format::Tuple(vec![is_byte(0x00), Format::Align(1), is_byte(0xFF)]);
-        let d = Compiler::compile_one(&f).unwrap();
-        accepts(
-            &d,
-            &[0x00, 0xFF],
-            &[],
-            Value::Tuple(vec![Value::U8(0x00), Value::UNIT, Value::U8(0xFF)]),
+    fn value_condense_stream() {
+        let nested = Value::Seq(vec![
+            Value::Seq(vec![Value::U8(1)]),
+            Value::U8(2),
+            Value::UNIT,
+        ]);
+        let flat = Value::Seq(vec![Value::U8(1), Value::U8(2)]);
+        assert_eq!(nested.condense_stream(), flat);
+        assert!(nested.stream_eq(&flat));
+        assert!(!nested.stream_eq(&Value::Seq(vec![Value::U8(2), Value::U8(1)])));
+
+        let deep = Value::Seq(vec![
+            Value::Seq(vec![Value::Seq(vec![]), Value::Seq(vec![Value::UNIT])]),
+            Value::Tuple(vec![Value::Seq(vec![Value::U8(3)])]),
+        ]);
+        assert_eq!(
+            deep.condense_stream(),
+            Value::Seq(vec![Value::Tuple(vec![Value::Seq(vec![Value::U8(3)])])])
         );
+        assert_eq!(Value::U8(4).condense_stream(), Value::U8(4));
     }
 
     #[test]
-    fn compile_align2() {
-        let f = Format::Tuple(vec![is_byte(0x00), Format::Align(2), is_byte(0xFF)]);
+    fn value_heap_size() {
+        let elem = std::mem::size_of::<Value>();
+        let seq = Value::Seq(vec![Value::U8(0); 1000]);
+        assert_eq!(seq.heap_size(), 1000 * elem);
+        assert_eq!(Value::U8(0).heap_size(), 0);
+        assert_eq!(Value::UNIT.heap_size(), 0);
+
+        let nested = Value::variant("some", seq.clone());
+        assert_eq!(nested.heap_size(), elem + seq.heap_size());
+        let mapped = Value::Mapped(Box::new(Value::U8(0)), Box::new(nested.clone()));
+        assert_eq!(mapped.heap_size(), 2 * elem + nested.heap_size());
+    }
+
+    #[test]
+    fn compile_tlv_stream() {
+        let any_byte = Format::Byte(ByteSet::full());
+        let f = tlv_stream(
+            any_byte.clone(),
+            any_byte.clone(),
+            [
+                (Pattern::U8(1), "name", repeat(any_byte.clone())),
+                (Pattern::U8(2), "flag", any_byte.clone()),
+            ],
+            repeat(any_byte),
+        );
         let d = Compiler::compile_one(&f).unwrap();
-        rejects(&d, &[0x00, 0xFF]);
-        rejects(&d, &[0x00, 0x99, 0x99, 0xFF]);
+        let entry = |tag: u8, length: u8, value: Value| {
+            Value::record([
+                ("tag", Value::U8(tag)),
+                ("length", Value::U8(length)),
+                ("value", value),
+            ])
+        };
         accepts(
             &d,
-            &[0x00, 0x99, 0xFF],
+            &[0x01, 0x02, b'h', b'i', 0x02, 0x01, 0xFF, 0x07, 0x00],
             &[],
-            Value::Tuple(vec![Value::U8(0x00), Value::UNIT, Value::U8(0xFF)]),
+            Value::Seq(vec![
+                entry(
+                    1,
+                    2,
+                    Value::Branch(
+                        0,
+                        Box::new(Value::variant(
+                            "name",
+                            Value::Seq(vec![Value::U8(b'h'), Value::U8(b'i')]),
+                        )),
+                    ),
+                ),
+                entry(
+                    2,
+                    1,
+                    Value::Branch(1, Box::new(Value::variant("flag", Value::U8(0xFF)))),
+                ),
+                entry(
+                    7,
+                    0,
+                    Value::Branch(2, Box::new(Value::variant("unknown", Value::Seq(vec![])))),
+                ),
+            ]),
         );
+        // value is shorter than its declared length
+        rejects(&d, &[0x02, 0x02, 0xFF]);
     }
 
     #[test]
-    fn compile_peek_not() {
+    fn compile_absolute_and_slice_pos() {
         let any_byte = Format::Byte(ByteSet::full());
-        let a = Format::Tuple(vec![is_byte(0xFF), is_byte(0xFF)]);
-        let peek_not = Format::PeekNot(Box::new(a));
-        let f = Format::Tuple(vec![peek_not, any_byte.clone(), any_byte.clone()]);
-        let d = Compiler::compile_one(&f).unwrap();
-        rejects(&d, &[]);
-        rejects(&d, &[0xFF]);
-        rejects(&d, &[0xFF, 0xFF]);
-        accepts(
+        let f = record([
+            ("header", any_byte.clone()),
+            (
+                "table",
+                Format::Slice(
+                    Expr::U8(3),
+                    Box::new(record([
+                        ("tag", any_byte),
+                        ("abs", Format::AbsolutePos),
+                        ("rel", Format::SlicePos),
+                    ])),
+                ),
+            ),
+            ("abs", Format::AbsolutePos),
+            ("rel", Format::SlicePos),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[0x00, 0x01, 0x02, 0x03],
+            &[],
+            Value::record([
+                ("header", Value::U8(0x00)),
+                (
+                    "table",
+                    Value::record([
+                        ("tag", Value::U8(0x01)),
+                        ("abs", Value::U32(2)),
+                        ("rel", Value::U32(1)),
+                    ]),
+                ),
+                ("abs", Value::U32(4)),
+                ("rel", Value::U32(4)),
+            ]),
+        );
+    }
+
+    #[test]
+    fn compile_with_trailer() {
+        let padding = repeat(is_byte(0x00));
+        let checksum = Format::Byte(ByteSet::full());
+        let table = with_trailer(
+            repeat_count(Expr::U8(2), Format::Byte(ByteSet::full())),
+            record([("padding", padding), ("checksum", checksum)]),
+        );
+        let f = tuple([Format::Slice(Expr::U8(6), Box::new(table)), is_byte(0xFF)]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let (val, remain) = d
+            .parse(
+                &program,
+                &Scope::Empty,
+                ReadCtxt::new(&[0x0A, 0x0B, 0x00, 0x00, 0x00, 0x15, 0xFF]),
+            )
+            .unwrap();
+        assert!(remain.remaining().is_empty());
+        let Value::Tuple(elts) = val else {
+            panic!("expected tuple, found {val:?}")
+        };
+        assert_eq!(
+            elts[0].record_proj("body"),
+            &Value::Seq(vec![Value::U8(0x0A), Value::U8(0x0B)])
+        );
+        assert_eq!(
+            elts[0].record_proj("trailer").coerce_mapped_value(),
+            &Value::record([
+                (
+                    "padding",
+                    Value::Seq(vec![Value::U8(0x00), Value::U8(0x00), Value::U8(0x00)])
+                ),
+                ("checksum", Value::U8(0x15)),
+            ])
+        );
+
+        // the trailer does not extend to the end of the slice
+        let short_trailer = with_trailer(Format::Byte(ByteSet::full()), is_byte(0x00));
+        let d =
+            Compiler::compile_one(&Format::Slice(Expr::U8(3), Box::new(short_trailer))).unwrap();
+        rejects(&d, &[0x01, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn value_path_set() {
+        let mut v = Value::record([
+            ("version", Value::U16(1)),
+            (
+                "tables",
+                Value::Seq(vec![
+                    Value::record([("tag", Value::U32(0x68656164)), ("length", Value::U32(54))]),
+                    Value::Mapped(
+                        Box::new(Value::U8(0)),
+                        Box::new(Value::record([("tag", Value::U32(0x6d617870))])),
+                    ),
+                ]),
+            ),
+        ]);
+        let path = [
+            Accessor::Field("tables".into()),
+            Accessor::Index(0),
+            Accessor::Field("length".into()),
+        ];
+        v.set(&path, Value::U32(32)).unwrap();
+        assert_eq!(v.get(&path), Some(&Value::U32(32)));
+
+        let mapped = [
+            Accessor::Field("tables".into()),
+            Accessor::Index(1),
+            Accessor::Field("tag".into()),
+        ];
+        *v.get_mut(&mapped).unwrap() = Value::U32(0);
+        assert_eq!(v.get(&mapped), Some(&Value::U32(0)));
+
+        let invalid = [
+            Accessor::Field("tables".into()),
+            Accessor::Index(2),
+            Accessor::Field("tag".into()),
+        ];
+        assert_eq!(
+            v.set(&invalid, Value::U32(0)),
+            Err(PathError {
+                position: 1,
+                accessor: Accessor::Index(2)
+            })
+        );
+        assert_eq!(v.get(&invalid), None);
+    }
+
+    fn table_directory_type() -> ValueType {
+        let table = ValueType::Record(vec![
+            ("tag".into(), ValueType::Base(BaseType::U32)),
+            ("length".into(), ValueType::Base(BaseType::U32)),
+        ]);
+        ValueType::Record(vec![
+            ("version".into(), ValueType::Base(BaseType::U16)),
+            ("tables".into(), ValueType::Seq(Box::new(table))),
+            (
+                "checksum".into(),
+                ValueType::Union(std::collections::BTreeMap::from([
+                    ("none".into(), ValueType::UNIT),
+                    ("some".into(), ValueType::Base(BaseType::U32)),
+                ])),
+            ),
+        ])
+    }
+
+    #[test]
+    fn value_type_validate_ok() {
+        let v = Value::record([
+            ("version", Value::U16(1)),
+            (
+                "tables",
+                Value::Seq(vec![
+                    Value::record([("tag", Value::U32(0x68656164)), ("length", Value::U32(54))]),
+                    Value::Mapped(
+                        Box::new(Value::U8(0)),
+                        Box::new(Value::record([
+                            ("tag", Value::U32(0x6d617870)),
+                            ("length", Value::U32(6)),
+                        ])),
+                    ),
+                ]),
+            ),
+            (
+                "checksum",
+                Value::Branch(1, Box::new(Value::variant("none", Value::UNIT))),
+            ),
+        ]);
+        assert_eq!(table_directory_type().validate(&v), Ok(()));
+    }
+
+    #[test]
+    fn value_type_validate_mismatch() {
+        let v = Value::record([
+            ("version", Value::U16(1)),
+            (
+                "tables",
+                Value::Seq(vec![Value::record([
+                    ("tag", Value::U32(0x68656164)),
+                    ("length", Value::U16(54)),
+                ])]),
+            ),
+            ("checksum", Value::variant("some", Value::U32(0))),
+        ]);
+        assert_eq!(
+            table_directory_type().validate(&v),
+            Err(SchemaError {
+                path: vec![
+                    Accessor::Field("tables".into()),
+                    Accessor::Index(0),
+                    Accessor::Field("length".into()),
+                ],
+                kind: SchemaErrorKind::Mismatch {
+                    expected: ValueType::Base(BaseType::U32),
+                    found: Value::U16(54),
+                },
+            })
+        );
+
+        let v = Value::record([
+            ("version", Value::U16(1)),
+            ("tables", Value::Seq(vec![])),
+            ("checksum", Value::variant("crc", Value::U32(0))),
+        ]);
+        assert_eq!(
+            table_directory_type().validate(&v),
+            Err(SchemaError {
+                path: vec![Accessor::Field("checksum".into())],
+                kind: SchemaErrorKind::UnknownVariant("crc".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn compile_bit_flags() {
+        let u16be = map(
+            tuple([Format::Byte(ByteSet::full()), Format::Byte(ByteSet::full())]),
+            lambda("x", Expr::U16Be(Box::new(var("x")))),
+        );
+        let f = bit_flags(
+            BaseType::U16,
+            u16be,
+            [("is_last", 0x1, 15), ("offset", 0x7FFF, 0)],
+        );
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let parse = |input: &[u8]| {
+            let (val, _) = d
+                .parse(&program, &Scope::Empty, ReadCtxt::new(input))
+                .unwrap();
+            val.coerce_mapped_value().clone()
+        };
+        assert_eq!(
+            parse(&[0x81, 0x02]),
+            Value::record([
+                ("is_last", Value::Bool(true)),
+                ("offset", Value::U16(0x0102))
+            ])
+        );
+        assert_eq!(
+            parse(&[0x7F, 0xFF]),
+            Value::record([
+                ("is_last", Value::Bool(false)),
+                ("offset", Value::U16(0x7FFF))
+            ])
+        );
+    }
+
+    #[test]
+    fn explain_union_branches() {
+        let f = Format::Union(vec![
+            tuple([is_byte(0x01), Format::Byte(ByteSet::full())]),
+            tuple([is_byte(0x02), Format::Byte(ByteSet::full())]),
+        ]);
+        let Decoder::Branch(tree, _) = Compiler::compile_one(&f).unwrap() else {
+            panic!("expected branch decoder");
+        };
+        assert_eq!(
+            tree.explain(),
+            vec![
+                BranchExplanation {
+                    index: 0,
+                    prefixes: vec![vec![ByteSet::from([0x01])]],
+                },
+                BranchExplanation {
+                    index: 1,
+                    prefixes: vec![vec![ByteSet::from([0x02])]],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn eval_arith() {
+        let eval = |e: Expr| e.eval_value(&Scope::Empty);
+        assert_eq!(eval(div(Expr::U8(200), Expr::U8(7))), Value::U8(28));
+        assert_eq!(eval(div(Expr::U16(1000), Expr::U16(10))), Value::U16(100));
+        assert_eq!(eval(div(Expr::U32(7), Expr::U32(8))), Value::U32(0));
+        assert_eq!(eval(mul(Expr::U16(300), Expr::U16(200))), Value::U16(60000));
+        assert_eq!(eval(add(Expr::U32(1), Expr::U32(2))), Value::U32(3));
+        assert_eq!(
+            eval(bit_xor(Expr::U8(0b1100), Expr::U8(0b1010))),
+            Value::U8(0b0110)
+        );
+        assert_eq!(eval(shr(Expr::U32(0x100), Expr::U32(4))), Value::U32(0x10));
+    }
+
+    #[test]
+    fn eval_signed_shift() {
+        let eval = |e: Expr| e.eval_value(&Scope::Empty);
+        assert_eq!(eval(shl(Expr::I16(-3), Expr::I16(2))), Value::I16(-12));
+        assert_eq!(eval(shl(Expr::I8(0x10), Expr::I8(3))), Value::I8(-128));
+        // right-shifts of signed values are arithmetic, preserving the sign
+        assert_eq!(eval(shr(Expr::I8(-128), Expr::I8(7))), Value::I8(-1));
+        assert_eq!(eval(shr(Expr::I32(-16), Expr::I32(2))), Value::I32(-4));
+        assert_eq!(eval(shr(Expr::I64(64), Expr::I64(3))), Value::I64(8));
+
+        let eval_in = |mode: ArithMode, e: Expr| e.try_eval_value(&Scope::Empty, mode).ok();
+        assert_eq!(
+            eval_in(ArithMode::Saturating, shr(Expr::I16(-5), Expr::I16(16))),
+            Some(Value::I16(-1))
+        );
+        assert_eq!(
+            eval_in(ArithMode::Saturating, shr(Expr::I16(5), Expr::I16(16))),
+            Some(Value::I16(0))
+        );
+        assert_eq!(
+            eval_in(ArithMode::Wrapping, shl(Expr::I32(1), Expr::I32(33))),
+            Some(Value::I32(2))
+        );
+        assert_eq!(
+            eval_in(ArithMode::Wrapping, shr(Expr::I8(1), Expr::I8(-1))),
+            None
+        );
+    }
+
+    #[test]
+    fn eval_int_rel() {
+        let eval = |e: Expr| e.eval_value(&Scope::Empty);
+        assert_eq!(eval(expr_lt(Expr::U8(1), Expr::U8(2))), Value::Bool(true));
+        assert_eq!(
+            eval(expr_lte(Expr::U16(2), Expr::U16(2))),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval(expr_gt(Expr::U32(1), Expr::U32(2))),
+            Value::Bool(false)
+        );
+        assert_eq!(
+            eval(expr_gte(Expr::U64(3), Expr::U64(2))),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval(expr_lt(Expr::I16(-1), Expr::I16(0))),
+            Value::Bool(true)
+        );
+        assert_eq!(
+            eval(expr_gte(Expr::I8(-5), Expr::I8(-4))),
+            Value::Bool(false)
+        );
+    }
+
+    #[test]
+    fn eval_as_signed() {
+        let eval = |e: Expr| e.eval_value(&Scope::Empty);
+        assert_eq!(eval(as_signed8(Expr::U8(0x80))), Value::I8(-128));
+        assert_eq!(eval(as_signed8(Expr::U8(0x7F))), Value::I8(127));
+        assert_eq!(eval(as_signed16(Expr::U16(0xFFFF))), Value::I16(-1));
+        assert_eq!(eval(as_signed16(Expr::U16(0x1234))), Value::I16(0x1234));
+        assert_eq!(
+            eval(as_signed32(Expr::U32(0x8000_0000))),
+            Value::I32(i32::MIN)
+        );
+        // sign-extension is preserved when widening the reinterpreted value
+        assert_eq!(
+            eval(as_signed16(Expr::U16(0xFF80))),
+            Value::I16(i16::from(-128i8))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "mismatched operands")]
+    fn eval_int_rel_mismatched() {
+        expr_gte(Expr::U8(1), Expr::U16(1)).eval_value(&Scope::Empty);
+    }
+
+    #[test]
+    fn parse_arith_mode() {
+        // the body is preceded by a 4-byte header that is counted by the length field
+        let any_byte = Format::Byte(ByteSet::full());
+        let f = record([
+            ("len", any_byte.clone()),
+            (
+                "body",
+                repeat_count(sub(var("len"), Expr::U8(4)), any_byte.clone()),
+            ),
+        ]);
+        let module = FormatModule::new();
+        let mut program = Compiler::compile_program(&module, &f).unwrap();
+        let malformed = [0x02, 0xAA];
+
+        assert!(matches!(
+            program.run(ReadCtxt::new(&malformed)),
+            Err(ParseError::Overflow { offset: 1 })
+        ));
+
+        program.set_arith_mode(ArithMode::Saturating);
+        let (v, remain) = program.run(ReadCtxt::new(&malformed)).unwrap();
+        assert_eq!(
+            v,
+            Value::record([("len", Value::U8(0x02)), ("body", Value::Seq(vec![]))])
+        );
+        assert_eq!(remain.remaining(), &[0xAA]);
+
+        // wraps around to a count of 254, which the input is too short for
+        program.set_arith_mode(ArithMode::Wrapping);
+        assert!(program.run(ReadCtxt::new(&malformed)).is_err());
+
+        // the mode applies equally to slice lengths, in both interpreters
+        let f = record([
+            ("len", any_byte.clone()),
+            (
+                "body",
+                Format::Slice(sub(var("len"), Expr::U8(4)), Box::new(repeat(any_byte))),
+            ),
+        ]);
+        let mut program = Compiler::compile_program(&module, &f).unwrap();
+        assert!(matches!(
+            program.run(ReadCtxt::new(&malformed)),
+            Err(ParseError::Overflow { offset: 1 })
+        ));
+        assert!(matches!(
+            program.run_with_loc(ReadCtxt::new(&malformed)),
+            Err(ParseError::Overflow { offset: 1 })
+        ));
+
+        program.set_arith_mode(ArithMode::Saturating);
+        let (v, remain) = program.run(ReadCtxt::new(&malformed)).unwrap();
+        assert_eq!(
+            v,
+            Value::record([("len", Value::U8(0x02)), ("body", Value::Seq(vec![]))])
+        );
+        assert_eq!(remain.remaining(), &[0xAA]);
+        let (_, remain) = program.run_with_loc(ReadCtxt::new(&malformed)).unwrap();
+        assert_eq!(remain.remaining(), &[0xAA]);
+
+        // division by zero is an error in every mode
+        let f = record([
+            ("n", Format::Byte(ByteSet::full())),
+            ("q", Format::Compute(div(Expr::U8(1), var("n")))),
+        ]);
+        let mut program = Compiler::compile_program(&module, &f).unwrap();
+        program.set_arith_mode(ArithMode::Saturating);
+        assert!(matches!(
+            program.run(ReadCtxt::new(&[0x00])),
+            Err(ParseError::Overflow { offset: 1 })
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn eval_div_by_zero() {
+        div(Expr::U16(1), Expr::U16(0)).eval_value(&Scope::Empty);
+    }
+
+    #[test]
+    fn compile_signed_int16() {
+        let s16be = map(
+            tuple([Format::Byte(ByteSet::full()), Format::Byte(ByteSet::full())]),
+            lambda("x", Expr::I16Be(Box::new(var("x")))),
+        );
+        let f = record([
+            ("delta", s16be),
+            ("glyph", Format::Compute(add(var("delta"), Expr::I16(100)))),
+            (
+                "is_minus_one",
+                Format::Compute(expr_match(
+                    var("delta"),
+                    [
+                        (Pattern::I16(-1), Expr::Bool(true)),
+                        (Pattern::Wildcard, Expr::Bool(false)),
+                    ],
+                )),
+            ),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let parse = |input: &[u8]| {
+            let (val, _) = d
+                .parse(&program, &Scope::Empty, ReadCtxt::new(input))
+                .unwrap();
+            ["delta", "glyph", "is_minus_one"].map(|name| {
+                val.get(&[Accessor::Field(name.into())])
+                    .unwrap()
+                    .coerce_mapped_value()
+                    .clone()
+            })
+        };
+        assert_eq!(
+            parse(&[0xFF, 0xFF]),
+            [Value::I16(-1), Value::I16(99), Value::Bool(true)]
+        );
+        assert_eq!(
+            parse(&[0xFF, 0x38]),
+            [Value::I16(-200), Value::I16(-100), Value::Bool(false)]
+        );
+        assert_eq!(
+            parse(&[0x00, 0x05]),
+            [Value::I16(5), Value::I16(105), Value::Bool(false)]
+        );
+    }
+
+    #[test]
+    fn compile_indexed_array() {
+        let platforms = ["unicode", "mac", "windows"]
+            .map(|name| Expr::Variant(name.into(), Box::new(Expr::UNIT)));
+        let f = indexed_array(
+            platforms,
+            "platform",
+            repeat_count(Expr::U8(2), Format::Byte(ByteSet::full())),
+        );
+        let d = Compiler::compile_one(&f).unwrap();
+        let entry = |name: &'static str, bs: [u8; 2]| {
+            Value::Tuple(vec![
+                Value::variant(name, Value::UNIT),
+                Value::Seq(bs.into_iter().map(Value::U8).collect()),
+            ])
+        };
+        accepts(
+            &d,
+            &[0x00, 0x03, 0x01, 0x00, 0x03, 0x0A, 0xFF],
+            &[0xFF],
+            Value::Seq(vec![
+                entry("unicode", [0x00, 0x03]),
+                entry("mac", [0x01, 0x00]),
+                entry("windows", [0x03, 0x0A]),
+            ]),
+        );
+        rejects(&d, &[0x00, 0x03, 0x01, 0x00, 0x03]);
+    }
+
+    #[test]
+    fn compile_for_each_pair() {
+        let f = record([
+            (
+                "offsets",
+                repeat_count(Expr::U8(3), Format::Byte(ByteSet::full())),
+            ),
+            (
+                "regions",
+                for_each_pair(
+                    var("offsets"),
+                    ValueType::Base(BaseType::U8),
+                    ["start", "end"],
+                    repeat_count(sub(var("end"), var("start")), Format::Byte(ByteSet::full())),
+                ),
+            ),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let (val, remain) = d
+            .parse(
+                &program,
+                &Scope::Empty,
+                ReadCtxt::new(&[1, 3, 6, 0xA0, 0xA1, 0xB0, 0xB1, 0xB2, 0xFF]),
+            )
+            .unwrap();
+        assert_eq!(remain.remaining(), &[0xFF]);
+        let bytes = |bs: &[u8]| Value::Seq(bs.iter().copied().map(Value::U8).collect());
+        assert_eq!(
+            val.record_proj("regions"),
+            &Value::Seq(vec![bytes(&[0xA0, 0xA1]), bytes(&[0xB0, 0xB1, 0xB2])])
+        );
+    }
+
+    #[test]
+    fn match_tree_merges_identical_branches() {
+        let f = Format::Union(vec![union([is_byte(0x01), is_byte(0x03)]), is_byte(0x02)]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let Decoder::Branch(tree, _) = &d else {
+            panic!("expected branch decoder, found {d:?}")
+        };
+        assert_eq!(tree.branches.len(), 2);
+        assert_eq!(tree.branches[0].0, ByteSet::from([0x01, 0x03]));
+        accepts(
+            &d,
+            &[0x03],
+            &[],
+            Value::Branch(0, Box::new(Value::Branch(1, Box::new(Value::U8(0x03))))),
+        );
+        accepts(
+            &d,
+            &[0x02],
+            &[],
+            Value::Branch(1, Box::new(Value::U8(0x02))),
+        );
+    }
+
+    #[test]
+    fn compile_dispatch_by() {
+        let f = record([
+            ("flags", Format::Byte(ByteSet::full())),
+            (
+                "body",
+                dispatch_by(
+                    bit_and(var("flags"), Expr::U8(0x0F)),
+                    [(Value::U8(1), is_byte(0x11)), (Value::U8(2), is_byte(0x22))],
+                    Some(Format::Byte(ByteSet::full())),
+                ),
+            ),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let body = |flags: u8, branch: usize, byte: u8| {
+            Value::record([
+                ("flags", Value::U8(flags)),
+                ("body", Value::Branch(branch, Box::new(Value::U8(byte)))),
+            ])
+        };
+        accepts(&d, &[0xF1, 0x11], &[], body(0xF1, 0, 0x11));
+        rejects(&d, &[0xF1, 0x22]);
+        accepts(&d, &[0x02, 0x22], &[], body(0x02, 1, 0x22));
+        // unlisted keys fall back on the default format
+        accepts(&d, &[0x05, 0x99], &[], body(0x05, 2, 0x99));
+
+        let strict = dispatch_by(Expr::U8(3), [(Value::U8(1), is_byte(0x11))], None);
+        rejects(&Compiler::compile_one(&strict).unwrap(), &[0x11]);
+    }
+
+    #[test]
+    fn compile_repeat_count_element_lookahead() {
+        // the following field starts with the same byte as both element branches, so the
+        // choice of branch within each element must look ahead into the remaining repetitions
+        let elem = alts([
+            ("a", is_byte(0x00)),
+            ("ab", tuple([is_byte(0x00), is_byte(0x01)])),
+        ]);
+        let f = tuple([repeat_count(Expr::U8(2), elem.clone()), is_byte(0x00)]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let a = || Value::Branch(0, Box::new(Value::variant("a", Value::U8(0x00))));
+        let ab = || {
+            let bytes = Value::Tuple(vec![Value::U8(0x00), Value::U8(0x01)]);
+            Value::Branch(1, Box::new(Value::variant("ab", bytes)))
+        };
+        let expect = |elems: Vec<Value>| Value::Tuple(vec![Value::Seq(elems), Value::U8(0x00)]);
+        accepts(&d, &[0x00, 0x00, 0x01, 0x00], &[], expect(vec![a(), ab()]));
+        accepts(&d, &[0x00, 0x01, 0x00, 0x00], &[], expect(vec![ab(), a()]));
+        accepts(&d, &[0x00, 0x00, 0x00], &[], expect(vec![a(), a()]));
+        rejects(&d, &[0x00, 0x00, 0x01]);
+
+        // the final element is followed only by what follows the repetition
+        let f = tuple([repeat_count(Expr::U8(2), elem.clone()), is_byte(0x01)]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let expect = |elems: Vec<Value>| Value::Tuple(vec![Value::Seq(elems), Value::U8(0x01)]);
+        accepts(&d, &[0x00, 0x00, 0x01], &[], expect(vec![a(), a()]));
+        let f = tuple([repeat_count(Expr::U8(3), elem.clone()), is_byte(0x01)]);
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[0x00, 0x00, 0x00, 0x01],
+            &[],
+            expect(vec![a(), a(), a()]),
+        );
+
+        // the same holds when the repetition is a named format, called from where it is followed
+        let mut module = FormatModule::new();
+        let elems = module.define_format("test.elems", repeat_count(Expr::U8(2), elem.clone()));
+        let f = tuple([elems.call(), is_byte(0x01)]);
+        let program = Compiler::compile_program(&module, &f).unwrap();
+        let (val, remain) = program.run(ReadCtxt::new(&[0x00, 0x00, 0x01])).unwrap();
+        assert_value_eq(&val, &expect(vec![a(), a()]));
+        assert!(remain.remaining().is_empty());
+
+        // with a count only known at parse time, the final element is still told apart
+        let f = record([
+            ("n", Format::Byte(ByteSet::full())),
+            ("elems", repeat_count(var("n"), elem)),
+            ("last", is_byte(0x02)),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[0x02, 0x00, 0x00, 0x01, 0x02],
+            &[],
+            Value::record([
+                ("n", Value::U8(2)),
+                ("elems", Value::Seq(vec![a(), ab()])),
+                ("last", Value::U8(0x02)),
+            ]),
+        );
+    }
+
+    #[test]
+    fn compile_detect_endian() {
+        let u16_from = |big: bool| {
+            let bytes = tuple([Format::Byte(ByteSet::full()), Format::Byte(ByteSet::full())]);
+            let x = Box::new(var("x"));
+            map(
+                bytes,
+                lambda("x", if big { Expr::U16Be(x) } else { Expr::U16Le(x) }),
+            )
+        };
+        let f = detect_endian(
+            u16_from(true),
+            Value::U16(0xFEFF),
+            Value::U16(0xFFFE),
+            |big| repeat(u16_from(big)),
+        );
+        let d = Compiler::compile_one(&f).unwrap();
+        // values are mapped from the pairs of bytes they were read from
+        let word = |hi: u8, lo: u8, n: u16| {
+            Value::Mapped(
+                Box::new(Value::Tuple(vec![Value::U8(hi), Value::U8(lo)])),
+                Box::new(Value::U16(n)),
+            )
+        };
+        let body = |bom: Value, branch: usize, words: Vec<Value>| {
+            Value::record([
+                ("bom", bom),
+                ("body", Value::Branch(branch, Box::new(Value::Seq(words)))),
+            ])
+        };
+        let data = [0x12, 0x34, 0x00, 0x01];
+        accepts(
+            &d,
+            &[&[0xFE, 0xFF][..], &data].concat(),
+            &[],
+            body(
+                word(0xFE, 0xFF, 0xFEFF),
+                0,
+                vec![word(0x12, 0x34, 0x1234), word(0x00, 0x01, 0x0001)],
+            ),
+        );
+        accepts(
+            &d,
+            &[&[0xFF, 0xFE][..], &data].concat(),
+            &[],
+            body(
+                word(0xFF, 0xFE, 0xFFFE),
+                1,
+                vec![word(0x12, 0x34, 0x3412), word(0x00, 0x01, 0x0100)],
+            ),
+        );
+        rejects(&d, &[&[0x00, 0x00][..], &data].concat());
+    }
+
+    #[test]
+    fn compile_odd_width_ints() {
+        let bytes = |n: usize| tuple((0..n).map(|_| Format::Byte(ByteSet::full())));
+        let f = tuple([
+            map(bytes(3), lambda("x", u24be(var("x")))),
+            map(bytes(3), lambda("x", u24le(var("x")))),
+            map(bytes(6), lambda("x", u48be(var("x")))),
+            map(bytes(6), lambda("x", u48le(var("x")))),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let input = [
+            0x01, 0x02, 0x03, // u24be
+            0x01, 0x02, 0x03, // u24le
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, // u48be
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, // u48le
+        ];
+        let (val, remain) = d
+            .parse(&program, &Scope::Empty, ReadCtxt::new(&input))
+            .unwrap();
+        assert!(remain.remaining().is_empty());
+        let Value::Tuple(elts) = val else {
+            panic!("expected tuple, found {val:?}")
+        };
+        let elts: Vec<&Value> = elts.iter().map(Value::coerce_mapped_value).collect();
+        assert_eq!(
+            elts,
+            [
+                &Value::U32(0x010203),
+                &Value::U32(0x030201),
+                &Value::U64(0x010203040506),
+                &Value::U64(0x060504030201),
+            ]
+        );
+    }
+
+    #[test]
+    fn project_path_nested() {
+        let header = record([
+            ("version", Format::Byte(ByteSet::full())),
+            (
+                "metrics",
+                record([
+                    ("count", Format::Byte(ByteSet::full())),
+                    ("scale", Format::Byte(ByteSet::full())),
+                ]),
+            ),
+        ]);
+        let f = record([
+            ("header", header),
+            (
+                "count",
+                Format::Compute(project_path(
+                    var("header"),
+                    [
+                        Accessor::Field("metrics".into()),
+                        Accessor::Field("count".into()),
+                    ],
+                )),
+            ),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let (val, _) = d
+            .parse(&program, &Scope::Empty, ReadCtxt::new(&[1, 7, 2]))
+            .unwrap();
+        assert_eq!(val.record_proj("count"), &Value::U8(7));
+    }
+
+    #[test]
+    #[should_panic(expected = "scale not found in record")]
+    fn project_path_missing_field() {
+        let f = record([
+            ("header", record([("count", Format::Byte(ByteSet::full()))])),
+            (
+                "scale",
+                Format::Compute(project_path(
+                    var("header"),
+                    [Accessor::Field("scale".into())],
+                )),
+            ),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let _ = d.parse(&program, &Scope::Empty, ReadCtxt::new(&[1]));
+    }
+
+    #[test]
+    fn minimize_failure_prefix() {
+        let f = tuple([
+            is_byte(0x7F),
+            repeat_count(Expr::U8(3), Format::Byte(ByteSet::full())),
+            is_byte(0x00),
+            repeat(Format::Byte(ByteSet::full())),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let mut input = vec![0x7F, 0x01, 0x02, 0x03, 0xFF];
+        input.resize(1000, 0x00);
+        rejects(&d, &input);
+        assert_eq!(
+            d.minimize_failure(&program, &input),
+            vec![0x7F, 0x01, 0x02, 0x03, 0xFF]
+        );
+
+        // inputs that parse are left alone
+        input[4] = 0x00;
+        assert_eq!(d.minimize_failure(&program, &input), input);
+    }
+
+    #[test]
+    fn compile_cstring() {
+        let d = Compiler::compile_one(&cstring()).unwrap();
+        let program = Program::new();
+        let parse = |input: &'static [u8]| {
+            d.parse(&program, &Scope::Empty, ReadCtxt::new(input))
+                .map(|(val, remain)| (val.coerce_mapped_value().clone(), remain.remaining()))
+        };
+
+        let (val, remain) = parse(&[0x00, 0x41]).unwrap();
+        assert_eq!(val, Value::Seq(vec![]));
+        assert_eq!(remain, &[0x41]);
+
+        let (val, remain) = parse(b"hi\0rest").unwrap();
+        assert_eq!(val, Value::Seq(vec![Value::U8(b'h'), Value::U8(b'i')]));
+        assert_eq!(remain, b"rest");
+
+        assert!(parse(b"unterminated").is_err());
+    }
+
+    #[test]
+    fn compile_repeat_dedup() {
+        let entry = record([
+            ("id", Format::Byte(ByteSet::full())),
+            ("value", Format::Byte(ByteSet::full())),
+        ]);
+        let f = repeat_dedup(
+            lambda("e", record_proj(var("e"), "id")),
+            BaseType::U8,
+            entry,
+        );
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let (val, remain) = d
+            .parse(
+                &program,
+                &Scope::Empty,
+                ReadCtxt::new(&[1, 10, 2, 20, 1, 30, 3, 40, 4, 50]),
+            )
+            .unwrap();
+        assert!(remain.remaining().is_empty());
+        let entry =
+            |id: u8, value: u8| Value::record([("id", Value::U8(id)), ("value", Value::U8(value))]);
+        assert_eq!(
+            val.coerce_mapped_value(),
+            &Value::Seq(vec![entry(1, 10), entry(2, 20), entry(3, 40), entry(4, 50)])
+        );
+    }
+
+    #[test]
+    fn compile_try_map() {
+        // accepts only the discriminants 1 and 2, mapping them to 'A' and 'B'
+        let f = try_map(
+            Format::Byte(ByteSet::full()),
+            lambda(
+                "b",
+                expr_match(
+                    var("b"),
+                    [
+                        (
+                            Pattern::U8(1),
+                            variant("some", Expr::AsChar(Box::new(Expr::U32(0x41)))),
+                        ),
+                        (
+                            Pattern::U8(2),
+                            variant("some", Expr::AsChar(Box::new(Expr::U32(0x42)))),
+                        ),
+                        (Pattern::Wildcard, variant("none", Expr::UNIT)),
+                    ],
+                ),
+            ),
+        );
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        for (input, expected, tail) in [
+            (&[0x01, 0xFF][..], 'A', &[0xFF][..]),
+            (&[0x02][..], 'B', &[][..]),
+        ] {
+            let (val, remain) = d
+                .parse(&program, &Scope::Empty, ReadCtxt::new(input))
+                .unwrap();
+            assert_eq!(val.coerce_mapped_value(), &Value::Char(expected));
+            assert_eq!(remain.remaining(), tail);
+        }
+        rejects(&d, &[0x00]);
+        rejects(&d, &[0x03]);
+    }
+
+    #[test]
+    fn compile_fixed_point() {
+        let f = Format::Compute(fixed16_16(Expr::U32(0x00010000)));
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[],
+            &[],
+            Value::Tuple(vec![Value::U16(1), Value::U16(0)]),
+        );
+        let f = Format::Compute(f2dot14(Expr::U16(0x4000)));
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[],
+            &[],
+            Value::Tuple(vec![Value::U16(1), Value::U16(0)]),
+        );
+        let f = Format::Compute(f2dot14(Expr::U16(0x7000)));
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[],
+            &[],
+            Value::Tuple(vec![Value::U16(1), Value::U16(0x3000)]),
+        );
+    }
+
+    #[test]
+    fn compile_format_args() {
+        let mut module = FormatModule::new();
+        let zeroes = module.define_format_args(
+            "zeroes",
+            vec![(Label::Borrowed("n"), ValueType::Base(BaseType::U8))],
+            repeat_count(var("n"), is_byte(0x00)),
+        );
+        let f = Format::Tuple(vec![
+            zeroes.call_args(vec![Expr::U8(1)]),
+            zeroes.call_args(vec![Expr::U8(2)]),
+        ]);
+        let program = Compiler::compile_program(&module, &f).unwrap();
+        let (val, remain) = program
+            .run(ReadCtxt::new(&[0x00, 0x00, 0x00, 0xFF]))
+            .unwrap();
+        assert_eq!(
+            val,
+            Value::Tuple(vec![
+                Value::Seq(vec![Value::U8(0x00)]),
+                Value::Seq(vec![Value::U8(0x00), Value::U8(0x00)]),
+            ])
+        );
+        assert_eq!(remain.remaining(), &[0xFF]);
+        assert!(program.run(ReadCtxt::new(&[0x00, 0x00])).is_err());
+    }
+
+    #[test]
+    fn parse_with_budget_counts_rereads() {
+        let table = || {
+            Format::WithRelativeOffset(
+                Expr::U32(2),
+                Box::new(repeat_count(Expr::U8(4), Format::Byte(ByteSet::full()))),
+            )
+        };
+        let f = record([
+            ("header", tuple([is_byte(0x01), is_byte(0x02)])),
+            ("first", table()),
+            ("second", table()),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let input = [0x01, 0x02, 0x00, 0x00, 0xAA, 0xBB, 0xCC, 0xDD];
+        let parse =
+            |budget| d.parse_with_budget(&program, &Scope::Empty, ReadCtxt::new(&input), budget);
+
+        assert!(parse(None).is_ok());
+        assert!(parse(Some(10)).is_ok());
+        // the header and the first link fit within the budget, but re-reading the same bytes does not
+        let Err(err) = parse(Some(8)) else {
+            panic!("expected budget to be exceeded")
+        };
+        assert!(matches!(
+            err,
+            ParseError::ByteBudgetExceeded {
+                budget: 8,
+                offset: 6
+            }
+        ));
+        // the budget does not outlive the parse
+        assert!(d
+            .parse(&program, &Scope::Empty, ReadCtxt::new(&input))
+            .is_ok());
+    }
+
+    #[test]
+    fn parse_with_budget_counts_bits_as_bytes() {
+        let bit = Format::Byte(ByteSet::from(0..=1));
+        let f = tuple([
+            Format::Bits(Box::new(repeat_count(Expr::U8(8), bit))),
+            is_byte(0xFF),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let input = [0x5A, 0xFF];
+        let parse =
+            |budget| d.parse_with_budget(&program, &Scope::Empty, ReadCtxt::new(&input), budget);
+
+        assert!(parse(Some(2)).is_ok());
+        let Err(err) = parse(Some(1)) else {
+            panic!("expected budget to be exceeded")
+        };
+        assert!(matches!(
+            err,
+            ParseError::ByteBudgetExceeded {
+                budget: 1,
+                offset: 1
+            }
+        ));
+        let Err(err) = parse(Some(0)) else {
+            panic!("expected budget to be exceeded")
+        };
+        assert!(matches!(
+            err,
+            ParseError::ByteBudgetExceeded {
+                budget: 0,
+                offset: 0
+            }
+        ));
+    }
+
+    #[test]
+    fn parse_traced_stats() {
+        let mut module = FormatModule::new();
+        let short = module.define_format("test.short", is_byte(0x00));
+        let long = module.define_format("test.long", tuple([is_byte(0x01), byte_in(0x02..=0x03)]));
+        let item = module.define_format(
+            "test.item",
+            alts([("short", short.call()), ("long", long.call())]),
+        );
+        let f = repeat(item.call());
+        let program = Compiler::compile_program(&module, &f).unwrap();
+        let mut stats = ParseStats::new();
+        let input = [0x00, 0x01, 0x02, 0x00, 0x01, 0x03, 0x01, 0x02];
+        let (_, remain) = program.decoders[0]
+            .0
+            .parse_traced(&program, &Scope::Empty, ReadCtxt::new(&input), &mut stats)
+            .unwrap();
+        assert!(remain.remaining().is_empty());
+        assert_eq!(
+            stats.format("test.item"),
+            FormatStats { calls: 5, bytes: 8 }
+        );
+        assert_eq!(
+            stats.format("test.short"),
+            FormatStats { calls: 2, bytes: 2 }
+        );
+        assert_eq!(
+            stats.format("test.long"),
+            FormatStats { calls: 3, bytes: 6 }
+        );
+        assert_eq!(stats.branch_hits(Some("test.item"), 0), 2);
+        assert_eq!(stats.branch_hits(Some("test.item"), 1), 3);
+        assert_eq!(stats.branch_hits(None, 0), 0);
+        assert!(stats
+            .to_string()
+            .starts_with("formats:\n  test.item: 8 bytes in 5 calls\n"));
+    }
+
+    #[test]
+    fn run_with_context_names_failing_format() {
+        let mut module = FormatModule::new();
+        let any_byte = Format::Byte(ByteSet::full());
+        let u32be = module.define_format("test.u32be", repeat_count(Expr::U8(4), any_byte));
+        let header = module.define_format(
+            "test.header",
+            record([("version", u32be.call()), ("length", u32be.call())]),
+        );
+        let program = Compiler::compile_program(&module, &header.call()).unwrap();
+        let input = [0x00, 0x01, 0x00, 0x00, 0x00, 0x10];
+        let Err(err) = program.run_with_context(ReadCtxt::new(&input)) else {
+            panic!("truncated header should not parse");
+        };
+        assert!(matches!(err.error, ParseError::Overbyte { .. }));
+        assert_eq!(err.error.offset(), 6);
+        assert_eq!(err.context, vec!["test.header", "test.u32be"]);
+        assert!(err.to_string().contains("while parsing test.u32be"));
+        assert!(program.run_with_context(ReadCtxt::new(&[0; 8])).is_ok());
+    }
+
+    #[test]
+    fn run_memoized_shares_linked_subtables() {
+        let mut module = FormatModule::new();
+        let any_byte = Format::Byte(ByteSet::full());
+        let sub = module.define_format("test.sub", tuple([any_byte.clone(), any_byte.clone()]));
+        let link = |name: &'static str| Format::WithRelativeOffset(var(name), Box::new(sub.call()));
+        let f = record([
+            ("a", any_byte.clone()),
+            ("b", any_byte),
+            ("link_a", link("a")),
+            ("link_b", link("b")),
+        ]);
+        let program = Compiler::compile_program(&module, &f).unwrap();
+        let input = [0x02, 0x02, 0x00, 0x00, 0xAA, 0xBB];
+        let (res, cache) = program.run_memoized(ReadCtxt::new(&input));
+        let (v, _) = res.unwrap();
+        let expected = Value::Tuple(vec![Value::U8(0xAA), Value::U8(0xBB)]);
+        let link = |name: &'static str| match v.get(&[Accessor::Field(name.into())]) {
+            Some(Value::Shared(sub)) => Rc::clone(sub),
+            other => panic!("expected shared value, found {other:?}"),
+        };
+        // both links resolve to the same sub-table, which is decoded only once
+        let (link_a, link_b) = (link("link_a"), link("link_b"));
+        assert!(Rc::ptr_eq(&link_a, &link_b));
+        assert_eq!(link_a.as_ref(), &expected);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.hits(), 1);
+        let shared = cache.at_offset(4).collect::<Vec<_>>();
+        assert_eq!(shared.len(), 1);
+        assert!(Rc::ptr_eq(shared[0], &link_a));
+
+        // memoization is scoped to a single run
+        let (plain, _) = program.run(ReadCtxt::new(&input)).unwrap();
+        assert_eq!(
+            plain.get(&[Accessor::Field("link_a".into())]),
+            Some(&expected)
+        );
+        assert!(plain.diff(&v).is_empty());
+        let (_, cache) =
+            program.run_memoized(ReadCtxt::new(&[0x02, 0x03, 0x00, 0x00, 0xAA, 0xBB, 0xCC]));
+        assert_eq!((cache.len(), cache.hits()), (2, 0));
+    }
+
+    #[test]
+    fn compile_slice_length_overflow() {
+        let f = record([
+            ("count", Format::Byte(ByteSet::full())),
+            (
+                "entries",
+                Format::Slice(
+                    Expr::Arith(Arith::Mul, Box::new(var("count")), Box::new(Expr::U8(2))),
+                    Box::new(repeat(Format::Byte(ByteSet::full()))),
+                ),
+            ),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[0x01, 0xAA, 0xBB, 0xCC],
+            &[0xCC],
+            Value::record([
+                ("count", Value::U8(1)),
+                (
+                    "entries",
+                    Value::Seq(vec![Value::U8(0xAA), Value::U8(0xBB)]),
+                ),
+            ]),
+        );
+        // 0xFF * 2 overflows u8, which is the type the length is computed in
+        let program = Program::new();
+        let res = d.parse(&program, &Scope::Empty, ReadCtxt::new(&[0xFF, 0x00]));
+        assert!(matches!(res, Err(ParseError::Overflow { .. })));
+
+        let f = Format::Slice(
+            Expr::Arith(
+                Arith::Mul,
+                Box::new(Expr::U64(u64::MAX)),
+                Box::new(Expr::U64(2)),
+            ),
+            Box::new(Format::EMPTY),
+        );
+        let d = Compiler::compile_one(&f).unwrap();
+        let res = d.parse(&program, &Scope::Empty, ReadCtxt::new(&[0x00]));
+        assert!(matches!(res, Err(ParseError::Overflow { .. })));
+
+        // nested arithmetic is checked the same way as top-level arithmetic
+        let f = record([
+            ("count", Format::Byte(ByteSet::full())),
+            (
+                "entries",
+                Format::Slice(
+                    Expr::Arith(
+                        Arith::Mul,
+                        Box::new(Expr::Arith(
+                            Arith::Sub,
+                            Box::new(var("count")),
+                            Box::new(Expr::U8(3)),
+                        )),
+                        Box::new(Expr::U8(2)),
+                    ),
+                    Box::new(repeat(Format::Byte(ByteSet::full()))),
+                ),
+            ),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let res = d.parse(&program, &Scope::Empty, ReadCtxt::new(&[0x01, 0x00]));
+        assert!(matches!(res, Err(ParseError::Overflow { .. })));
+    }
+
+    #[test]
+    fn compile_cumulative_sum() {
+        let f = Format::Compute(cumulative_sum(
+            BaseType::U16,
+            Expr::Seq(vec![Expr::U16(1), Expr::U16(2), Expr::U16(3)]),
+        ));
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[],
+            &[],
+            Value::Seq(vec![Value::U16(1), Value::U16(3), Value::U16(6)]),
+        );
+        let f = Format::Compute(deltas(
+            BaseType::U16,
+            Expr::Seq(vec![Expr::U16(1), Expr::U16(3), Expr::U16(6)]),
+        ));
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[],
+            &[],
+            Value::Seq(vec![Value::U16(1), Value::U16(2), Value::U16(3)]),
+        );
+    }
+
+    #[test]
+    fn compile_sparse_to_dense() {
+        let entry = |ix, v| Expr::Tuple(vec![Expr::U8(ix), Expr::U8(v)]);
+        let f = sparse_to_dense(
+            ValueType::Base(BaseType::U8),
+            Expr::U8(5),
+            Expr::U8(0),
+            Expr::Seq(vec![entry(1, 0xAA), entry(3, 0xBB)]),
+        );
+        let d = Compiler::compile_one(&f).unwrap();
+        let (v, _) = d
+            .parse(&Program::new(), &Scope::Empty, ReadCtxt::new(&[]))
+            .unwrap();
+        assert_value_eq(
+            v.coerce_mapped_value(),
+            &Value::Seq(vec![
+                Value::U8(0),
+                Value::U8(0xAA),
+                Value::U8(0),
+                Value::U8(0xBB),
+                Value::U8(0),
+            ]),
+        );
+    }
+
+    #[test]
+    fn compile_sparse_to_dense_too_short() {
+        let entry = |ix, v| Expr::Tuple(vec![Expr::U8(ix), Expr::U8(v)]);
+        let f = sparse_to_dense(
+            ValueType::Base(BaseType::U8),
+            Expr::U8(3),
+            Expr::U8(0),
+            Expr::Seq(vec![entry(1, 0xAA), entry(3, 0xBB)]),
+        );
+        let mut program = Compiler::compile_program(&FormatModule::new(), &f).unwrap();
+        assert!(program.run(ReadCtxt::new(&[])).is_err());
+        // the length is checked up front, rather than relying on the padding to underflow
+        program.set_arith_mode(ArithMode::Saturating);
+        assert!(program.run(ReadCtxt::new(&[])).is_err());
+    }
+
+    #[test]
+    fn compile_repeat_count_huge() {
+        let f = repeat_count(Expr::U32(u32::MAX), is_byte(0x00));
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let res = d.parse(&program, &Scope::Empty, ReadCtxt::new(&[0x00, 0x00]));
+        assert!(matches!(res, Err(ParseError::Overbyte { offset: 2 })));
+    }
+
+    #[derive(Default)]
+    struct EventLog(Vec<String>);
+
+    impl Visitor for EventLog {
+        fn enter_record(&mut self) {
+            self.0.push("enter_record".to_string());
+        }
+
+        fn field(&mut self, name: &Label, value: &Value) {
+            self.0.push(format!("field {name} = {value:?}"));
+        }
+
+        fn exit_record(&mut self) {
+            self.0.push("exit_record".to_string());
+        }
+
+        fn enter_tuple(&mut self) {
+            self.0.push("enter_tuple".to_string());
+        }
+
+        fn exit_tuple(&mut self) {
+            self.0.push("exit_tuple".to_string());
+        }
+
+        fn value(&mut self, value: &Value) {
+            self.0.push(format!("value {value:?}"));
+        }
+    }
+
+    #[test]
+    fn compile_record_visit() {
+        let f = Format::Tuple(vec![
+            record([
+                ("len", Format::Byte(ByteSet::full())),
+                ("data", repeat_count(var("len"), is_byte(0xFF))),
+            ]),
+            is_byte(0x00),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let mut log = EventLog::default();
+        let input = d
+            .parse_visit(
+                &program,
+                &Scope::Empty,
+                ReadCtxt::new(&[0x01, 0xFF, 0x00, 0x7F]),
+                &mut log,
+            )
+            .unwrap();
+        assert_eq!(input.remaining(), &[0x7F]);
+        assert_eq!(
+            log.0,
+            vec![
+                "enter_tuple",
+                "enter_record",
+                "field len = U8(1)",
+                "field data = Seq([U8(255)])",
+                "exit_record",
+                "value U8(0)",
+                "exit_tuple",
+            ]
+        );
+    }
+
+    #[test]
+    fn compile_optional_or() {
+        let u16be = map(
+            tuple([Format::Byte(ByteSet::full()), Format::Byte(ByteSet::full())]),
+            lambda("x", Expr::U16Be(Box::new(var("x")))),
+        );
+        let f = record([
+            ("length", Format::Byte(ByteSet::full())),
+            (
+                "version",
+                Format::Slice(var("length"), Box::new(optional_or(u16be, Expr::U16(0)))),
+            ),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let version = |input: &[u8]| {
+            let (val, remain) = d
+                .parse(&program, &Scope::Empty, ReadCtxt::new(input))
+                .unwrap();
+            assert_eq!(remain.remaining(), &[0xFF]);
+            val.record_proj("version").coerce_mapped_value().clone()
+        };
+        // present
+        assert_eq!(version(&[2, 0x00, 0x03, 0xFF]), Value::U16(3));
+        // table too short, falls back to the default
+        assert_eq!(version(&[1, 0x00, 0xFF]), Value::U16(0));
+        assert_eq!(version(&[0, 0xFF]), Value::U16(0));
+    }
+
+    #[test]
+    fn compile_repeat_until_last_nullable() {
+        // each element is either a `0x01` byte or, failing that, an empty match yielding `0`
+        let elem = optional_or(is_byte(0x01), Expr::U8(0));
+        let until_empty =
+            repeat_until_last(lambda("x", expr_eq(var("x"), Expr::U8(0))), elem.clone());
+        let d = Compiler::compile_one(&until_empty).unwrap();
+        let byte = |b: u8| Value::Branch(0, Box::new(Value::U8(b)));
+        let empty = Value::Branch(1, Box::new(Value::U8(0x00)));
+        accepts(
+            &d,
+            &[0x01, 0x01, 0x02],
+            &[0x02],
+            Value::Seq(vec![byte(0x01), byte(0x01), empty.clone()]),
+        );
+        accepts(&d, &[], &[], Value::Seq(vec![empty]));
+
+        // an empty element that does not satisfy the predicate would repeat forever
+        let until_two = repeat_until_last(lambda("x", expr_eq(var("x"), Expr::U8(2))), elem);
+        let d = Compiler::compile_one(&until_two).unwrap();
+        rejects(&d, &[0x01, 0x02]);
+
+        // elements that can never consume input are rejected outright
+        let until_empty = repeat_until_last(lambda("x", Expr::Bool(false)), Format::EMPTY);
+        assert!(Compiler::compile_one(&until_empty).is_err());
+    }
+
+    #[test]
+    fn compile_peek_field_then() {
+        let f = peek_field_then(
+            [("length", Format::Byte(ByteSet::full()))],
+            "len",
+            Format::Slice(var("len"), Box::new(repeat(Format::Byte(ByteSet::full())))),
+        );
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let (val, remain) = d
+            .parse(
+                &program,
+                &Scope::Empty,
+                ReadCtxt::new(&[0x03, 0xAA, 0xBB, 0xCC]),
+            )
+            .unwrap();
+        assert_eq!(
+            val.coerce_mapped_value(),
+            &Value::Seq(vec![Value::U8(0x03), Value::U8(0xAA), Value::U8(0xBB)])
+        );
+        assert_eq!(remain.remaining(), &[0xCC]);
+    }
+
+    #[test]
+    fn compile_repeat_count_in() {
+        let module = FormatModule::new();
+        let pair = Format::Tuple(vec![is_byte(0x00), Format::Byte(ByteSet::full())]);
+        let expect = Value::Seq(vec![
+            Value::Tuple(vec![Value::U8(0x00), Value::U8(0x01)]),
+            Value::Tuple(vec![Value::U8(0x00), Value::U8(0x02)]),
+        ]);
+        let input = [0x00, 0x01, 0x00, 0x02, 0xFF];
+
+        let f = repeat_count_in(&module, CountUnit::Elements, Expr::U8(2), pair.clone());
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(&d, &input, &[0xFF], expect.clone());
+
+        let f = repeat_count_in(&module, CountUnit::Bytes, Expr::U16(4), pair.clone());
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let (val, remain) = d
+            .parse(&program, &Scope::Empty, ReadCtxt::new(&input))
+            .unwrap();
+        assert_eq!(val.coerce_mapped_value(), &expect);
+        assert_eq!(remain.remaining(), &[0xFF]);
+
+        // a byte-length that is not a multiple of the element size leaves a partial element behind
+        let f = repeat_count_in(&module, CountUnit::Bytes, Expr::U16(5), pair);
+        let d = Compiler::compile_one(&f).unwrap();
+        rejects(&d, &input);
+    }
+
+    #[test]
+    fn compile_repeat_while_input() {
+        let pair = tuple([Format::Byte(ByteSet::full()), Format::Byte(ByteSet::full())]);
+        let f = tuple([
+            Format::Slice(
+                Expr::U8(4),
+                Box::new(Format::RepeatWhileInput(Box::new(pair))),
+            ),
+            Format::Byte(ByteSet::full()),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06],
+            &[0x06],
+            Value::Tuple(vec![
+                Value::Seq(vec![
+                    Value::Tuple(vec![Value::U8(0x01), Value::U8(0x02)]),
+                    Value::Tuple(vec![Value::U8(0x03), Value::U8(0x04)]),
+                ]),
+                Value::U8(0x05),
+            ]),
+        );
+    }
+
+    #[test]
+    fn compile_repeat_while_input_overread() {
+        let pair = tuple([Format::Byte(ByteSet::full()), Format::Byte(ByteSet::full())]);
+        let f = Format::Slice(
+            Expr::U8(3),
+            Box::new(Format::RepeatWhileInput(Box::new(pair))),
+        );
+        let d = Compiler::compile_one(&f).unwrap();
+        rejects(&d, &[0x01, 0x02, 0x03, 0x04]);
+        assert!(Compiler::compile_one(&Format::RepeatWhileInput(Box::new(Format::EMPTY))).is_err());
+    }
+
+    #[test]
+    fn compile_with_relative_offset() {
+        let f = tuple([
+            Format::WithRelativeOffset(Expr::U8(2), Box::new(is_byte(0xCC))),
+            Format::Byte(ByteSet::full()),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[0xAA, 0xBB, 0xCC, 0xDD],
+            &[0xBB, 0xCC, 0xDD],
+            Value::Tuple(vec![Value::U8(0xCC), Value::U8(0xAA)]),
+        );
+        rejects(&d, &[0xAA, 0xBB, 0xDD]);
+        rejects(&d, &[0xAA]);
+    }
+
+    #[test]
+    fn compile_follow_offset() {
+        let f = tuple([
+            Format::FollowOffset(Expr::U8(2), Box::new(is_byte(0xCC))),
+            Format::Byte(ByteSet::full()),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[0xAA, 0xBB, 0xCC, 0xDD, 0xEE],
+            &[0xEE],
+            Value::Tuple(vec![Value::U8(0xCC), Value::U8(0xDD)]),
+        );
+        rejects(&d, &[0xAA, 0xBB, 0xDD, 0xEE]);
+        rejects(&d, &[0xAA, 0xBB, 0xCC]);
+    }
+
+    #[test]
+    fn compile_newtype() {
+        let u32be = map(
+            tuple(std::iter::repeat(Format::Byte(ByteSet::full())).take(4)),
+            lambda("x", Expr::U32Be(Box::new(var("x")))),
+        );
+        let f = Format::Newtype(
+            "Tag".into(),
+            Box::new(u32be),
+            Some(lambda("t", expr_ne(var("t"), Expr::U32(0)))),
+        );
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let (val, remain) = d
+            .parse(
+                &program,
+                &Scope::Empty,
+                ReadCtxt::new(&[0x6E, 0x61, 0x6D, 0x65, 0xFF]),
+            )
+            .unwrap();
+        assert_eq!(val.coerce_mapped_value(), &Value::U32(0x6E61_6D65));
+        assert_eq!(remain.remaining(), &[0xFF]);
+        rejects(&d, &[0x00, 0x00, 0x00, 0x00, 0xFF]);
+        rejects(&d, &[0x6E, 0x61, 0x6D]);
+    }
+
+    #[test]
+    fn compile_parse_linked() {
+        let glyph = record([
+            ("id", Format::Byte(ByteSet::full())),
+            ("width", Format::Byte(ByteSet::full())),
+        ]);
+        let f = record([
+            ("glyphs", repeat_count(Expr::U8(2), glyph)),
+            ("default_glyph", Format::Byte(ByteSet::full())),
+            ("missing_glyph", Format::Byte(ByteSet::full())),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let mut links = LinkTable::new();
+        links.add_anchor("glyph", "id");
+        links.add_reference("default_glyph", "glyph");
+        links.add_reference("missing_glyph", "glyph");
+        let input = [0x07, 0x10, 0x09, 0x20, 0x09, 0x05];
+        let (_, remain) = d
+            .parse_linked(&program, &Scope::Empty, ReadCtxt::new(&input), &mut links)
+            .unwrap();
+        assert!(remain.remaining().is_empty());
+        assert_eq!(
+            links.links(),
+            &[
+                Link {
+                    field: "default_glyph".into(),
+                    id: Value::U8(0x09),
+                    target: Some(Value::record([
+                        ("id", Value::U8(0x09)),
+                        ("width", Value::U8(0x20)),
+                    ])),
+                },
+                Link {
+                    field: "missing_glyph".into(),
+                    id: Value::U8(0x05),
+                    target: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vector_round_trip() {
+        let module = FormatModule::new();
+        let f = record([
+            ("tag", is_byte(0x01)),
+            ("value", Format::Byte(ByteSet::full())),
+        ]);
+        let vector = TestVector::record(&module, &f, vec![0x01, 0x2A]).unwrap();
+        assert_eq!(
+            vector.expected,
+            Value::record([("tag", Value::U8(0x01)), ("value", Value::U8(0x2A))])
+        );
+        let json = serde_json::to_string(&vector).unwrap();
+        let loaded: TestVector = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, vector);
+        assert!(loaded.replay(&module, &f).is_ok());
+
+        let changed = record([
+            ("tag", is_byte(0x01)),
+            (
+                "value",
+                map(Format::Byte(ByteSet::full()), lambda("x", as_u16(var("x")))),
+            ),
+        ]);
+        assert!(matches!(
+            loaded.replay(&module, &changed),
+            Err(Mismatch::Value { .. })
+        ));
+        let stricter = record([
+            ("tag", is_byte(0x02)),
+            ("value", Format::Byte(ByteSet::full())),
+        ]);
+        assert!(matches!(
+            loaded.replay(&module, &stricter),
+            Err(Mismatch::Parse(..))
+        ));
+    }
+
+    #[test]
+    fn interned_tags_share_storage() {
+        let tag = repeat_count(Expr::U8(4), Format::Byte(ByteSet::full()));
+        let f = record([
+            ("script", tag.clone()),
+            ("lang", tag.clone()),
+            ("feature", tag),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let (mut val, _) = d
+            .parse(&program, &Scope::Empty, ReadCtxt::new(b"latnDEU latn"))
+            .unwrap();
+        let mut interner = Interner::new();
+        let tags = interner.intern_strings(&mut val);
+        assert_eq!(tags.len(), 3);
+        assert!(Rc::ptr_eq(&tags[0], &tags[2]));
+        assert!(!Rc::ptr_eq(&tags[0], &tags[1]));
+        assert_eq!(interner.len(), 2);
+
+        // the decoded value itself holds the shared copies
+        let field = |name: &'static str| match val.get(&[Accessor::Field(name.into())]) {
+            Some(Value::Shared(tag)) => Rc::clone(tag),
+            other => panic!("expected shared value, found {other:?}"),
+        };
+        assert!(Rc::ptr_eq(&field("script"), &field("feature")));
+        assert!(Rc::ptr_eq(&field("lang"), &tags[1]));
+        assert_eq!(
+            field("script").as_ref(),
+            &Value::Seq(b"latn".iter().copied().map(Value::U8).collect())
+        );
+
+        // interning again finds nothing left to share
+        assert!(interner.intern_strings(&mut val).is_empty());
+    }
+
+    #[test]
+    fn compile_repeat1() {
+        let f = repeat1(is_byte(0x00));
+        let d = Compiler::compile_one(&f).unwrap();
+        rejects(&d, &[]);
+        rejects(&d, &[0xFF]);
+        accepts(&d, &[0x00], &[], Value::Seq(vec![Value::U8(0x00)]));
+        accepts(
+            &d,
+            &[0x00, 0xFF],
+            &[0xFF],
+            Value::Seq(vec![Value::U8(0x00)]),
+        );
+        accepts(
+            &d,
+            &[0x00, 0x00],
+            &[],
+            Value::Seq(vec![Value::U8(0x00), Value::U8(0x00)]),
+        );
+    }
+
+    #[test]
+    fn compile_align1() {
+        let f = Format::Tuple(vec![is_byte(0x00), Format::Align(1), is_byte(0xFF)]);
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[0x00, 0xFF],
+            &[],
+            Value::Tuple(vec![Value::U8(0x00), Value::UNIT, Value::U8(0xFF)]),
+        );
+    }
+
+    #[test]
+    fn compile_align2() {
+        let f = Format::Tuple(vec![is_byte(0x00), Format::Align(2), is_byte(0xFF)]);
+        let d = Compiler::compile_one(&f).unwrap();
+        rejects(&d, &[0x00, 0xFF]);
+        rejects(&d, &[0x00, 0x99, 0x99, 0xFF]);
+        accepts(
+            &d,
+            &[0x00, 0x99, 0xFF],
+            &[],
+            Value::Tuple(vec![Value::U8(0x00), Value::UNIT, Value::U8(0xFF)]),
+        );
+    }
+
+    #[test]
+    fn compile_pad_to() {
+        let f = tuple([
+            Format::Slice(
+                Expr::U32(6),
+                Box::new(tuple([
+                    repeat_count(Expr::U8(2), Format::Byte(ByteSet::full())),
+                    pad_to(Expr::U32(4)),
+                    pad_to(Expr::U32(4)),
+                ])),
+            ),
+            is_byte(0xFF),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[0x01, 0x02, 0x00, 0x00, 0x03, 0x04, 0xFF],
+            &[],
+            Value::Tuple(vec![
+                Value::Tuple(vec![
+                    Value::Seq(vec![Value::U8(0x01), Value::U8(0x02)]),
+                    Value::Mapped(
+                        Box::new(Value::record([
+                            ("pos", Value::U32(2)),
+                            (
+                                "padding",
+                                Value::Branch(
+                                    0,
+                                    Box::new(Value::Seq(vec![Value::U8(0x00), Value::U8(0x00)])),
+                                ),
+                            ),
+                        ])),
+                        Box::new(Value::UNIT),
+                    ),
+                    Value::Mapped(
+                        Box::new(Value::record([
+                            ("pos", Value::U32(4)),
+                            ("padding", Value::Branch(0, Box::new(Value::Seq(vec![])))),
+                        ])),
+                        Box::new(Value::UNIT),
+                    ),
+                ]),
+                Value::U8(0xFF),
+            ]),
+        );
+        // padding must be zeroed
+        rejects(&d, &[0x01, 0x02, 0x00, 0x01, 0x03, 0x04, 0xFF]);
+
+        // already past the target length
+        let f = tuple([
+            repeat_count(Expr::U8(3), Format::Byte(ByteSet::full())),
+            pad_to(Expr::U32(2)),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        rejects(&d, &[0x01, 0x02, 0x03]);
+    }
+
+    #[test]
+    fn compile_align_padding_preserved() {
+        let f = record([
+            ("head", is_byte(0x01)),
+            ("pad", align_padding(4)),
+            ("tail", is_byte(0x02)),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let padding = |v: &Value| {
+            v.get(&[Accessor::Field("pad".into())])
+                .unwrap()
+                .coerce_mapped_value()
+                .clone()
+        };
+        let (v, _) = d
+            .parse(
+                &program,
+                &Scope::Empty,
+                ReadCtxt::new(&[0x01, 0xAA, 0x00, 0xCC, 0x02]),
+            )
+            .unwrap();
+        assert_eq!(
+            padding(&v),
+            Value::Seq(vec![Value::U8(0xAA), Value::U8(0x00), Value::U8(0xCC)])
+        );
+
+        // already aligned, so no padding is consumed
+        let f = tuple([repeat_count(Expr::U8(4), is_byte(0x00)), align_padding(4)]);
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(
+            &d,
+            &[0x00, 0x00, 0x00, 0x00, 0xFF],
+            &[0xFF],
+            Value::Tuple(vec![
+                Value::Seq(vec![Value::U8(0x00); 4]),
+                Value::Mapped(
+                    Box::new(Value::record([
+                        ("pos", Value::U32(4)),
+                        ("padding", Value::Seq(vec![])),
+                    ])),
+                    Box::new(Value::Seq(vec![])),
+                ),
+            ]),
+        );
+    }
+
+    #[test]
+    fn compile_with_checksum() {
+        let any_byte = Format::Byte(ByteSet::full());
+        let body = record([
+            ("len", any_byte.clone()),
+            ("data", repeat_count(var("len"), any_byte.clone())),
+        ]);
+        let input = [0x03, 0x10, 0x20, 0xF0, 0xAA];
+        let checksum = |kind: ChecksumKind| {
+            let f = tuple([with_checksum(kind, body.clone()), any_byte.clone()]);
+            let d = Compiler::compile_one(&f).unwrap();
+            let (v, _) = d
+                .parse(&Program::new(), &Scope::Empty, ReadCtxt::new(&input))
+                .unwrap();
+            let path = [Accessor::Index(0), Accessor::Field("checksum".into())];
+            v.get(&path).unwrap().coerce_mapped_value().clone()
+        };
+        // 0x03 + 0x10 + 0x20 + 0xF0, excluding the trailing byte
+        assert_eq!(checksum(ChecksumKind::Sum32), Value::U32(0x123));
+        assert_eq!(checksum(ChecksumKind::Sum8), Value::U8(0x23));
+
+        let f = with_checksum(ChecksumKind::Sum32, Format::EMPTY);
+        let d = Compiler::compile_one(&f).unwrap();
+        let (v, _) = d
+            .parse(&Program::new(), &Scope::Empty, ReadCtxt::new(&[]))
+            .unwrap();
+        let path = [Accessor::Field("checksum".into())];
+        assert_eq!(v.get(&path).unwrap(), &Value::U32(0));
+    }
+
+    #[test]
+    fn compile_align_record_padding() {
+        let any_byte = Format::Byte(ByteSet::full());
+        let padded = record([
+            ("len", any_byte.clone()),
+            ("data", repeat_count(var("len"), any_byte.clone())),
+            ("pad", Format::Align(4)),
+        ]);
+        let f = tuple([padded.clone(), Format::AbsolutePos]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        for len in 0..8u8 {
+            let mut input = vec![len];
+            input.extend(std::iter::repeat_n(0x99, 7 + len as usize));
+            let (v, _) = d
+                .parse(&program, &Scope::Empty, ReadCtxt::new(&input))
+                .unwrap();
+            let Value::Tuple(vs) = v else {
+                panic!("expected tuple")
+            };
+            let Value::U32(pos) = vs[1] else {
+                panic!("expected position")
+            };
+            assert_eq!(pos, (len as u32 + 1).next_multiple_of(4));
+        }
+
+        // alignment is relative to the start of the outer stream, even within a slice
+        let f = tuple([
+            any_byte.clone(),
+            Format::Slice(
+                Expr::U8(7),
+                Box::new(tuple([any_byte, Format::Align(4), Format::AbsolutePos])),
+            ),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let (v, _) = d
+            .parse(&program, &Scope::Empty, ReadCtxt::new(&[0x00; 8]))
+            .unwrap();
+        let Value::Tuple(vs) = v else {
+            panic!("expected tuple")
+        };
+        let Value::Tuple(inner) = &vs[1] else {
+            panic!("expected tuple")
+        };
+        assert_eq!(inner[2], Value::U32(4));
+    }
+
+    #[test]
+    fn compile_signature() {
+        let f = signature("PNG", b"\x89PNG", Format::Byte(ByteSet::full()));
+        let d = Compiler::compile_one(&f).unwrap();
+        accepts(&d, b"\x89PNG\x01\x02", &[0x02], Value::U8(0x01));
+
+        let program = Program::new();
+        let Err(err) = d.parse(&program, &Scope::Empty, ReadCtxt::new(b"GIF89a")) else {
+            panic!("mismatched signature should not parse");
+        };
+        assert!(
+            matches!(&err, ParseError::WrongFormat { expected, offset: 0 } if expected == "PNG")
+        );
+        assert_eq!(
+            err.to_string(),
+            "not a valid PNG file (signature mismatch at offset 0)"
+        );
+
+        // failures within the body are reported as usual
+        let Err(err) = d.parse(&program, &Scope::Empty, ReadCtxt::new(b"\x89PNG")) else {
+            panic!("truncated body should not parse");
+        };
+        assert!(matches!(err, ParseError::Overbyte { offset: 4 }));
+    }
+
+    #[test]
+    fn compile_peek_not() {
+        let any_byte = Format::Byte(ByteSet::full());
+        let a = Format::Tuple(vec![is_byte(0xFF), is_byte(0xFF)]);
+        let peek_not = Format::PeekNot(Box::new(a));
+        let f = Format::Tuple(vec![peek_not, any_byte.clone(), any_byte.clone()]);
+        let d = Compiler::compile_one(&f).unwrap();
+        rejects(&d, &[]);
+        rejects(&d, &[0xFF]);
+        rejects(&d, &[0xFF, 0xFF]);
+        accepts(
             &d,
             &[0x00, 0xFF],
             &[],
@@ -2024,6 +6643,83 @@ mod tests {
         );
     }
 
+    #[test]
+    fn compile_peek_not_guarded_run() {
+        let f = tuple([
+            Format::PeekNot(Box::new(is_byte(0xFF))),
+            repeat(not_byte(0x00)),
+        ]);
+        assert!(Format::PeekNot(Box::new(is_byte(0xFF))).is_nullable(&FormatModule::new()));
+        let d = Compiler::compile_one(&f).unwrap();
+        let run = |bytes: &[u8]| {
+            Value::Tuple(vec![
+                Value::UNIT,
+                Value::Seq(bytes.iter().copied().map(Value::U8).collect()),
+            ])
+        };
+        accepts(&d, &[0x01, 0xFF, 0x00], &[0x00], run(&[0x01, 0xFF]));
+        accepts(&d, &[0x00], &[0x00], run(&[]));
+        accepts(&d, &[], &[], run(&[]));
+        rejects(&d, &[0xFF, 0x01]);
+    }
+
+    #[test]
+    fn compile_bit_slice() {
+        let bit = Format::Byte(ByteSet::from([0, 1]));
+        let f = tuple([
+            bit_slice(Expr::U8(12), repeat(bit)),
+            Format::Byte(ByteSet::full()),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let bits = [1, 1, 0, 1, 0, 1, 0, 1, 1, 0, 1, 1];
+        accepts(
+            &d,
+            &[0xAB, 0xCD, 0xEF, 0x00],
+            &[0x00],
+            Value::Tuple(vec![
+                Value::Seq(bits.into_iter().map(Value::U8).collect()),
+                Value::U8(0xEF),
+            ]),
+        );
+        // the bit-slice may not extend past the end of the input
+        rejects(&d, &[0xAB]);
+    }
+
+    #[test]
+    fn compile_msb_bit_fields() {
+        let f = tuple([
+            msb_bit_fields(BaseType::U16, [("flag", 1), ("value", 15)]),
+            Format::Byte(ByteSet::full()),
+        ]);
+        let d = Compiler::compile_one(&f).unwrap();
+        let program = Program::new();
+        let parse = |input: &[u8]| {
+            let (val, rest) = d
+                .parse(&program, &Scope::Empty, ReadCtxt::new(input))
+                .unwrap();
+            let Value::Tuple(vs) = val else {
+                panic!("expected tuple, found {val:?}")
+            };
+            (vs[0].coerce_mapped_value().clone(), rest.remaining().len())
+        };
+        assert_eq!(
+            parse(&[0x81, 0x02, 0xFF]),
+            (
+                Value::record([("flag", Value::Bool(true)), ("value", Value::U16(0x0102))]),
+                0
+            )
+        );
+        assert_eq!(
+            parse(&[0x7F, 0xFE, 0x00, 0x00]),
+            (
+                Value::record([("flag", Value::Bool(false)), ("value", Value::U16(0x7FFE))]),
+                1
+            )
+        );
+        // the field must be followed by the trailing byte, as the bit-view resynchronizes after 2 bytes
+        rejects(&d, &[0x81, 0x02]);
+    }
+
     #[test]
     fn compile_peek_not_switch() {
         let any_byte = Format::Byte(ByteSet::full());
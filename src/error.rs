@@ -33,6 +33,34 @@ pub enum ParseError<V: Clone = Value> {
     NoValidBranch {
         offset: usize,
     },
+    Overflow {
+        offset: usize,
+    },
+    /// The signature of a [`Format::Signature`](crate::Format::Signature) did not match
+    WrongFormat {
+        expected: Label,
+        offset: usize,
+    },
+    /// A complete parse left `count` bytes of input unconsumed
+    Leftover {
+        count: usize,
+        offset: usize,
+    },
+    /// A parse with a ceiling on the total number of bytes read attempted to read beyond it
+    ByteBudgetExceeded {
+        budget: usize,
+        offset: usize,
+    },
+    /// Reading further input from the underlying source failed
+    Io {
+        kind: std::io::ErrorKind,
+        offset: usize,
+    },
+    /// A [`Format::AbsolutePos`](crate::Format::AbsolutePos) or [`Format::SlicePos`](crate::Format::SlicePos)
+    /// whose value is too large to represent as a `u32`
+    PositionOverflow {
+        offset: usize,
+    },
 }
 
 impl<V: std::fmt::Debug + Clone> std::fmt::Display for ParseError<V> {
@@ -82,6 +110,39 @@ impl<V: std::fmt::Debug + Clone> std::fmt::Display for ParseError<V> {
                     "no valid branch found for content starting at offset {offset}"
                 )
             }
+            Self::Overflow { offset } => {
+                write!(
+                    f,
+                    "length computation at offset {offset} overflowed its numeric bounds"
+                )
+            }
+            Self::WrongFormat { expected, offset } => {
+                write!(
+                    f,
+                    "not a valid {expected} file (signature mismatch at offset {offset})"
+                )
+            }
+            Self::Leftover { count, offset } => {
+                write!(
+                    f,
+                    "{count} bytes left unconsumed at offset {offset} when end-of-input expected"
+                )
+            }
+            Self::ByteBudgetExceeded { budget, offset } => {
+                write!(
+                    f,
+                    "attempted to read byte at offset {offset}, but the budget of {budget} bytes has been exhausted"
+                )
+            }
+            Self::Io { kind, offset } => {
+                write!(f, "failed to read input past offset {offset}: {kind}")
+            }
+            Self::PositionOverflow { offset } => {
+                write!(
+                    f,
+                    "position at offset {offset} cannot be represented as a u32"
+                )
+            }
         }
     }
 }
@@ -117,6 +178,48 @@ impl ParseError<ParsedValue> {
 }
 
 impl<V: Clone> ParseError<V> {
+    /// Returns the input offset at which the error was encountered.
+    pub fn offset(&self) -> usize {
+        match self {
+            Self::Fail { offset, .. }
+            | Self::Trailing { offset, .. }
+            | Self::Overrun { offset, .. }
+            | Self::Overbyte { offset }
+            | Self::Unexpected { offset, .. }
+            | Self::NoValidBranch { offset }
+            | Self::Overflow { offset }
+            | Self::WrongFormat { offset, .. }
+            | Self::Leftover { offset, .. }
+            | Self::ByteBudgetExceeded { offset, .. }
+            | Self::Io { offset, .. }
+            | Self::PositionOverflow { offset } => *offset,
+        }
+    }
+
+    /// Returns `true` if `self` and `other` are the same kind of error, encountered at the same offset.
+    ///
+    /// Any other details of the error (e.g. scope-bindings or the byte found) are not compared.
+    pub fn same_failure<W: Clone>(&self, other: &ParseError<W>) -> bool {
+        self.kind() == other.kind() && self.offset() == other.offset()
+    }
+
+    fn kind(&self) -> u8 {
+        match self {
+            Self::Fail { .. } => 0,
+            Self::Trailing { .. } => 1,
+            Self::Overrun { .. } => 2,
+            Self::Overbyte { .. } => 3,
+            Self::Unexpected { .. } => 4,
+            Self::NoValidBranch { .. } => 5,
+            Self::Overflow { .. } => 6,
+            Self::WrongFormat { .. } => 7,
+            Self::Leftover { .. } => 8,
+            Self::ByteBudgetExceeded { .. } => 9,
+            Self::Io { .. } => 10,
+            Self::PositionOverflow { .. } => 11,
+        }
+    }
+
     pub fn trailing(byte: u8, offset: usize) -> Self {
         Self::Trailing { byte, offset }
     }
@@ -132,6 +235,30 @@ impl<V: Clone> ParseError<V> {
         Self::Overbyte { offset }
     }
 
+    pub fn overflow(offset: usize) -> Self {
+        Self::Overflow { offset }
+    }
+
+    pub fn wrong_format(expected: Label, offset: usize) -> Self {
+        Self::WrongFormat { expected, offset }
+    }
+
+    pub fn leftover(count: usize, offset: usize) -> Self {
+        Self::Leftover { count, offset }
+    }
+
+    pub fn byte_budget_exceeded(budget: usize, offset: usize) -> Self {
+        Self::ByteBudgetExceeded { budget, offset }
+    }
+
+    pub fn io(kind: std::io::ErrorKind, offset: usize) -> Self {
+        Self::Io { kind, offset }
+    }
+
+    pub fn position_overflow(offset: usize) -> Self {
+        Self::PositionOverflow { offset }
+    }
+
     pub fn unexpected(found: u8, expected: ByteSet, offset: usize) -> Self {
         Self::Unexpected {
             found,
@@ -1,5 +1,8 @@
 use crate::byte_set::ByteSet;
-use crate::{Arith, Expr, Format, IntRel, IntoLabel, Label, Pattern, ValueType};
+use crate::decoder::{Accessor, Value};
+use crate::{
+    Arith, BaseType, Expr, Format, FormatModule, IntRel, IntoLabel, Label, Pattern, ValueType,
+};
 
 pub fn packed_bits_u8<const N: usize>(
     field_bit_lengths: [u8; N],
@@ -33,6 +36,38 @@ fn mask_bits(x: Expr, high_bits_used: u8, nbits: u8) -> Expr {
     bit_and(shr(x, Expr::U8(shift)), Expr::U8(mask))
 }
 
+/// Parses `format`, a `base`-typed number, and decomposes it into a record of named flags and sub-fields.
+///
+/// Each field `(name, mask, shift)` selects the bits `(x >> shift) & mask` of the parsed value `x`. A
+/// field whose `mask` is `1` yields a boolean flag; any other field yields a `base`-typed number.
+///
+/// # Panics
+///
+/// Panics if `base` is not numeric, or if any `mask` or `shift` does not fit in `base`.
+pub fn bit_flags<Name: IntoLabel>(
+    base: BaseType,
+    format: Format,
+    fields: impl IntoIterator<Item = (Name, u32, u32)>,
+) -> Format {
+    const BINDING_NAME: &str = "flagbits";
+    let fields = fields
+        .into_iter()
+        .map(|(name, mask, shift)| {
+            let bits = bit_and(
+                shr(var(BINDING_NAME), lit_of(base, shift)),
+                lit_of(base, mask),
+            );
+            let field = if mask == 1 {
+                expr_ne(bits, zero_of(base))
+            } else {
+                bits
+            };
+            (name.into(), field)
+        })
+        .collect();
+    map(format, lambda(BINDING_NAME, Expr::Record(fields)))
+}
+
 pub fn var<Name: IntoLabel>(name: Name) -> Expr {
     Expr::Var(name.into())
 }
@@ -41,6 +76,23 @@ pub fn lambda<Name: IntoLabel>(name: Name, body: Expr) -> Expr {
     Expr::Lambda(name.into(), Box::new(body))
 }
 
+/// Composes two lambda expressions into a single lambda that applies `inner` and then `outer` to its
+/// argument, so that `map(f, compose(g, h))` is equivalent to `map(map(f, h), g)` without the
+/// intermediate layer of mapped value.
+///
+/// # Panics
+///
+/// Panics if either `outer` or `inner` is not an [`Expr::Lambda`].
+pub fn compose(outer: Expr, inner: Expr) -> Expr {
+    match (outer, inner) {
+        (Expr::Lambda(outer_name, outer_body), Expr::Lambda(inner_name, inner_body)) => lambda(
+            inner_name,
+            expr_match(*inner_body, [(bind(outer_name), *outer_body)]),
+        ),
+        (outer, inner) => panic!("compose: expected two lambdas, found {outer:?} and {inner:?}"),
+    }
+}
+
 pub fn variant<Name: IntoLabel>(name: Name, value: Expr) -> Expr {
     Expr::Variant(name.into(), Box::new(value))
 }
@@ -103,6 +155,13 @@ pub fn optional(format: Format) -> Format {
     alts([("some", format), ("none", Format::EMPTY)])
 }
 
+/// Parses `format` if possible, and otherwise yields the value of `default` without consuming any input.
+///
+/// Unlike [`optional`], the value is not wrapped in a variant, so `default` must have the same type as `format`.
+pub fn optional_or(format: Format, default: Expr) -> Format {
+    Format::UnionNondet(vec![format, Format::Compute(default)])
+}
+
 pub fn repeat(format: Format) -> Format {
     Format::Repeat(Box::new(format))
 }
@@ -111,10 +170,161 @@ pub fn repeat1(format: Format) -> Format {
     Format::Repeat1(Box::new(format))
 }
 
+/// Repeats `format` one or more times, separated by (and discarding) occurrences of `sep`.
+pub fn repeat_sep(format: Format, sep: Format) -> Format {
+    Format::RepeatSep(Box::new(format), Box::new(sep))
+}
+
 pub fn repeat_count(len: Expr, format: Format) -> Format {
     Format::RepeatCount(len, Box::new(format))
 }
 
+/// Unit of measure for the length-parameter of a repetition
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CountUnit {
+    /// The length is the number of elements
+    Elements,
+    /// The length is the total number of bytes occupied by all elements, each of which must be of a fixed size
+    Bytes,
+}
+
+/// Parses a length with `len_format`, which must yield a number, followed by exactly that many bytes,
+/// yielding the sequence of bytes alone.
+pub fn length_prefixed_bytes(len_format: Format) -> Format {
+    map(
+        record([
+            ("len", len_format),
+            (
+                "bytes",
+                repeat_count(var("len"), Format::Byte(ByteSet::full())),
+            ),
+        ]),
+        lambda("x", record_proj(var("x"), "bytes")),
+    )
+}
+
+/// Repeats `format` according to `len`, which is interpreted either as an element-count or a byte-length depending on `unit`.
+///
+/// For [`CountUnit::Bytes`], the element-count is `len` divided by the fixed size of `format`, and the
+/// repetition is restricted to a slice of `len` bytes. For example, the `seg_count_x2` field of an OpenType
+/// `cmap` format-4 subtable is a byte-length over two-byte elements, so that
+/// `repeat_count_in(module, CountUnit::Bytes, var("seg_count_x2"), u16be)` yields `seg_count_x2 / 2` elements
+/// without the halving being written out by hand. A `len` that is not a multiple of the size of `format`
+/// fails the parse, rather than leaving the remainder of the slice unread.
+///
+/// # Panics
+///
+/// Panics if `unit` is [`CountUnit::Bytes`] and `format` does not have a fixed, non-zero size.
+pub fn repeat_count_in(
+    module: &FormatModule,
+    unit: CountUnit,
+    len: Expr,
+    format: Format,
+) -> Format {
+    match unit {
+        CountUnit::Elements => repeat_count(len, format),
+        CountUnit::Bytes => {
+            let size = match format.match_bounds(module).is_exact() {
+                Some(n) if n > 0 => n,
+                _ => panic!(
+                    "byte-length repetition over format without fixed, non-zero size: {format:?}"
+                ),
+            };
+            let size = u32::try_from(size).expect("element size exceeds u32::MAX");
+            let count = Expr::Arith(
+                Arith::Div,
+                Box::new(as_u32(len.clone())),
+                Box::new(Expr::U32(size)),
+            );
+            let elems = tuple([repeat_count(count, format), Format::EndOfInput]);
+            Format::Slice(
+                len,
+                Box::new(map(elems, lambda("x", tuple_proj(var("x"), 0)))),
+            )
+        }
+    }
+}
+
+/// Parses `format` once for each element of the sequence `seq`, with the element bound to `name`.
+pub fn for_each(seq: Expr, name: impl IntoLabel, format: Format) -> Format {
+    Format::ForEach(seq, name.into(), Box::new(format))
+}
+
+/// Parses `format` once for each pair of adjacent elements of the sequence `seq`, whose elements are of
+/// type `elem_type`, with the earlier and later element of each pair bound to the respective names in `names`.
+///
+/// Yields one fewer value than there are elements in `seq` (or none, if `seq` is empty), e.g. for
+/// parsing the regions delimited by consecutive entries of an offset table.
+pub fn for_each_pair(
+    seq: Expr,
+    elem_type: ValueType,
+    names: [impl IntoLabel; 2],
+    format: Format,
+) -> Format {
+    // pairs each element with its predecessor, carried as a sequence of at most one element
+    let pairs = flat_map_accum(
+        lambda(
+            "prev_x",
+            expr_match(
+                tuple_proj(var("prev_x"), 0),
+                [
+                    (
+                        Pattern::Seq(vec![bind("prev")]),
+                        Expr::Tuple(vec![
+                            Expr::Seq(vec![tuple_proj(var("prev_x"), 1)]),
+                            Expr::Seq(vec![Expr::Tuple(vec![
+                                var("prev"),
+                                tuple_proj(var("prev_x"), 1),
+                            ])]),
+                        ]),
+                    ),
+                    (
+                        Pattern::Wildcard,
+                        Expr::Tuple(vec![
+                            Expr::Seq(vec![tuple_proj(var("prev_x"), 1)]),
+                            Expr::Seq(vec![]),
+                        ]),
+                    ),
+                ],
+            ),
+        ),
+        Expr::Seq(vec![]),
+        ValueType::Seq(Box::new(elem_type)),
+        seq,
+    );
+    let [fst, snd] = names.map(|name| name.into());
+    for_each(
+        pairs,
+        "pair",
+        Format::Let(
+            fst,
+            tuple_proj(var("pair"), 0),
+            Box::new(Format::Let(
+                snd,
+                tuple_proj(var("pair"), 1),
+                Box::new(format),
+            )),
+        ),
+    )
+}
+
+/// Parses `element` once for each of the given index values, in order, with the current index bound to `name`.
+///
+/// Yields a sequence of `(index, element)` pairs, e.g. for a run of per-platform tables whose order is
+/// given by the variants of an enum.
+pub fn indexed_array(
+    index_values: impl IntoIterator<Item = Expr>,
+    name: impl IntoLabel,
+    element: Format,
+) -> Format {
+    let name = name.into();
+    for_each(
+        Expr::Seq(index_values.into_iter().collect()),
+        name.clone(),
+        tuple([Format::Compute(var(name)), element]),
+    )
+}
+
 pub fn repeat_between(min: Expr, max: Expr, format: Format) -> Format {
     Format::RepeatBetween(min, max, Box::new(format))
 }
@@ -127,6 +337,12 @@ pub fn repeat_until_seq(cond: Expr, format: Format) -> Format {
     Format::RepeatUntilSeq(cond, Box::new(format))
 }
 
+/// Repeats `format` until the byte-sequence `terminator` is found, which is consumed but not included
+/// in the resulting sequence.
+pub fn until_seq(terminator: &[u8], format: Format) -> Format {
+    Format::UntilSeq(terminator.to_vec(), Box::new(format))
+}
+
 pub fn if_then_else(cond: Expr, format0: Format, format1: Format) -> Format {
     Format::Match(
         cond,
@@ -145,10 +361,479 @@ pub fn if_then_else_variant(cond: Expr, format0: Format, format1: Format) -> For
     )
 }
 
+/// Conditionally parses `field` when `present` evaluates to true, yielding a `some`-variant of the
+/// parsed value, or else parses nothing and yields a `none`-variant.
+///
+/// Intended for record fields whose presence depends on the value of an earlier field (e.g. a version number).
+pub fn cond_field(present: Expr, field: Format) -> Format {
+    if_then_else(
+        present,
+        Format::Variant("some".into(), Box::new(field)),
+        Format::Variant("none".into(), Box::new(Format::EMPTY)),
+    )
+}
+
+/// Parses `format` as with [`cond_field`], provided that at least `n` bytes remain in the innermost
+/// enclosing slice (or the input, if there is none), and otherwise parses nothing.
+///
+/// Intended for fields that were added in later versions of a format, so that older and shorter
+/// variants of a structure are tolerated rather than rejected. Once enough bytes are known to remain,
+/// a failure to parse `format` is not recovered from. The `none` and `some` variants yielded can be
+/// converted into [`Value::Option`] by [`Value::flatten_singletons`].
+pub fn if_remaining(n: u32, format: Format) -> Format {
+    let enough = Format::Peek(Box::new(map(
+        repeat_count(Expr::U32(n), Format::Byte(ByteSet::full())),
+        lambda("_", Expr::Bool(true)),
+    )));
+    map(
+        record([
+            ("enough", optional_or(enough, Expr::Bool(false))),
+            ("value", cond_field(var("enough"), format)),
+        ]),
+        lambda("x", record_proj(var("x"), "value")),
+    )
+}
+
+/// Parses the record-fields in `prefix` without advancing the stream position, binds the value of the
+/// last field of `prefix` to the identifier `bind`, and then parses `body` from the original position.
+///
+/// The value produced is that of `body`.
+pub fn peek_field_then<Name: IntoLabel>(
+    prefix: impl IntoIterator<Item = (Name, Format)>,
+    bind: impl IntoLabel,
+    body: Format,
+) -> Format {
+    let prefix: Vec<(Label, Format)> = prefix
+        .into_iter()
+        .map(|(label, format)| (label.into(), format))
+        .collect();
+    let Some((last, _)) = prefix.last() else {
+        panic!("peek_field_then: empty prefix");
+    };
+    let field = record_proj(var("__peek"), last.clone());
+    map(
+        record([
+            ("__peek", Format::Peek(Box::new(Format::Record(prefix)))),
+            ("__body", Format::Let(bind.into(), field, Box::new(body))),
+        ]),
+        lambda("x", record_proj(var("x"), "__body")),
+    )
+}
+
+/// Parses a stream of tagged-length-value (TLV) entries until the end of the input.
+///
+/// Each entry is a record with fields `tag` and `length`, parsed by the respective formats, followed by
+/// a field `value` parsed from a slice of `length` bytes. The format of `value` is selected by matching
+/// `tag` against the patterns of `value_by_tag` in order, with each parsed value wrapped in a variant of the
+/// corresponding name; tags that match none of the patterns are parsed with `default`, as an `unknown`-variant.
+pub fn tlv_stream<Name: IntoLabel>(
+    tag: Format,
+    length: Format,
+    value_by_tag: impl IntoIterator<Item = (Pattern, Name, Format)>,
+    default: Format,
+) -> Format {
+    let branches = (value_by_tag.into_iter())
+        .map(|(pattern, label, format)| (pattern, label.into(), format))
+        .chain(std::iter::once((
+            Pattern::Wildcard,
+            Label::Borrowed("unknown"),
+            default,
+        )));
+    repeat(record([
+        ("tag", tag),
+        ("length", length),
+        (
+            "value",
+            Format::Slice(var("length"), Box::new(match_variant(var("tag"), branches))),
+        ),
+    ]))
+}
+
+/// Parses the format associated with the first entry of `table` whose value is equal to `key`, or else
+/// `default` if there is one (failing otherwise).
+///
+/// Generalizes dispatch on a parsed tag or version-number to keys computed by arbitrary expressions.
+/// As with [`Format::Match`], all of the formats must produce values of the same type; use
+/// [`match_variant`] directly for heterogeneous branches.
+///
+/// # Panics
+///
+/// Panics if any key in `table` is not a boolean, numeric, or character value, or a tuple thereof.
+pub fn dispatch_by(
+    key: Expr,
+    table: impl IntoIterator<Item = (Value, Format)>,
+    default: Option<Format>,
+) -> Format {
+    fn literal_pattern(value: &Value) -> Pattern {
+        match value {
+            Value::Bool(b) => Pattern::Bool(*b),
+            Value::U8(n) => Pattern::U8(*n),
+            Value::U16(n) => Pattern::U16(*n),
+            Value::U32(n) => Pattern::U32(*n),
+            Value::U64(n) => Pattern::U64(*n),
+            Value::I8(n) => Pattern::I8(*n),
+            Value::I16(n) => Pattern::I16(*n),
+            Value::I32(n) => Pattern::I32(*n),
+            Value::I64(n) => Pattern::I64(*n),
+            Value::Char(c) => Pattern::Char(*c),
+            Value::Tuple(vs) => Pattern::Tuple(vs.iter().map(literal_pattern).collect()),
+            other => panic!("dispatch_by: cannot use {other:?} as a literal key"),
+        }
+    }
+    let branches = (table.into_iter())
+        .map(|(value, format)| (literal_pattern(&value), format))
+        .chain(std::iter::once((
+            Pattern::Wildcard,
+            default.unwrap_or(Format::Fail),
+        )))
+        .collect();
+    Format::Match(key, branches)
+}
+
+/// Parses a byte-order mark with `bom`, followed by a body whose byte order is selected by the value of
+/// the mark, as fields `bom` and `body`.
+///
+/// `body` is called with `true` to build the big-endian variant of the body format, which is used when
+/// the parsed mark is equal to `big`, and with `false` for the little-endian variant, used when it is equal
+/// to `little`. Both variants must produce values of the same type; any other mark is rejected.
+pub fn detect_endian(
+    bom: Format,
+    big: Value,
+    little: Value,
+    body: impl Fn(bool) -> Format,
+) -> Format {
+    record([
+        ("bom", bom),
+        (
+            "body",
+            dispatch_by(var("bom"), [(big, body(true)), (little, body(false))], None),
+        ),
+    ])
+}
+
+/// Parses `format` from a bit-level view of the next `len` bits of input (least-significant bit first),
+/// then skips ahead to the next byte boundary.
+///
+/// As with [`Format::Slice`], any bits left unconsumed by `format` within the `len`-bit window are skipped.
+pub fn bit_slice(len: Expr, format: Format) -> Format {
+    Format::Bits(Box::new(Format::Slice(len, Box::new(format))))
+}
+
+/// Parses a whole number of bytes as a sequence of named bit-fields of the given widths, read
+/// most-significant bit first (i.e. in network order), via a [`Format::Bits`] view of the input.
+///
+/// A field of width `1` yields a boolean flag; any wider field yields a `base`-typed number.
+///
+/// # Panics
+///
+/// Panics if `base` is not an unsigned integer type, if any field is wider than `base`, or if the
+/// widths do not sum to a non-zero multiple of 8.
+pub fn msb_bit_fields<Name: IntoLabel>(
+    base: BaseType,
+    fields: impl IntoIterator<Item = (Name, u32)>,
+) -> Format {
+    const BINDING_NAME: &str = "msbbits";
+    let max_width = match base {
+        BaseType::U8 => 8,
+        BaseType::U16 => 16,
+        BaseType::U32 => 32,
+        BaseType::U64 => 64,
+        _ => panic!("non-unsigned base-type {base:?}"),
+    };
+    let widen = |x: Expr| match base {
+        BaseType::U8 => x,
+        BaseType::U16 => as_u16(x),
+        BaseType::U32 => as_u32(x),
+        _ => as_u64(x),
+    };
+    // The bit-stream of `Format::Bits` is least-significant bit first within each byte
+    let bit_at = |k: u32| tuple_proj(var(BINDING_NAME), (k - k % 8 + (7 - k % 8)) as usize);
+
+    let mut total = 0;
+    let mut record = Vec::new();
+    for (name, width) in fields {
+        assert!(
+            (1..=max_width).contains(&width),
+            "bad bit-field width {width} for {base:?}"
+        );
+        let field = if width == 1 {
+            expr_ne(bit_at(total), Expr::U8(0))
+        } else {
+            (0..width)
+                .map(|j| {
+                    let bit = widen(bit_at(total + j));
+                    match width - 1 - j {
+                        0 => bit,
+                        shift => shl(bit, lit_of(base, shift)),
+                    }
+                })
+                .reduce(bit_or)
+                .unwrap()
+        };
+        record.push((name.into(), field));
+        total += width;
+    }
+    assert!(
+        total > 0 && total % 8 == 0,
+        "bad bit-field widths: total width {total} is not a whole number of bytes"
+    );
+
+    let bit = Format::Byte(ByteSet::from([0, 1]));
+    map(
+        bit_slice(Expr::U32(total), tuple((0..total).map(|_| bit.clone()))),
+        lambda(BINDING_NAME, Expr::Record(record)),
+    )
+}
+
+/// Skips ahead to the next multiple of `n` bytes from the start of the input, as does [`Format::Align`],
+/// but yields the sequence of padding bytes skipped rather than a unit value.
+///
+/// The padding is preserved as-is, so that it can be reproduced exactly whether or not it is zeroed.
+pub fn align_padding(n: u32) -> Format {
+    let pad_len = rem(
+        sub(Expr::U32(n), rem(var("pos"), Expr::U32(n))),
+        Expr::U32(n),
+    );
+    map(
+        record([
+            ("pos", Format::AbsolutePos),
+            (
+                "padding",
+                repeat_count(pad_len, Format::Byte(ByteSet::full())),
+            ),
+        ]),
+        lambda("x", record_proj(var("x"), "padding")),
+    )
+}
+
+/// Consumes zero-valued padding bytes until the position within the innermost enclosing slice
+/// (see [`Format::SlicePos`]) reaches `target`, a U32-valued expression, yielding a unit value.
+///
+/// Fails without consuming any input if the current position is already beyond `target`.
+pub fn pad_to(target: Expr) -> Format {
+    map(
+        record([
+            ("pos", Format::SlicePos),
+            (
+                "padding",
+                if_then_else(
+                    expr_lte(var("pos"), target.clone()),
+                    repeat_count(sub(target, var("pos")), is_byte(0x00)),
+                    Format::Fail,
+                ),
+            ),
+        ]),
+        lambda("_", Expr::UNIT),
+    )
+}
+
+/// Parses `body` followed by `trailer`, as fields of the same names, where `trailer` must consume
+/// everything that remains of the innermost enclosing slice (or the input, if there is none).
+///
+/// Intended for layouts where meaningful data is followed by padding, a checksum, or both, up to
+/// the end of a length-delimited region.
+pub fn with_trailer(body: Format, trailer: Format) -> Format {
+    record([
+        ("body", body),
+        (
+            "trailer",
+            map(
+                tuple([trailer, Format::EndOfInput]),
+                lambda("x", tuple_proj(var("x"), 0)),
+            ),
+        ),
+    ])
+}
+
+/// Algorithm by which [`with_checksum`] summarizes the bytes of a region
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumKind {
+    /// The sum of the bytes modulo 256, as a `u8`
+    Sum8,
+    /// The sum of the bytes, as a `u32`
+    Sum32,
+}
+
+/// Parses `body`, yielding a record of its value (as `body`) alongside a checksum of the bytes it
+/// spans (as `checksum`), computed according to `kind`, for the caller to verify as they see fit.
+///
+/// The extent of `body` is determined by parsing it ahead of time, so it is parsed twice in all.
+pub fn with_checksum(kind: ChecksumKind, body: Format) -> Format {
+    let span = record([
+        ("start", Format::AbsolutePos),
+        ("body", body.clone()),
+        ("end", Format::AbsolutePos),
+    ]);
+    let span_len = sub(record_proj(var("x"), "end"), record_proj(var("x"), "start"));
+    let (acc, byte) = (
+        tuple_proj(var("acc_byte"), 0),
+        tuple_proj(var("acc_byte"), 1),
+    );
+    let (zero, total, base) = match kind {
+        ChecksumKind::Sum8 => (
+            Expr::U8(0),
+            as_u8(rem(add(as_u16(acc), as_u16(byte)), Expr::U16(256))),
+            BaseType::U8,
+        ),
+        ChecksumKind::Sum32 => (Expr::U32(0), add(acc, as_u32(byte)), BaseType::U32),
+    };
+    let totals = flat_map_accum(
+        lambda(
+            "acc_byte",
+            Expr::Tuple(vec![total.clone(), Expr::Seq(vec![total])]),
+        ),
+        zero.clone(),
+        ValueType::Base(base),
+        var("bytes"),
+    );
+    let last_total = expr_match(
+        sub_seq(totals, sub(var("len"), Expr::U32(1)), Expr::U32(1)),
+        [(Pattern::Seq(vec![bind("total")]), var("total"))],
+    );
+    let checksum = expr_match(
+        var("len"),
+        [(Pattern::U32(0), zero), (Pattern::Wildcard, last_total)],
+    );
+    map(
+        record([
+            (
+                "len",
+                Format::Peek(Box::new(map(span, lambda("x", span_len)))),
+            ),
+            (
+                "bytes",
+                Format::Peek(Box::new(repeat_count(
+                    var("len"),
+                    Format::Byte(ByteSet::full()),
+                ))),
+            ),
+            ("body", body),
+            ("checksum", Format::Compute(checksum)),
+        ]),
+        lambda(
+            "x",
+            Expr::Record(vec![
+                ("body".into(), record_proj(var("x"), "body")),
+                ("checksum".into(), record_proj(var("x"), "checksum")),
+            ]),
+        ),
+    )
+}
+
+/// Parses a sequence of `format` until the end of input, keeping only the first element for each
+/// distinct value of `key`, a lambda projecting each element onto a `key_type`-typed number.
+///
+/// Later elements whose key has already been seen are parsed but otherwise discarded.
+pub fn repeat_dedup(key: Expr, key_type: BaseType, format: Format) -> Format {
+    let Expr::Lambda(key_name, key_body) = key else {
+        panic!("repeat_dedup: key must be a lambda, found {key:?}")
+    };
+    let seen = tuple_proj(var("seen_x"), 0);
+    let elem = tuple_proj(var("seen_x"), 1);
+    let is_seen = expr_gt(
+        seq_length(flat_map(
+            lambda(
+                "k",
+                expr_match(
+                    expr_eq(var("k"), var("key")),
+                    [
+                        (Pattern::Bool(true), Expr::Seq(vec![var("k")])),
+                        (Pattern::Wildcard, Expr::Seq(vec![])),
+                    ],
+                ),
+            ),
+            seen.clone(),
+        )),
+        Expr::U32(0),
+    );
+    let step = expr_match(
+        is_seen,
+        [
+            (
+                Pattern::Bool(true),
+                Expr::Tuple(vec![seen.clone(), Expr::Seq(vec![])]),
+            ),
+            (
+                Pattern::Wildcard,
+                Expr::Tuple(vec![
+                    flat_map(
+                        lambda("ks", var("ks")),
+                        Expr::Seq(vec![seen, Expr::Seq(vec![var("key")])]),
+                    ),
+                    Expr::Seq(vec![elem.clone()]),
+                ]),
+            ),
+        ],
+    );
+    map(
+        repeat(format),
+        lambda(
+            "xs",
+            flat_map_accum(
+                lambda(
+                    "seen_x",
+                    expr_match(
+                        expr_match(elem, [(Pattern::Binding(key_name), *key_body)]),
+                        [(bind("key"), step)],
+                    ),
+                ),
+                Expr::Seq(vec![]),
+                ValueType::Seq(Box::new(ValueType::Base(key_type))),
+                var("xs"),
+            ),
+        ),
+    )
+}
+
 pub fn map(f: Format, expr: Expr) -> Format {
     Format::Map(Box::new(f), expr)
 }
 
+/// Parses `f` and applies the lambda `expr` to the result, which must evaluate to either a `some`-variant
+/// or a `none`-variant.
+///
+/// Yields the contents of the `some`-variant, or fails the parse on `none`. Unlike following a `map`
+/// with a separate validation step, the transformation and the check for its success are one and the same.
+pub fn try_map(f: Format, expr: Expr) -> Format {
+    map(
+        record([
+            ("__raw", map(f, expr)),
+            (
+                "__ok",
+                Format::Match(
+                    var("__raw"),
+                    vec![
+                        (
+                            Pattern::variant("some", bind("x")),
+                            Format::Compute(var("x")),
+                        ),
+                        (Pattern::variant("none", Pattern::Wildcard), Format::Fail),
+                    ],
+                ),
+            ),
+        ]),
+        lambda("x", record_proj(var("x"), "__ok")),
+    )
+}
+
+/// Parses `f` and binds its value to `name`, failing the parse unless the boolean expression `cond`
+/// holds of it.
+///
+/// Yields the value of `f` unchanged when `cond` is satisfied.
+pub fn where_lambda(f: Format, name: impl IntoLabel, cond: Expr) -> Format {
+    let name = name.into();
+    map(
+        record([
+            (name.clone(), f),
+            (
+                Label::Borrowed("__where"),
+                if_then_else(cond, Format::EMPTY, Format::Fail),
+            ),
+        ]),
+        lambda("x", record_proj(var("x"), name)),
+    )
+}
+
 pub fn is_byte(b: u8) -> Format {
     Format::Byte(ByteSet::from([b]))
 }
@@ -168,10 +853,33 @@ pub fn not_byte(b: u8) -> Format {
     Format::Byte(!ByteSet::from([b]))
 }
 
+/// Parses a C-style string: a sequence of non-zero bytes followed by a NUL terminator.
+///
+/// The terminator is consumed but excluded from the resulting sequence; input that ends before
+/// the terminator is reached is rejected.
+pub fn cstring() -> Format {
+    map(
+        tuple([repeat(not_byte(0x00)), is_byte(0x00)]),
+        lambda("x", tuple_proj(var("x"), 0)),
+    )
+}
+
 pub fn is_bytes(bytes: &[u8]) -> Format {
     tuple(bytes.iter().copied().map(is_byte))
 }
 
+/// Parses the signature `magic` followed by `body`, yielding the value of `body`.
+///
+/// If the signature does not match, parsing fails with an error reporting that the input is not a
+/// valid `format_name` file.
+pub fn signature(format_name: impl IntoLabel, magic: &[u8], body: Format) -> Format {
+    Format::Signature(
+        format_name.into(),
+        Box::new(is_bytes(magic)),
+        Box::new(body),
+    )
+}
+
 pub fn record_proj(head: impl Into<Expr>, label: impl IntoLabel) -> Expr {
     Expr::RecordProj(Box::new(head.into()), label.into())
 }
@@ -192,6 +900,10 @@ pub fn expr_gt(x: Expr, y: Expr) -> Expr {
     Expr::IntRel(IntRel::Gt, Box::new(x), Box::new(y))
 }
 
+pub fn expr_lte(x: Expr, y: Expr) -> Expr {
+    Expr::IntRel(IntRel::Lte, Box::new(x), Box::new(y))
+}
+
 pub fn expr_gte(x: Expr, y: Expr) -> Expr {
     Expr::IntRel(IntRel::Gte, Box::new(x), Box::new(y))
 }
@@ -212,10 +924,57 @@ pub fn as_u64(x: Expr) -> Expr {
     Expr::AsU64(Box::new(x))
 }
 
+/// Reinterprets a `U8` as the `I8` with the same two's-complement bit-pattern (e.g. `0x80` as `-128`)
+pub fn as_signed8(x: Expr) -> Expr {
+    Expr::AsSigned8(Box::new(x))
+}
+
+/// Reinterprets a `U16` as the `I16` with the same two's-complement bit-pattern (e.g. `0xFFFF` as `-1`)
+pub fn as_signed16(x: Expr) -> Expr {
+    Expr::AsSigned16(Box::new(x))
+}
+
+/// Reinterprets a `U32` as the `I32` with the same two's-complement bit-pattern
+pub fn as_signed32(x: Expr) -> Expr {
+    Expr::AsSigned32(Box::new(x))
+}
+
 pub fn as_char(x: Expr) -> Expr {
     Expr::AsChar(Box::new(x))
 }
 
+/// Zero-extends the `width`-tuple of U8-valued bytes `bytes` to a `full`-tuple, padding with zero
+/// bytes on the high-order end according to the byte-order `big_endian`.
+fn widen_bytes(bytes: Expr, width: usize, full: usize, big_endian: bool) -> Expr {
+    let pad = vec![Expr::U8(0); full - width];
+    let elts = (0..width).map(|ix| tuple_proj(bytes.clone(), ix));
+    Expr::Tuple(if big_endian {
+        pad.into_iter().chain(elts).collect()
+    } else {
+        elts.chain(pad).collect()
+    })
+}
+
+/// Assembles a U32 from a 3-tuple of bytes in big-endian order.
+pub fn u24be(bytes: Expr) -> Expr {
+    Expr::U32Be(Box::new(widen_bytes(bytes, 3, 4, true)))
+}
+
+/// Assembles a U32 from a 3-tuple of bytes in little-endian order.
+pub fn u24le(bytes: Expr) -> Expr {
+    Expr::U32Le(Box::new(widen_bytes(bytes, 3, 4, false)))
+}
+
+/// Assembles a U64 from a 6-tuple of bytes in big-endian order.
+pub fn u48be(bytes: Expr) -> Expr {
+    Expr::U64Be(Box::new(widen_bytes(bytes, 6, 8, true)))
+}
+
+/// Assembles a U64 from a 6-tuple of bytes in little-endian order.
+pub fn u48le(bytes: Expr) -> Expr {
+    Expr::U64Le(Box::new(widen_bytes(bytes, 6, 8, false)))
+}
+
 pub fn add(x: Expr, y: Expr) -> Expr {
     Expr::Arith(Arith::Add, Box::new(x), Box::new(y))
 }
@@ -224,6 +983,14 @@ pub fn sub(x: Expr, y: Expr) -> Expr {
     Expr::Arith(Arith::Sub, Box::new(x), Box::new(y))
 }
 
+pub fn mul(x: Expr, y: Expr) -> Expr {
+    Expr::Arith(Arith::Mul, Box::new(x), Box::new(y))
+}
+
+pub fn div(x: Expr, y: Expr) -> Expr {
+    Expr::Arith(Arith::Div, Box::new(x), Box::new(y))
+}
+
 pub fn rem(x: Expr, y: Expr) -> Expr {
     Expr::Arith(Arith::Rem, Box::new(x), Box::new(y))
 }
@@ -232,6 +999,10 @@ pub fn bit_or(x: Expr, y: Expr) -> Expr {
     Expr::Arith(Arith::BitOr, Box::new(x), Box::new(y))
 }
 
+pub fn bit_xor(x: Expr, y: Expr) -> Expr {
+    Expr::Arith(Arith::BitXor, Box::new(x), Box::new(y))
+}
+
 pub fn bit_and(x: Expr, y: Expr) -> Expr {
     Expr::Arith(Arith::BitAnd, Box::new(x), Box::new(y))
 }
@@ -244,10 +1015,40 @@ pub fn shr(value: Expr, places: Expr) -> Expr {
     Expr::Arith(Arith::Shr, Box::new(value), Box::new(places))
 }
 
+/// Decodes a raw U32-valued Fixed16.16 number into a tuple `(integer_part, fractional_numerator)`,
+/// both U16-valued, where the fractional part is `fractional_numerator / 2^16`.
+///
+/// The integer part is the raw two's-complement bit-pattern of the signed 16-bit integer part.
+pub fn fixed16_16(raw: Expr) -> Expr {
+    Expr::Tuple(vec![
+        as_u16(shr(raw.clone(), Expr::U32(16))),
+        as_u16(bit_and(raw, Expr::U32(0xFFFF))),
+    ])
+}
+
+/// Decodes a raw U16-valued F2Dot14 number into a tuple `(integer_part, fractional_numerator)`,
+/// both U16-valued, where the fractional part is `fractional_numerator / 2^14`.
+///
+/// The integer part is the raw two's-complement bit-pattern of the signed 2-bit integer part.
+pub fn f2dot14(raw: Expr) -> Expr {
+    Expr::Tuple(vec![
+        shr(raw.clone(), Expr::U16(14)),
+        bit_and(raw, Expr::U16(0x3FFF)),
+    ])
+}
+
 pub fn seq_length(seq: Expr) -> Expr {
     Expr::SeqLength(Box::new(seq))
 }
 
+pub fn seq_ix(seq: Expr, index: Expr) -> Expr {
+    Expr::SeqIx(Box::new(seq), Box::new(index))
+}
+
+pub fn seq_is_empty(seq: Expr) -> Expr {
+    expr_eq(seq_length(seq), Expr::U32(0))
+}
+
 pub fn sub_seq(seq: Expr, start: Expr, length: Expr) -> Expr {
     Expr::SubSeq(Box::new(seq), Box::new(start), Box::new(length))
 }
@@ -271,3 +1072,129 @@ pub fn flat_map_list(f: Expr, ret_type: ValueType, seq: Expr) -> Expr {
 pub fn dup(count: Expr, expr: Expr) -> Expr {
     Expr::Dup(Box::new(count), Box::new(expr))
 }
+
+pub fn tuple_proj(head: Expr, index: usize) -> Expr {
+    Expr::TupleProj(Box::new(head), index)
+}
+
+/// Projects a value nested within `head` by following `path`, one accessor at a time.
+///
+/// Field-accessors become record-projections, index-accessors become tuple-projections, and
+/// variant-accessors unwrap the contents of the named variant (failing to match any other variant).
+pub fn project_path(head: Expr, path: impl IntoIterator<Item = Accessor>) -> Expr {
+    path.into_iter()
+        .fold(head, |expr, accessor| match accessor {
+            Accessor::Field(label) => record_proj(expr, label),
+            Accessor::Index(ix) => tuple_proj(expr, ix),
+            Accessor::Variant(label) => expr_match(
+                expr,
+                [(Pattern::variant(label, bind("inner")), var("inner"))],
+            ),
+        })
+}
+
+/// Returns the literal zero of the given numeric base-type
+fn zero_of(base: BaseType) -> Expr {
+    match base {
+        BaseType::U8 => Expr::U8(0),
+        BaseType::U16 => Expr::U16(0),
+        BaseType::U32 => Expr::U32(0),
+        BaseType::U64 => Expr::U64(0),
+        BaseType::I8 => Expr::I8(0),
+        BaseType::I16 => Expr::I16(0),
+        BaseType::I32 => Expr::I32(0),
+        BaseType::I64 => Expr::I64(0),
+        BaseType::Bool | BaseType::Char => panic!("non-numeric base-type {base:?}"),
+    }
+}
+
+/// Returns the literal `n` of the given numeric base-type
+fn lit_of(base: BaseType, n: u32) -> Expr {
+    match base {
+        BaseType::U8 => Expr::U8(u8::try_from(n).expect("literal out of range for U8")),
+        BaseType::U16 => Expr::U16(u16::try_from(n).expect("literal out of range for U16")),
+        BaseType::U32 => Expr::U32(n),
+        BaseType::U64 => Expr::U64(u64::from(n)),
+        BaseType::I8 => Expr::I8(i8::try_from(n).expect("literal out of range for I8")),
+        BaseType::I16 => Expr::I16(i16::try_from(n).expect("literal out of range for I16")),
+        BaseType::I32 => Expr::I32(i32::try_from(n).expect("literal out of range for I32")),
+        BaseType::I64 => Expr::I64(i64::from(n)),
+        BaseType::Bool | BaseType::Char => panic!("non-numeric base-type {base:?}"),
+    }
+}
+
+/// Transforms a sequence of `base`-typed numbers into the sequence of running totals of its elements,
+/// e.g. decoding the delta-coded sequence `[1, 2, 3]` into the absolute values `[1, 3, 6]`.
+pub fn cumulative_sum(base: BaseType, seq: Expr) -> Expr {
+    let total = add(tuple_proj(var("acc_x"), 0), tuple_proj(var("acc_x"), 1));
+    flat_map_accum(
+        lambda(
+            "acc_x",
+            Expr::Tuple(vec![total.clone(), Expr::Seq(vec![total])]),
+        ),
+        zero_of(base),
+        ValueType::Base(base),
+        seq,
+    )
+}
+
+/// Transforms a sequence of `base`-typed numbers into the sequence of differences between each element
+/// and its predecessor (treating the first element as the difference from zero), e.g. `[1, 3, 6]` into `[1, 2, 3]`.
+///
+/// This is the inverse of [`cumulative_sum`], and requires the sequence to be non-decreasing.
+pub fn deltas(base: BaseType, seq: Expr) -> Expr {
+    flat_map_accum(
+        lambda(
+            "prev_x",
+            Expr::Tuple(vec![
+                tuple_proj(var("prev_x"), 1),
+                Expr::Seq(vec![sub(
+                    tuple_proj(var("prev_x"), 1),
+                    tuple_proj(var("prev_x"), 0),
+                )]),
+            ]),
+        ),
+        zero_of(base),
+        ValueType::Base(base),
+        seq,
+    )
+}
+
+/// Expands a sequence of `(index, value)` entries, in strictly increasing order of index, into a dense
+/// sequence of `len` elements of type `elem_type`, filling every index without an entry with `default`.
+///
+/// For example, `[(1, 0xAA), (3, 0xBB)]` with length 5 and default 0 expands to `[0, 0xAA, 0, 0xBB, 0]`.
+///
+/// Fails to parse if any entry has an index of `len` or greater.
+pub fn sparse_to_dense(elem_type: ValueType, len: Expr, default: Expr, entries: Expr) -> Format {
+    let concat = |seqs: Vec<Expr>| flat_map(lambda("xs", var("xs")), Expr::Seq(seqs));
+    let entry = || tuple_proj(var("dense_entry"), 1);
+    let gap = sub(
+        as_u32(tuple_proj(entry(), 0)),
+        seq_length(tuple_proj(var("dense_entry"), 0)),
+    );
+    let filled = flat_map_list(
+        lambda(
+            "dense_entry",
+            concat(vec![
+                dup(gap, default.clone()),
+                Expr::Seq(vec![tuple_proj(entry(), 1)]),
+            ]),
+        ),
+        elem_type,
+        entries,
+    );
+    let tail = dup(
+        sub(as_u32(len.clone()), seq_length(var("dense_filled"))),
+        default,
+    );
+    // NOTE - the length is checked before padding, as the padding would otherwise underflow
+    map(
+        where_lambda(
+            Format::Compute(filled),
+            "dense_filled",
+            expr_lte(seq_length(var("dense_filled")), as_u32(len)),
+        ),
+        lambda("dense_filled", concat(vec![var("dense_filled"), tail])),
+    )
+}
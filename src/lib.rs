@@ -2,7 +2,8 @@
 #![deny(rust_2018_idioms)]
 
 use std::cmp::Ordering;
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
 use std::ops::Add;
 use std::rc::Rc;
 
@@ -53,12 +54,30 @@ pub enum BaseType {
     U16,
     U32,
     U64,
+    I8,
+    I16,
+    I32,
+    I64,
     Char,
 }
 
 impl BaseType {
     fn is_numeric(&self) -> bool {
-        matches!(self, Self::U8 | Self::U16 | Self::U32 | Self::U64)
+        matches!(
+            self,
+            Self::U8
+                | Self::U16
+                | Self::U32
+                | Self::U64
+                | Self::I8
+                | Self::I16
+                | Self::I32
+                | Self::I64
+        )
+    }
+
+    fn is_signed(&self) -> bool {
+        matches!(self, Self::I8 | Self::I16 | Self::I32 | Self::I64)
     }
 }
 
@@ -71,6 +90,8 @@ pub enum ValueType {
     Record(Vec<(Label, ValueType)>),
     Union(BTreeMap<Label, ValueType>),
     Seq(Box<ValueType>),
+    /// Nominal wrapper around an inner type, distinct from any other type (including its own inner type)
+    Newtype(Label, Box<ValueType>),
 }
 
 fn mk_value_expr(vt: &ValueType) -> Option<Expr> {
@@ -82,6 +103,10 @@ fn mk_value_expr(vt: &ValueType) -> Option<Expr> {
             BaseType::U16 => Expr::U16(0),
             BaseType::U32 => Expr::U32(0),
             BaseType::U64 => Expr::U64(0),
+            BaseType::I8 => Expr::I8(0),
+            BaseType::I16 => Expr::I16(0),
+            BaseType::I32 => Expr::I32(0),
+            BaseType::I64 => Expr::I64(0),
             BaseType::Char => Expr::AsChar(Box::new(Expr::U32(0))),
         }),
         ValueType::Tuple(ts) => {
@@ -103,6 +128,7 @@ fn mk_value_expr(vt: &ValueType) -> Option<Expr> {
             Some(Expr::Variant(lbl.clone(), Box::new(mk_value_expr(branch)?)))
         }
         ValueType::Seq(t) => Some(Expr::Seq(vec![mk_value_expr(t.as_ref())?])),
+        ValueType::Newtype(_name, t) => mk_value_expr(t.as_ref()),
     }
 }
 
@@ -142,7 +168,9 @@ impl ValueType {
         match (self, other) {
             (ValueType::Any, rhs) => Ok(rhs.clone()),
             (lhs, ValueType::Any) => Ok(lhs.clone()),
-            (ValueType::Empty, ValueType::Empty) => Ok(ValueType::Empty),
+            // NOTE - `Empty` is the type of `Format::Fail`, which never yields a value, so it is compatible with any other branch
+            (ValueType::Empty, rhs) => Ok(rhs.clone()),
+            (lhs, ValueType::Empty) => Ok(lhs.clone()),
             (ValueType::Base(b1), ValueType::Base(b2)) => {
                 if b1 == b2 {
                     Ok(ValueType::Base(*b1))
@@ -201,6 +229,9 @@ impl ValueType {
                 Ok(ValueType::Union(bs))
             }
             (ValueType::Seq(t1), ValueType::Seq(t2)) => Ok(ValueType::Seq(Box::new(t1.unify(t2)?))),
+            (ValueType::Newtype(n1, t1), ValueType::Newtype(n2, t2)) if n1 == n2 => {
+                Ok(ValueType::Newtype(n1.clone(), Box::new(t1.unify(t2)?)))
+            }
             (t1, t2) => Err(UnificationError::Unsatisfiable(t1.clone(), t2.clone())),
         }
     }
@@ -225,6 +256,7 @@ pub enum Arith {
     Rem,
     BitAnd,
     BitOr,
+    BitXor,
     Shl,
     Shr,
 }
@@ -238,6 +270,10 @@ pub enum Expr {
     U16(u16),
     U32(u32),
     U64(u64),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
     Tuple(Vec<Expr>),
     TupleProj(Box<Expr>, usize),
     Record(Vec<(Label, Expr)>),
@@ -255,6 +291,12 @@ pub enum Expr {
     AsU32(Box<Expr>),
     AsU64(Box<Expr>),
     AsChar(Box<Expr>),
+    /// Reinterprets a `U8` as the `I8` with the same two's-complement bit-pattern
+    AsSigned8(Box<Expr>),
+    /// Reinterprets a `U16` as the `I16` with the same two's-complement bit-pattern
+    AsSigned16(Box<Expr>),
+    /// Reinterprets a `U32` as the `I32` with the same two's-complement bit-pattern
+    AsSigned32(Box<Expr>),
 
     U16Be(Box<Expr>),
     U16Le(Box<Expr>),
@@ -262,8 +304,14 @@ pub enum Expr {
     U32Le(Box<Expr>),
     U64Be(Box<Expr>),
     U64Le(Box<Expr>),
+    I16Be(Box<Expr>),
+    I16Le(Box<Expr>),
+    I32Be(Box<Expr>),
+    I32Le(Box<Expr>),
 
     SeqLength(Box<Expr>),
+    /// Element of a sequence at a U32-valued index, which must be in-bounds
+    SeqIx(Box<Expr>, Box<Expr>),
     SubSeq(Box<Expr>, Box<Expr>, Box<Expr>),
     SubSeqInflate(Box<Expr>, Box<Expr>, Box<Expr>),
     FlatMap(Box<Expr>, Box<Expr>),
@@ -287,6 +335,60 @@ impl Expr {
 
         Expr::RecordProj(head, label)
     }
+
+    /// Returns `true` if evaluating this expression can never raise a parse error.
+    ///
+    /// Matches (which may be refutable) and the flat-map family (which propagate errors out of their
+    /// lambdas) are conservatively assumed to be fallible.
+    pub fn is_infallible(&self) -> bool {
+        match self {
+            Expr::Var(_)
+            | Expr::Bool(_)
+            | Expr::U8(_)
+            | Expr::U16(_)
+            | Expr::U32(_)
+            | Expr::U64(_)
+            | Expr::I8(_)
+            | Expr::I16(_)
+            | Expr::I32(_)
+            | Expr::I64(_) => true,
+            Expr::Tuple(xs) | Expr::Seq(xs) => xs.iter().all(Expr::is_infallible),
+            Expr::Record(fields) => fields.iter().all(|(_, x)| x.is_infallible()),
+            Expr::TupleProj(x, _)
+            | Expr::RecordProj(x, _)
+            | Expr::Variant(_, x)
+            | Expr::Lambda(_, x)
+            | Expr::AsU8(x)
+            | Expr::AsU16(x)
+            | Expr::AsU32(x)
+            | Expr::AsU64(x)
+            | Expr::AsChar(x)
+            | Expr::AsSigned8(x)
+            | Expr::AsSigned16(x)
+            | Expr::AsSigned32(x)
+            | Expr::U16Be(x)
+            | Expr::U16Le(x)
+            | Expr::U32Be(x)
+            | Expr::U32Le(x)
+            | Expr::U64Be(x)
+            | Expr::U64Le(x)
+            | Expr::I16Be(x)
+            | Expr::I16Le(x)
+            | Expr::I32Be(x)
+            | Expr::I32Le(x)
+            | Expr::SeqLength(x) => x.is_infallible(),
+            Expr::IntRel(_, x, y) | Expr::Arith(_, x, y) | Expr::SeqIx(x, y) | Expr::Dup(x, y) => {
+                x.is_infallible() && y.is_infallible()
+            }
+            Expr::SubSeq(seq, ix, len) | Expr::SubSeqInflate(seq, ix, len) => {
+                seq.is_infallible() && ix.is_infallible() && len.is_infallible()
+            }
+            Expr::Match(..)
+            | Expr::FlatMap(..)
+            | Expr::FlatMapAccum(..)
+            | Expr::FlatMapList(..) => false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -310,6 +412,10 @@ impl Expr {
             Expr::U16(_n) => Ok(ValueType::Base(BaseType::U16)),
             Expr::U32(_n) => Ok(ValueType::Base(BaseType::U32)),
             Expr::U64(_n) => Ok(ValueType::Base(BaseType::U64)),
+            Expr::I8(_n) => Ok(ValueType::Base(BaseType::I8)),
+            Expr::I16(_n) => Ok(ValueType::Base(BaseType::I16)),
+            Expr::I32(_n) => Ok(ValueType::Base(BaseType::I32)),
+            Expr::I64(_n) => Ok(ValueType::Base(BaseType::I64)),
             Expr::Tuple(exprs) => {
                 let mut ts = Vec::new();
                 for expr in exprs {
@@ -391,6 +497,18 @@ impl Expr {
                 ValueType::Base(b) if b.is_numeric() => Ok(ValueType::Base(BaseType::U64)),
                 x => Err(anyhow!("cannot convert {x:?} to U64")),
             },
+            Expr::AsSigned8(x) => match x.infer_type(scope)? {
+                ValueType::Base(BaseType::U8) => Ok(ValueType::Base(BaseType::I8)),
+                x => Err(anyhow!("unsound type cast AsSigned8(_ : {x:?})")),
+            },
+            Expr::AsSigned16(x) => match x.infer_type(scope)? {
+                ValueType::Base(BaseType::U16) => Ok(ValueType::Base(BaseType::I16)),
+                x => Err(anyhow!("unsound type cast AsSigned16(_ : {x:?})")),
+            },
+            Expr::AsSigned32(x) => match x.infer_type(scope)? {
+                ValueType::Base(BaseType::U32) => Ok(ValueType::Base(BaseType::I32)),
+                x => Err(anyhow!("unsound type cast AsSigned32(_ : {x:?})")),
+            },
             Expr::AsChar(x) => match x.infer_type(scope)? {
                 ValueType::Base(b) if b.is_numeric() => Ok(ValueType::Base(BaseType::Char)),
                 x => Err(anyhow!("unsound type cast AsChar(_ : {x:?})")),
@@ -442,10 +560,40 @@ impl Expr {
                     )),
                 }
             }
+            Expr::I16Be(bytes) | Expr::I16Le(bytes) => {
+                let _t = bytes.infer_type(scope)?;
+                match _t.as_tuple_type() {
+                    [ValueType::Base(BaseType::U8), ValueType::Base(BaseType::U8)] => {
+                        Ok(ValueType::Base(BaseType::I16))
+                    }
+                    other => Err(anyhow!("I16Be/Le: expected (U8, U8), found {other:#?}")),
+                }
+            }
+            Expr::I32Be(bytes) | Expr::I32Le(bytes) => {
+                let _t = bytes.infer_type(scope)?;
+                match _t.as_tuple_type() {
+                    [ValueType::Base(BaseType::U8), ValueType::Base(BaseType::U8), ValueType::Base(BaseType::U8), ValueType::Base(BaseType::U8)] => {
+                        Ok(ValueType::Base(BaseType::I32))
+                    }
+                    other => Err(anyhow!(
+                        "I32Be/Le: expected (U8, U8, U8, U8), found {other:#?}"
+                    )),
+                }
+            }
             Expr::SeqLength(seq) => match seq.infer_type(scope)? {
                 ValueType::Seq(_t) => Ok(ValueType::Base(BaseType::U32)),
                 other => Err(anyhow!("seq-length called on non-sequence type: {other:?}")),
             },
+            Expr::SeqIx(seq, index) => match seq.infer_type(scope)? {
+                ValueType::Seq(t) => {
+                    let index_type = index.infer_type(scope)?;
+                    if index_type != ValueType::Base(BaseType::U32) {
+                        return Err(anyhow!("SeqIx index: expected U32, found {index_type:?}"));
+                    }
+                    Ok(*t)
+                }
+                other => Err(anyhow!("SeqIx: expected Seq, found {other:?}")),
+            },
             Expr::SubSeq(seq, start, length) => match seq.infer_type(scope)? {
                 ValueType::Seq(t) => {
                     let start_type = start.infer_type(scope)?;
@@ -616,10 +764,21 @@ pub enum Format {
     EndOfInput,
     /// Skips bytes if necessary to align the current offset to a multiple of N
     Align(usize),
+    /// Yields the current offset into the whole input buffer as a `u32`, without consuming any input
+    ///
+    /// Within a `Slice`, this is still measured from the start of the buffer (as needed to compute
+    /// the targets of offsets that are themselves measured from the start of the file).
+    AbsolutePos,
+    /// Yields the current offset into the innermost enclosing `Slice` as a `u32`, without consuming any input
+    ///
+    /// Outside of any `Slice`, this is the same as `AbsolutePos`.
+    SlicePos,
     /// Matches a byte in the given byte set
     Byte(ByteSet),
     /// Wraps the value from the inner format in a variant
     Variant(Label, Box<Format>),
+    /// Wraps the value from the inner format in a named newtype, failing if the optional predicate rejects it
+    Newtype(Label, Box<Format>, Option<Expr>),
     /// Matches the union of all the formats, which must have the same type
     Union(Vec<Format>),
     /// Nondeterministic unions, where the formats are not mutually exclusive
@@ -633,6 +792,8 @@ pub enum Format {
     Repeat(Box<Format>),
     /// Repeat a format one-or-more times
     Repeat1(Box<Format>),
+    /// Repeat a format one-or-more times, with a separator format between consecutive elements whose values are discarded
+    RepeatSep(Box<Format>, Box<Format>),
     /// Repeat a format an exact number of times
     RepeatCount(Expr, Box<Format>),
     /// Repeat a format at least N and at most M times
@@ -641,16 +802,28 @@ pub enum Format {
     RepeatUntilLast(Expr, Box<Format>),
     /// Repeat a format until a condition is satisfied by the sequence
     RepeatUntilSeq(Expr, Box<Format>),
+    /// Repeat a format until the given terminator byte-sequence is found at the current position,
+    /// consuming the terminator but not including it in the sequence
+    UntilSeq(Vec<u8>, Box<Format>),
+    /// Parse a format once for each element of a sequence, with the element bound to a name
+    ForEach(Expr, Label, Box<Format>),
+    /// Repeat a format for as long as input remains in the current slice (or the overall buffer)
+    RepeatWhileInput(Box<Format>),
     /// Parse a format without advancing the stream position afterwards
     Peek(Box<Format>),
     /// Attempt to parse a format and fail if it succeeds
     PeekNot(Box<Format>),
+    /// Matches a leading signature (e.g. magic bytes) followed by a body format whose value is produced,
+    /// reporting a mismatched signature as the input not being of the named format
+    Signature(Label, Box<Format>, Box<Format>),
     /// Restrict a format to a sub-stream of a given number of bytes (skips any leftover bytes in the sub-stream)
     Slice(Expr, Box<Format>),
     /// Parse bitstream
     Bits(Box<Format>),
     /// Matches a format at a byte offset relative to the current stream position
     WithRelativeOffset(Expr, Box<Format>),
+    /// Matches a format at a byte offset relative to the current stream position, continuing from where it ends
+    FollowOffset(Expr, Box<Format>),
     /// Map a value with a lambda expression
     Map(Box<Format>, Expr),
     /// Compute a value
@@ -668,6 +841,73 @@ pub enum Format {
 impl Format {
     pub const EMPTY: Format = Format::Tuple(Vec::new());
 
+    /// Returns a format that parses `magic` and then `body`, yielding the value of `body`, as does
+    /// `Format::Signature(_, magic, body)` apart from how a mismatched signature is reported.
+    pub(crate) fn signature_equivalent(magic: &Format, body: &Format) -> Format {
+        let body_of = Expr::TupleProj(Box::new(Expr::Var("x".into())), 1);
+        Format::Map(
+            Box::new(Format::Tuple(vec![magic.clone(), body.clone()])),
+            Expr::Lambda("x".into(), Box::new(body_of)),
+        )
+    }
+
+    /// Returns a format that repeats `body` for as long as `terminator` does not follow, and then
+    /// consumes `terminator`, as does `Format::UntilSeq(terminator, body)`.
+    pub(crate) fn until_seq_equivalent(terminator: &[u8], body: &Format) -> Format {
+        let terminator = Format::Tuple(
+            terminator
+                .iter()
+                .map(|b| Format::Byte(ByteSet::from([*b])))
+                .collect(),
+        );
+        let proj = |ix: usize| {
+            Expr::Lambda(
+                "x".into(),
+                Box::new(Expr::TupleProj(Box::new(Expr::Var("x".into())), ix)),
+            )
+        };
+        let guarded = Format::Map(
+            Box::new(Format::Tuple(vec![
+                Format::PeekNot(Box::new(terminator.clone())),
+                body.clone(),
+            ])),
+            proj(1),
+        );
+        Format::Map(
+            Box::new(Format::Tuple(vec![
+                Format::Repeat(Box::new(guarded)),
+                terminator,
+            ])),
+            proj(0),
+        )
+    }
+
+    /// Returns a format that parses `elem` followed by any number of `sep`-prefixed repetitions of `elem`,
+    /// yielding the sequence of elements, as does `Format::RepeatSep(elem, sep)`.
+    pub(crate) fn repeat_sep_equivalent(elem: &Format, sep: &Format) -> Format {
+        let x = || Box::new(Expr::Var("x".into()));
+        let sep_elem = Format::Map(
+            Box::new(Format::Tuple(vec![sep.clone(), elem.clone()])),
+            Expr::Lambda("x".into(), Box::new(Expr::TupleProj(x(), 1))),
+        );
+        // the first element is prepended to the rest by flattening `[[first], rest]`
+        let elems = Expr::Seq(vec![
+            Expr::Seq(vec![Expr::TupleProj(x(), 0)]),
+            Expr::TupleProj(x(), 1),
+        ]);
+        let identity = Expr::Lambda("xs".into(), Box::new(Expr::Var("xs".into())));
+        Format::Map(
+            Box::new(Format::Tuple(vec![
+                elem.clone(),
+                Format::Repeat(Box::new(sep_elem)),
+            ])),
+            Expr::Lambda(
+                "x".into(),
+                Box::new(Expr::FlatMap(Box::new(identity), Box::new(elems))),
+            ),
+        )
+    }
+
     pub fn alts<Name: IntoLabel>(fields: impl IntoIterator<Item = (Name, Format)>) -> Format {
         Format::Union(
             fields
@@ -685,6 +925,74 @@ impl Format {
                 .collect(),
         )
     }
+
+    /// Hoists the leading record-fields shared by every branch of a union out into an enclosing record,
+    /// whose final field `tail` is the union of the remaining fields of each branch.
+    ///
+    /// Branches may be records or variants of records (as built by [`Format::alts`]), in which case each
+    /// tail keeps the variant of its branch. Anything other than a union of at least two such branches
+    /// with a non-empty common prefix is returned unchanged, as is any union whose common prefix already
+    /// has a field named `tail`.
+    ///
+    /// The hoisted fields are still in scope for the tails, but the value produced is restructured into
+    /// `{ <shared fields>, tail: <union of tails> }`.
+    pub fn factor_union_prefix(self) -> Format {
+        type Split<'a> = (Option<&'a Label>, &'a [(Label, Format)]);
+
+        fn split(branch: &Format) -> Option<Split<'_>> {
+            match branch {
+                Format::Record(fields) => Some((None, fields)),
+                Format::Variant(label, inner) => match inner.as_ref() {
+                    Format::Record(fields) => Some((Some(label), fields)),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+
+        let (branches, is_nondet) = match &self {
+            Format::Union(branches) => (branches, false),
+            Format::UnionNondet(branches) => (branches, true),
+            _ => return self,
+        };
+        let Some(parts) = branches.iter().map(split).collect::<Option<Vec<_>>>() else {
+            return self;
+        };
+        let Some(((_, first), rest)) = parts.split_first() else {
+            return self;
+        };
+        let prefix_len = rest.iter().fold(first.len(), |len, (_, fields)| {
+            Iterator::zip(first[..len].iter(), fields.iter())
+                .take_while(|(x, y)| x == y)
+                .count()
+        });
+        if rest.is_empty() || prefix_len == 0 {
+            return self;
+        }
+        // the enclosing record cannot hold two fields of the same name
+        if first[..prefix_len].iter().any(|(label, _)| label == "tail") {
+            return self;
+        }
+
+        let tails = parts
+            .iter()
+            .map(|(label, fields)| {
+                let tail = Format::Record(fields[prefix_len..].to_vec());
+                match label {
+                    Some(label) => Format::Variant((*label).clone(), Box::new(tail)),
+                    None => tail,
+                }
+            })
+            .collect();
+        let union = if is_nondet {
+            Format::UnionNondet(tails)
+        } else {
+            Format::Union(tails)
+        };
+        let mut fields = first[..prefix_len].to_vec();
+        fields.push((Label::Borrowed("tail"), union));
+        Format::Record(fields)
+    }
 }
 
 impl Format {
@@ -695,8 +1003,10 @@ impl Format {
             Format::Fail => Bounds::exact(0),
             Format::EndOfInput => Bounds::exact(0),
             Format::Align(n) => Bounds::new(0, Some(n - 1)),
+            Format::AbsolutePos | Format::SlicePos => Bounds::exact(0),
             Format::Byte(_) => Bounds::exact(1),
             Format::Variant(_label, f) => f.match_bounds(module),
+            Format::Newtype(_name, f, _validate) => f.match_bounds(module),
             Format::Union(branches) | Format::UnionNondet(branches) => branches
                 .iter()
                 .map(|f| f.match_bounds(module))
@@ -714,17 +1024,30 @@ impl Format {
                 .unwrap_or(Bounds::exact(0)),
             Format::Repeat(_) => Bounds::new(0, None),
             Format::Repeat1(f) => f.match_bounds(module) * Bounds::new(1, None),
+            Format::RepeatSep(f, sep) => {
+                f.match_bounds(module)
+                    + (sep.match_bounds(module) + f.match_bounds(module)) * Bounds::new(0, None)
+            }
             Format::RepeatCount(expr, f) => f.match_bounds(module) * expr.bounds(),
             Format::RepeatBetween(xmin, xmax, f) => {
                 f.match_bounds(module) * (Bounds::union(xmin.bounds(), xmax.bounds()))
             }
             Format::RepeatUntilLast(_, f) => f.match_bounds(module) * Bounds::new(1, None),
+            Format::UntilSeq(terminator, f) => {
+                f.match_bounds(module) * Bounds::new(0, None) + Bounds::exact(terminator.len())
+            }
             Format::RepeatUntilSeq(_, _f) => Bounds::new(0, None),
+            Format::ForEach(..) => Bounds::new(0, None),
+            Format::RepeatWhileInput(_) => Bounds::new(0, None),
             Format::Peek(_) => Bounds::exact(0),
             Format::PeekNot(_) => Bounds::exact(0),
+            Format::Signature(_name, magic, body) => {
+                magic.match_bounds(module) + body.match_bounds(module)
+            }
             Format::Slice(expr, _) => expr.bounds(),
             Format::Bits(f) => f.match_bounds(module).bits_to_bytes(),
             Format::WithRelativeOffset(_, _) => Bounds::exact(0),
+            Format::FollowOffset(expr, f) => expr.bounds() + f.match_bounds(module),
             Format::Map(f, _expr) => f.match_bounds(module),
             Format::Compute(_) => Bounds::exact(0),
             Format::Let(_name, _expr, f) => f.match_bounds(module),
@@ -745,8 +1068,10 @@ impl Format {
             Format::Fail => Bounds::exact(0),
             Format::EndOfInput => Bounds::exact(0),
             Format::Align(n) => Bounds::new(0, Some(n - 1)),
+            Format::AbsolutePos | Format::SlicePos => Bounds::exact(0),
             Format::Byte(_) => Bounds::exact(1),
             Format::Variant(_label, f) => f.lookahead_bounds(module),
+            Format::Newtype(_name, f, _validate) => f.lookahead_bounds(module),
             Format::Union(branches) | Format::UnionNondet(branches) => branches
                 .iter()
                 .map(|f| f.lookahead_bounds(module))
@@ -764,17 +1089,31 @@ impl Format {
                 .unwrap_or(Bounds::exact(0)),
             Format::Repeat(_) => Bounds::new(0, None),
             Format::Repeat1(f) => f.lookahead_bounds(module) * Bounds::new(1, None),
+            Format::RepeatSep(f, sep) => {
+                f.lookahead_bounds(module)
+                    + (sep.lookahead_bounds(module) + f.lookahead_bounds(module))
+                        * Bounds::new(0, None)
+            }
             Format::RepeatCount(expr, f) => f.lookahead_bounds(module) * expr.bounds(),
             Format::RepeatBetween(xmin, xmax, f) => {
                 f.lookahead_bounds(module) * Bounds::union(xmin.bounds(), xmax.bounds())
             }
             Format::RepeatUntilLast(_, f) => f.lookahead_bounds(module) * Bounds::new(1, None),
+            Format::UntilSeq(terminator, f) => {
+                f.lookahead_bounds(module) * Bounds::new(0, None) + Bounds::exact(terminator.len())
+            }
             Format::RepeatUntilSeq(_, _f) => Bounds::new(0, None),
+            Format::ForEach(..) => Bounds::new(0, None),
+            Format::RepeatWhileInput(_) => Bounds::new(0, None),
             Format::Peek(f) => f.lookahead_bounds(module),
             Format::PeekNot(f) => f.lookahead_bounds(module),
+            Format::Signature(_name, magic, body) => {
+                magic.lookahead_bounds(module) + body.lookahead_bounds(module)
+            }
             Format::Slice(expr, _) => expr.bounds(),
             Format::Bits(f) => f.lookahead_bounds(module).bits_to_bytes(),
             Format::WithRelativeOffset(expr, f) => expr.bounds() + f.lookahead_bounds(module),
+            Format::FollowOffset(expr, f) => expr.bounds() + f.lookahead_bounds(module),
             Format::Map(f, _expr) => f.lookahead_bounds(module),
             Format::Compute(_) => Bounds::exact(0),
             Format::Let(_name, _expr, f) => f.lookahead_bounds(module),
@@ -788,9 +1127,19 @@ impl Format {
         }
     }
 
+    /// Returns the minimum number of bytes that any successful match of the format must consume
+    fn min_length(&self, module: &FormatModule) -> usize {
+        self.match_bounds(module).min
+    }
+
     /// Returns `true` if the format could match the empty byte string
     fn is_nullable(&self, module: &FormatModule) -> bool {
-        self.match_bounds(module).min == 0
+        self.min_length(module) == 0
+    }
+
+    /// Returns `true` if the format can never consume any input, i.e. every successful match is empty
+    fn is_non_progressing(&self, module: &FormatModule) -> bool {
+        self.match_bounds(module).max == Some(0)
     }
 
     /// True if the compilation of this format depends on the format that follows it
@@ -800,8 +1149,10 @@ impl Format {
             Format::Fail => false,
             Format::EndOfInput => false,
             Format::Align(..) => false,
+            Format::AbsolutePos | Format::SlicePos => false,
             Format::Byte(..) => false,
             Format::Variant(_label, f) => f.depends_on_next(module),
+            Format::Newtype(_name, f, _validate) => f.depends_on_next(module),
             Format::Union(branches) | Format::UnionNondet(branches) => {
                 Format::union_depends_on_next(branches, module)
             }
@@ -809,15 +1160,23 @@ impl Format {
             Format::Record(fields) => fields.iter().any(|(_, f)| f.depends_on_next(module)),
             Format::Repeat(..) => true,
             Format::Repeat1(..) => true,
+            Format::RepeatSep(..) => true,
             Format::RepeatBetween(..) => true,
-            Format::RepeatCount(..) => false,
+            Format::RepeatCount(_, f) => f.depends_on_next(module),
             Format::RepeatUntilLast(..) => false,
+            Format::UntilSeq(..) => false,
             Format::RepeatUntilSeq(..) => false,
+            Format::ForEach(..) => false,
+            Format::RepeatWhileInput(..) => false,
             Format::Peek(..) => false,
             Format::PeekNot(..) => false,
+            Format::Signature(_name, magic, body) => {
+                magic.depends_on_next(module) || body.depends_on_next(module)
+            }
             Format::Slice(..) => false,
             Format::Bits(..) => false,
             Format::WithRelativeOffset(..) => false,
+            Format::FollowOffset(..) => false,
             Format::Map(f, _expr) => f.depends_on_next(module),
             Format::Compute(..) => false,
             Format::Let(_name, _expr, f) => f.depends_on_next(module),
@@ -835,7 +1194,30 @@ impl Format {
             }
             fs.push(f.clone());
         }
-        MatchTree::build(module, &fs, Rc::new(Next::Empty)).is_none()
+        // the branches must be told apart regardless of what follows, not merely at the end of the input
+        let any_byte = Format::Byte(ByteSet::full());
+        let anything = Rc::new(Next::Repeat(
+            MaybeTyped::Untyped(&any_byte),
+            Rc::new(Next::Empty),
+        ));
+        MatchTree::build(module, &fs, anything, MatchTree::MAX_DEPTH).is_err()
+    }
+
+    /// Returns `true` if the format is guaranteed to succeed, regardless of the input.
+    ///
+    /// This only holds for formats that consume no input and compute their value from infallible
+    /// expressions; references to other formats are conservatively assumed to be fallible.
+    pub fn is_infallible(&self) -> bool {
+        match self {
+            Format::Compute(expr) => expr.is_infallible(),
+            Format::Variant(_label, f) => f.is_infallible(),
+            Format::Newtype(_name, f, None) => f.is_infallible(),
+            Format::Tuple(fields) => fields.iter().all(Format::is_infallible),
+            Format::Record(fields) => fields.iter().all(|(_, f)| f.is_infallible()),
+            Format::Map(f, expr) => f.is_infallible() && expr.is_infallible(),
+            Format::Let(_name, expr, f) => expr.is_infallible() && f.is_infallible(),
+            _ => false,
+        }
     }
 }
 
@@ -867,7 +1249,8 @@ impl Format {
             | Format::Repeat1(format)
             | Format::RepeatCount(_, format)
             | Format::RepeatUntilLast(_, format)
-            | Format::RepeatUntilSeq(_, format) => format.is_ascii_char_format(module),
+            | Format::RepeatUntilSeq(_, format)
+            | Format::RepeatWhileInput(format) => format.is_ascii_char_format(module),
             Format::Slice(_, format) => format.is_ascii_string_format(module),
             // NOTE there may be other cases we should consider ASCII
             _ => false,
@@ -982,11 +1365,16 @@ impl FormatModule {
             Format::Fail => Ok(ValueType::Empty),
             Format::EndOfInput => Ok(ValueType::Tuple(vec![])),
             Format::Align(_n) => Ok(ValueType::Tuple(vec![])),
+            Format::AbsolutePos | Format::SlicePos => Ok(ValueType::Base(BaseType::U32)),
             Format::Byte(_bs) => Ok(ValueType::Base(BaseType::U8)),
             Format::Variant(label, f) => Ok(ValueType::Union(BTreeMap::from([(
                 label.clone(),
                 self.infer_format_type(scope, f)?,
             )]))),
+            Format::Newtype(name, f, _validate) => Ok(ValueType::Newtype(
+                name.clone(),
+                Box::new(self.infer_format_type(scope, f)?),
+            )),
             Format::Union(branches) | Format::UnionNondet(branches) => {
                 let mut t = ValueType::Any;
                 for f in branches {
@@ -1011,22 +1399,43 @@ impl FormatModule {
                 }
                 Ok(ValueType::Record(ts))
             }
-            Format::Repeat(a) | Format::Repeat1(a) => {
+            Format::RepeatSep(a, sep) => {
+                self.infer_format_type(scope, sep)?;
+                let t = self.infer_format_type(scope, a)?;
+                Ok(ValueType::Seq(Box::new(t)))
+            }
+            Format::Repeat(a) | Format::Repeat1(a) | Format::RepeatWhileInput(a) => {
                 let t = self.infer_format_type(scope, a)?;
                 Ok(ValueType::Seq(Box::new(t)))
             }
             Format::RepeatCount(_, a)
             | Format::RepeatBetween(_, _, a)
             | Format::RepeatUntilLast(_, a)
-            | Format::RepeatUntilSeq(_, a) => {
+            | Format::RepeatUntilSeq(_, a)
+            | Format::UntilSeq(_, a) => {
                 let t = self.infer_format_type(scope, a)?;
                 Ok(ValueType::Seq(Box::new(t)))
             }
+            Format::ForEach(expr, name, a) => match expr.infer_type(scope)? {
+                ValueType::Seq(elem_type) => {
+                    let mut child_scope = TypeScope::child(scope);
+                    child_scope.push(name.clone(), *elem_type);
+                    let t = self.infer_format_type(&child_scope, a)?;
+                    Ok(ValueType::Seq(Box::new(t)))
+                }
+                other => Err(anyhow!("ForEach: expected sequence, found {other:?}")),
+            },
             Format::Peek(a) => self.infer_format_type(scope, a),
             Format::PeekNot(_a) => Ok(ValueType::Tuple(vec![])),
+            Format::Signature(_name, magic, body) => {
+                self.infer_format_type(scope, magic)?;
+                self.infer_format_type(scope, body)
+            }
             Format::Slice(_expr, a) => self.infer_format_type(scope, a),
             Format::Bits(a) => self.infer_format_type(scope, a),
-            Format::WithRelativeOffset(_expr, a) => self.infer_format_type(scope, a),
+            Format::WithRelativeOffset(_expr, a) | Format::FollowOffset(_expr, a) => {
+                self.infer_format_type(scope, a)
+            }
             Format::Map(a, expr) => {
                 let arg_type = self.infer_format_type(scope, a)?;
                 match expr {
@@ -1127,6 +1536,8 @@ enum Next<'a> {
     Tuple(MTFormatSlice<'a>, Rc<Next<'a>>),
     Record(MTFieldSlice<'a>, Rc<Next<'a>>),
     Repeat(MTFormatRef<'a>, Rc<Next<'a>>),
+    /// Zero or more repetitions of a separator (the second format) followed by an element (the first)
+    RepeatSep(MTFormatRef<'a>, MTFormatRef<'a>, Rc<Next<'a>>),
     RepeatCount(usize, MTFormatRef<'a>, Rc<Next<'a>>),
     RepeatMax(usize, MTFormatRef<'a>, Rc<Next<'a>>), // dual to [RepeatCount] for 0..=N repeats
     RepeatBetween(usize, usize, MTFormatRef<'a>, Rc<Next<'a>>), // extension of RepeatMax/RepeatCount for N..=M repeats
@@ -1153,12 +1564,65 @@ struct MatchTreeLevel<'a> {
 type LevelBranch<'a> = HashSet<(usize, Rc<Next<'a>>)>;
 
 /// A byte-level prefix-tree evaluated to a fixed depth.
-#[derive(Clone, Debug, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct MatchTree {
     accept: Option<usize>,
     branches: Vec<(ByteSet, MatchTree)>,
 }
 
+/// The byte-level prefixes along which a [`MatchTree`] decides on a particular branch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BranchExplanation {
+    /// Index of the branch being explained
+    pub index: usize,
+    /// Sequences of byte-sets, one per lookahead position, after which the branch is selected
+    /// (unless the input goes on to match a longer prefix routing elsewhere)
+    pub prefixes: Vec<Vec<ByteSet>>,
+}
+
+/// Two branches of an alternation that could not be told apart when building a [`MatchTree`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AmbiguousBranches {
+    /// Labels of the first two ambiguous branches (the variant name if any, otherwise the index)
+    pub labels: (Label, Label),
+    /// Byte-sets, one per lookahead position, of the common prefix after which the branches remain ambiguous
+    pub prefix: Vec<ByteSet>,
+    /// Whether the branches might yet be distinguished with a deeper lookahead limit
+    pub depth_exceeded: bool,
+}
+
+impl fmt::Display for AmbiguousBranches {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (a, b) = &self.labels;
+        write!(f, "branches '{a}' and '{b}' are ambiguous after prefix [")?;
+        for (i, bs) in self.prefix.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            match (bs.len(), bs.min_elem()) {
+                (1, Some(b)) => write!(f, "{b:#04x}")?,
+                _ => write!(f, "{bs:?}")?,
+            }
+        }
+        write!(f, "]")?;
+        if self.depth_exceeded {
+            write!(f, " (lookahead limit reached)")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AmbiguousBranches {}
+
+/// Indices of two branches a [`MatchTreeLevel`] could not disambiguate, along with the byte-sets
+/// of the prefix after which they were found to be ambiguous.
+#[derive(Debug)]
+struct Ambiguity {
+    indices: (usize, usize),
+    prefix: Vec<ByteSet>,
+    depth_exceeded: bool,
+}
+
 impl<'a> MatchTreeStep<'a> {
     /// Returns a `MatchTreeStep` that rejects all inputs without branching.
     fn reject() -> MatchTreeStep<'a> {
@@ -1445,6 +1909,11 @@ impl<'a> MatchTreeStep<'a> {
                 let next1 = next.clone();
                 tree.union(MatchTreeStep::<'a>::from_mt_format(module, *a, next1))
             }
+            Next::RepeatSep(a, sep, next0) => {
+                let tree = MatchTreeStep::<'a>::from_next(module, next0.clone());
+                let next1 = Rc::new(Next::Cat(*a, next.clone()));
+                tree.union(MatchTreeStep::<'a>::from_mt_format(module, *sep, next1))
+            }
             Next::RepeatBetween(n, m, a, next0) => {
                 let min = *n;
                 let max = *m;
@@ -1525,8 +1994,12 @@ impl<'a> MatchTreeStep<'a> {
             TypedFormat::Align(_) => {
                 Self::accept() // FIXME
             }
+            TypedFormat::AbsolutePos | TypedFormat::SlicePos => Self::from_next(module, next),
             TypedFormat::Byte(bs) => Self::branch(*bs, next),
             TypedFormat::Variant(_, _label, f) => Self::from_gt_format(module, f, next.clone()),
+            TypedFormat::Newtype(_, _name, f, _validate) => {
+                Self::from_gt_format(module, f, next.clone())
+            }
             TypedFormat::Union(_, branches) | TypedFormat::UnionNondet(_, branches) => {
                 let mut tree = Self::reject();
                 for f in branches {
@@ -1551,7 +2024,7 @@ impl<'a> MatchTreeStep<'a> {
                 ),
             },
 
-            TypedFormat::Repeat(_, a) => {
+            TypedFormat::Repeat(_, a) | TypedFormat::RepeatWhileInput(_, a) => {
                 let tree = Self::from_next(module, next.clone());
                 tree.union(Self::from_gt_format(
                     module,
@@ -1654,6 +2127,9 @@ impl<'a> MatchTreeStep<'a> {
             TypedFormat::RepeatUntilSeq(_, _expr, _a) => {
                 Self::accept() // FIXME
             }
+            TypedFormat::ForEach(_, _expr, _name, _a) => {
+                Self::accept() // FIXME
+            }
             TypedFormat::Peek(_, a) => {
                 let tree = Self::from_next(module, next.clone());
                 let peek = Self::from_gt_format(module, a, Rc::new(Next::Empty));
@@ -1702,6 +2178,18 @@ impl<'a> MatchTreeStep<'a> {
                     }
                 }
             }
+            TypedFormat::FollowOffset(_, expr, a) => match expr.bounds().is_exact() {
+                None => Self::accept(),
+                Some(n) => Self::from_slice(
+                    module,
+                    n,
+                    Rc::new(Next::Empty),
+                    Rc::new(Next::Tuple(
+                        MaybeTyped::Typed(std::slice::from_ref(a.as_ref())),
+                        next,
+                    )),
+                ),
+            },
             TypedFormat::Map(_, f, _expr) => Self::from_gt_format(module, f, next),
             TypedFormat::Compute(_, _expr) => Self::from_next(module, next),
             TypedFormat::Let(_, _name, _expr, f) => Self::from_gt_format(module, f, next),
@@ -1732,8 +2220,10 @@ impl<'a> MatchTreeStep<'a> {
             Format::Align(_) => {
                 Self::accept() // FIXME
             }
+            Format::AbsolutePos | Format::SlicePos => Self::from_next(module, next),
             Format::Byte(bs) => Self::branch(*bs, next),
             Format::Variant(_label, f) => Self::from_format(module, f, next.clone()),
+            Format::Newtype(_name, f, _validate) => Self::from_format(module, f, next.clone()),
             Format::Union(branches) | Format::UnionNondet(branches) => {
                 let mut tree = Self::reject();
                 for f in branches {
@@ -1743,7 +2233,7 @@ impl<'a> MatchTreeStep<'a> {
             }
             Format::Tuple(fields) => Self::from_tuple(module, fields, next),
             Format::Record(fields) => Self::from_record(module, fields, next),
-            Format::Repeat(a) => {
+            Format::Repeat(a) | Format::RepeatWhileInput(a) => {
                 let tree = Self::from_next(module, next.clone());
                 tree.union(Self::from_format(
                     module,
@@ -1756,6 +2246,15 @@ impl<'a> MatchTreeStep<'a> {
                 a,
                 Rc::new(Next::Repeat(MaybeTyped::Untyped(a), next.clone())),
             ),
+            Format::RepeatSep(a, sep) => Self::from_format(
+                module,
+                a,
+                Rc::new(Next::RepeatSep(
+                    MaybeTyped::Untyped(a),
+                    MaybeTyped::Untyped(sep),
+                    next.clone(),
+                )),
+            ),
             Format::RepeatCount(expr, a) => {
                 let bounds = expr.bounds();
                 if let Some(n) = bounds.is_exact() {
@@ -1789,6 +2288,12 @@ impl<'a> MatchTreeStep<'a> {
             Format::RepeatUntilSeq(_expr, _a) => {
                 Self::accept() // FIXME
             }
+            Format::UntilSeq(_terminator, _a) => {
+                Self::accept() // FIXME
+            }
+            Format::ForEach(_expr, _name, _a) => {
+                Self::accept() // FIXME
+            }
             Format::Peek(a) => {
                 let tree = Self::from_next(module, next.clone());
                 let peek = Self::from_format(module, a, Rc::new(Next::Empty));
@@ -1799,6 +2304,11 @@ impl<'a> MatchTreeStep<'a> {
                 let peek = Self::from_format(module, a, Rc::new(Next::Empty));
                 tree.peek_not(peek)
             }
+            Format::Signature(_name, magic, body) => Self::from_format(
+                module,
+                magic,
+                Rc::new(Next::Cat(MaybeTyped::Untyped(body), next)),
+            ),
             Format::Slice(expr, f) => {
                 let inside = Rc::new(Next::Cat(
                     MaybeTyped::Untyped(f.as_ref()),
@@ -1837,6 +2347,18 @@ impl<'a> MatchTreeStep<'a> {
                     }
                 }
             }
+            Format::FollowOffset(expr, a) => match expr.bounds().is_exact() {
+                None => Self::accept(),
+                Some(n) => Self::from_slice(
+                    module,
+                    n,
+                    Rc::new(Next::Empty),
+                    Rc::new(Next::Tuple(
+                        MaybeTyped::Untyped(std::slice::from_ref(a.as_ref())),
+                        next,
+                    )),
+                ),
+            },
             Format::Map(f, _expr) => Self::from_format(module, f, next),
             Format::Compute(_expr) => Self::from_next(module, next),
             Format::Let(_name, _expr, f) => Self::from_format(module, f, next),
@@ -1864,15 +2386,15 @@ impl<'a> MatchTreeLevel<'a> {
 
     /// Attempts to modify `self` such that `index` is marked as the unique index of the accepting format.
     ///
-    /// Returns `Err(())` if a different index was already marked as accepting, and `Ok(())` otherwise.
-    fn merge_accept(&mut self, index: usize) -> Result<(), ()> {
+    /// Returns `Err(i)` if a different index `i` was already marked as accepting, and `Ok(())` otherwise.
+    fn merge_accept(&mut self, index: usize) -> Result<(), usize> {
         match self.accept {
             None => {
                 self.accept = Some(index);
                 Ok(())
             }
             Some(i) if i == index => Ok(()),
-            Some(_) => Err(()),
+            Some(i) => Err(i),
         }
     }
 
@@ -1913,7 +2435,7 @@ impl<'a> MatchTreeLevel<'a> {
         mut self,
         index: usize,
         step: MatchTreeStep<'a>,
-    ) -> Result<MatchTreeLevel<'a>, ()> {
+    ) -> Result<MatchTreeLevel<'a>, usize> {
         if step.accept {
             self.merge_accept(index)?;
         }
@@ -1943,32 +2465,54 @@ impl<'a> MatchTreeLevel<'a> {
     /// Attempts to accumulate a `MatchTree` recursively up to an overall depth of `depth` layers,
     /// with the immediate layer constructed based on a bundle of indexed choice-points ([`LevelBranch`]).
     ///
-    /// If the depth limit has been reached without a decisive choice of which index to accept, returns None.
+    /// If two indices both accept the same input, or the depth limit has been reached without a decisive
+    /// choice of which index to accept, returns an [`Ambiguity`] naming two of the undecided indices.
     ///
     /// Otherwise, returns a `MatchTree` that is guaranteed to decide on a unique branch for
     /// all input within at most `depth` bytes of lookahead.
-    fn grow(module: &'a FormatModule, nexts: LevelBranch<'a>, depth: usize) -> Option<MatchTree> {
+    fn grow(
+        module: &'a FormatModule,
+        nexts: LevelBranch<'a>,
+        depth: usize,
+    ) -> Result<MatchTree, Ambiguity> {
         if let Some(tree) = Self::accepts(&nexts) {
-            Some(tree)
+            Ok(tree)
         } else if depth > 0 {
             let mut tree = Self::reject();
             let mut tmp = Vec::from_iter(nexts);
             tmp.sort_by_key(|(ix, _)| *ix);
             for (i, next) in tmp.into_iter() {
                 let subtree = MatchTreeStep::from_next(module, next);
-                tree = tree.merge_step(i, subtree).ok()?;
+                tree = tree.merge_step(i, subtree).map_err(|prior| Ambiguity {
+                    indices: (prior, i),
+                    prefix: Vec::new(),
+                    depth_exceeded: false,
+                })?;
             }
-            let mut branches = Vec::new();
+            let mut branches: Vec<(ByteSet, MatchTree)> = Vec::new();
             for (bs, nexts) in tree.branches {
-                let t = Self::grow(module, nexts, depth - 1)?;
-                branches.push((bs, t));
+                let t = Self::grow(module, nexts, depth - 1).map_err(|mut ambiguity| {
+                    ambiguity.prefix.insert(0, bs);
+                    ambiguity
+                })?;
+                // Merge branches whose sub-trees are identical, as `merge_branch` tends to fragment byte-sets
+                match branches.iter_mut().find(|(_, t0)| *t0 == t) {
+                    Some((bs0, _)) => *bs0 = bs0.union(&bs),
+                    None => branches.push((bs, t)),
+                }
             }
-            Some(MatchTree {
+            Ok(MatchTree {
                 accept: tree.accept,
                 branches,
             })
         } else {
-            None
+            let indices = BTreeSet::from_iter(nexts.iter().map(|(i, _)| *i));
+            let mut indices = indices.into_iter();
+            Err(Ambiguity {
+                indices: (indices.next().unwrap(), indices.next().unwrap()),
+                prefix: Vec::new(),
+                depth_exceeded: true,
+            })
         }
     }
 }
@@ -1992,17 +2536,63 @@ impl MatchTree {
         }
     }
 
+    /// Returns an explanation, for each branch the tree can select, of which byte-sequences route to it.
+    ///
+    /// Branches are listed in order of their index; those the tree never selects are omitted.
+    pub fn explain(&self) -> Vec<BranchExplanation> {
+        fn walk(
+            tree: &MatchTree,
+            path: &mut Vec<ByteSet>,
+            acc: &mut BTreeMap<usize, Vec<Vec<ByteSet>>>,
+        ) {
+            if let Some(index) = tree.accept {
+                acc.entry(index).or_default().push(path.clone());
+            }
+            for (bs, subtree) in &tree.branches {
+                path.push(*bs);
+                walk(subtree, path, acc);
+                path.pop();
+            }
+        }
+
+        let mut acc = BTreeMap::new();
+        walk(self, &mut Vec::new(), &mut acc);
+        acc.into_iter()
+            .map(|(index, prefixes)| BranchExplanation { index, prefixes })
+            .collect()
+    }
+
+    /// Default number of bytes of lookahead a `MatchTree` may examine before giving up on deciding a branch.
+    pub const MAX_DEPTH: usize = 80;
+
     /// Constructs a new `MatchTreeLevel` from an alternation of branches and a follow-set of partially decomposed formats,
-    /// to within a fixed but externally opaque lookahead-depth.
+    /// to within a lookahead-depth of `max_depth` bytes.
     ///
     /// A `FormatModule` is also accepted to contextualize any contextually dependent formats, e.g. [`Format::ItemVar`]
-    fn build(module: &FormatModule, branches: &[Format], next: Rc<Next<'_>>) -> Option<MatchTree> {
+    ///
+    /// If the branches cannot be told apart, returns an error identifying two that are ambiguous.
+    fn build(
+        module: &FormatModule,
+        branches: &[Format],
+        next: Rc<Next<'_>>,
+        max_depth: usize,
+    ) -> Result<MatchTree, AmbiguousBranches> {
         let mut nexts = HashSet::new();
         for (i, f) in branches.iter().enumerate() {
             nexts.insert((i, Rc::new(Next::Cat(MaybeTyped::Untyped(f), next.clone()))));
         }
-        const MAX_DEPTH: usize = 80;
-        MatchTreeLevel::grow(module, nexts, MAX_DEPTH)
+        MatchTreeLevel::grow(module, nexts, max_depth).map_err(|ambiguity| {
+            let label = |i: usize| match &branches[i] {
+                Format::Variant(label, _) => label.clone(),
+                _ => Label::Owned(i.to_string()),
+            };
+            let (a, b) = ambiguity.indices;
+            AmbiguousBranches {
+                labels: (label(a), label(b)),
+                prefix: ambiguity.prefix,
+                depth_exceeded: ambiguity.depth_exceeded,
+            }
+        })
     }
 }
 
@@ -1,5 +1,5 @@
 use crate::byte_set::ByteSet;
-use crate::decoder::{Compiler, ScopeEntry};
+use crate::decoder::{signed_places, ArithMode, Compiler, ModeArith, Overflow, ScopeEntry};
 use crate::error::{LocParseResult, ParseError};
 use crate::read::ReadCtxt;
 use crate::{
@@ -9,6 +9,7 @@ use crate::{
 };
 use std::borrow::Cow;
 use std::cmp::Ordering;
+use std::rc::Rc;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 pub enum ParseLoc {
@@ -341,6 +342,55 @@ impl ParsedValue {
                     ..
                 }),
             ) => i0 == i1,
+            (
+                Pattern::U8Range(lo, hi),
+                ParsedValue::Flat(Parsed {
+                    inner: Value::U8(i1),
+                    ..
+                }),
+            ) => (*lo..=*hi).contains(i1),
+            (
+                Pattern::U16Range(lo, hi),
+                ParsedValue::Flat(Parsed {
+                    inner: Value::U16(i1),
+                    ..
+                }),
+            ) => (*lo..=*hi).contains(i1),
+            (
+                Pattern::U32Range(lo, hi),
+                ParsedValue::Flat(Parsed {
+                    inner: Value::U32(i1),
+                    ..
+                }),
+            ) => (*lo..=*hi).contains(i1),
+            (
+                Pattern::I8(i0),
+                ParsedValue::Flat(Parsed {
+                    inner: Value::I8(i1),
+                    ..
+                }),
+            ) => i0 == i1,
+            (
+                Pattern::I16(i0),
+                ParsedValue::Flat(Parsed {
+                    inner: Value::I16(i1),
+                    ..
+                }),
+            ) => i0 == i1,
+            (
+                Pattern::I32(i0),
+                ParsedValue::Flat(Parsed {
+                    inner: Value::I32(i1),
+                    ..
+                }),
+            ) => i0 == i1,
+            (
+                Pattern::I64(i0),
+                ParsedValue::Flat(Parsed {
+                    inner: Value::I64(i1),
+                    ..
+                }),
+            ) => i0 == i1,
             (
                 Pattern::Char(c0),
                 ParsedValue::Flat(Parsed {
@@ -362,6 +412,16 @@ impl ParsedValue {
             (Pattern::Variant(label0, p), ParsedValue::Variant(label1, v)) if label0 == label1 => {
                 v.matches_inner(scope, p)
             }
+            (Pattern::Or(ps), head) => {
+                let len = scope.entries.len();
+                for p in ps {
+                    if head.matches_inner(scope, p) {
+                        return true;
+                    }
+                    scope.entries.truncate(len);
+                }
+                false
+            }
             _ => false,
         }
     }
@@ -397,6 +457,10 @@ impl ParsedValue {
             | Value::U16(_)
             | Value::U32(_)
             | Value::U64(_)
+            | Value::I8(_)
+            | Value::I16(_)
+            | Value::I32(_)
+            | Value::I64(_)
             | Value::Char(_) => ParsedValue::Flat(Parsed {
                 loc: ParseLoc::Synthesized,
                 inner: expr_value,
@@ -434,6 +498,14 @@ impl ParsedValue {
             Value::Variant(lab, inner) => {
                 ParsedValue::Variant(lab, Box::new(ParsedValue::from_evaluated(*inner)))
             }
+            Value::Option(Some(inner)) => ParsedValue::Variant(
+                Label::Borrowed("some"),
+                Box::new(ParsedValue::from_evaluated(*inner)),
+            ),
+            Value::Option(None) => ParsedValue::Variant(
+                Label::Borrowed("none"),
+                Box::new(ParsedValue::from_evaluated(Value::UNIT)),
+            ),
             Value::Mapped(orig, image) => {
                 let orig = Box::new(ParsedValue::from_evaluated(*orig));
                 let image = Box::new(ParsedValue::from_evaluated(*image));
@@ -443,6 +515,7 @@ impl ParsedValue {
                 let inner = Box::new(ParsedValue::from_evaluated(*inner));
                 ParsedValue::Branch(ix, inner)
             }
+            Value::Shared(inner) => ParsedValue::from_evaluated(Rc::unwrap_or_clone(inner)),
         }
     }
 
@@ -469,30 +542,41 @@ impl ParsedValue {
 }
 
 impl Expr {
-    pub fn eval_with_loc<'a>(&'a self, scope: &'a LocScope<'a>) -> Cow<'a, ParsedValue> {
-        match self {
+    fn try_eval_with_loc<'a>(
+        &'a self,
+        scope: &'a LocScope<'a>,
+        mode: ArithMode,
+    ) -> Result<Cow<'a, ParsedValue>, Overflow> {
+        Ok(match self {
             Expr::Var(name) => Cow::Borrowed(scope.get_value_by_name(name)),
             Expr::Bool(b) => Cow::Owned(ParsedValue::from_evaluated(Value::Bool(*b))),
             Expr::U8(i) => Cow::Owned(ParsedValue::from_evaluated(Value::U8(*i))),
             Expr::U16(i) => Cow::Owned(ParsedValue::from_evaluated(Value::U16(*i))),
             Expr::U32(i) => Cow::Owned(ParsedValue::from_evaluated(Value::U32(*i))),
             Expr::U64(i) => Cow::Owned(ParsedValue::from_evaluated(Value::U64(*i))),
+            Expr::I8(i) => Cow::Owned(ParsedValue::from_evaluated(Value::I8(*i))),
+            Expr::I16(i) => Cow::Owned(ParsedValue::from_evaluated(Value::I16(*i))),
+            Expr::I32(i) => Cow::Owned(ParsedValue::from_evaluated(Value::I32(*i))),
+            Expr::I64(i) => Cow::Owned(ParsedValue::from_evaluated(Value::I64(*i))),
             Expr::Tuple(exprs) => Cow::Owned(ParsedValue::from_evaluated(Value::Tuple(
                 exprs
                     .iter()
-                    .map(|expr| expr.eval_value_with_loc(scope))
-                    .collect(),
+                    .map(|expr| expr.try_eval_value_with_loc(scope, mode))
+                    .collect::<Result<_, _>>()?,
             ))),
-            Expr::TupleProj(head, index) => match head.eval_with_loc(scope) {
+            Expr::TupleProj(head, index) => match head.try_eval_with_loc(scope, mode)? {
                 Cow::Owned(v) => Cow::Owned(v.coerce_mapped_value().tuple_proj(*index).clone()),
                 Cow::Borrowed(v) => Cow::Borrowed(v.coerce_mapped_value().tuple_proj(*index)),
             },
             Expr::Record(fields) => Cow::Owned(ParsedValue::from_evaluated(Value::record(
                 fields
                     .iter()
-                    .map(|(label, expr)| (label.clone(), expr.eval_value_with_loc(scope))),
+                    .map(|(label, expr)| {
+                        Ok((label.clone(), expr.try_eval_value_with_loc(scope, mode)?))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
             ))),
-            Expr::RecordProj(head, label) => match head.eval_with_loc(scope) {
+            Expr::RecordProj(head, label) => match head.try_eval_with_loc(scope, mode)? {
                 Cow::Owned(v) => {
                     Cow::Owned(v.coerce_mapped_value().record_proj(label.as_ref()).clone())
                 }
@@ -502,20 +586,21 @@ impl Expr {
             },
             Expr::Variant(label, expr) => Cow::Owned(ParsedValue::from_evaluated(Value::variant(
                 label.clone(),
-                expr.eval_value_with_loc(scope),
+                expr.try_eval_value_with_loc(scope, mode)?,
             ))),
             Expr::Seq(exprs) => Cow::Owned(ParsedValue::from_evaluated(Value::Seq(
                 exprs
                     .iter()
-                    .map(|expr| expr.eval_value_with_loc(scope))
-                    .collect(),
+                    .map(|expr| expr.try_eval_value_with_loc(scope, mode))
+                    .collect::<Result<_, _>>()?,
             ))),
             Expr::Match(head, branches) => {
-                let head = head.eval_with_loc(scope);
+                let head = head.try_eval_with_loc(scope, mode)?;
                 for (pattern, expr) in branches {
                     if let Some(pattern_scope) = head.matches(scope, pattern) {
-                        let value = expr.eval_value_with_loc(&LocScope::Multi(&pattern_scope));
-                        return Cow::Owned(ParsedValue::from_evaluated(value));
+                        let value =
+                            expr.try_eval_value_with_loc(&LocScope::Multi(&pattern_scope), mode)?;
+                        return Ok(Cow::Owned(ParsedValue::from_evaluated(value)));
                     }
                 }
                 panic!("non-exhaustive patterns");
@@ -523,192 +608,359 @@ impl Expr {
             Expr::Lambda(_, _) => panic!("cannot eval lambda"),
 
             Expr::IntRel(IntRel::Eq, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::Bool(x == y),
                     (Value::U16(x), Value::U16(y)) => Value::Bool(x == y),
                     (Value::U32(x), Value::U32(y)) => Value::Bool(x == y),
                     (Value::U64(x), Value::U64(y)) => Value::Bool(x == y),
+                    (Value::I8(x), Value::I8(y)) => Value::Bool(x == y),
+                    (Value::I16(x), Value::I16(y)) => Value::Bool(x == y),
+                    (Value::I32(x), Value::I32(y)) => Value::Bool(x == y),
+                    (Value::I64(x), Value::I64(y)) => Value::Bool(x == y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::IntRel(IntRel::Ne, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::Bool(x != y),
                     (Value::U16(x), Value::U16(y)) => Value::Bool(x != y),
                     (Value::U32(x), Value::U32(y)) => Value::Bool(x != y),
                     (Value::U64(x), Value::U64(y)) => Value::Bool(x != y),
+                    (Value::I8(x), Value::I8(y)) => Value::Bool(x != y),
+                    (Value::I16(x), Value::I16(y)) => Value::Bool(x != y),
+                    (Value::I32(x), Value::I32(y)) => Value::Bool(x != y),
+                    (Value::I64(x), Value::I64(y)) => Value::Bool(x != y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::IntRel(IntRel::Lt, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::Bool(x < y),
                     (Value::U16(x), Value::U16(y)) => Value::Bool(x < y),
                     (Value::U32(x), Value::U32(y)) => Value::Bool(x < y),
                     (Value::U64(x), Value::U64(y)) => Value::Bool(x < y),
+                    (Value::I8(x), Value::I8(y)) => Value::Bool(x < y),
+                    (Value::I16(x), Value::I16(y)) => Value::Bool(x < y),
+                    (Value::I32(x), Value::I32(y)) => Value::Bool(x < y),
+                    (Value::I64(x), Value::I64(y)) => Value::Bool(x < y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::IntRel(IntRel::Gt, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::Bool(x > y),
                     (Value::U16(x), Value::U16(y)) => Value::Bool(x > y),
                     (Value::U32(x), Value::U32(y)) => Value::Bool(x > y),
                     (Value::U64(x), Value::U64(y)) => Value::Bool(x > y),
+                    (Value::I8(x), Value::I8(y)) => Value::Bool(x > y),
+                    (Value::I16(x), Value::I16(y)) => Value::Bool(x > y),
+                    (Value::I32(x), Value::I32(y)) => Value::Bool(x > y),
+                    (Value::I64(x), Value::I64(y)) => Value::Bool(x > y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::IntRel(IntRel::Lte, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::Bool(x <= y),
                     (Value::U16(x), Value::U16(y)) => Value::Bool(x <= y),
                     (Value::U32(x), Value::U32(y)) => Value::Bool(x <= y),
                     (Value::U64(x), Value::U64(y)) => Value::Bool(x <= y),
+                    (Value::I8(x), Value::I8(y)) => Value::Bool(x <= y),
+                    (Value::I16(x), Value::I16(y)) => Value::Bool(x <= y),
+                    (Value::I32(x), Value::I32(y)) => Value::Bool(x <= y),
+                    (Value::I64(x), Value::I64(y)) => Value::Bool(x <= y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::IntRel(IntRel::Gte, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::Bool(x >= y),
                     (Value::U16(x), Value::U16(y)) => Value::Bool(x >= y),
                     (Value::U32(x), Value::U32(y)) => Value::Bool(x >= y),
                     (Value::U64(x), Value::U64(y)) => Value::Bool(x >= y),
+                    (Value::I8(x), Value::I8(y)) => Value::Bool(x >= y),
+                    (Value::I16(x), Value::I16(y)) => Value::Bool(x >= y),
+                    (Value::I32(x), Value::I32(y)) => Value::Bool(x >= y),
+                    (Value::I64(x), Value::I64(y)) => Value::Bool(x >= y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::Arith(Arith::Add, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
-                    (Value::U8(x), Value::U8(y)) => Value::U8(u8::checked_add(x, y).unwrap()),
-                    (Value::U16(x), Value::U16(y)) => Value::U16(u16::checked_add(x, y).unwrap()),
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_add(x, y).unwrap()),
-                    (Value::U64(x), Value::U64(y)) => Value::U64(u64::checked_add(x, y).unwrap()),
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.add_in(mode, y)?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.add_in(mode, y)?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.add_in(mode, y)?),
+                    (Value::U64(x), Value::U64(y)) => Value::U64(x.add_in(mode, y)?),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x.add_in(mode, y)?),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x.add_in(mode, y)?),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x.add_in(mode, y)?),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x.add_in(mode, y)?),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::Arith(Arith::Sub, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
-                    (Value::U8(x), Value::U8(y)) => Value::U8(u8::checked_sub(x, y).unwrap()),
-                    (Value::U16(x), Value::U16(y)) => Value::U16(u16::checked_sub(x, y).unwrap()),
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_sub(x, y).unwrap()),
-                    (Value::U64(x), Value::U64(y)) => Value::U64(u64::checked_sub(x, y).unwrap()),
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.sub_in(mode, y)?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.sub_in(mode, y)?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.sub_in(mode, y)?),
+                    (Value::U64(x), Value::U64(y)) => Value::U64(x.sub_in(mode, y)?),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x.sub_in(mode, y)?),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x.sub_in(mode, y)?),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x.sub_in(mode, y)?),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x.sub_in(mode, y)?),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::Arith(Arith::Mul, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
-                    (Value::U8(x), Value::U8(y)) => Value::U8(u8::checked_mul(x, y).unwrap()),
-                    (Value::U16(x), Value::U16(y)) => Value::U16(u16::checked_mul(x, y).unwrap()),
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_mul(x, y).unwrap()),
-                    (Value::U64(x), Value::U64(y)) => Value::U64(u64::checked_mul(x, y).unwrap()),
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.mul_in(mode, y)?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.mul_in(mode, y)?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.mul_in(mode, y)?),
+                    (Value::U64(x), Value::U64(y)) => Value::U64(x.mul_in(mode, y)?),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x.mul_in(mode, y)?),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x.mul_in(mode, y)?),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x.mul_in(mode, y)?),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x.mul_in(mode, y)?),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::Arith(Arith::Div, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
-                    (Value::U8(x), Value::U8(y)) => Value::U8(u8::checked_div(x, y).unwrap()),
-                    (Value::U16(x), Value::U16(y)) => Value::U16(u16::checked_div(x, y).unwrap()),
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_div(x, y).unwrap()),
-                    (Value::U64(x), Value::U64(y)) => Value::U64(u64::checked_div(x, y).unwrap()),
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.div_in(mode, y)?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.div_in(mode, y)?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.div_in(mode, y)?),
+                    (Value::U64(x), Value::U64(y)) => Value::U64(x.div_in(mode, y)?),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x.div_in(mode, y)?),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x.div_in(mode, y)?),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x.div_in(mode, y)?),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x.div_in(mode, y)?),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::Arith(Arith::Rem, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
-                    (Value::U8(x), Value::U8(y)) => Value::U8(u8::checked_rem(x, y).unwrap()),
-                    (Value::U16(x), Value::U16(y)) => Value::U16(u16::checked_rem(x, y).unwrap()),
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_rem(x, y).unwrap()),
-                    (Value::U64(x), Value::U64(y)) => Value::U64(u64::checked_rem(x, y).unwrap()),
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.rem_in(mode, y)?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.rem_in(mode, y)?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.rem_in(mode, y)?),
+                    (Value::U64(x), Value::U64(y)) => Value::U64(x.rem_in(mode, y)?),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x.rem_in(mode, y)?),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x.rem_in(mode, y)?),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x.rem_in(mode, y)?),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x.rem_in(mode, y)?),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::Arith(Arith::BitAnd, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::U8(x & y),
                     (Value::U16(x), Value::U16(y)) => Value::U16(x & y),
                     (Value::U32(x), Value::U32(y)) => Value::U32(x & y),
                     (Value::U64(x), Value::U64(y)) => Value::U64(x & y),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x & y),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x & y),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x & y),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x & y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::Arith(Arith::BitOr, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
                     (Value::U8(x), Value::U8(y)) => Value::U8(x | y),
                     (Value::U16(x), Value::U16(y)) => Value::U16(x | y),
                     (Value::U32(x), Value::U32(y)) => Value::U32(x | y),
                     (Value::U64(x), Value::U64(y)) => Value::U64(x | y),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x | y),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x | y),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x | y),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x | y),
+                    (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
+                },
+            )),
+            Expr::Arith(Arith::BitXor, x, y) => Cow::Owned(ParsedValue::from_evaluated(
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x ^ y),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x ^ y),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x ^ y),
+                    (Value::U64(x), Value::U64(y)) => Value::U64(x ^ y),
+                    (Value::I8(x), Value::I8(y)) => Value::I8(x ^ y),
+                    (Value::I16(x), Value::I16(y)) => Value::I16(x ^ y),
+                    (Value::I32(x), Value::I32(y)) => Value::I32(x ^ y),
+                    (Value::I64(x), Value::I64(y)) => Value::I64(x ^ y),
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::Arith(Arith::Shl, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
-                    (Value::U8(x), Value::U8(y)) => {
-                        Value::U8(u8::checked_shl(x, u32::from(y)).unwrap())
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.shl_in(mode, u32::from(y))?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.shl_in(mode, u32::from(y))?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.shl_in(mode, y)?),
+                    (Value::U64(x), Value::U64(y)) => {
+                        Value::U64(x.shl_in(mode, u32::try_from(y).unwrap_or(u32::MAX))?)
                     }
-                    (Value::U16(x), Value::U16(y)) => {
-                        Value::U16(u16::checked_shl(x, u32::from(y)).unwrap())
+                    (Value::I8(x), Value::I8(y)) => {
+                        Value::I8(x.shl_in(mode, signed_places(y.into())?)?)
                     }
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_shl(x, y).unwrap()),
-                    (Value::U64(x), Value::U64(y)) => {
-                        Value::U64(u64::checked_shl(x, u32::try_from(y).unwrap()).unwrap())
+                    (Value::I16(x), Value::I16(y)) => {
+                        Value::I16(x.shl_in(mode, signed_places(y.into())?)?)
+                    }
+                    (Value::I32(x), Value::I32(y)) => {
+                        Value::I32(x.shl_in(mode, signed_places(y.into())?)?)
+                    }
+                    (Value::I64(x), Value::I64(y)) => {
+                        Value::I64(x.shl_in(mode, signed_places(y)?)?)
                     }
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
             Expr::Arith(Arith::Shr, x, y) => Cow::Owned(ParsedValue::from_evaluated(
-                match (x.eval_value_with_loc(scope), y.eval_value_with_loc(scope)) {
-                    (Value::U8(x), Value::U8(y)) => {
-                        Value::U8(u8::checked_shr(x, u32::from(y)).unwrap())
+                match (
+                    x.try_eval_value_with_loc(scope, mode)?,
+                    y.try_eval_value_with_loc(scope, mode)?,
+                ) {
+                    (Value::U8(x), Value::U8(y)) => Value::U8(x.shr_in(mode, u32::from(y))?),
+                    (Value::U16(x), Value::U16(y)) => Value::U16(x.shr_in(mode, u32::from(y))?),
+                    (Value::U32(x), Value::U32(y)) => Value::U32(x.shr_in(mode, y)?),
+                    (Value::U64(x), Value::U64(y)) => {
+                        Value::U64(x.shr_in(mode, u32::try_from(y).unwrap_or(u32::MAX))?)
                     }
-                    (Value::U16(x), Value::U16(y)) => {
-                        Value::U16(u16::checked_shr(x, u32::from(y)).unwrap())
+                    (Value::I8(x), Value::I8(y)) => {
+                        Value::I8(x.shr_in(mode, signed_places(y.into())?)?)
                     }
-                    (Value::U32(x), Value::U32(y)) => Value::U32(u32::checked_shr(x, y).unwrap()),
-                    (Value::U64(x), Value::U64(y)) => {
-                        Value::U64(u64::checked_shr(x, u32::try_from(y).unwrap()).unwrap())
+                    (Value::I16(x), Value::I16(y)) => {
+                        Value::I16(x.shr_in(mode, signed_places(y.into())?)?)
+                    }
+                    (Value::I32(x), Value::I32(y)) => {
+                        Value::I32(x.shr_in(mode, signed_places(y.into())?)?)
+                    }
+                    (Value::I64(x), Value::I64(y)) => {
+                        Value::I64(x.shr_in(mode, signed_places(y)?)?)
                     }
                     (x, y) => panic!("mismatched operands {x:?}, {y:?}"),
                 },
             )),
 
+            Expr::AsSigned8(x) => Cow::Owned(ParsedValue::from_evaluated(
+                match x.try_eval_value_with_loc(scope, mode)? {
+                    Value::U8(x) => Value::I8(x as i8),
+                    x => panic!("cannot reinterpret {x:?} as I8"),
+                },
+            )),
+            Expr::AsSigned16(x) => Cow::Owned(ParsedValue::from_evaluated(
+                match x.try_eval_value_with_loc(scope, mode)? {
+                    Value::U16(x) => Value::I16(x as i16),
+                    x => panic!("cannot reinterpret {x:?} as I16"),
+                },
+            )),
+            Expr::AsSigned32(x) => Cow::Owned(ParsedValue::from_evaluated(
+                match x.try_eval_value_with_loc(scope, mode)? {
+                    Value::U32(x) => Value::I32(x as i32),
+                    x => panic!("cannot reinterpret {x:?} as I32"),
+                },
+            )),
             Expr::AsU8(x) => Cow::Owned(ParsedValue::from_evaluated(
-                match x.eval_value_with_loc(scope) {
+                match x.try_eval_value_with_loc(scope, mode)? {
                     Value::U8(x) => Value::U8(x),
-                    Value::U16(x) => Value::U8(u8::try_from(x).unwrap()),
-                    Value::U32(x) => Value::U8(u8::try_from(x).unwrap()),
-                    Value::U64(x) => Value::U8(u8::try_from(x).unwrap()),
+                    Value::U16(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
+                    Value::U32(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
+                    Value::U64(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
+                    Value::I8(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
+                    Value::I16(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
+                    Value::I32(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
+                    Value::I64(x) => Value::U8(u8::try_from(x).map_err(|_| Overflow)?),
                     x => panic!("cannot convert {x:?} to U8"),
                 },
             )),
             Expr::AsU16(x) => Cow::Owned(ParsedValue::from_evaluated(
-                match x.eval_value_with_loc(scope) {
+                match x.try_eval_value_with_loc(scope, mode)? {
                     Value::U8(x) => Value::U16(u16::from(x)),
                     Value::U16(x) => Value::U16(x),
-                    Value::U32(x) => Value::U16(u16::try_from(x).unwrap()),
-                    Value::U64(x) => Value::U16(u16::try_from(x).unwrap()),
+                    Value::U32(x) => Value::U16(u16::try_from(x).map_err(|_| Overflow)?),
+                    Value::U64(x) => Value::U16(u16::try_from(x).map_err(|_| Overflow)?),
+                    Value::I8(x) => Value::U16(u16::try_from(x).map_err(|_| Overflow)?),
+                    Value::I16(x) => Value::U16(u16::try_from(x).map_err(|_| Overflow)?),
+                    Value::I32(x) => Value::U16(u16::try_from(x).map_err(|_| Overflow)?),
+                    Value::I64(x) => Value::U16(u16::try_from(x).map_err(|_| Overflow)?),
                     x => panic!("cannot convert {x:?} to U16"),
                 },
             )),
             Expr::AsU32(x) => Cow::Owned(ParsedValue::from_evaluated(
-                match x.eval_value_with_loc(scope) {
+                match x.try_eval_value_with_loc(scope, mode)? {
                     Value::U8(x) => Value::U32(u32::from(x)),
                     Value::U16(x) => Value::U32(u32::from(x)),
                     Value::U32(x) => Value::U32(x),
-                    Value::U64(x) => Value::U32(u32::try_from(x).unwrap()),
+                    Value::U64(x) => Value::U32(u32::try_from(x).map_err(|_| Overflow)?),
+                    Value::I8(x) => Value::U32(u32::try_from(x).map_err(|_| Overflow)?),
+                    Value::I16(x) => Value::U32(u32::try_from(x).map_err(|_| Overflow)?),
+                    Value::I32(x) => Value::U32(u32::try_from(x).map_err(|_| Overflow)?),
+                    Value::I64(x) => Value::U32(u32::try_from(x).map_err(|_| Overflow)?),
                     x => panic!("cannot convert {x:?} to U32"),
                 },
             )),
             Expr::AsU64(x) => Cow::Owned(ParsedValue::from_evaluated(
-                match x.eval_value_with_loc(scope) {
+                match x.try_eval_value_with_loc(scope, mode)? {
                     Value::U8(x) => Value::U64(u64::from(x)),
                     Value::U16(x) => Value::U64(u64::from(x)),
                     Value::U32(x) => Value::U64(u64::from(x)),
                     Value::U64(x) => Value::U64(x),
+                    Value::I8(x) => Value::U64(u64::try_from(x).map_err(|_| Overflow)?),
+                    Value::I16(x) => Value::U64(u64::try_from(x).map_err(|_| Overflow)?),
+                    Value::I32(x) => Value::U64(u64::try_from(x).map_err(|_| Overflow)?),
+                    Value::I64(x) => Value::U64(u64::try_from(x).map_err(|_| Overflow)?),
                     x => panic!("cannot convert {x:?} to U64"),
                 },
             )),
 
             Expr::U16Be(bytes) => {
-                match bytes.eval_value_with_loc(scope).unwrap_tuple().as_slice() {
+                match bytes
+                    .try_eval_value_with_loc(scope, mode)?
+                    .unwrap_tuple()
+                    .as_slice()
+                {
                     [Value::U8(hi), Value::U8(lo)] => Cow::Owned(ParsedValue::from_evaluated(
                         Value::U16(u16::from_be_bytes([*hi, *lo])),
                     )),
@@ -716,7 +968,11 @@ impl Expr {
                 }
             }
             Expr::U16Le(bytes) => {
-                match bytes.eval_value_with_loc(scope).unwrap_tuple().as_slice() {
+                match bytes
+                    .try_eval_value_with_loc(scope, mode)?
+                    .unwrap_tuple()
+                    .as_slice()
+                {
                     [Value::U8(lo), Value::U8(hi)] => Cow::Owned(ParsedValue::from_evaluated(
                         Value::U16(u16::from_le_bytes([*lo, *hi])),
                     )),
@@ -724,7 +980,11 @@ impl Expr {
                 }
             }
             Expr::U32Be(bytes) => {
-                match bytes.eval_value_with_loc(scope).unwrap_tuple().as_slice() {
+                match bytes
+                    .try_eval_value_with_loc(scope, mode)?
+                    .unwrap_tuple()
+                    .as_slice()
+                {
                     [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d)] => {
                         Cow::Owned(ParsedValue::from_evaluated(Value::U32(u32::from_be_bytes(
                             [*a, *b, *c, *d],
@@ -734,7 +994,11 @@ impl Expr {
                 }
             }
             Expr::U32Le(bytes) => {
-                match bytes.eval_value_with_loc(scope).unwrap_tuple().as_slice() {
+                match bytes
+                    .try_eval_value_with_loc(scope, mode)?
+                    .unwrap_tuple()
+                    .as_slice()
+                {
                     [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d)] => {
                         Cow::Owned(ParsedValue::from_evaluated(Value::U32(u32::from_le_bytes(
                             [*a, *b, *c, *d],
@@ -744,7 +1008,11 @@ impl Expr {
                 }
             }
             Expr::U64Be(bytes) => {
-                match bytes.eval_value_with_loc(scope).unwrap_tuple().as_slice() {
+                match bytes
+                    .try_eval_value_with_loc(scope, mode)?
+                    .unwrap_tuple()
+                    .as_slice()
+                {
                     [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d), Value::U8(e), Value::U8(f), Value::U8(g), Value::U8(h)] => {
                         Cow::Owned(ParsedValue::from_evaluated(Value::U64(u64::from_be_bytes(
                             [*a, *b, *c, *d, *e, *f, *g, *h],
@@ -754,7 +1022,11 @@ impl Expr {
                 }
             }
             Expr::U64Le(bytes) => {
-                match bytes.eval_value_with_loc(scope).unwrap_tuple().as_slice() {
+                match bytes
+                    .try_eval_value_with_loc(scope, mode)?
+                    .unwrap_tuple()
+                    .as_slice()
+                {
                     [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d), Value::U8(e), Value::U8(f), Value::U8(g), Value::U8(h)] => {
                         Cow::Owned(ParsedValue::from_evaluated(Value::U64(u64::from_le_bytes(
                             [*a, *b, *c, *d, *e, *f, *g, *h],
@@ -763,8 +1035,60 @@ impl Expr {
                     _ => panic!("U32Le: expected (U8, U8, U8, U8, U8, U8, U8, U8)"),
                 }
             }
+            Expr::I16Be(bytes) => {
+                match bytes
+                    .try_eval_value_with_loc(scope, mode)?
+                    .unwrap_tuple()
+                    .as_slice()
+                {
+                    [Value::U8(hi), Value::U8(lo)] => Cow::Owned(ParsedValue::from_evaluated(
+                        Value::I16(i16::from_be_bytes([*hi, *lo])),
+                    )),
+                    _ => panic!("I16Be: expected (U8, U8)"),
+                }
+            }
+            Expr::I16Le(bytes) => {
+                match bytes
+                    .try_eval_value_with_loc(scope, mode)?
+                    .unwrap_tuple()
+                    .as_slice()
+                {
+                    [Value::U8(lo), Value::U8(hi)] => Cow::Owned(ParsedValue::from_evaluated(
+                        Value::I16(i16::from_le_bytes([*lo, *hi])),
+                    )),
+                    _ => panic!("I16Le: expected (U8, U8)"),
+                }
+            }
+            Expr::I32Be(bytes) => {
+                match bytes
+                    .try_eval_value_with_loc(scope, mode)?
+                    .unwrap_tuple()
+                    .as_slice()
+                {
+                    [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d)] => {
+                        Cow::Owned(ParsedValue::from_evaluated(Value::I32(i32::from_be_bytes(
+                            [*a, *b, *c, *d],
+                        ))))
+                    }
+                    _ => panic!("I32Be: expected (U8, U8, U8, U8)"),
+                }
+            }
+            Expr::I32Le(bytes) => {
+                match bytes
+                    .try_eval_value_with_loc(scope, mode)?
+                    .unwrap_tuple()
+                    .as_slice()
+                {
+                    [Value::U8(a), Value::U8(b), Value::U8(c), Value::U8(d)] => {
+                        Cow::Owned(ParsedValue::from_evaluated(Value::I32(i32::from_le_bytes(
+                            [*a, *b, *c, *d],
+                        ))))
+                    }
+                    _ => panic!("I32Le: expected (U8, U8, U8, U8)"),
+                }
+            }
             Expr::AsChar(bytes) => Cow::Owned(ParsedValue::from_evaluated(
-                match bytes.eval_value_with_loc(scope) {
+                match bytes.try_eval_value_with_loc(scope, mode)? {
                     Value::U8(x) => Value::Char(char::from(x)),
                     Value::U16(x) => {
                         Value::Char(char::from_u32(x as u32).unwrap_or(char::REPLACEMENT_CHARACTER))
@@ -773,14 +1097,14 @@ impl Expr {
                         Value::Char(char::from_u32(x).unwrap_or(char::REPLACEMENT_CHARACTER))
                     }
                     Value::U64(x) => Value::Char(
-                        char::from_u32(u32::try_from(x).unwrap())
+                        char::from_u32(u32::try_from(x).map_err(|_| Overflow)?)
                             .unwrap_or(char::REPLACEMENT_CHARACTER),
                     ),
                     _ => panic!("AsChar: expected U8, U16, U32, or U64"),
                 },
             )),
             Expr::SeqLength(seq) => match seq
-                .eval_with_loc(scope)
+                .try_eval_with_loc(scope, mode)?
                 .coerce_mapped_value()
                 .get_sequence()
             {
@@ -790,15 +1114,32 @@ impl Expr {
                 }
                 _ => panic!("SeqLength: expected Seq"),
             },
+            Expr::SeqIx(seq, index) => match seq
+                .try_eval_with_loc(scope, mode)?
+                .coerce_mapped_value()
+                .get_sequence()
+            {
+                Some(values) => {
+                    let index = index.try_eval_value_with_loc(scope, mode)?.unwrap_usize();
+                    match values.get(index) {
+                        Some(v) => Cow::Owned(v.clone()),
+                        None => panic!(
+                            "SeqIx: index {index} out of bounds for sequence of length {}",
+                            values.len()
+                        ),
+                    }
+                }
+                _ => panic!("SeqIx: expected Seq"),
+            },
             Expr::SubSeq(seq, start, length) => {
                 match seq
-                    .eval_with_loc(scope)
+                    .try_eval_with_loc(scope, mode)?
                     .coerce_mapped_value()
                     .get_sequence()
                 {
                     Some(values) => {
-                        let start = start.eval_value_with_loc(scope).unwrap_usize();
-                        let length = length.eval_value_with_loc(scope).unwrap_usize();
+                        let start = start.try_eval_value_with_loc(scope, mode)?.unwrap_usize();
+                        let length = length.try_eval_value_with_loc(scope, mode)?.unwrap_usize();
                         let values = &values[start..];
                         let values = &values[..length];
                         Cow::Owned(ParsedValue::from_evaluated_seq(values.to_vec()))
@@ -808,13 +1149,13 @@ impl Expr {
             }
             Expr::SubSeqInflate(seq, start, length) => {
                 match seq
-                    .eval_with_loc(scope)
+                    .try_eval_with_loc(scope, mode)?
                     .coerce_mapped_value()
                     .get_sequence()
                 {
                     Some(vs0) => {
-                        let start = start.eval_value_with_loc(scope).unwrap_usize();
-                        let length = length.eval_value_with_loc(scope).unwrap_usize();
+                        let start = start.try_eval_value_with_loc(scope, mode)?.unwrap_usize();
+                        let length = length.try_eval_value_with_loc(scope, mode)?.unwrap_usize();
                         let mut vs = Vec::new();
                         for i in 0..length {
                             if i + start < vs0.len() {
@@ -830,14 +1171,14 @@ impl Expr {
             }
             Expr::FlatMap(expr, seq) => {
                 match seq
-                    .eval_with_loc(scope)
+                    .try_eval_with_loc(scope, mode)?
                     .coerce_mapped_value()
                     .get_sequence()
                 {
                     Some(values) => {
                         let mut vs = Vec::new();
                         for v in values {
-                            if let Value::Seq(vn) = expr.eval_lambda_with_loc(scope, v) {
+                            if let Value::Seq(vn) = expr.try_eval_lambda_with_loc(scope, v, mode)? {
                                 vs.extend(vn);
                             } else {
                                 panic!("FlatMap: expected Seq");
@@ -849,15 +1190,16 @@ impl Expr {
                 }
             }
             Expr::FlatMapAccum(expr, accum, _accum_type, seq) => {
-                match seq.eval_value_with_loc(scope) {
+                match seq.try_eval_value_with_loc(scope, mode)? {
                     Value::Seq(values) => {
-                        let mut accum = accum.eval_value_with_loc(scope);
+                        let mut accum = accum.try_eval_value_with_loc(scope, mode)?;
                         let mut vs = Vec::new();
                         for v in values {
-                            let ret = expr.eval_lambda_with_loc(
+                            let ret = expr.try_eval_lambda_with_loc(
                                 scope,
                                 &ParsedValue::from_evaluated(Value::Tuple(vec![accum, v])),
-                            );
+                                mode,
+                            )?;
                             accum = match ret.unwrap_tuple().as_mut_slice() {
                                 [accum, Value::Seq(vn)] => {
                                     vs.extend_from_slice(vn);
@@ -871,58 +1213,95 @@ impl Expr {
                     _ => panic!("FlatMapAccum: expected Seq"),
                 }
             }
-            Expr::FlatMapList(expr, _ret_type, seq) => match seq.eval_value_with_loc(scope) {
-                Value::Seq(values) => {
-                    let mut vs = Vec::new();
-                    for v in values {
-                        let arg = Value::Tuple(vec![Value::Seq(vs), v]);
-                        // TODO can we avoid cloning arg here?
-                        if let Value::Seq(vn) = expr
-                            .eval_lambda_with_loc(scope, &ParsedValue::from_evaluated(arg.clone()))
-                        {
-                            vs = match arg {
-                                Value::Tuple(mut args) => match args.remove(0) {
-                                    Value::Seq(vs) => vs,
+            Expr::FlatMapList(expr, _ret_type, seq) => {
+                match seq.try_eval_value_with_loc(scope, mode)? {
+                    Value::Seq(values) => {
+                        let mut vs = Vec::new();
+                        for v in values {
+                            let arg = Value::Tuple(vec![Value::Seq(vs), v]);
+                            // TODO can we avoid cloning arg here?
+                            if let Value::Seq(vn) = expr.try_eval_lambda_with_loc(
+                                scope,
+                                &ParsedValue::from_evaluated(arg.clone()),
+                                mode,
+                            )? {
+                                vs = match arg {
+                                    Value::Tuple(mut args) => match args.remove(0) {
+                                        Value::Seq(vs) => vs,
+                                        _ => unreachable!(),
+                                    },
                                     _ => unreachable!(),
-                                },
-                                _ => unreachable!(),
-                            };
-                            vs.extend(vn);
-                        } else {
-                            panic!("FlatMapList: expected Seq");
+                                };
+                                vs.extend(vn);
+                            } else {
+                                panic!("FlatMapList: expected Seq");
+                            }
                         }
+                        Cow::Owned(ParsedValue::from_evaluated(Value::Seq(vs)))
                     }
-                    Cow::Owned(ParsedValue::from_evaluated(Value::Seq(vs)))
+                    _ => panic!("FlatMapList: expected Seq"),
                 }
-                _ => panic!("FlatMapList: expected Seq"),
-            },
+            }
             Expr::Dup(count, expr) => {
-                let count = count.eval_value_with_loc(scope).unwrap_usize();
-                let v = expr.eval_value_with_loc(scope);
+                let count = count.try_eval_value_with_loc(scope, mode)?.unwrap_usize();
+                let v = expr.try_eval_value_with_loc(scope, mode)?;
                 let mut vs = Vec::new();
                 for _ in 0..count {
                     vs.push(v.clone());
                 }
                 Cow::Owned(ParsedValue::from_evaluated(Value::Seq(vs)))
             }
-        }
+        })
     }
 
-    pub fn eval_value_with_loc<'a>(&self, scope: &'a LocScope<'a>) -> Value {
-        self.eval_with_loc(scope)
+    fn try_eval_value_with_loc<'a>(
+        &self,
+        scope: &'a LocScope<'a>,
+        mode: ArithMode,
+    ) -> Result<Value, Overflow> {
+        Ok(self
+            .try_eval_with_loc(scope, mode)?
             .coerce_mapped_value()
-            .clone_into_value()
+            .clone_into_value())
     }
 
-    fn eval_lambda_with_loc<'a>(&self, scope: &'a LocScope<'a>, arg: &ParsedValue) -> Value {
+    fn try_eval_lambda_with_loc<'a>(
+        &self,
+        scope: &'a LocScope<'a>,
+        arg: &ParsedValue,
+        mode: ArithMode,
+    ) -> Result<Value, Overflow> {
         match self {
             Expr::Lambda(name, expr) => {
                 let child_scope = LocSingleScope::new(scope, name, arg);
-                expr.eval_value_with_loc(&LocScope::Single(child_scope))
+                expr.try_eval_value_with_loc(&LocScope::Single(child_scope), mode)
             }
             _ => panic!("expected Lambda"),
         }
     }
+
+    pub fn eval_with_loc<'a>(&'a self, scope: &'a LocScope<'a>) -> Cow<'a, ParsedValue> {
+        self.try_eval_with_loc(scope, ArithMode::Checked)
+            .unwrap_or_else(|Overflow| panic!("arithmetic overflow"))
+    }
+
+    pub fn eval_value_with_loc<'a>(&self, scope: &'a LocScope<'a>) -> Value {
+        self.eval_with_loc(scope)
+            .coerce_mapped_value()
+            .clone_into_value()
+    }
+
+    /// Evaluates a numeric expression denoting a length in bytes, in the nominal type of the expression
+    /// (as the generated parsers do) before converting it to `usize`.
+    ///
+    /// Returns `None` if any step of the computation over- or underflows, rather than panicking.
+    fn eval_length_with_loc<'a>(&self, scope: &'a LocScope<'a>, mode: ArithMode) -> Option<usize> {
+        Some(
+            self.try_eval_value_with_loc(scope, mode)
+                .ok()?
+                .unwrap_usize(),
+        )
+    }
 }
 
 impl Program {
@@ -934,6 +1313,38 @@ impl Program {
             .0
             .parse_with_loc(self, &LocScope::Empty, input)
     }
+
+    /// Evaluates `expr` under the arithmetic mode of this program, failing at `offset` on overflow.
+    fn eval_with_loc<'a>(
+        &self,
+        expr: &'a Expr,
+        scope: &'a LocScope<'a>,
+        offset: usize,
+    ) -> LocParseResult<Cow<'a, ParsedValue>> {
+        (expr.try_eval_with_loc(scope, self.arith_mode()))
+            .map_err(|Overflow| ParseError::overflow(offset))
+    }
+
+    fn eval_value_with_loc<'a>(
+        &self,
+        expr: &Expr,
+        scope: &'a LocScope<'a>,
+        offset: usize,
+    ) -> LocParseResult<Value> {
+        (expr.try_eval_value_with_loc(scope, self.arith_mode()))
+            .map_err(|Overflow| ParseError::overflow(offset))
+    }
+
+    fn eval_lambda_with_loc<'a>(
+        &self,
+        expr: &Expr,
+        scope: &'a LocScope<'a>,
+        arg: &ParsedValue,
+        offset: usize,
+    ) -> LocParseResult<Value> {
+        (expr.try_eval_lambda_with_loc(scope, arg, self.arith_mode()))
+            .map_err(|Overflow| ParseError::overflow(offset))
+    }
 }
 
 pub type LocScopeEntry = ScopeEntry<ParsedValue>;
@@ -1095,7 +1506,10 @@ impl Decoder {
             Decoder::Call(n, es) => {
                 let mut new_scope = LocMultiScope::with_capacity(&LocScope::Empty, es.len());
                 for (name, e) in es {
-                    let v = e.eval_with_loc(scope).as_ref().clone();
+                    let v = program
+                        .eval_with_loc(e, scope, input.offset)?
+                        .as_ref()
+                        .clone();
                     new_scope.push(name.clone(), v);
                 }
                 program.decoders[*n]
@@ -1114,6 +1528,22 @@ impl Decoder {
                     .ok_or(ParseError::overrun(skip, input.offset))?;
                 Ok((ParsedValue::unit_spanning(start_offset, skip), input))
             }
+            Decoder::AbsolutePos => {
+                let pos = u32::try_from(input.offset)
+                    .map_err(|_| ParseError::position_overflow(input.offset))?;
+                Ok((
+                    ParsedValue::new_flat(Value::U32(pos), start_offset, 0),
+                    input,
+                ))
+            }
+            Decoder::SlicePos => {
+                let pos = u32::try_from(input.slice_offset())
+                    .map_err(|_| ParseError::position_overflow(input.offset))?;
+                Ok((
+                    ParsedValue::new_flat(Value::U32(pos), start_offset, 0),
+                    input,
+                ))
+            }
             Decoder::Byte(bs) => {
                 let (b, input) = input
                     .read_byte()
@@ -1128,6 +1558,18 @@ impl Decoder {
                 let (v, input) = d.parse_with_loc(program, scope, input)?;
                 Ok((ParsedValue::Variant(label.clone(), Box::new(v)), input))
             }
+            Decoder::Newtype(d, validate) => {
+                let (v, next_input) = d.parse_with_loc(program, scope, input)?;
+                if let Some(expr) = validate {
+                    if !program
+                        .eval_lambda_with_loc(expr, scope, &v, input.offset)?
+                        .unwrap_bool()
+                    {
+                        return Err(ParseError::<ParsedValue>::loc_fail(scope, input));
+                    }
+                }
+                Ok((v, next_input))
+            }
             Decoder::Branch(tree, branches) => {
                 let index = tree.matches(input).ok_or(ParseError::NoValidBranch {
                     offset: input.offset,
@@ -1198,11 +1640,32 @@ impl Decoder {
                 let totlen = input.offset - start_offset;
                 Ok((ParsedValue::new_seq(v, start_offset, totlen), input))
             }
-            Decoder::RepeatCount(expr, a) => {
+            Decoder::RepeatSep(tree, a, sep) => {
+                let (va, mut input) = a.parse_with_loc(program, scope, input)?;
+                let mut v = vec![va];
+                while tree.matches(input).ok_or(ParseError::NoValidBranch {
+                    offset: input.offset,
+                })? == 0
+                {
+                    let (_, next_input) = sep.parse_with_loc(program, scope, input)?;
+                    let (va, next_input) = a.parse_with_loc(program, scope, next_input)?;
+                    input = next_input;
+                    v.push(va);
+                }
+                let totlen = input.offset - start_offset;
+                Ok((ParsedValue::new_seq(v, start_offset, totlen), input))
+            }
+            Decoder::RepeatCount(expr, a, a_last) => {
                 let mut input = input;
-                let count = expr.eval_value_with_loc(scope).unwrap_usize();
-                let mut v = Vec::with_capacity(count);
-                for _ in 0..count {
+                let count = program
+                    .eval_value_with_loc(expr, scope, input.offset)?
+                    .unwrap_usize();
+                let mut v = Vec::with_capacity(Ord::min(count, input.remaining().len()));
+                for ix in 0..count {
+                    let a = match a_last {
+                        Some(a_last) if ix + 1 == count => a_last,
+                        _ => a,
+                    };
                     let (va, next_input) = a.parse_with_loc(program, scope, input)?;
                     input = next_input;
                     v.push(va);
@@ -1212,8 +1675,12 @@ impl Decoder {
             }
             Decoder::RepeatBetween(tree, min, max, a) => {
                 let mut input = input;
-                let min = min.eval_value_with_loc(scope).unwrap_usize();
-                let max = max.eval_value_with_loc(scope).unwrap_usize();
+                let min = program
+                    .eval_value_with_loc(min, scope, input.offset)?
+                    .unwrap_usize();
+                let max = program
+                    .eval_value_with_loc(max, scope, input.offset)?
+                    .unwrap_usize();
                 let mut v = Vec::new();
                 loop {
                     if tree.matches(input).ok_or(ParseError::NoValidBranch {
@@ -1238,16 +1705,39 @@ impl Decoder {
                 let mut v = Vec::new();
                 loop {
                     let (va, next_input) = a.parse_with_loc(program, scope, input)?;
+                    let progressed = next_input.offset > input.offset;
                     input = next_input;
-                    let done = expr.eval_lambda_with_loc(scope, &va).unwrap_bool();
+                    let done = program
+                        .eval_lambda_with_loc(expr, scope, &va, input.offset)?
+                        .unwrap_bool();
                     v.push(va);
                     if done {
                         break;
+                    } else if !progressed {
+                        // an empty non-terminal iteration would be repeated identically forever
+                        return Err(ParseError::<ParsedValue>::loc_fail(scope, input));
                     }
                 }
                 let totlen = input.offset - start_offset;
                 Ok((ParsedValue::new_seq(v, start_offset, totlen), input))
             }
+            Decoder::UntilSeq(terminator, a) => {
+                let mut input = input;
+                let mut v = Vec::new();
+                loop {
+                    if input.remaining().starts_with(terminator) {
+                        let (_, input) = input.split_at(terminator.len()).unwrap();
+                        let totlen = input.offset - start_offset;
+                        return Ok((ParsedValue::new_seq(v, start_offset, totlen), input));
+                    }
+                    if input.remaining().is_empty() {
+                        return Err(ParseError::overbyte(input.offset));
+                    }
+                    let (va, next_input) = a.parse_with_loc(program, scope, input)?;
+                    input = next_input;
+                    v.push(va);
+                }
+            }
             Decoder::RepeatUntilSeq(expr, a) => {
                 let mut input = input;
                 let mut v = Vec::new();
@@ -1256,7 +1746,9 @@ impl Decoder {
                     input = next_input;
                     v.push(va);
                     let vs = ParsedValue::from_evaluated_seq(v);
-                    let done = expr.eval_lambda_with_loc(scope, &vs).unwrap_bool();
+                    let done = program
+                        .eval_lambda_with_loc(expr, scope, &vs, input.offset)?
+                        .unwrap_bool();
                     v = match vs {
                         ParsedValue::Seq(v) => v.inner,
                         _ => unreachable!(),
@@ -1268,6 +1760,34 @@ impl Decoder {
                 let totlen = input.offset - start_offset;
                 Ok((ParsedValue::new_seq(v, start_offset, totlen), input))
             }
+            Decoder::ForEach(expr, name, a) => {
+                let mut input = input;
+                let seq = program.eval_with_loc(expr, scope, input.offset)?;
+                let Some(elems) = seq.coerce_mapped_value().get_sequence() else {
+                    panic!("ForEach: expected Seq")
+                };
+                let mut v = Vec::with_capacity(elems.len());
+                for elem in elems {
+                    let elem_scope = LocSingleScope::new(scope, name, elem);
+                    let (va, next_input) =
+                        a.parse_with_loc(program, &LocScope::Single(elem_scope), input)?;
+                    input = next_input;
+                    v.push(va);
+                }
+                let totlen = input.offset - start_offset;
+                Ok((ParsedValue::new_seq(v, start_offset, totlen), input))
+            }
+            Decoder::RepeatWhileInput(a) => {
+                let mut input = input;
+                let mut v = Vec::new();
+                while !input.remaining().is_empty() {
+                    let (va, next_input) = a.parse_with_loc(program, scope, input)?;
+                    input = next_input;
+                    v.push(va);
+                }
+                let totlen = input.offset - start_offset;
+                Ok((ParsedValue::new_seq(v, start_offset, totlen), input))
+            }
             Decoder::Peek(a) => {
                 let (v, _next_input) = a.parse_with_loc(program, scope, input)?;
                 Ok((v, input))
@@ -1279,8 +1799,16 @@ impl Decoder {
                     Ok((ParsedValue::unit_at(start_offset), input))
                 }
             }
+            Decoder::Signature(name, magic, body) => {
+                let (_, input) = magic
+                    .parse_with_loc(program, scope, input)
+                    .map_err(|_| ParseError::wrong_format(name.clone(), input.offset))?;
+                body.parse_with_loc(program, scope, input)
+            }
             Decoder::Slice(expr, a) => {
-                let size = expr.eval_value_with_loc(scope).unwrap_usize();
+                let size = expr
+                    .eval_length_with_loc(scope, program.arith_mode())
+                    .ok_or(ParseError::overflow(input.offset))?;
                 let (slice, input) = input
                     .split_at(size)
                     .ok_or(ParseError::overrun(size, input.offset))?;
@@ -1303,30 +1831,44 @@ impl Decoder {
                 Ok((v, input))
             }
             Decoder::WithRelativeOffset(expr, a) => {
-                let offset = expr.eval_value_with_loc(scope).unwrap_usize();
+                let offset = program
+                    .eval_value_with_loc(expr, scope, input.offset)?
+                    .unwrap_usize();
                 let (_, slice) = input
                     .split_at(offset)
                     .ok_or(ParseError::overrun(offset, input.offset))?;
                 let (v, _) = a.parse_with_loc(program, scope, slice)?;
                 Ok((v, input))
             }
+            Decoder::FollowOffset(expr, a) => {
+                let offset = program
+                    .eval_value_with_loc(expr, scope, input.offset)?
+                    .unwrap_usize();
+                let (_, input) = input
+                    .split_at(offset)
+                    .ok_or(ParseError::overrun(offset, input.offset))?;
+                a.parse_with_loc(program, scope, input)
+            }
             Decoder::Map(d, expr) => {
                 let (orig, input) = d.parse_with_loc(program, scope, input)?;
-                let v = expr.eval_lambda_with_loc(scope, &orig);
+                let v = program.eval_lambda_with_loc(expr, scope, &orig, input.offset)?;
                 let image = ParsedValue::inherit(&orig, v);
                 Ok((ParsedValue::Mapped(Box::new(orig), Box::new(image)), input))
             }
             Decoder::Compute(expr) => {
-                let v = expr.eval_with_loc(scope);
+                let v = program.eval_with_loc(expr, scope, input.offset)?;
                 Ok((v.as_ref().clone(), input))
             }
             Decoder::Let(name, expr, d) => {
-                let v = expr.eval_with_loc(scope).as_ref().clone();
+                let v = program
+                    .eval_with_loc(expr, scope, input.offset)?
+                    .as_ref()
+                    .clone();
                 let let_scope = LocSingleScope::new(scope, name, &v);
                 d.parse_with_loc(program, &LocScope::Single(let_scope), input)
             }
             Decoder::Match(head, branches) => {
-                let head = head.eval_with_loc(scope);
+                let head = program.eval_with_loc(head, scope, input.offset)?;
                 for (index, (pattern, decoder)) in branches.iter().enumerate() {
                     if let Some(pattern_scope) = head.matches(scope, pattern) {
                         let (v, input) = decoder.parse_with_loc(
@@ -1340,12 +1882,14 @@ impl Decoder {
                 panic!("non-exhaustive patterns");
             }
             Decoder::Dynamic(name, DynFormat::Huffman(lengths_expr, opt_values_expr), d) => {
-                let lengths_val = lengths_expr.eval_with_loc(scope);
+                let lengths_val = program.eval_with_loc(lengths_expr, scope, input.offset)?;
                 let lengths = value_to_vec_usize(lengths_val.as_ref());
                 let lengths = match opt_values_expr {
                     None => lengths,
                     Some(e) => {
-                        let values = value_to_vec_usize(e.eval_with_loc(scope).as_ref());
+                        let values = value_to_vec_usize(
+                            program.eval_with_loc(e, scope, input.offset)?.as_ref(),
+                        );
                         let mut new_lengths = [0].repeat(values.len());
                         for i in 0..lengths.len() {
                             new_lengths[values[i]] = lengths[i];
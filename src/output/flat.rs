@@ -125,6 +125,7 @@ fn check_covered(
         Format::Fail => {}
         Format::EndOfInput => {}
         Format::Align(_) => {}
+        Format::AbsolutePos | Format::SlicePos => {}
         Format::Byte(_) => {
             return Err(format!("uncovered byte: {:?}", path));
         }
@@ -133,6 +134,7 @@ fn check_covered(
             check_covered(module, path, format)?;
             path.pop();
         }
+        Format::Newtype(_name, format, _validate) => check_covered(module, path, format)?,
         Format::Union(branches) | Format::UnionNondet(branches) => {
             for format in branches {
                 check_covered(module, path, format)?;
@@ -152,15 +154,19 @@ fn check_covered(
         }
         Format::Repeat(format)
         | Format::Repeat1(format)
+        | Format::RepeatSep(format, _)
         | Format::RepeatCount(_, format)
         | Format::RepeatBetween(_, _, format)
         | Format::RepeatUntilLast(_, format)
-        | Format::RepeatUntilSeq(_, format) => {
+        | Format::RepeatUntilSeq(_, format)
+        | Format::UntilSeq(_, format)
+        | Format::ForEach(_, _, format)
+        | Format::RepeatWhileInput(format) => {
             check_covered(module, path, format)?;
         }
         Format::Peek(_) => {}    // FIXME
         Format::PeekNot(_) => {} // FIXME
-        Format::Slice(_, format) => {
+        Format::Slice(_, format) | Format::Signature(_, _, format) => {
             check_covered(module, path, format)?;
         }
 
@@ -168,6 +174,9 @@ fn check_covered(
             check_covered(module, path, format)?;
         }
         Format::WithRelativeOffset(_, _) => {} // FIXME
+        Format::FollowOffset(_, format) => {
+            check_covered(module, path, format)?;
+        }
         Format::Map(format, _expr) => check_covered(module, path, format)?,
         Format::Compute(_expr) => {}
         Format::Let(_name, _expr, format) => check_covered(module, path, format)?,
@@ -188,6 +197,9 @@ impl<'module, W: io::Write> Context<'module, W> {
     }
 
     pub fn write_flat(&mut self, value: &Value, format: &Format) -> io::Result<()> {
+        if let Value::Shared(value) = value {
+            return self.write_flat(value, format);
+        }
         match format {
             Format::ItemVar(level, _args) => {
                 let label = self.module.get_name(*level);
@@ -200,6 +212,7 @@ impl<'module, W: io::Write> Context<'module, W> {
             Format::Fail => Ok(()),
             Format::EndOfInput => Ok(()),
             Format::Align(_) => Ok(()),
+            Format::AbsolutePos | Format::SlicePos => Ok(()),
             Format::Byte(_) => Ok(()),
             Format::Variant(label, format) => match value {
                 Value::Variant(label2, value) => {
@@ -211,6 +224,7 @@ impl<'module, W: io::Write> Context<'module, W> {
                 }
                 _ => panic!("expected variant, found {value:?}"),
             },
+            Format::Newtype(_name, format, _validate) => self.write_flat(value, format),
             Format::Union(branches) | Format::UnionNondet(branches) => match value {
                 Value::Branch(index, value) => {
                     let format = &branches[*index];
@@ -240,10 +254,14 @@ impl<'module, W: io::Write> Context<'module, W> {
             },
             Format::Repeat(format)
             | Format::Repeat1(format)
+            | Format::RepeatSep(format, _)
             | Format::RepeatCount(_, format)
             | Format::RepeatBetween(_, _, format)
             | Format::RepeatUntilLast(_, format)
-            | Format::RepeatUntilSeq(_, format) => match value {
+            | Format::RepeatUntilSeq(_, format)
+            | Format::UntilSeq(_, format)
+            | Format::ForEach(_, _, format)
+            | Format::RepeatWhileInput(format) => match value {
                 Value::Seq(values) => {
                     for v in values {
                         self.write_flat(v, format)?;
@@ -255,8 +273,11 @@ impl<'module, W: io::Write> Context<'module, W> {
             Format::Peek(format) => self.write_flat(value, format),
             Format::PeekNot(format) => self.write_flat(value, format),
             Format::Slice(_, format) => self.write_flat(value, format),
+            Format::Signature(_, _, format) => self.write_flat(value, format),
             Format::Bits(format) => self.write_flat(value, format),
-            Format::WithRelativeOffset(_, format) => self.write_flat(value, format),
+            Format::WithRelativeOffset(_, format) | Format::FollowOffset(_, format) => {
+                self.write_flat(value, format)
+            }
             Format::Map(_format, _expr) => Ok(()),
             Format::Compute(_expr) => Ok(()),
             Format::Let(_name, _expr, format) => self.write_flat(value, format),
@@ -81,9 +81,13 @@ impl<'module> MonoidalPrinter<'module> {
             Format::Record(fields) => fields.iter().all(|(_, f)| self.is_implied_value_format(f)),
             Format::Repeat(format)
             | Format::Repeat1(format)
+            | Format::RepeatSep(format, _)
             | Format::RepeatCount(_, format)
             | Format::RepeatUntilSeq(_, format)
-            | Format::RepeatUntilLast(_, format) => self.is_implied_value_format(format),
+            | Format::RepeatUntilLast(_, format)
+            | Format::UntilSeq(_, format)
+            | Format::ForEach(_, _, format)
+            | Format::RepeatWhileInput(format) => self.is_implied_value_format(format),
             Format::Slice(_, format) => self.is_implied_value_format(format),
             _ => false,
         }
@@ -139,6 +143,7 @@ impl<'module> MonoidalPrinter<'module> {
             Value::Char(_) => true,
             Value::Bool(_) => true,
             Value::U8(_) | Value::U16(_) | Value::U32(_) | Value::U64(_) => true,
+            Value::I8(_) | Value::I16(_) | Value::I32(_) | Value::I64(_) => true,
             Value::Tuple(values) => values.is_empty(),
             Value::Record(fields) => fields.is_empty(),
             Value::Seq(values) => values.is_empty(),
@@ -173,6 +178,9 @@ impl<'module> MonoidalPrinter<'module> {
                 None => self.is_atomic_value(value.as_ref(), None),
                 f => panic!("expected format suitable for branch: {f:?}"),
             },
+            Value::Shared(value) => self.is_atomic_value(value.as_ref(), format),
+            Value::Option(None) => true,
+            Value::Option(Some(value)) => self.is_atomic_value(value.as_ref(), None),
         }
     }
 
@@ -222,6 +230,10 @@ impl<'module> MonoidalPrinter<'module> {
                     Value::U16(i) => Fragment::DisplayAtom(Rc::new(*i)),
                     Value::U32(i) => Fragment::DisplayAtom(Rc::new(*i)),
                     Value::U64(i) => Fragment::DisplayAtom(Rc::new(*i)),
+                    Value::I8(i) => Fragment::DisplayAtom(Rc::new(*i)),
+                    Value::I16(i) => Fragment::DisplayAtom(Rc::new(*i)),
+                    Value::I32(i) => Fragment::DisplayAtom(Rc::new(*i)),
+                    Value::I64(i) => Fragment::DisplayAtom(Rc::new(*i)),
                     Value::Char(c) => Fragment::DebugAtom(Rc::new(*c)),
                     _ => unreachable!("found non-flat Value in ParsedValue::Flat: {inner:?}"),
                 };
@@ -286,6 +298,7 @@ impl<'module> MonoidalPrinter<'module> {
             Format::Fail => panic!("uninhabited format (value={value:?}"),
             Format::EndOfInput => self.compile_parsed_value(value),
             Format::Align(_) => self.compile_parsed_value(value),
+            Format::AbsolutePos | Format::SlicePos => self.compile_parsed_value(value),
             Format::Byte(_) => self.compile_parsed_value(value),
             Format::Variant(label, format) => match value {
                 ParsedValue::Variant(label2, value) => {
@@ -297,6 +310,9 @@ impl<'module> MonoidalPrinter<'module> {
                 }
                 _ => panic!("expected variant, found {value:?}"),
             },
+            Format::Newtype(_name, format, _validate) => {
+                self.compile_parsed_decoded_value(value, format)
+            }
             Format::Union(branches) | Format::UnionNondet(branches) => match value {
                 ParsedValue::Branch(n, value) => {
                     let format = &branches[*n];
@@ -322,10 +338,14 @@ impl<'module> MonoidalPrinter<'module> {
             },
             Format::Repeat(format)
             | Format::Repeat1(format)
+            | Format::RepeatSep(format, _)
             | Format::RepeatCount(_, format)
             | Format::RepeatBetween(_, _, format)
             | Format::RepeatUntilLast(_, format)
-            | Format::RepeatUntilSeq(_, format) => match value {
+            | Format::RepeatUntilSeq(_, format)
+            | Format::UntilSeq(_, format)
+            | Format::ForEach(_, _, format)
+            | Format::RepeatWhileInput(format) => match value {
                 ParsedValue::Seq(values) => {
                     if self.flags.tables_for_record_sequences
                         && self.try_as_record_with_atomic_fields(format).is_some()
@@ -343,9 +363,11 @@ impl<'module> MonoidalPrinter<'module> {
             },
             Format::Peek(format) => self.compile_parsed_decoded_value(value, format),
             Format::PeekNot(_format) => self.compile_parsed_value(value),
-            Format::Slice(_, format) => self.compile_parsed_decoded_value(value, format),
+            Format::Slice(_, format) | Format::Signature(_, _, format) => {
+                self.compile_parsed_decoded_value(value, format)
+            }
             Format::Bits(format) => self.compile_parsed_decoded_value(value, format),
-            Format::WithRelativeOffset(_, format) => {
+            Format::WithRelativeOffset(_, format) | Format::FollowOffset(_, format) => {
                 self.compile_parsed_decoded_value(value, format)
             }
             Format::Map(format, _expr) => {
@@ -378,6 +400,9 @@ impl<'module> MonoidalPrinter<'module> {
     }
 
     pub fn compile_decoded_value(&mut self, value: &Value, fmt: &Format) -> Fragment {
+        if let Value::Shared(value) = value {
+            return self.compile_decoded_value(value, fmt);
+        }
         let mut frag = Fragment::Empty;
         match fmt {
             Format::ItemVar(level, _args) => {
@@ -401,6 +426,7 @@ impl<'module> MonoidalPrinter<'module> {
             Format::Fail => panic!("uninhabited format (value={value:?}"),
             Format::EndOfInput => self.compile_value(value),
             Format::Align(_) => self.compile_value(value),
+            Format::AbsolutePos | Format::SlicePos => self.compile_value(value),
             Format::Byte(_) => self.compile_value(value),
             Format::Variant(label, format) => match value {
                 Value::Variant(label2, value) => {
@@ -412,6 +438,7 @@ impl<'module> MonoidalPrinter<'module> {
                 }
                 _ => panic!("expected variant, found {value:?}"),
             },
+            Format::Newtype(_name, format, _validate) => self.compile_decoded_value(value, format),
             Format::Union(branches) | Format::UnionNondet(branches) => match value {
                 Value::Branch(n, value) => {
                     let format = &branches[*n];
@@ -437,10 +464,14 @@ impl<'module> MonoidalPrinter<'module> {
             },
             Format::Repeat(format)
             | Format::Repeat1(format)
+            | Format::RepeatSep(format, _)
             | Format::RepeatCount(_, format)
             | Format::RepeatBetween(_, _, format)
             | Format::RepeatUntilLast(_, format)
-            | Format::RepeatUntilSeq(_, format) => match value {
+            | Format::RepeatUntilSeq(_, format)
+            | Format::UntilSeq(_, format)
+            | Format::ForEach(_, _, format)
+            | Format::RepeatWhileInput(format) => match value {
                 Value::Seq(values) => {
                     if self.flags.tables_for_record_sequences
                         && self.try_as_record_with_atomic_fields(format).is_some()
@@ -458,9 +489,13 @@ impl<'module> MonoidalPrinter<'module> {
             },
             Format::Peek(format) => self.compile_decoded_value(value, format),
             Format::PeekNot(_format) => self.compile_value(value),
-            Format::Slice(_, format) => self.compile_decoded_value(value, format),
+            Format::Slice(_, format) | Format::Signature(_, _, format) => {
+                self.compile_decoded_value(value, format)
+            }
             Format::Bits(format) => self.compile_decoded_value(value, format),
-            Format::WithRelativeOffset(_, format) => self.compile_decoded_value(value, format),
+            Format::WithRelativeOffset(_, format) | Format::FollowOffset(_, format) => {
+                self.compile_decoded_value(value, format)
+            }
             Format::Map(format, _expr) => {
                 if self.flags.collapse_mapped_values {
                     self.compile_value(value)
@@ -498,6 +533,10 @@ impl<'module> MonoidalPrinter<'module> {
             Value::U16(i) => Fragment::DisplayAtom(Rc::new(*i)),
             Value::U32(i) => Fragment::DisplayAtom(Rc::new(*i)),
             Value::U64(i) => Fragment::DisplayAtom(Rc::new(*i)),
+            Value::I8(i) => Fragment::DisplayAtom(Rc::new(*i)),
+            Value::I16(i) => Fragment::DisplayAtom(Rc::new(*i)),
+            Value::I32(i) => Fragment::DisplayAtom(Rc::new(*i)),
+            Value::I64(i) => Fragment::DisplayAtom(Rc::new(*i)),
             Value::Char(c) => Fragment::DebugAtom(Rc::new(*c)),
             Value::Tuple(vals) => self.compile_tuple(vals, None),
             Value::Seq(vals) => self.compile_seq(vals, None),
@@ -511,6 +550,9 @@ impl<'module> MonoidalPrinter<'module> {
                 }
             }
             Value::Branch(_n, value) => self.compile_value(value),
+            Value::Option(None) => self.compile_variant("none", &Value::UNIT, None),
+            Value::Option(Some(value)) => self.compile_variant("some", value, None),
+            Value::Shared(value) => self.compile_value(value),
         }
     }
 
@@ -1402,6 +1444,11 @@ impl<'module> MonoidalPrinter<'module> {
                 prec,
                 Precedence::BITOR,
             ),
+            Expr::Arith(Arith::BitXor, lhs, rhs) => cond_paren(
+                self.compile_binop(" ^ ", lhs, rhs, Precedence::BITXOR, Precedence::BITXOR),
+                prec,
+                Precedence::BITXOR,
+            ),
             Expr::Arith(Arith::Shl, lhs, rhs) => cond_paren(
                 self.compile_binop(" << ", lhs, rhs, Precedence::BITSHIFT, Precedence::BITSHIFT),
                 prec,
@@ -1412,6 +1459,21 @@ impl<'module> MonoidalPrinter<'module> {
                 prec,
                 Precedence::BITSHIFT,
             ),
+            Expr::AsSigned8(expr) => cond_paren(
+                self.compile_prefix("as-signed8", None, expr),
+                prec,
+                Precedence::CAST_PREFIX,
+            ),
+            Expr::AsSigned16(expr) => cond_paren(
+                self.compile_prefix("as-signed16", None, expr),
+                prec,
+                Precedence::CAST_PREFIX,
+            ),
+            Expr::AsSigned32(expr) => cond_paren(
+                self.compile_prefix("as-signed32", None, expr),
+                prec,
+                Precedence::CAST_PREFIX,
+            ),
             Expr::AsU8(expr) => cond_paren(
                 self.compile_prefix("as-u8", None, expr),
                 prec,
@@ -1467,11 +1529,36 @@ impl<'module> MonoidalPrinter<'module> {
                 prec,
                 Precedence::CAST_PREFIX,
             ),
+            Expr::I16Be(bytes) => cond_paren(
+                self.compile_prefix("i16be", None, bytes),
+                prec,
+                Precedence::CAST_PREFIX,
+            ),
+            Expr::I16Le(bytes) => cond_paren(
+                self.compile_prefix("i16le", None, bytes),
+                prec,
+                Precedence::CAST_PREFIX,
+            ),
+            Expr::I32Be(bytes) => cond_paren(
+                self.compile_prefix("i32be", None, bytes),
+                prec,
+                Precedence::CAST_PREFIX,
+            ),
+            Expr::I32Le(bytes) => cond_paren(
+                self.compile_prefix("i32le", None, bytes),
+                prec,
+                Precedence::CAST_PREFIX,
+            ),
             Expr::SeqLength(seq) => cond_paren(
                 self.compile_prefix("seq-length", None, seq),
                 prec,
                 Precedence::FUNAPP,
             ),
+            Expr::SeqIx(seq, index) => cond_paren(
+                self.compile_prefix("seq-ix", Some(&[index]), seq),
+                prec,
+                Precedence::FUNAPP,
+            ),
             Expr::SubSeq(seq, start, length) => cond_paren(
                 self.compile_prefix("sub-seq", Some(&[start, length]), seq),
                 prec,
@@ -1525,6 +1612,10 @@ impl<'module> MonoidalPrinter<'module> {
             Expr::U16(i) => Fragment::DisplayAtom(Rc::new(*i)),
             Expr::U32(i) => Fragment::DisplayAtom(Rc::new(*i)),
             Expr::U64(i) => Fragment::DisplayAtom(Rc::new(*i)),
+            Expr::I8(i) => Fragment::DisplayAtom(Rc::new(*i)),
+            Expr::I16(i) => Fragment::DisplayAtom(Rc::new(*i)),
+            Expr::I32(i) => Fragment::DisplayAtom(Rc::new(*i)),
+            Expr::I64(i) => Fragment::DisplayAtom(Rc::new(*i)),
             Expr::Tuple(..) => Fragment::String("(...)".into()),
             Expr::Record(..) => Fragment::String("{ ... }".into()),
             Expr::Variant(label, expr) => Fragment::String("{ ".into())
@@ -1574,16 +1665,39 @@ impl<'module> MonoidalPrinter<'module> {
                 prec,
                 Precedence::FORMAT_COMPOUND,
             ),
+            Format::Newtype(name, format, _validate) => cond_paren(
+                self.compile_nested_format(
+                    "newtype",
+                    Some(&[Fragment::String(name.clone())]),
+                    format,
+                    prec,
+                ),
+                prec,
+                Precedence::FORMAT_COMPOUND,
+            ),
             Format::Repeat(format) => cond_paren(
                 self.compile_nested_format("repeat", None, format, prec),
                 prec,
                 Precedence::FORMAT_COMPOUND,
             ),
+            Format::RepeatWhileInput(format) => cond_paren(
+                self.compile_nested_format("repeat-while-input", None, format, prec),
+                prec,
+                Precedence::FORMAT_COMPOUND,
+            ),
             Format::Repeat1(format) => cond_paren(
                 self.compile_nested_format("repeat1", None, format, prec),
                 prec,
                 Precedence::FORMAT_COMPOUND,
             ),
+            Format::RepeatSep(format, sep) => {
+                let sep_frag = self.compile_format(sep, Precedence::ATOM);
+                cond_paren(
+                    self.compile_nested_format("repeat-sep", Some(&[sep_frag]), format, prec),
+                    prec,
+                    Precedence::FORMAT_COMPOUND,
+                )
+            }
             Format::RepeatCount(len, format) => {
                 let expr_frag = self.compile_expr(len, Precedence::ATOM);
                 cond_paren(
@@ -1616,6 +1730,15 @@ impl<'module> MonoidalPrinter<'module> {
                     Precedence::FORMAT_COMPOUND,
                 )
             }
+            Format::UntilSeq(terminator, format) => {
+                let terminator = Expr::Seq(terminator.iter().copied().map(Expr::U8).collect());
+                let terminator_frag = self.compile_expr(&terminator, Precedence::ATOM);
+                cond_paren(
+                    self.compile_nested_format("until-seq", Some(&[terminator_frag]), format, prec),
+                    prec,
+                    Precedence::FORMAT_COMPOUND,
+                )
+            }
             Format::RepeatUntilSeq(expr, format) => {
                 let expr_frag = self.compile_expr(expr, Precedence::ATOM);
                 cond_paren(
@@ -1639,6 +1762,19 @@ impl<'module> MonoidalPrinter<'module> {
                 prec,
                 Precedence::FORMAT_COMPOUND,
             ),
+            Format::Signature(name, magic, format) => {
+                let magic_frag = self.compile_format(magic, Precedence::ATOM);
+                cond_paren(
+                    self.compile_nested_format(
+                        "signature",
+                        Some(&[Fragment::String(name.clone()), magic_frag]),
+                        format,
+                        prec,
+                    ),
+                    prec,
+                    Precedence::FORMAT_COMPOUND,
+                )
+            }
             Format::Slice(len, format) => {
                 let expr_frag = self.compile_expr(len, Precedence::ATOM);
                 cond_paren(
@@ -1665,6 +1801,14 @@ impl<'module> MonoidalPrinter<'module> {
                     Precedence::FORMAT_COMPOUND,
                 )
             }
+            Format::FollowOffset(offset, format) => {
+                let expr_frag = self.compile_expr(offset, Precedence::ATOM);
+                cond_paren(
+                    self.compile_nested_format("follow-offset", Some(&[expr_frag]), format, prec),
+                    prec,
+                    Precedence::FORMAT_COMPOUND,
+                )
+            }
             Format::Map(format, expr) => {
                 let expr_frag = self.compile_expr(expr, Precedence::ATOM);
                 cond_paren(
@@ -1681,6 +1825,19 @@ impl<'module> MonoidalPrinter<'module> {
                 prec,
                 Precedence::FORMAT_COMPOUND,
             ),
+            Format::ForEach(expr, name, format) => {
+                let expr_frag = self.compile_expr(expr, Precedence::ATOM);
+                cond_paren(
+                    self.compile_nested_format(
+                        "for-each",
+                        Some(&[Fragment::String(name.clone()), expr_frag]),
+                        format,
+                        prec,
+                    ),
+                    prec,
+                    Precedence::FORMAT_COMPOUND,
+                )
+            }
             Format::Let(name, expr, format) => {
                 let expr_frag = self.compile_expr(expr, Precedence::ATOM);
                 cond_paren(
@@ -1732,6 +1889,8 @@ impl<'module> MonoidalPrinter<'module> {
             Format::Fail => Fragment::String("fail".into()),
             Format::EndOfInput => Fragment::String("end-of-input".into()),
             Format::Align(n) => Fragment::String(format!("align {n}").into()),
+            Format::AbsolutePos => Fragment::String("absolute-pos".into()),
+            Format::SlicePos => Fragment::String("slice-pos".into()),
 
             Format::Byte(bs) => match bs.len() {
                 0 => unreachable!("matches against the empty byteset are unsatisfiable"),
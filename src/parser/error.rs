@@ -15,6 +15,10 @@ pub enum ParseError {
     Overrun(OverrunKind),
     /// A `Format::EndOfInput` token occurring anywhere except the final offset of a Slice or the overall buffer.
     IncompleteParse { bytes_remaining: usize },
+    /// A `Format::AbsolutePos` or `Format::SlicePos` token whose value is too large to represent as a `u32`.
+    PositionOverflow,
+    /// A sequence converted into a fixed-size array did not have the expected number of elements.
+    WrongLength,
     /// Any unrecoverable error in the state of the Parser itself.
     InternalError(StateError),
 }
@@ -42,6 +46,8 @@ impl std::fmt::Display for ParseError {
                 f,
                 "incomplete parse: expected end-of-stream, but {n} bytes remain unconsumed"
             ),
+            ParseError::PositionOverflow => write!(f, "current position cannot be represented as a u32"),
+            ParseError::WrongLength => write!(f, "sequence length does not match the expected array length"),
             ParseError::Overrun(k) => match k {
                 OverrunKind::EndOfStream => write!(f, "offset would extend past end of stream"),
                 OverrunKind::EndOfSlice => write!(f, "offset would extend past end of slice"),
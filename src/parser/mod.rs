@@ -259,4 +259,22 @@ impl<'a> Parser<'a> {
     pub fn get_current_offset(&self) -> ByteOffset {
         self.offset.get_current_offset()
     }
+
+    /// Returns the index of the current byte in the overall buffer, as implied by a `Format::AbsolutePos` token.
+    ///
+    /// Will fail with `ParseError::PositionOverflow` if the index cannot be represented as a `u32`.
+    pub fn get_absolute_pos(&self) -> PResult<u32> {
+        let (pos, _) = self.get_current_offset().as_bytes();
+        u32::try_from(pos).map_err(|_| ParseError::PositionOverflow)
+    }
+
+    /// Returns the index of the current byte relative to the start of the innermost slice (or the overall buffer,
+    /// if no slice is open), as implied by a `Format::SlicePos` token.
+    ///
+    /// Will fail with `ParseError::PositionOverflow` if the index cannot be represented as a `u32`.
+    pub fn get_slice_pos(&self) -> PResult<u32> {
+        let (pos, _) = self.get_current_offset().as_bytes();
+        let (start, _) = self.offset.current_slice_start().as_bytes();
+        u32::try_from(pos - start).map_err(|_| ParseError::PositionOverflow)
+    }
 }
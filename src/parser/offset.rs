@@ -208,6 +208,11 @@ impl ViewStack {
         ret
     }
 
+    /// Returns the starting [`ByteOffset`] of the most recently-opened slice still on the `ViewStack`, if any.
+    pub(crate) fn get_slice_start(&self) -> Option<ByteOffset> {
+        self.stack.iter().rev().find_map(Lens::get_start)
+    }
+
     /// Performs a stack-pop operation on an owned `ViewStack`, returning the
     /// resulting `ViewStack` and the former topmost element.
     pub(crate) fn escape(mut self) -> (ViewStack, Option<Lens>) {
@@ -277,10 +282,19 @@ impl ViewStack {
 /// both for limited-view (Slice) and speculative (Peek, PeekNot, UnionNondet) parsing.
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
 pub(crate) enum Lens {
-    PeekNot { checkpoint: ByteOffset },
-    Peek { checkpoint: ByteOffset },
-    Slice { endpoint: ByteOffset },
-    Alts { checkpoint: ByteOffset },
+    PeekNot {
+        checkpoint: ByteOffset,
+    },
+    Peek {
+        checkpoint: ByteOffset,
+    },
+    Slice {
+        start: ByteOffset,
+        endpoint: ByteOffset,
+    },
+    Alts {
+        checkpoint: ByteOffset,
+    },
 }
 
 impl Lens {
@@ -314,7 +328,15 @@ impl Lens {
     /// until one is found or the ViewStack is exhausted.
     pub(crate) fn get_endpoint(&self) -> Option<ByteOffset> {
         match self {
-            Lens::Slice { endpoint } => Some(*endpoint),
+            Lens::Slice { endpoint, .. } => Some(*endpoint),
+            _ => None,
+        }
+    }
+
+    /// Returns the offset at which the current Lens begins, if it is a Slice.
+    pub(crate) fn get_start(&self) -> Option<ByteOffset> {
+        match self {
+            Lens::Slice { start, .. } => Some(*start),
             _ => None,
         }
     }
@@ -404,6 +426,7 @@ impl BufferOffset {
     /// it is implicitly assumed to specify a number of bytes.
     pub(crate) unsafe fn open_slice_unchecked(&mut self, slice_len: usize) {
         self.push_lens(Lens::Slice {
+            start: self.current_offset,
             endpoint: self.current_offset.increment_by(slice_len),
         })
     }
@@ -430,7 +453,7 @@ impl BufferOffset {
         let mut stack = ViewStack::new();
         std::mem::swap(&mut stack, &mut self.view_stack);
         match stack.escape() {
-            (stack, Some(Lens::Slice { endpoint })) => {
+            (stack, Some(Lens::Slice { endpoint, .. })) => {
                 if self.current_offset > endpoint {
                     return Err(ParseError::InternalError(StateError::SliceOverrun));
                 }
@@ -500,6 +523,14 @@ impl BufferOffset {
         self.view_stack.get_limit().unwrap_or(self.max_offset)
     }
 
+    /// Returns the greatest-lower-bound for the offset implied by the internal state of `self`.
+    ///
+    /// If at least one `Lens::Slice` is active, the most-recently-added will be respected and its start-point returned.
+    /// Otherwise, returns the start of the buffer.
+    pub(crate) fn current_slice_start(&self) -> ByteOffset {
+        self.view_stack.get_slice_start().unwrap_or_default()
+    }
+
     /// Returns the number of bytes (or bits, in bits-mode) 'remaining'; this will be the largest value of `n`
     /// for which `self.try_increment(n)` will return an `Ok` value.
     ///
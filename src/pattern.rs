@@ -13,10 +13,20 @@ pub enum Pattern {
     U16(u16),
     U32(u32),
     U64(u64),
+    /// Matches any value within the inclusive range `lo..=hi`
+    U8Range(u8, u8),
+    U16Range(u16, u16),
+    U32Range(u32, u32),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
     Char(char),
     Tuple(Vec<Pattern>),
     Variant(Label, Box<Pattern>),
     Seq(Vec<Pattern>),
+    /// Matches if any of the alternatives match; every alternative must bind the same names at the same types
+    Or(Vec<Pattern>),
 }
 
 impl Pattern {
@@ -34,6 +44,10 @@ impl Pattern {
         Pattern::Binding(name.into())
     }
 
+    pub fn or(alts: impl IntoIterator<Item = Pattern>) -> Pattern {
+        Pattern::Or(alts.into_iter().collect())
+    }
+
     pub(crate) fn build_scope(&self, scope: &mut TypeScope<'_>, t: Rc<ValueType>) {
         match (self, t.as_ref()) {
             (Pattern::Binding(name), t) => {
@@ -45,6 +59,14 @@ impl Pattern {
             (Pattern::U8(..), ValueType::Base(BaseType::U8)) => {}
             (Pattern::U16(..), ValueType::Base(BaseType::U16)) => {}
             (Pattern::U32(..), ValueType::Base(BaseType::U32)) => {}
+            (Pattern::U64(..), ValueType::Base(BaseType::U64)) => {}
+            (Pattern::U8Range(..), ValueType::Base(BaseType::U8)) => {}
+            (Pattern::U16Range(..), ValueType::Base(BaseType::U16)) => {}
+            (Pattern::U32Range(..), ValueType::Base(BaseType::U32)) => {}
+            (Pattern::I8(..), ValueType::Base(BaseType::I8)) => {}
+            (Pattern::I16(..), ValueType::Base(BaseType::I16)) => {}
+            (Pattern::I32(..), ValueType::Base(BaseType::I32)) => {}
+            (Pattern::I64(..), ValueType::Base(BaseType::I64)) => {}
             (Pattern::Tuple(ps), ValueType::Tuple(ts)) if ps.len() == ts.len() => {
                 for (p, t) in Iterator::zip(ps.iter(), ts.iter()) {
                     p.build_scope(scope, Rc::new(t.clone()));
@@ -55,6 +77,12 @@ impl Pattern {
                     p.build_scope(scope, Rc::new((**t).clone()));
                 }
             }
+            (Pattern::Or(ps), _) => {
+                // all alternatives bind the same names, so the first one suffices
+                if let Some(p) = ps.first() {
+                    p.build_scope(scope, t);
+                }
+            }
             (Pattern::Variant(label, p), ValueType::Union(branches)) => {
                 if let Some(t) = branches.get(label) {
                     // FIXME - this is pretty bad, but it is hard to do better without more destructive changes
@@ -42,7 +42,8 @@ pub(crate) enum ArithLevel {
 pub(crate) enum BitwiseLevel {
     Shift = 0, // Highest bitwise precedence
     And = 1,
-    Or = 2,
+    Xor = 2,
+    Or = 3,
 }
 
 /// Intransitive partial relation over operator subclasses
@@ -94,8 +95,11 @@ impl IntransitiveOrd for BitwiseLevel {
             (BitwiseLevel::Shift, _) => Relation::Superior,
             (_, BitwiseLevel::Shift) => Relation::Inferior,
             (BitwiseLevel::And, BitwiseLevel::And) => Relation::Congruent,
-            (BitwiseLevel::And, BitwiseLevel::Or) => Relation::Superior,
-            (BitwiseLevel::Or, BitwiseLevel::And) => Relation::Inferior,
+            (BitwiseLevel::And, _) => Relation::Superior,
+            (_, BitwiseLevel::And) => Relation::Inferior,
+            (BitwiseLevel::Xor, BitwiseLevel::Xor) => Relation::Congruent,
+            (BitwiseLevel::Xor, BitwiseLevel::Or) => Relation::Superior,
+            (BitwiseLevel::Or, BitwiseLevel::Xor) => Relation::Inferior,
             (BitwiseLevel::Or, BitwiseLevel::Or) => Relation::Congruent,
         }
     }
@@ -154,6 +158,7 @@ impl Precedence {
     pub(crate) const COMPARE: Self = Precedence::Comparison(CompareLevel::Comparison);
     pub(crate) const EQUALITY: Self = Precedence::Comparison(CompareLevel::Equality);
     pub(crate) const BITOR: Self = Precedence::BitwiseInfix(BitwiseLevel::Or);
+    pub(crate) const BITXOR: Self = Precedence::BitwiseInfix(BitwiseLevel::Xor);
     pub(crate) const ADDSUB: Self = Precedence::ArithInfix(ArithLevel::AddSub);
     pub(crate) const BITAND: Self = Precedence::BitwiseInfix(BitwiseLevel::And);
     pub(crate) const DIVREM: Self = Precedence::ArithInfix(ArithLevel::DivRem);
@@ -6,6 +6,7 @@ pub use crate::parser::{
     error::{PResult, ParseError},
     Parser,
 };
+pub use std::num::{NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8};
 
 pub fn u16le(input: (u8, u8)) -> u16 {
     u16::from_le_bytes([input.0, input.1])
@@ -77,6 +78,22 @@ pub fn u64be(input: (u8, u8, u8, u8, u8, u8, u8, u8)) -> u64 {
     ])
 }
 
+pub fn i16le(input: (u8, u8)) -> i16 {
+    i16::from_le_bytes([input.0, input.1])
+}
+
+pub fn i16be(input: (u8, u8)) -> i16 {
+    i16::from_be_bytes([input.0, input.1])
+}
+
+pub fn i32le(input: (u8, u8, u8, u8)) -> i32 {
+    i32::from_le_bytes([input.0, input.1, input.2, input.3])
+}
+
+pub fn i32be(input: (u8, u8, u8, u8)) -> i32 {
+    i32::from_be_bytes([input.0, input.1, input.2, input.3])
+}
+
 pub fn dup32<T: Clone>(count: u32, value: T) -> Vec<T> {
     Vec::from_iter(std::iter::repeat(value).take(count as usize))
 }
@@ -2,12 +2,24 @@
 pub struct ReadCtxt<'a> {
     pub input: &'a [u8],
     pub offset: usize,
+    /// Offset at which the innermost enclosing slice of `input` begins
+    pub start: usize,
 }
 
 impl<'a> ReadCtxt<'a> {
     pub fn new(input: &'a [u8]) -> ReadCtxt<'a> {
         let offset = 0;
-        ReadCtxt { input, offset }
+        let start = 0;
+        ReadCtxt {
+            input,
+            offset,
+            start,
+        }
+    }
+
+    /// Returns the current offset relative to the start of the innermost enclosing slice
+    pub fn slice_offset(&self) -> usize {
+        self.offset - self.start
     }
 
     pub fn remaining(&self) -> &'a [u8] {
@@ -22,6 +34,7 @@ impl<'a> ReadCtxt<'a> {
                 ReadCtxt {
                     input: self.input,
                     offset: self.offset + 1,
+                    start: self.start,
                 },
             ))
         } else {
@@ -30,14 +43,20 @@ impl<'a> ReadCtxt<'a> {
     }
 
     pub fn split_at(&self, n: usize) -> Option<(ReadCtxt<'a>, ReadCtxt<'a>)> {
-        if self.offset + n <= self.input.len() {
+        if self
+            .offset
+            .checked_add(n)
+            .is_some_and(|end| end <= self.input.len())
+        {
             let fst = ReadCtxt {
                 input: &self.input[..self.offset + n],
                 offset: self.offset,
+                start: self.offset,
             };
             let snd = ReadCtxt {
                 input: self.input,
                 offset: self.offset + n,
+                start: self.start,
             };
             Some((fst, snd))
         } else {
@@ -50,6 +50,7 @@ pub enum UType {
     Tuple(Vec<Rc<UType>>),
     Record(Vec<(Label, Rc<UType>)>),
     Seq(Rc<UType>),
+    Newtype(Label, Rc<UType>),
 }
 
 impl From<BaseType> for UType {
@@ -107,6 +108,10 @@ impl UType {
             }
             ValueType::Union(..) => None,
             ValueType::Seq(inner) => Some(Self::Seq(Rc::new(Self::from_valuetype(inner)?))),
+            ValueType::Newtype(name, inner) => Some(Self::Newtype(
+                name.clone(),
+                Rc::new(Self::from_valuetype(inner)?),
+            )),
         }
     }
 }
@@ -275,7 +280,7 @@ impl UType {
             }
             UType::Tuple(ts) => Box::new(ts.iter().cloned()),
             UType::Record(fs) => Box::new(fs.iter().map(|(_l, t)| t.clone())),
-            UType::Seq(t) => Box::new(std::iter::once(t.clone())),
+            UType::Seq(t) | UType::Newtype(_, t) => Box::new(std::iter::once(t.clone())),
         }
     }
 }
@@ -493,7 +498,8 @@ pub enum UintSet {
 impl UintSet {
     pub fn contains(&self, b: BaseType) -> bool {
         match self {
-            UintSet::Any | UintSet::Any32 => b.is_numeric(),
+            UintSet::Any => b.is_numeric(),
+            UintSet::Any32 => b.is_numeric() && !b.is_signed(),
             UintSet::Short8 => b == BaseType::U8 || b == BaseType::U16,
         }
     }
@@ -647,15 +653,15 @@ impl TypeChecker {
                 let var = self.init_var_simple(UType::Base(BaseType::Bool))?.0;
                 Ok(var)
             }
-            Pattern::U8(_) => {
+            Pattern::U8(_) | Pattern::U8Range(..) => {
                 let var = self.init_var_simple(UType::Base(BaseType::U8))?.0;
                 Ok(var)
             }
-            Pattern::U16(_) => {
+            Pattern::U16(_) | Pattern::U16Range(..) => {
                 let var = self.init_var_simple(UType::Base(BaseType::U16))?.0;
                 Ok(var)
             }
-            Pattern::U32(_) => {
+            Pattern::U32(_) | Pattern::U32Range(..) => {
                 let var = self.init_var_simple(UType::Base(BaseType::U32))?.0;
                 Ok(var)
             }
@@ -663,6 +669,22 @@ impl TypeChecker {
                 let var = self.init_var_simple(UType::Base(BaseType::U64))?.0;
                 Ok(var)
             }
+            Pattern::I8(_) => {
+                let var = self.init_var_simple(UType::Base(BaseType::I8))?.0;
+                Ok(var)
+            }
+            Pattern::I16(_) => {
+                let var = self.init_var_simple(UType::Base(BaseType::I16))?.0;
+                Ok(var)
+            }
+            Pattern::I32(_) => {
+                let var = self.init_var_simple(UType::Base(BaseType::I32))?.0;
+                Ok(var)
+            }
+            Pattern::I64(_) => {
+                let var = self.init_var_simple(UType::Base(BaseType::I64))?.0;
+                Ok(var)
+            }
             Pattern::Char(_) => {
                 let var = self.init_var_simple(UType::Base(BaseType::Char))?.0;
                 Ok(var)
@@ -696,6 +718,38 @@ impl TypeChecker {
                 )?;
                 Ok(seq_uvar)
             }
+            Pattern::Or(alts) => {
+                let or_var = self.get_new_uvar();
+                let Some((first, rest)) = alts.split_first() else {
+                    return Ok(or_var);
+                };
+                let len = scope.entries.len();
+                let first_var = self.infer_var_scope_pattern(first, scope)?;
+                self.unify_var_pair(or_var, first_var)?;
+                let bound = scope.entries[len..].to_vec();
+                for alt in rest {
+                    // bindings of the remaining alternatives are unified with those of the first, rather than re-bound
+                    let mut alt_scope = UMultiScope::new(scope.parent);
+                    let alt_var = self.infer_var_scope_pattern(alt, &mut alt_scope)?;
+                    self.unify_var_pair(or_var, alt_var)?;
+                    for (name, _) in bound.iter() {
+                        if !alt_scope.entries.iter().any(|(n, _)| n == name) {
+                            return Err(TCErrorKind::InconsistentBinding(name.clone()).into());
+                        }
+                    }
+                    for (name, alt_v) in alt_scope.entries.iter() {
+                        match bound.iter().find(|(n, _)| n == name) {
+                            Some((_, v)) => {
+                                self.unify_var_pair(*v, *alt_v)?;
+                            }
+                            None => {
+                                return Err(TCErrorKind::InconsistentBinding(name.clone()).into())
+                            }
+                        }
+                    }
+                }
+                Ok(or_var)
+            }
         }
     }
 
@@ -903,7 +957,7 @@ impl TypeChecker {
                 }
                 Ok(())
             }
-            UType::Seq(inner) => {
+            UType::Seq(inner) | UType::Newtype(_, inner) => {
                 self.occurs_in(v, inner.clone())?;
                 Ok(())
             }
@@ -1215,6 +1269,16 @@ impl TypeChecker {
                 }
                 Ok(Rc::new(UType::Record(fs0)))
             }
+            (UType::Newtype(n1, t1), UType::Newtype(n2, t2)) => {
+                if n1 != n2 {
+                    return Err(UnificationError::Unsatisfiable(left, right).into());
+                }
+                if t1 == t2 {
+                    return Ok(left);
+                }
+                let inner = self.unify_utype(t1.clone(), t2.clone())?;
+                Ok(Rc::new(UType::Newtype(n1.clone(), inner)))
+            }
             (&UType::Var(v1), &UType::Var(v2)) => {
                 self.unify_var_pair(v1, v2)?;
                 Ok(Rc::new(UType::Var(Ord::min(v1, v2))))
@@ -1288,6 +1352,10 @@ impl TypeChecker {
             Expr::U16(_) => self.init_var_simple(UType::Base(BaseType::U16))?.0,
             Expr::U32(_) => self.init_var_simple(UType::Base(BaseType::U32))?.0,
             Expr::U64(_) => self.init_var_simple(UType::Base(BaseType::U64))?.0,
+            Expr::I8(_) => self.init_var_simple(UType::Base(BaseType::I8))?.0,
+            Expr::I16(_) => self.init_var_simple(UType::Base(BaseType::I16))?.0,
+            Expr::I32(_) => self.init_var_simple(UType::Base(BaseType::I32))?.0,
+            Expr::I64(_) => self.init_var_simple(UType::Base(BaseType::I64))?.0,
             Expr::Tuple(ts) => {
                 let newvar = self.get_new_uvar();
                 let mut uts = Vec::with_capacity(ts.len());
@@ -1429,12 +1497,42 @@ impl TypeChecker {
                 self.unify_utype(ut, Rc::new(UType::tuple([BaseType::U8; 4])))?;
                 newvar
             }
+            Expr::AsSigned8(x) => {
+                let newvar = self.init_var_simple(UType::Base(BaseType::I8))?.0;
+                let ut = self.infer_utype_expr(x.as_ref(), scope)?;
+                self.unify_utype(ut, Rc::new(UType::Base(BaseType::U8)))?;
+                newvar
+            }
+            Expr::AsSigned16(x) => {
+                let newvar = self.init_var_simple(UType::Base(BaseType::I16))?.0;
+                let ut = self.infer_utype_expr(x.as_ref(), scope)?;
+                self.unify_utype(ut, Rc::new(UType::Base(BaseType::U16)))?;
+                newvar
+            }
+            Expr::AsSigned32(x) => {
+                let newvar = self.init_var_simple(UType::Base(BaseType::I32))?.0;
+                let ut = self.infer_utype_expr(x.as_ref(), scope)?;
+                self.unify_utype(ut, Rc::new(UType::Base(BaseType::U32)))?;
+                newvar
+            }
             Expr::U64Be(bytes) | Expr::U64Le(bytes) => {
                 let newvar = self.init_var_simple(UType::Base(BaseType::U64))?.0;
                 let ut = self.infer_utype_expr(bytes.as_ref(), scope)?;
                 self.unify_utype(ut, Rc::new(UType::tuple([BaseType::U8; 8])))?;
                 newvar
             }
+            Expr::I16Be(bytes) | Expr::I16Le(bytes) => {
+                let newvar = self.init_var_simple(UType::Base(BaseType::I16))?.0;
+                let ut = self.infer_utype_expr(bytes.as_ref(), scope)?;
+                self.unify_utype(ut, Rc::new(UType::tuple([BaseType::U8; 2])))?;
+                newvar
+            }
+            Expr::I32Be(bytes) | Expr::I32Le(bytes) => {
+                let newvar = self.init_var_simple(UType::Base(BaseType::I32))?.0;
+                let ut = self.infer_utype_expr(bytes.as_ref(), scope)?;
+                self.unify_utype(ut, Rc::new(UType::tuple([BaseType::U8; 4])))?;
+                newvar
+            }
             Expr::SeqLength(seq_expr) => {
                 // REVIEW - does this always have to be U32?
                 let newvar = self.init_var_simple(UType::Base(BaseType::U32))?.0;
@@ -1443,6 +1541,18 @@ impl TypeChecker {
                 self.unify_var_proj_elem(seq_var, elem_var)?;
                 newvar
             }
+            Expr::SeqIx(seq_expr, index_expr) => {
+                let newvar = self.get_new_uvar();
+                let seq_var = self.infer_var_expr(seq_expr.as_ref(), scope)?;
+
+                let index_t = self.infer_utype_expr(index_expr.as_ref(), scope)?;
+                self.unify_utype_baseset(index_t, BaseSet::USome)?;
+
+                // the element type of the sequence is the type of the whole expression
+                self.unify_var_proj_elem(seq_var, newvar)?;
+
+                newvar
+            }
             Expr::SubSeq(seq_expr, start_expr, len_expr) => {
                 let newvar = self.get_new_uvar();
                 let seq_var = self.infer_var_expr(seq_expr.as_ref(), scope)?;
@@ -2227,6 +2337,46 @@ impl TypeChecker {
 
     /// Assigns new metavariables and simple constraints for a format, and returns the novel toplevel UVar
     ///
+    // NOTE - separate method so that `infer_var_format` does not need a larger stack frame
+    #[inline(never)]
+    fn infer_var_for_each(
+        &mut self,
+        seq: &Expr,
+        lab: &Label,
+        inner: &Format,
+        ctxt: Ctxt<'_>,
+    ) -> TCResult<UVar> {
+        let newvar = self.get_new_uvar();
+        let seq_var = self.infer_var_expr(seq, ctxt.scope)?;
+        let elem_var = self.get_new_uvar();
+        self.unify_var_utype(seq_var, Rc::new(UType::Seq(Rc::new(UType::Var(elem_var)))))?;
+        let newscope = UScope::Single(USingleScope::new(ctxt.scope, lab, elem_var));
+        let new_ctxt = ctxt.with_scope(&newscope);
+        let inner_t = self.infer_utype_format(inner, new_ctxt)?;
+        self.unify_var_utype(newvar, Rc::new(UType::Seq(inner_t)))?;
+        Ok(newvar)
+    }
+
+    // NOTE - separate method so that `infer_var_format` does not need a larger stack frame
+    #[inline(never)]
+    fn infer_var_newtype(
+        &mut self,
+        name: &Label,
+        inner: &Format,
+        validate: Option<&Expr>,
+        ctxt: Ctxt<'_>,
+    ) -> TCResult<UVar> {
+        let newvar = self.get_new_uvar();
+        let inner_t = self.infer_utype_format(inner, ctxt)?;
+        if let Some(f) = validate {
+            let (in_var, out_var) = self.infer_vars_expr_lambda(f, ctxt.scope)?;
+            self.unify_var_utype(in_var, inner_t.clone())?;
+            self.unify_var_utype(out_var, Rc::new(UType::Base(BaseType::Bool)))?;
+        }
+        self.unify_var_utype(newvar, Rc::new(UType::Newtype(name.clone(), inner_t)))?;
+        Ok(newvar)
+    }
+
     pub(crate) fn infer_var_format(&mut self, f: &Format, ctxt: Ctxt<'_>) -> TCResult<UVar> {
         match f {
             Format::ItemVar(level, args) => {
@@ -2250,6 +2400,9 @@ impl TypeChecker {
             }
             Format::Fail => Ok(self.init_var_simple(UType::Empty)?.0),
             Format::EndOfInput | Format::Align(_) => Ok(self.init_var_simple(UType::UNIT)?.0),
+            Format::AbsolutePos | Format::SlicePos => {
+                Ok(self.init_var_simple(UType::Base(BaseType::U32))?.0)
+            }
             Format::Byte(_set) => {
                 // FIXME - this may be a bit overly pedantic
                 if _set.is_empty() {
@@ -2264,6 +2417,9 @@ impl TypeChecker {
                 self.add_uvar_variant(newvar, cname.clone(), t_inner)?;
                 Ok(newvar)
             }
+            Format::Newtype(name, inner, validate) => {
+                self.infer_var_newtype(name, inner, validate.as_ref(), ctxt)
+            }
             Format::Union(branches) | Format::UnionNondet(branches) => {
                 let newvar = self.infer_var_format_union(branches, ctxt)?;
                 Ok(newvar)
@@ -2291,7 +2447,7 @@ impl TypeChecker {
                 self.unify_var_utype(newvar, Rc::new(UType::Record(fields)))?;
                 Ok(newvar)
             }
-            Format::Repeat(inner) | Format::Repeat1(inner) => {
+            Format::Repeat(inner) | Format::Repeat1(inner) | Format::RepeatWhileInput(inner) => {
                 let newvar = self.get_new_uvar();
                 let t = self.infer_utype_format(inner, ctxt)?;
                 self.unify_var_utype(newvar, Rc::new(UType::Seq(t)))?;
@@ -2346,6 +2502,15 @@ impl TypeChecker {
                 let _peek_t = self.infer_utype_format(peek, ctxt)?;
                 Ok(newvar)
             }
+            Format::RepeatSep(elem, sep) => {
+                self.infer_var_format(&Format::repeat_sep_equivalent(elem, sep), ctxt)
+            }
+            Format::UntilSeq(terminator, body) => {
+                self.infer_var_format(&Format::until_seq_equivalent(terminator, body), ctxt)
+            }
+            Format::Signature(_name, magic, body) => {
+                self.infer_var_format(&Format::signature_equivalent(magic, body), ctxt)
+            }
             Format::Slice(sz, inner) => {
                 let newvar = self.get_new_uvar();
                 let sz_t = self.infer_utype_expr(sz, ctxt.scope)?;
@@ -2360,7 +2525,7 @@ impl TypeChecker {
                 self.unify_var_utype(newvar, inner_t)?;
                 Ok(newvar)
             }
-            Format::WithRelativeOffset(ofs, inner) => {
+            Format::WithRelativeOffset(ofs, inner) | Format::FollowOffset(ofs, inner) => {
                 let newvar = self.get_new_uvar();
                 let sz_t = self.infer_utype_expr(ofs, ctxt.scope)?;
                 self.unify_utype_baseset(sz_t, BaseSet::USome)?;
@@ -2383,6 +2548,7 @@ impl TypeChecker {
                 self.unify_var_utype(newvar, xt)?;
                 Ok(newvar)
             }
+            Format::ForEach(seq, lab, inner) => self.infer_var_for_each(seq, lab, inner, ctxt),
             Format::Let(lab, x, inner) => {
                 let newvar = self.get_new_uvar();
                 let xvar = self.infer_var_expr(x, ctxt.scope)?;
@@ -2496,6 +2662,10 @@ impl TypeChecker {
                 Some(ValueType::Record(vfs))
             }
             UType::Seq(t0) => Some(ValueType::Seq(Box::new(self.reify(t0.clone())?))),
+            UType::Newtype(name, t0) => Some(ValueType::Newtype(
+                name.clone(),
+                Box::new(self.reify(t0.clone())?),
+            )),
         }
     }
 
@@ -2548,14 +2718,15 @@ pub enum Polarity {
 
 #[derive(Debug)]
 pub struct TCError {
-    err: TCErrorKind,
+    // NOTE - boxed so that the `TCResult`s threaded through the (deeply recursive) inference methods stay small
+    err: Box<TCErrorKind>,
     _trace: Vec<Box<dyn std::fmt::Debug + 'static + Send + Sync>>,
 }
 
 impl From<TCErrorKind> for TCError {
     fn from(value: TCErrorKind) -> Self {
         Self {
-            err: value,
+            err: Box::new(value),
             _trace: Vec::new(),
         }
     }
@@ -2588,6 +2759,7 @@ pub enum TCErrorKind {
     Unification(ConstraintError),
     InfiniteType(UVar, Constraints),
     MultipleSolutions(UVar, BaseSet),
+    InconsistentBinding(Label), // or-pattern alternative that does not bind the same names as the others
 }
 
 impl From<TypeError> for TCErrorKind {
@@ -2614,7 +2786,7 @@ where
 {
     fn from(value: (ConstraintError, T)) -> Self {
         Self {
-            err: TCErrorKind::Unification(value.0),
+            err: Box::new(TCErrorKind::Unification(value.0)),
             _trace: vec![Box::new(value.1)],
         }
     }
@@ -2668,6 +2840,8 @@ impl std::fmt::Display for TCErrorKind {
                 }
             Self::MultipleSolutions(uv, bs) =>
                 write!(f, "no unique solution for `{uv} {}`", bs.to_constraint()),
+            Self::InconsistentBinding(name) =>
+                write!(f, "binding `{name}` does not occur in every alternative of an or-pattern"),
         }
     }
 }